@@ -0,0 +1,59 @@
+//! Benchmarks of the hot paths of the storage layer. to catch performance regressions, save a
+//! baseline before a change and compare against it afterwards:
+//!
+//! cargo bench -- --save-baseline before
+//! cargo bench -- --baseline before
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use podcasts::{fixtures, generate_library, Episode, DEFAULT_FILE_TEMPLATE};
+
+fn feed_parsing(c: &mut Criterion) {
+    let syntax = fixtures::read("syntax.xml").expect("Can't read syntax.xml");
+    let http_203 = fixtures::read("http_203.xml").expect("Can't read http_203.xml");
+
+    c.bench_function("parse syntax feed", |b| {
+        b.iter(|| Episode::parse_feed(black_box(&syntax), 15913066141282366353))
+    });
+    c.bench_function("parse http 203 feed", |b| {
+        b.iter(|| Episode::parse_feed(black_box(&http_203), 12772734294147401495))
+    });
+}
+
+fn episodes_storage(c: &mut Criterion) {
+    let (_podcast, episodes) = generate_library(1, 10_000).pop().unwrap();
+
+    let mut csv_writer = csv::Writer::from_writer(Vec::new());
+    for episode in episodes.iter() {
+        csv_writer.serialize(episode).unwrap();
+    }
+    let contents = csv_writer.into_inner().unwrap();
+
+    c.bench_function("write 10000 episodes", |b| {
+        b.iter(|| {
+            let mut csv_writer = csv::Writer::from_writer(Vec::new());
+            for episode in episodes.iter() {
+                csv_writer.serialize(black_box(episode)).unwrap();
+            }
+            csv_writer.flush().unwrap();
+        })
+    });
+    c.bench_function("read 10000 episodes", |b| {
+        b.iter(|| {
+            let episodes: Vec<Episode> = csv::Reader::from_reader(black_box(&contents[..]))
+                .deserialize()
+                .filter_map(|item| item.ok())
+                .collect();
+            episodes
+        })
+    });
+    c.bench_function("file names of 10000 episodes", |b| {
+        b.iter(|| {
+            episodes
+                .iter()
+                .map(|episode| episode.file_name(DEFAULT_FILE_TEMPLATE))
+                .collect::<Vec<String>>()
+        })
+    });
+}
+
+criterion_group!(benches, feed_parsing, episodes_storage);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+//! Updates the podcasts saved by pcasts and downloads the newest episode of each one, using the
+//! library instead of the CLI
+//!
+//! cargo run --example library
+use podcasts::{Config, Library, NoProgress, UpdateStatus};
+use std::{env, path::PathBuf, process, sync::Arc};
+
+fn main() {
+    let home_directory = env::var("HOME").expect("Can't find $HOME dir variable");
+    let app_directory = PathBuf::from(home_directory).join(".podcasts");
+    let config = Config::new(app_directory.clone(), app_directory.join("episodes")).with_progress(Arc::new(NoProgress));
+    let library = Library::new(config);
+
+    let updates = match library.update(None) {
+        Ok(updates) => updates,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let mut newest = Vec::new();
+    for update in updates {
+        match update.status {
+            UpdateStatus::Failed(error) => eprintln!("Can't update {}. {}", update.podcast.name(), error),
+            _ => newest.extend(
+                library
+                    .episodes(update.podcast.id)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .take(1),
+            ),
+        }
+    }
+
+    match library.download_episodes(newest) {
+        Ok(downloaded) => {
+            for (episode, path) in downloaded {
+                println!("Downloaded {} to {}", episode.title, path.display());
+            }
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
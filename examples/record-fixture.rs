@@ -0,0 +1,25 @@
+//! Records a live feed (or any other URL) as a new test fixture
+//!
+//! cargo run --features fixtures --example record-fixture <url> [file name]
+use podcasts::fixtures;
+use std::{env, process};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let url = match args.next() {
+        Some(url) => url,
+        None => {
+            eprintln!("Usage: cargo run --features fixtures --example record-fixture <url> [file name]");
+            process::exit(1);
+        }
+    };
+    let file = args.next();
+
+    match fixtures::record(&url, file.as_deref()) {
+        Ok(path) => println!("Recorded {} to {}", url, path.display()),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! The cover art of the podcasts, saved in the app directory so that players can show it
+use crate::{file_system::FileSystem, timings::Phase, web::Web, Config, Errors};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time,
+};
+
+/// The directory inside the app directory which holds the artwork of each podcast
+const ART_DIRECTORY: &str = "art";
+
+/// Path of the artwork of the podcast with the passed id. the file may not exist
+pub fn path(app_directory: &Path, podcast_id: u64) -> PathBuf {
+    app_directory.join(ART_DIRECTORY).join(format!("{}.jpg", podcast_id))
+}
+
+/// The URL of the artwork of the channel. the iTunes image is preferred since it's usually the
+/// larger one
+pub fn image_url(channel: &rss::Channel) -> Option<String> {
+    channel
+        .itunes_ext()
+        .and_then(|itunes| itunes.image())
+        .or_else(|| channel.image().map(|image| image.url()))
+        .filter(|url| !url.is_empty())
+        .map(|url| url.to_string())
+}
+
+/// Downloads the artwork of the passed podcasts. failed downloads are reported and skipped, so
+/// they don't fail the command which found the artwork
+pub fn download(config: &Config, artwork: &[(u64, String)]) -> Result<(), Errors> {
+    if artwork.is_empty() {
+        return Ok(());
+    }
+
+    let urls: Vec<&str> = artwork.iter().map(|(_podcast_id, url)| url.as_str()).collect();
+    let responses = config.timings.measure(Phase::Fetch, || {
+        Web::new(time::Duration::from_secs(10), &config.http)
+            .stats(&config.network)
+            .progress(Arc::clone(&config.progress))
+            .jobs(config.jobs)
+            .get(&urls)
+    });
+
+    for (url, response) in responses {
+        let bytes = match response {
+            Ok(bytes) if !bytes.is_empty() => bytes,
+            Ok(_bytes) => {
+                tracing::warn!("Can't download artwork {}. The response is empty", url);
+                continue;
+            }
+            Err(error) => {
+                tracing::warn!("Can't download artwork {}. {}", url, error);
+                continue;
+            }
+        };
+
+        // Podcasts of the same network may share their artwork
+        for (podcast_id, _url) in artwork.iter().filter(|(_podcast_id, artwork_url)| artwork_url == url) {
+            let mut file = FileSystem::new(
+                &config.app_directory.join(ART_DIRECTORY),
+                &format!("{}.jpg", podcast_id),
+                vec![],
+            )
+            .atomic()?;
+            file.write_all(&bytes[..])?;
+            file.commit()?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn art_image_url() {
+        let feed = r###"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel>
+<title>Feed</title><link>https://example.com</link>
+<image><url>https://example.com/small.jpg</url><title>Feed</title><link>https://example.com</link></image>
+<itunes:image href="https://example.com/large.jpg"/>
+</channel></rss>"###;
+        let channel = rss::Channel::read_from(feed.as_bytes()).unwrap();
+        assert_eq!(image_url(&channel), Some("https://example.com/large.jpg".to_string()));
+
+        let feed =
+            r###"<rss version="2.0"><channel><title>Feed</title><link>https://example.com</link></channel></rss>"###;
+        let channel = rss::Channel::read_from(feed.as_bytes()).unwrap();
+        assert_eq!(image_url(&channel), None);
+    }
+}
@@ -0,0 +1,367 @@
+//! Fills in the metadata which episodes saved by older versions lack, like their durations and
+//! show notes, or the artwork of their podcast, by fetching the feeds of their podcasts again
+use crate::{
+    art,
+    cache::ResponseCache,
+    episodes::{read_episodes, Episode},
+    feed,
+    file_system::{FilePermissions, FileSystem},
+    headers::HeaderList,
+    journal::Journal,
+    podcasts::{load_podcasts, Podcast},
+    resolver,
+    timings::Phase,
+    web::Web,
+    Config, Errors,
+};
+use bytes::Bytes;
+use clap::ArgMatches;
+use colored::*;
+use std::{collections::HashMap, fmt, io::Write, sync::Arc, time};
+
+/// How long the fetched feeds are reused, so a backfill which is run again doesn't fetch them again
+const FEED_CACHE_TTL: time::Duration = time::Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Duration,
+    Description,
+    // The artwork of the podcast, since the feeds rarely have per episode artwork
+    Artwork,
+}
+
+impl Field {
+    pub fn parse(field: &str) -> Option<Self> {
+        match field.trim() {
+            "duration" => Some(Field::Duration),
+            "description" => Some(Field::Description),
+            "artwork" => Some(Field::Artwork),
+            _ => None,
+        }
+    }
+
+    /// Whether the episode has the field. the artwork belongs to the podcast and not the episode
+    fn is_set(self, episode: &Episode) -> bool {
+        match self {
+            Field::Duration => episode.duration > 0,
+            Field::Description => !episode.description.is_empty(),
+            Field::Artwork => true,
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = match self {
+            Field::Duration => "duration",
+            Field::Description => "description",
+            Field::Artwork => "artwork",
+        };
+        write!(f, "{}", field)
+    }
+}
+
+/// Fills in the fields the saved episodes lack from the same episodes in the feed. what the saved
+/// episodes have is kept. returns how many episodes were changed
+fn fill(episodes: &mut [Episode], feed: &[Episode], fields: &[Field]) -> usize {
+    let feed: HashMap<&str, &Episode> = feed.iter().map(|episode| (episode.guid.as_str(), episode)).collect();
+
+    let mut filled = 0;
+    for episode in episodes.iter_mut() {
+        let found = match feed.get(episode.guid.as_str()) {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let missing: Vec<Field> = fields
+            .iter()
+            .copied()
+            .filter(|field| *field != Field::Artwork && !field.is_set(episode) && field.is_set(found))
+            .collect();
+        for field in missing.iter() {
+            match field {
+                Field::Duration => episode.duration = found.duration,
+                Field::Description => episode.description = found.description.clone(),
+                Field::Artwork => {}
+            }
+        }
+        if !missing.is_empty() {
+            filled += 1;
+        }
+    }
+
+    filled
+}
+
+pub struct Backfiller<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Backfiller<'a> {
+    /// Constructs a new Backfiller struct which is used to work with the sub command "backfill"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        // Always present because it has a default value
+        let fields = self
+            .matches
+            .values_of("fields")
+            .unwrap()
+            .filter_map(Field::parse)
+            .collect::<Vec<Field>>();
+        let podcasts = load_podcasts(self.config)?;
+        let podcasts = match self.matches.values_of("id") {
+            Some(ids) => resolver::podcasts(&podcasts, ids)?.into_iter().cloned().collect(),
+            None => podcasts,
+        };
+
+        let mut library = Vec::new();
+        for podcast in podcasts {
+            // Podcasts which were never updated have nothing to backfill
+            let file = match FileSystem::new(
+                &self.config.app_directory,
+                &podcast.id.to_string(),
+                vec![FilePermissions::Read],
+            )
+            .open()
+            {
+                Ok(file) => file,
+                Err(_error) => continue,
+            };
+            let episodes = read_episodes(file, self.config.strict)?;
+            library.push((podcast, episodes));
+        }
+
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+        self.backfill(&mut library, &fields, &mut writer)?;
+        self.coverage(&library, &fields, writer)
+    }
+
+    /// Whether the podcast lacks any of the fields
+    fn is_missing(&self, podcast: &Podcast, episodes: &[Episode], fields: &[Field]) -> bool {
+        fields.iter().any(|field| match field {
+            Field::Artwork => !art::path(&self.config.app_directory, podcast.id).exists(),
+            field => episodes.iter().any(|episode| !field.is_set(episode)),
+        })
+    }
+
+    /// The feeds of the passed podcasts, or why they couldn't be fetched. the ones fetched by a
+    /// recent backfill are reused, the rest are fetched in parallel, as many at a time per host as
+    /// its history allows
+    fn feeds(&self, podcasts: &[&Podcast]) -> Result<HashMap<u64, Result<Bytes, String>>, Errors> {
+        let cache = ResponseCache::new(&self.config.app_directory, FEED_CACHE_TTL);
+        let mut feeds = HashMap::new();
+        let mut urls = Vec::new();
+        for podcast in podcasts {
+            match cache.get(&podcast.rss_url) {
+                Some(bytes) => {
+                    feeds.insert(podcast.id, Ok(bytes));
+                }
+                None => urls.push(podcast.rss_url.as_str()),
+            }
+        }
+
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?
+            .by_url(podcasts.iter().map(|podcast| (podcast.rss_url.as_str(), podcast.id)));
+        let web = Web::new(time::Duration::from_secs(10), &self.config.http)
+            .stats(&self.config.network)
+            .progress(Arc::clone(&self.config.progress))
+            .jobs(self.config.jobs)
+            .limit_rate(self.config.limit_rate)
+            .headers(headers);
+        let responses: HashMap<&str, Result<Bytes, Errors>> = self
+            .config
+            .timings
+            .measure(Phase::Fetch, || web.get(&urls))
+            .into_iter()
+            .collect();
+
+        for (url, response) in responses {
+            if let Ok(bytes) = &response {
+                // Only an optimization, so failing to cache isn't an error
+                let _ = cache.put(url, bytes);
+            }
+            for podcast in podcasts.iter().filter(|podcast| podcast.rss_url == url) {
+                let response = match &response {
+                    Ok(bytes) => Ok(bytes.clone()),
+                    Err(error) => Err(error.to_string()),
+                };
+                feeds.insert(podcast.id, response);
+            }
+        }
+
+        Ok(feeds)
+    }
+
+    /// Fills in the fields of the podcasts which lack any of them and writes how many episodes of
+    /// each one changed
+    fn backfill<W>(
+        &self,
+        library: &mut [(Podcast, Vec<Episode>)],
+        fields: &[Field],
+        mut writer: W,
+    ) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let missing: Vec<&Podcast> = library
+            .iter()
+            .filter(|(podcast, episodes)| self.is_missing(podcast, episodes, fields))
+            .map(|(podcast, _episodes)| podcast)
+            .collect();
+        if missing.is_empty() {
+            writeln!(writer, "Nothing to backfill")?;
+            return Ok(());
+        }
+        let mut feeds = self.feeds(&missing)?;
+
+        let mut journal = Journal::new(&self.config.app_directory);
+        let mut artwork = Vec::new();
+        for (podcast, episodes) in library.iter_mut() {
+            let bytes = match feeds.remove(&podcast.id) {
+                Some(Ok(bytes)) => bytes,
+                Some(Err(error)) => {
+                    writeln!(writer, "{:40} {}", podcast.name(), format!("failed: {}", error).red())?;
+                    continue;
+                }
+                None => continue,
+            };
+
+            if fields.contains(&Field::Artwork) && !art::path(&self.config.app_directory, podcast.id).exists() {
+                if let Some(image_url) = feed::read(&bytes[..]).ok().as_ref().and_then(art::image_url) {
+                    artwork.push((podcast.id, image_url));
+                }
+            }
+
+            let filled = match Episode::parse_feed(&bytes[..], podcast.id) {
+                Ok(feed) => fill(episodes, &feed, fields),
+                Err(error) => {
+                    writeln!(writer, "{:40} {}", podcast.name(), format!("failed: {}", error).red())?;
+                    continue;
+                }
+            };
+            if filled == 0 {
+                continue;
+            }
+
+            let file = FileSystem::new(&self.config.app_directory, &podcast.id.to_string(), vec![]).atomic()?;
+            let mut csv_writer = csv::Writer::from_writer(file);
+            for episode in episodes.iter() {
+                csv_writer.serialize(episode)?;
+            }
+            let file = csv_writer
+                .into_inner()
+                .map_err(|error| Errors::IO(error.into_error()))?;
+            journal.stage(file)?;
+            writeln!(
+                writer,
+                "{:40} {}",
+                podcast.name(),
+                format!("filled {} episodes", filled).green()
+            )?;
+        }
+        journal.commit()?;
+
+        art::download(self.config, &artwork)
+    }
+
+    /// Writes how many of the episodes have each field
+    fn coverage<W>(&self, library: &[(Podcast, Vec<Episode>)], fields: &[Field], mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let percent = |count: usize, total: usize| match total {
+            0 => 100,
+            total => count * 100 / total,
+        };
+
+        for field in fields {
+            let (count, total, unit) = match field {
+                Field::Artwork => (
+                    library
+                        .iter()
+                        .filter(|(podcast, _episodes)| art::path(&self.config.app_directory, podcast.id).exists())
+                        .count(),
+                    library.len(),
+                    "podcasts",
+                ),
+                field => (
+                    library
+                        .iter()
+                        .flat_map(|(_podcast, episodes)| episodes.iter())
+                        .filter(|episode| field.is_set(episode))
+                        .count(),
+                    library.iter().map(|(_podcast, episodes)| episodes.len()).sum(),
+                    "episodes",
+                ),
+            };
+            writeln!(
+                writer,
+                "{:12} {} of {} {} ({}%)",
+                field.to_string().green(),
+                count,
+                total,
+                unit,
+                percent(count, total)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, podcasts::read_podcasts};
+
+    #[test]
+    fn backfill_fill() {
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,duration,description
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,0,
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,1800,Saved notes
+c,C,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,0,
+"###;
+        let mut episodes = read_episodes(input.as_bytes(), true).unwrap();
+        let feed = r###"guid,title,pub_date,link,podcast,podcast_id,duration,description
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,3600,About Rust
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,1700,Other notes
+"###;
+        let feed = read_episodes(feed.as_bytes(), true).unwrap();
+
+        assert_eq!(fill(&mut episodes, &feed, &[Field::Duration]), 1);
+        assert_eq!((episodes[0].duration, episodes[0].description.as_str()), (3600, ""));
+        assert_eq!(fill(&mut episodes, &feed, &[Field::Duration, Field::Description]), 1);
+        assert_eq!(episodes[0].description, "About Rust");
+        assert_eq!(
+            (episodes[1].duration, episodes[1].description.as_str()),
+            (1800, "Saved notes")
+        );
+        assert_eq!(episodes[2].duration, 0);
+        assert_eq!(Field::parse(" artwork"), Some(Field::Artwork));
+        assert_eq!(Field::parse("chapters"), None);
+
+        let podcasts = read_podcasts("id,url,rss_url,title\n7,-,-,Podcast\n".as_bytes(), true).unwrap();
+        let library = vec![(podcasts[0].clone(), episodes)];
+        let matches = ArgMatches::default();
+        let config = fixtures::config();
+        let backfiller = Backfiller::new(&matches, &config);
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        backfiller
+            .coverage(
+                &library,
+                &[Field::Duration, Field::Description, Field::Artwork],
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "duration     2 of 3 episodes (66%)\ndescription  2 of 3 episodes (66%)\nartwork      0 of 1 podcasts (0%)\n"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! The whole library in one archive, to move it to another machine or keep it somewhere safe.
+//! unlike the daily snapshots it holds all the files of the app directory, e.g the headers and the
+//! gpodder account, and the downloaded episodes unless they're excluded. the podcasts keep their
+//! ids, since they're saved in the files
+use crate::{
+    cache::CACHE_DIRECTORY, crash::REPORTS_DIRECTORY, debug::FEEDS_DIRECTORY, file_system::FileSystem,
+    journal::JOURNAL_FILE, lock::LOCK_FILE, snapshot::SNAPSHOTS_DIRECTORY, stats::NETWORK_STATS_FILE, Config, Errors,
+};
+use clap::ArgMatches;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Component, Path},
+    process,
+};
+
+/// The directory of the archive which holds the files of the app directory
+const LIBRARY_DIRECTORY: &str = "library";
+
+/// The directory of the archive which holds the downloaded episodes
+const DOWNLOADS_DIRECTORY: &str = "downloads";
+
+/// Whether the entry of the app directory with the passed name belongs to the library. the
+/// caches, the reports and the files of the running process are particular to this machine
+fn is_library(name: &str) -> bool {
+    let excluded = [
+        SNAPSHOTS_DIRECTORY,
+        CACHE_DIRECTORY,
+        REPORTS_DIRECTORY,
+        FEEDS_DIRECTORY,
+        LOCK_FILE,
+        JOURNAL_FILE,
+        NETWORK_STATS_FILE,
+    ];
+    !excluded.contains(&name) && !name.ends_with(".part") && !name.contains(".tmp-")
+}
+
+/// Writes the library, and the downloaded episodes if audio is set, into a compressed archive
+fn save<W>(config: &Config, audio: bool, writer: W) -> Result<W, Errors>
+where
+    W: Write,
+{
+    let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    let mut entries: Vec<_> = fs::read_dir(&config.app_directory)?
+        .filter_map(|entry| entry.ok())
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let path = entry.path();
+        // The downloads are inside the app directory unless another directory is configured
+        if !is_library(&name) || path == config.download_directory {
+            continue;
+        }
+
+        let archive_path = Path::new(LIBRARY_DIRECTORY).join(&name);
+        match entry.file_type()?.is_dir() {
+            true => builder.append_dir_all(archive_path, path)?,
+            false => builder.append_path_with_name(path, archive_path)?,
+        }
+    }
+    if audio && config.download_directory.is_dir() {
+        builder.append_dir_all(DOWNLOADS_DIRECTORY, &config.download_directory)?;
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// Moves the entries of the library in the from directory to the to directory
+fn move_library(config: &Config, from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_library(&name) || entry.path() == config.download_directory {
+            continue;
+        }
+        fs::rename(entry.path(), to.join(&name))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the library of the archive to the library directory and adds its downloaded episodes to
+/// the download directory
+fn extract<R>(config: &Config, mut archive: tar::Archive<R>, library: &Path) -> Result<(), Errors>
+where
+    R: io::Read,
+{
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.to_path_buf();
+        // Entries with other paths or types aren't written by backup and are skipped, and so are
+        // the ones which would be written outside of the directories. a link could point a later
+        // entry anywhere
+        let entry_type = entry.header().entry_type();
+        let is_safe = (entry_type.is_file() || entry_type.is_dir())
+            && entry_path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        let destination = match (
+            entry_path.strip_prefix(LIBRARY_DIRECTORY),
+            entry_path.strip_prefix(DOWNLOADS_DIRECTORY),
+        ) {
+            (Ok(relative), _) if is_safe => library.join(relative),
+            (_, Ok(relative)) if is_safe => config.download_directory.join(relative),
+            _ => continue,
+        };
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        entry.unpack(destination)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the library with the one of the backup at the passed path. the downloaded episodes of
+/// the backup are added to the ones in the download directory
+pub fn restore(config: &Config, path: &Path) -> Result<(), Errors> {
+    let open = || -> Result<tar::Archive<GzDecoder<fs::File>>, Errors> {
+        let file = fs::File::open(path)?;
+        Ok(tar::Archive::new(GzDecoder::new(file)))
+    };
+    let invalid = || Errors::InvalidBackup(path.display().to_string());
+
+    // Nothing is extracted before it's known that the archive has a library to replace it with
+    let podcast_list = Path::new(LIBRARY_DIRECTORY).join("podcast_list.csv");
+    let mut has_library = false;
+    for entry in open()?.entries().map_err(|_error| invalid())? {
+        let entry = entry.map_err(|_error| invalid())?;
+        if entry.path().map_err(|_error| invalid())? == podcast_list {
+            has_library = true;
+            break;
+        }
+    }
+    if !has_library {
+        return Err(invalid());
+    }
+
+    // The library is extracted next to the current one, which is only replaced once all of it
+    // was written. the names of the directories aren't part of the library
+    let restored = config.app_directory.join(format!(".restored.tmp-{}", process::id()));
+    let replaced = config.app_directory.join(format!(".replaced.tmp-{}", process::id()));
+    let _ = fs::remove_dir_all(&restored);
+    let _ = fs::remove_dir_all(&replaced);
+    fs::create_dir_all(&restored)?;
+    if let Err(error) = extract(config, open()?, &restored) {
+        let _ = fs::remove_dir_all(&restored);
+        return Err(error);
+    }
+
+    let swapped = move_library(config, &config.app_directory, &replaced)
+        .and_then(|_moved| move_library(config, &restored, &config.app_directory));
+    if let Err(error) = swapped {
+        // Puts back the current library
+        let _ = move_library(config, &replaced, &config.app_directory);
+        let _ = fs::remove_dir_all(&restored);
+        return Err(Errors::IO(error));
+    }
+    let _ = fs::remove_dir_all(&restored);
+    fs::remove_dir_all(&replaced)?;
+
+    Ok(())
+}
+
+pub struct Backup<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Backup<'a> {
+    /// Constructs a new Backup struct which is used to work with the sub command "backup"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        // Always present because it's a required argument
+        let out = Path::new(self.matches.value_of("out").unwrap());
+        let name = out
+            .file_name()
+            .ok_or_else(|| Errors::IO(io::Error::other("The output path has no file name")))?
+            .to_string_lossy()
+            .to_string();
+        let directory = out.parent().unwrap_or_else(|| Path::new(""));
+        let directory = match directory.as_os_str().is_empty() {
+            true => Path::new("."),
+            false => directory,
+        };
+        let audio = !self.matches.is_present("exclude-audio");
+
+        let file = FileSystem::new(directory, &name, vec![]).atomic()?;
+        let mut file = save(self.config, audio, file)?;
+        file.flush()?;
+        file.commit()?;
+        println!("Saved the library to {}", out.display());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn create_config(name: &str) -> Config {
+        let mut config = fixtures::config();
+        config.app_directory = fixtures::scratch().join(name);
+        config.download_directory = config.app_directory.join("episodes");
+        config
+    }
+
+    #[test]
+    fn backup_restore() {
+        let config = create_config("backup");
+        fs::create_dir_all(config.app_directory.join(SNAPSHOTS_DIRECTORY)).unwrap();
+        fs::create_dir_all(config.podcast_directory(1)).unwrap();
+        fs::write(config.app_directory.join("podcast_list.csv"), "podcasts").unwrap();
+        fs::write(config.app_directory.join("1"), "episodes").unwrap();
+        fs::write(config.app_directory.join("headers.csv"), "headers").unwrap();
+        fs::write(config.app_directory.join(LOCK_FILE), "").unwrap();
+        fs::write(config.podcast_directory(1).join("episode.mp3"), "audio").unwrap();
+        let archive = config.app_directory.with_extension("tar.gz");
+
+        fs::write(&archive, save(&config, false, Vec::new()).unwrap()).unwrap();
+        let restored = create_config("backup-restored");
+        fs::create_dir_all(&restored.app_directory).unwrap();
+        fs::write(restored.app_directory.join("podcast_list.csv"), "other").unwrap();
+        fs::write(restored.app_directory.join("2"), "other episodes").unwrap();
+        restore(&restored, &archive).expect("Can't restore backup");
+
+        let read = |name: &str| fs::read_to_string(restored.app_directory.join(name)).ok();
+        assert_eq!(read("podcast_list.csv").as_deref(), Some("podcasts"));
+        assert_eq!(read("1").as_deref(), Some("episodes"));
+        assert_eq!(read("headers.csv").as_deref(), Some("headers"));
+        assert_eq!(read("2"), None);
+        assert_eq!(read(LOCK_FILE), None);
+        assert!(!restored.podcast_directory(1).exists());
+
+        fs::write(&archive, save(&config, true, Vec::new()).unwrap()).unwrap();
+        restore(&restored, &archive).expect("Can't restore backup");
+        assert_eq!(
+            fs::read_to_string(restored.podcast_directory(1).join("episode.mp3")).unwrap(),
+            "audio"
+        );
+
+        // Other archives are refused without touching the library
+        fs::write(&archive, b"not an archive").unwrap();
+        assert!(matches!(restore(&restored, &archive), Err(Errors::InvalidBackup(_))));
+        assert_eq!(read("podcast_list.csv").as_deref(), Some("podcasts"));
+    }
+
+    #[test]
+    fn restore_partial_archive() {
+        let config = create_config("partial");
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(config.app_directory.join("podcast_list.csv"), "podcasts").unwrap();
+        // Random bytes barely compress, so the end of the archive is inside this file
+        let mut seed = 1u32;
+        let topics: Vec<u8> = (0..65_536)
+            .map(|_index| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (seed >> 16) as u8
+            })
+            .collect();
+        fs::write(config.app_directory.join("topics.csv"), topics).unwrap();
+        let bytes = save(&config, false, Vec::new()).unwrap();
+        let archive = config.app_directory.with_extension("tar.gz");
+        fs::write(&archive, &bytes[..bytes.len() - 1_000]).unwrap();
+
+        // The library is left as it was when the archive ends early
+        let restored = create_config("partial-restored");
+        fs::create_dir_all(&restored.app_directory).unwrap();
+        fs::write(restored.app_directory.join("podcast_list.csv"), "other").unwrap();
+        assert!(restore(&restored, &archive).is_err());
+        let names: Vec<String> = fs::read_dir(&restored.app_directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["podcast_list.csv".to_string()]);
+        assert_eq!(
+            fs::read_to_string(restored.app_directory.join("podcast_list.csv")).unwrap(),
+            "other"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_skips_links() {
+        let config = create_config("links");
+        let outside = fixtures::scratch().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        let mut append = |path: &str, entry_type: tar::EntryType, link: Option<&Path>, contents: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(entry_type);
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            if let Some(link) = link {
+                header.set_link_name(link).unwrap();
+            }
+            builder.append_data(&mut header, path, contents).unwrap();
+        };
+        append("library/podcast_list.csv", tar::EntryType::Regular, None, b"podcasts");
+        append("library/x", tar::EntryType::Symlink, Some(&outside), b"");
+        append("library/x/file", tar::EntryType::Regular, None, b"outside");
+        append(
+            "downloads/y",
+            tar::EntryType::Link,
+            Some(Path::new("library/podcast_list.csv")),
+            b"",
+        );
+        let archive = config.app_directory.with_extension("tar.gz");
+        fs::write(&archive, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        // A link could point the entries after it outside the app directory
+        restore(&config, &archive).expect("Can't restore backup");
+        assert!(!outside.join("file").exists());
+        assert!(!fs::symlink_metadata(config.app_directory.join("x"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(!config.download_directory.join("y").exists());
+        assert_eq!(
+            fs::read_to_string(config.app_directory.join("podcast_list.csv")).unwrap(),
+            "podcasts"
+        );
+    }
+}
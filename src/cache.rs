@@ -0,0 +1,87 @@
+use crate::{file_system::FileSystem, Errors};
+use bytes::Bytes;
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// The directory inside the app directory which holds the cached responses
+pub const CACHE_DIRECTORY: &str = "cache";
+
+/// Responses of directory APIs saved on disk, so repeated searches don't hit rate limited APIs
+#[derive(Debug)]
+pub struct ResponseCache {
+    directory: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn new(app_directory: &Path, ttl: Duration) -> Self {
+        Self {
+            directory: app_directory.join(CACHE_DIRECTORY),
+            ttl,
+        }
+    }
+
+    fn file_name(url: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        url.hash(&mut hasher);
+
+        format!("{:016x}", hasher.finish())
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        self.directory.join(Self::file_name(url))
+    }
+
+    /// The saved response of the passed URL, if it's younger than the TTL
+    pub fn get(&self, url: &str) -> Option<Bytes> {
+        let path = self.path(url);
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+        let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+        if age >= self.ttl {
+            return None;
+        }
+
+        fs::read(path).ok().map(Bytes::from)
+    }
+
+    /// Saves the response of the passed URL. it's replaced atomically, since the commands which
+    /// read the cache don't take the lock and may run at the same time
+    pub fn put(&self, url: &str, response: &[u8]) -> Result<(), Errors> {
+        let file_name = Self::file_name(url);
+        let mut file = FileSystem::new(&self.directory, &file_name, vec![]).atomic()?;
+        file.write_all(response)?;
+        file.commit()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn cache_ttl() {
+        let directory = fixtures::scratch();
+
+        let cache = ResponseCache::new(&directory, Duration::from_secs(60));
+        assert!(cache.get("https://example.com/search").is_none());
+
+        cache
+            .put("https://example.com/search", b"results")
+            .expect("Can't cache response");
+        assert_eq!(cache.get("https://example.com/search"), Some(Bytes::from("results")));
+        assert!(cache.get("https://example.com/other").is_none());
+
+        // Expired responses are ignored
+        let expired = ResponseCache::new(&directory, Duration::from_secs(0));
+        assert!(expired.get("https://example.com/search").is_none());
+    }
+}
@@ -0,0 +1,150 @@
+//! Cuts parts out of downloaded MP3 episodes, e.g the soundbites their feed highlights. MP3 is a
+//! sequence of independent frames, so a clip is the frames between two points in time and
+//! nothing has to be decoded
+use crate::tags;
+
+/// Length of the header of an MPEG audio frame
+const FRAME_HEADER_LENGTH: usize = 4;
+
+/// Bitrates of MPEG 1 layer III in kbit/s, by the index in the header
+const MPEG1_BITRATES: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+
+/// Bitrates of MPEG 2 and 2.5 layer III in kbit/s, by the index in the header
+const MPEG2_BITRATES: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+/// A layer III frame at an offset of the content
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    offset: usize,
+    length: usize,
+    // In seconds
+    duration: f64,
+}
+
+/// Reads the header of the layer III frame at the start of the bytes. None if they don't start
+/// with one or it uses a free or a reserved bitrate or sample rate
+fn frame_header(bytes: &[u8]) -> Option<(usize, f64)> {
+    if bytes.len() < FRAME_HEADER_LENGTH || bytes[0] != 0xff || bytes[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+
+    let version = (bytes[1] >> 3) & 0b11;
+    let layer = (bytes[1] >> 1) & 0b11;
+    let bitrate_index = (bytes[2] >> 4) as usize;
+    let sample_rate_index = ((bytes[2] >> 2) & 0b11) as usize;
+    let padding = ((bytes[2] >> 1) & 1) as usize;
+    // Version 1 is reserved and layer 1 is layer III
+    if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index == 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let (bitrate, sample_rate, samples) = match version {
+        3 => (
+            MPEG1_BITRATES[bitrate_index],
+            [44_100, 48_000, 32_000][sample_rate_index],
+            1152,
+        ),
+        2 => (
+            MPEG2_BITRATES[bitrate_index],
+            [22_050, 24_000, 16_000][sample_rate_index],
+            576,
+        ),
+        _ => (
+            MPEG2_BITRATES[bitrate_index],
+            [11_025, 12_000, 8_000][sample_rate_index],
+            576,
+        ),
+    };
+    let length = (samples / 8 * bitrate as usize * 1000) / sample_rate as usize + padding;
+
+    Some((length, samples as f64 / sample_rate as f64))
+}
+
+/// The audio frames of the MP3 content. bytes which aren't frames, like a tag at the end, are
+/// skipped, and so is the Xing or Info frame encoders put first, which holds no audio
+fn frames(content: &[u8]) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut offset = tags::tag_length(content);
+
+    while offset + FRAME_HEADER_LENGTH <= content.len() {
+        let (length, duration) = match frame_header(&content[offset..]) {
+            Some(header) if offset + header.0 <= content.len() => header,
+            _ => {
+                offset += 1;
+                continue;
+            }
+        };
+
+        let frame = &content[offset..offset + length];
+        let info = frames.is_empty()
+            && frame
+                .windows(4)
+                .take(64)
+                .any(|window| window == b"Xing" || window == b"Info");
+        if !info {
+            frames.push(Frame {
+                offset,
+                length,
+                duration,
+            });
+        }
+        offset += length;
+    }
+
+    frames
+}
+
+/// The frames of the MP3 content which start within the passed number of seconds after the start.
+/// empty if the content isn't MP3 or is shorter than the start
+pub fn cut(content: &[u8], start: f64, duration: f64) -> Vec<u8> {
+    let mut clip = Vec::new();
+    let mut time = 0.0;
+
+    for frame in frames(content) {
+        if time >= start + duration {
+            break;
+        }
+        if time >= start {
+            clip.extend_from_slice(&content[frame.offset..frame.offset + frame.length]);
+        }
+        time += frame.duration;
+    }
+
+    clip
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// MPEG 1 layer III at 128 kbit/s and 44.1 kHz, 417 bytes and about 26 ms per frame
+    fn create_mp3(count: usize) -> Vec<u8> {
+        let mut content = b"ID3\x04\x00\x00\x00\x00\x00\x02ab".to_vec();
+        for index in 0..count {
+            let mut frame = vec![0xff, 0xfb, 0x90, 0x00];
+            frame.resize(417, index as u8);
+            content.extend(frame);
+        }
+        content.extend(b"TAG");
+
+        content
+    }
+
+    #[test]
+    fn clips_cut() {
+        let content = create_mp3(100);
+        assert_eq!(frames(&content).len(), 100);
+        assert_eq!(frame_header(&content[12..]).map(|header| header.0), Some(417));
+
+        // Frames 20 to 38 start within the second half of the first second
+        let clip = cut(&content, 0.5, 0.5);
+        assert_eq!(clip.len(), 19 * 417);
+        assert_eq!(&clip[..5], &[0xff, 0xfb, 0x90, 0x00, 20]);
+        assert!(cut(&content, 10.0, 1.0).is_empty());
+        assert!(cut(b"not an mp3", 0.0, 1.0).is_empty());
+
+        let mut info = create_mp3(3);
+        info[12 + 36..12 + 40].copy_from_slice(b"Info");
+        assert_eq!(frames(&info).len(), 2);
+    }
+}
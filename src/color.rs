@@ -0,0 +1,61 @@
+//! Whether the output is colored. the escape sequences garble the logs and files the output is
+//! redirected to, so by default only terminals get colors, and none when NO_COLOR is set
+use std::{
+    env,
+    ffi::OsStr,
+    io::{self, IsTerminal},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// The choice passed with --color. anything else leaves it to the terminal
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "always" => ColorChoice::Always,
+            "never" => ColorChoice::Never,
+            _ => ColorChoice::Auto,
+        }
+    }
+
+    /// Whether the output to a terminal, or to anything else, is colored. an empty NO_COLOR
+    /// doesn't count, see https://no-color.org
+    fn enabled(self, is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => is_terminal && no_color.is_none_or(OsStr::is_empty),
+        }
+    }
+
+    /// Whether the output to stdout is colored
+    pub fn stdout(self) -> bool {
+        self.enabled(io::stdout().is_terminal(), env::var_os("NO_COLOR").as_deref())
+    }
+
+    /// Whether the diagnostics written to stderr are colored
+    pub fn stderr(self) -> bool {
+        self.enabled(io::stderr().is_terminal(), env::var_os("NO_COLOR").as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_choice() {
+        assert!(ColorChoice::parse("auto").enabled(true, None));
+        assert!(ColorChoice::parse("auto").enabled(true, Some(OsStr::new(""))));
+        assert!(!ColorChoice::parse("auto").enabled(false, None));
+        assert!(!ColorChoice::parse("auto").enabled(true, Some(OsStr::new("1"))));
+
+        assert!(ColorChoice::parse("always").enabled(false, Some(OsStr::new("1"))));
+        assert!(!ColorChoice::parse("never").enabled(true, None));
+    }
+}
@@ -0,0 +1,300 @@
+//! Two podcasts side by side, for deciding which of two similar shows to keep following: how often
+//! they release, how long their episodes are and the people and topics they share. the topics are
+//! the words which recur in the titles, since the transcripts are only saved as URLs
+use crate::{
+    dates,
+    episodes::{Episode, Episodes},
+    podcasts::{load_podcasts, Podcast},
+    resolver, text, Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+/// A word of the titles is a topic of the podcast once it's in this many episodes
+const MIN_TOPIC_EPISODES: usize = 2;
+
+/// Number of shared people and topics listed
+const SHARED_SHOWN: usize = 10;
+
+/// Width of the column of each podcast
+const COLUMN_WIDTH: usize = 28;
+
+/// What's compared of each podcast
+#[derive(Debug, Default, PartialEq)]
+struct Profile {
+    episodes: usize,
+    // Median seconds between consecutive releases. None with less than two dated episodes
+    cadence: Option<u64>,
+    last_release: Option<u64>,
+    // Mean seconds of the episodes whose duration is known
+    average_duration: Option<u64>,
+    // The people of the episodes by their folded names, with the number of their episodes
+    people: HashMap<String, (String, usize)>,
+    // The topics by the number of their episodes
+    topics: BTreeMap<String, usize>,
+}
+
+impl Profile {
+    fn new(episodes: &[Episode]) -> Self {
+        let mut releases: Vec<u64> = episodes.iter().filter_map(Episode::release_time).collect();
+        releases.sort_unstable();
+        let mut gaps: Vec<u64> = releases.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        gaps.sort_unstable();
+
+        let durations: Vec<u64> = episodes
+            .iter()
+            .map(|episode| episode.duration)
+            .filter(|duration| *duration > 0)
+            .collect();
+
+        let mut people: HashMap<String, (String, usize)> = HashMap::new();
+        for person in episodes.iter().flat_map(|episode| episode.people()) {
+            people.entry(text::fold(&person.name)).or_insert((person.name, 0)).1 += 1;
+        }
+
+        let mut topics = BTreeMap::new();
+        for episode in episodes {
+            let mut words = text::words(&episode.title);
+            // Counted once per episode
+            words.sort_unstable();
+            words.dedup();
+            for word in words {
+                *topics.entry(word).or_insert(0) += 1;
+            }
+        }
+        topics.retain(|_word, count| *count >= MIN_TOPIC_EPISODES);
+
+        Self {
+            episodes: episodes.len(),
+            cadence: gaps.get(gaps.len() / 2).copied(),
+            last_release: releases.last().copied(),
+            average_duration: match durations.len() {
+                0 => None,
+                count => Some(durations.iter().sum::<u64>() / count as u64),
+            },
+            people,
+            topics,
+        }
+    }
+}
+
+/// The time between releases, e.g "7d" or "12h"
+fn format_cadence(seconds: u64) -> String {
+    match seconds {
+        0..=86_399 => format!("{}h", (seconds / 3_600).max(1)),
+        _ => format!("{}d", (seconds + 43_200) / 86_400),
+    }
+}
+
+/// The passed value cut to fit in a column
+fn fit(value: &str) -> String {
+    match value.chars().count() > COLUMN_WIDTH - 2 {
+        true => format!("{}…", value.chars().take(COLUMN_WIDTH - 3).collect::<String>()),
+        false => value.to_string(),
+    }
+}
+
+pub struct Comparer<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Comparer<'a> {
+    /// Constructs a new Comparer struct which is used to work with the sub command "compare"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        let podcasts = load_podcasts(self.config)?;
+        let episodes = Episodes::new(self.matches, self.config);
+        let mut compared = Vec::new();
+        // Always present because they're required arguments
+        for id in [
+            self.matches.value_of("first").unwrap(),
+            self.matches.value_of("second").unwrap(),
+        ]
+        .iter()
+        {
+            let podcast = resolver::podcast(&podcasts, id)?;
+            // Podcasts which were never updated have no episodes yet
+            compared.push((podcast, episodes.podcast_episodes(podcast.id).unwrap_or_default()));
+        }
+        let writer = std::io::stdout();
+        let writer = writer.lock();
+
+        compare((compared[0].0, &compared[0].1), (compared[1].0, &compared[1].1), writer)
+    }
+}
+
+/// Writes the profiles of the two podcasts in columns, and the people and topics they share
+fn compare<W>(first: (&Podcast, &[Episode]), second: (&Podcast, &[Episode]), mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    let profiles = [Profile::new(first.1), Profile::new(second.1)];
+    let now = dates::now();
+    let row = |writer: &mut W, label: &str, values: [String; 2]| -> Result<(), Errors> {
+        writeln!(
+            writer,
+            "{:16}{:width$}{}",
+            label.green(),
+            values[0],
+            values[1],
+            width = COLUMN_WIDTH
+        )?;
+        Ok(())
+    };
+    let each = |value: &dyn Fn(&Profile) -> String| [value(&profiles[0]), value(&profiles[1])];
+
+    // The first name is padded before it's made bold, since the escapes would count as width
+    let names = [
+        format!("{:width$}", fit(first.0.name()), width = COLUMN_WIDTH)
+            .bold()
+            .to_string(),
+        fit(second.0.name()).bold().to_string(),
+    ];
+    row(&mut writer, "", names)?;
+    row(&mut writer, "ID:", [first.0.id.to_string(), second.0.id.to_string()])?;
+    row(&mut writer, "Episodes:", each(&|profile| profile.episodes.to_string()))?;
+    row(
+        &mut writer,
+        "Releases every:",
+        each(&|profile| profile.cadence.map(format_cadence).unwrap_or_else(|| "-".to_string())),
+    )?;
+    row(
+        &mut writer,
+        "Last release:",
+        each(&|profile| match profile.last_release {
+            Some(release) => dates::relative_age(release, now),
+            None => "-".to_string(),
+        }),
+    )?;
+    row(
+        &mut writer,
+        "Avg duration:",
+        each(&|profile| match profile.average_duration {
+            Some(duration) => dates::format_duration(duration),
+            None => "-".to_string(),
+        }),
+    )?;
+    row(
+        &mut writer,
+        "People:",
+        each(&|profile| profile.people.len().to_string()),
+    )?;
+    row(
+        &mut writer,
+        "Topics:",
+        each(&|profile| profile.topics.len().to_string()),
+    )?;
+
+    // The shared people and topics which are in the most episodes of both come first
+    let mut people: Vec<(&String, usize)> = profiles[0]
+        .people
+        .iter()
+        .filter_map(|(key, (name, count))| Some((name, count + profiles[1].people.get(key)?.1)))
+        .collect();
+    people.sort_by(|first, second| second.1.cmp(&first.1).then_with(|| first.0.cmp(second.0)));
+    let mut topics: Vec<(&String, usize)> = profiles[0]
+        .topics
+        .iter()
+        .filter_map(|(topic, count)| Some((topic, count + profiles[1].topics.get(topic)?)))
+        .collect();
+    topics.sort_by(|first, second| second.1.cmp(&first.1).then_with(|| first.0.cmp(second.0)));
+
+    for (label, shared) in [("Shared people:", people), ("Shared topics:", topics)].iter() {
+        let names: Vec<&str> = shared
+            .iter()
+            .take(SHARED_SHOWN)
+            .map(|(name, _count)| name.as_str())
+            .collect();
+        let names = match (names.is_empty(), shared.len() > SHARED_SHOWN) {
+            (true, _) => "none".to_string(),
+            (false, true) => format!("{} and {} more", names.join(", "), shared.len() - SHARED_SHOWN),
+            (false, false) => names.join(", "),
+        };
+        writeln!(writer, "{:16}{}", label.green(), names)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_episode(title: &str, published_at: u64, duration: u64, people: &str) -> Episode {
+        Episode {
+            guid: title.to_string(),
+            title: title.to_string(),
+            pub_date: String::new(),
+            link: "-".to_string(),
+            podcast: String::new(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at,
+            alternates: String::new(),
+            people: people.to_string(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        }
+    }
+
+    #[test]
+    fn compare_podcasts() {
+        let day = 86_400;
+        let now = dates::now();
+        let first = vec![
+            create_episode("Rust async runtimes", now - 14 * day, 3_600, "host:Ann;guest:Bob"),
+            create_episode("Rust error handling", now - 7 * day, 1_800, "host:Ann"),
+            create_episode("Testing with mocks", now - day, 0, "host:Ann;guest:Eve"),
+        ];
+        let second = vec![
+            create_episode("Why Rust? Part 1", now - 60 * day, 2_400, "host:Zed;guest:bob"),
+            create_episode("Rust in production", now - 30 * day, 2_400, "host:Zed"),
+        ];
+
+        let profile = Profile::new(&first);
+        assert_eq!(profile.episodes, 3);
+        assert_eq!(profile.cadence, Some(7 * day));
+        assert_eq!(profile.last_release, Some(now - day));
+        assert_eq!(profile.average_duration, Some(2_700));
+        assert_eq!(profile.people.len(), 3);
+        assert_eq!(profile.topics.keys().collect::<Vec<&String>>(), vec!["rust"]);
+
+        let mut podcasts = [
+            Podcast::new("-".to_string(), "a".to_string(), "Rustacean Station".to_string()),
+            Podcast::new("-".to_string(), "b".to_string(), "Rust Talk".to_string()),
+        ];
+        podcasts[1].id = 2;
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        compare((&podcasts[0], &first), (&podcasts[1], &second), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "                Rustacean Station           Rust Talk");
+        assert_eq!(lines[2], "Episodes:       3                           2");
+        assert_eq!(lines[3], "Releases every: 7d                          30d");
+        assert_eq!(lines[5], "Avg duration:   45:00                       40:00");
+        assert_eq!(lines[8], "Shared people:  Bob");
+        assert_eq!(lines[9], "Shared topics:  rust");
+    }
+}
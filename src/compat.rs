@@ -0,0 +1,172 @@
+//! Keeps the invocations of older versions working. the arguments of an invocation which changed
+//! are rewritten to the ones which replace them before they're parsed, and a hint about the change
+//! is printed the first time it's used, so the scripts of the users keep working across releases
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// An argument which was replaced by other arguments
+pub struct Migration {
+    /// The subcommands the argument belongs to, e.g ["episodes", "download"]
+    pub command: &'static [&'static str],
+    pub old: &'static str,
+    /// The arguments which replace it. a value passed with "=" is appended to the last of them
+    pub new: &'static [&'static str],
+    pub hint: &'static str,
+}
+
+/// The arguments which changed, oldest first. entries are kept for a few releases after the change
+const MIGRATIONS: &[Migration] = &[];
+
+/// The file inside the app directory which holds the migrations whose hint was already printed
+const HINTS_FILE: &str = "compat_hints";
+
+impl Migration {
+    /// Identifies the migration in the hints file
+    fn key(&self) -> String {
+        format!("{} {}", self.command.join(" "), self.old)
+    }
+}
+
+/// Rewrites the changed arguments of the passed command line. returns the new arguments together
+/// with the migrations which were applied
+fn rewrite(args: Vec<OsString>, migrations: &[Migration]) -> (Vec<OsString>, Vec<&Migration>) {
+    let mut rewritten = Vec::with_capacity(args.len());
+    let mut applied: Vec<&Migration> = Vec::new();
+
+    for arg in args {
+        let text = match arg.to_str() {
+            Some(text) => text,
+            None => {
+                rewritten.push(arg);
+                continue;
+            }
+        };
+        // The words of the command line so far which aren't flags, like the subcommands
+        let words: Vec<&str> = rewritten
+            .iter()
+            .filter_map(|arg| arg.to_str())
+            .filter(|arg| !arg.starts_with('-'))
+            .collect();
+
+        let migration = migrations.iter().find(|migration| {
+            let mut words = words.iter();
+            let in_command = migration
+                .command
+                .iter()
+                .all(|command| words.any(|word| word == command));
+            let matches_arg = text == migration.old || text.starts_with(&format!("{}=", migration.old));
+
+            in_command && matches_arg
+        });
+
+        match migration {
+            Some(migration) => {
+                let value = text.strip_prefix(migration.old).unwrap_or("").to_string();
+                let last = migration.new.len().saturating_sub(1);
+                for (index, new) in migration.new.iter().enumerate() {
+                    match index == last {
+                        true => rewritten.push(OsString::from(format!("{}{}", new, value))),
+                        false => rewritten.push(OsString::from(new)),
+                    }
+                }
+                if !applied.iter().any(|applied| applied.key() == migration.key()) {
+                    applied.push(migration);
+                }
+            }
+            None => rewritten.push(arg),
+        }
+    }
+
+    (rewritten, applied)
+}
+
+/// Writes the hints of the applied migrations which weren't shown before and records them
+fn hint<W>(applied: &[&Migration], app_directory: &Path, mut writer: W) -> io::Result<()>
+where
+    W: Write,
+{
+    let path = app_directory.join(HINTS_FILE);
+    let shown = fs::read_to_string(&path).unwrap_or_default();
+    let new: Vec<&&Migration> = applied
+        .iter()
+        .filter(|migration| !shown.lines().any(|line| line == migration.key()))
+        .collect();
+    if new.is_empty() {
+        return Ok(());
+    }
+
+    let mut contents = shown;
+    for migration in new {
+        writeln!(writer, "{} is deprecated. {}", migration.old, migration.hint)?;
+        contents.push_str(&format!("{}\n", migration.key()));
+    }
+
+    fs::create_dir_all(app_directory)?;
+    fs::write(path, contents)
+}
+
+/// The command line of the process with the changed arguments replaced
+pub fn args(app_directory: &Path) -> Vec<OsString> {
+    let (args, applied) = rewrite(std::env::args_os().collect(), MIGRATIONS);
+    // A hint which can't be recorded is shown again next time, which is better than failing
+    let _ = hint(&applied, app_directory, io::stderr());
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn compat_rewrite() {
+        let migrations = [
+            Migration {
+                command: &["episodes", "download"],
+                old: "--last",
+                new: &["--count"],
+                hint: "Use --count instead",
+            },
+            Migration {
+                command: &["podcasts"],
+                old: "--list-all",
+                new: &["--list", "--art"],
+                hint: "Use --list --art instead",
+            },
+        ];
+        let args = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<OsString>>();
+
+        let (rewritten, applied) = rewrite(
+            args(&["pcasts", "--strict", "episodes", "download", "--id", "1", "--last=2"]),
+            &migrations,
+        );
+        assert_eq!(
+            rewritten,
+            args(&["pcasts", "--strict", "episodes", "download", "--id", "1", "--count=2"])
+        );
+        assert_eq!(applied.len(), 1);
+
+        // Only the arguments of the command are rewritten
+        let (rewritten, applied) = rewrite(args(&["pcasts", "episodes", "list", "--last"]), &migrations);
+        assert_eq!(rewritten, args(&["pcasts", "episodes", "list", "--last"]));
+        assert!(applied.is_empty());
+
+        let (rewritten, applied) = rewrite(args(&["pcasts", "podcasts", "--list-all"]), &migrations);
+        assert_eq!(rewritten, args(&["pcasts", "podcasts", "--list", "--art"]));
+
+        // The hint is only shown the first time
+        let directory = fixtures::scratch();
+        let mut output = Vec::new();
+        hint(&applied, &directory, &mut output).unwrap();
+        hint(&applied, &directory, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "--list-all is deprecated. Use --list --art instead\n"
+        );
+    }
+}
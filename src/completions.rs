@@ -0,0 +1,427 @@
+//! Shell completion scripts for the commands and flags of pcasts. clap_complete generates them,
+//! and a function wrapped around the generated one completes the IDs of the saved podcasts by
+//! calling the hidden `complete-ids` command
+use crate::{podcasts::load_podcasts, Config, Errors};
+use clap::{App, ArgMatches};
+use clap_complete::Shell;
+use std::io::Write;
+
+/// The hidden command the scripts run to list the podcast IDs. clap_complete splits the paths of
+/// the subcommands at "__", so the name can't start with it
+pub const COMPLETE_IDS: &str = "complete-ids";
+
+/// The value name of the arguments which take podcast IDs. the scripts complete the saved IDs
+/// for them
+pub const PODCAST_ID: &str = "PODCAST_ID";
+
+/// A command of the app, e.g `pcasts podcasts keep`, and its arguments which take podcast IDs
+struct Command {
+    // The names of the subcommands leading to the command, e.g ["podcasts", "keep"]
+    names: Vec<String>,
+    subcommands: Vec<String>,
+    // The ways to write the flags which take podcast IDs, e.g "-r" and "--remove"
+    id_flags: Vec<String>,
+    // Whether a positional argument of the command takes podcast IDs
+    id_positional: bool,
+}
+
+impl Command {
+    /// The names of the command and its parents joined with "__", e.g "pcasts__podcasts__keep"
+    fn path(&self, bin_name: &str) -> String {
+        std::iter::once(bin_name)
+            .chain(self.names.iter().map(String::as_str))
+            .collect::<Vec<&str>>()
+            .join("__")
+    }
+}
+
+/// Whether the argument is marked with the PODCAST_ID value name
+fn takes_podcast_ids(arg: &clap::Arg) -> bool {
+    arg.get_value_names().is_some_and(|names| names.contains(&PODCAST_ID))
+}
+
+/// The commands of the app and of its subcommands, parents first
+fn commands(app: &App) -> Vec<Command> {
+    let mut commands = Vec::new();
+    collect(app, Vec::new(), &mut commands);
+
+    commands
+}
+
+fn collect(app: &App, names: Vec<String>, commands: &mut Vec<Command>) {
+    let subcommands: Vec<&App> = app
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .collect();
+    let arguments: Vec<&clap::Arg> = app.get_arguments().filter(|arg| takes_podcast_ids(arg)).collect();
+
+    commands.push(Command {
+        names: names.clone(),
+        subcommands: subcommands
+            .iter()
+            .map(|subcommand| subcommand.get_name().to_string())
+            .collect(),
+        id_flags: arguments
+            .iter()
+            .flat_map(|arg| {
+                let short = arg.get_short().map(|short| format!("-{}", short));
+                let long = arg.get_long().map(|long| format!("--{}", long.trim_start_matches('-')));
+                short.into_iter().chain(long)
+            })
+            .collect(),
+        id_positional: arguments.iter().any(|arg| arg.is_positional()),
+    });
+
+    for subcommand in subcommands {
+        let mut names = names.clone();
+        names.push(subcommand.get_name().to_string());
+        collect(subcommand, names, commands);
+    }
+}
+
+/// The script clap_complete generates for the shell
+fn generate(shell: Shell, app: &App, bin_name: &str) -> String {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut app.clone(), bin_name, &mut script);
+
+    String::from_utf8_lossy(&script).into_owned()
+}
+
+/// Quotes the text for bash and zsh
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// The `case` patterns which match the paths of the subcommands, for bash and zsh
+fn subcommand_paths(commands: &[Command], bin_name: &str) -> String {
+    commands
+        .iter()
+        .skip(1)
+        .map(|command| quote(&command.path(bin_name)))
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+/// The `case` patterns which match the path of a command and the word before the cursor, when
+/// the word is a flag which takes podcast IDs, and when the cursor can be on a positional ID
+fn id_patterns(commands: &[Command], bin_name: &str) -> (Vec<String>, Vec<String>) {
+    let flags = commands
+        .iter()
+        .flat_map(|command| {
+            command
+                .id_flags
+                .iter()
+                .map(move |flag| quote(&format!("{}:{}", command.path(bin_name), flag)))
+        })
+        .collect();
+    let positionals = commands
+        .iter()
+        .filter(|command| command.id_positional)
+        .map(|command| format!("{}*", quote(&format!("{}:", command.path(bin_name)))))
+        .collect();
+
+    (flags, positionals)
+}
+
+pub fn bash<W>(app: &App, bin_name: &str, mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    let commands = commands(app);
+    let (flags, positionals) = id_patterns(&commands, bin_name);
+    let ids = format!(
+        "$(compgen -W \"$({} {} 2>/dev/null | cut -f1)\" -- \"${{cur}}\")",
+        quote(bin_name),
+        COMPLETE_IDS
+    );
+
+    write!(writer, "{}", generate(Shell::Bash, app, bin_name))?;
+    writeln!(writer)?;
+    writeln!(writer, "_{}_podcast_ids() {{", bin_name)?;
+    // The generated function sets $cmd without declaring it local
+    writeln!(writer, "    local i path cur prev")?;
+    writeln!(writer, "    cur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+    writeln!(writer, "    prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+    writeln!(writer, "    path={}", quote(bin_name))?;
+    writeln!(writer, "    for ((i = 1; i < COMP_CWORD; i++)); do")?;
+    writeln!(writer, "        case \"${{path}}__${{COMP_WORDS[i]}}\" in")?;
+    writeln!(
+        writer,
+        "            {}) path=\"${{path}}__${{COMP_WORDS[i]}}\" ;;",
+        subcommand_paths(&commands, bin_name)
+    )?;
+    writeln!(writer, "        esac")?;
+    writeln!(writer, "    done")?;
+    writeln!(writer)?;
+    writeln!(writer, "    _{} \"$@\"", bin_name)?;
+    writeln!(writer, "    case \"${{path}}:${{prev}}\" in")?;
+    if !flags.is_empty() {
+        writeln!(writer, "        {})", flags.join("|"))?;
+        writeln!(writer, "            COMPREPLY=({})", ids)?;
+        writeln!(writer, "            ;;")?;
+    }
+    if !positionals.is_empty() {
+        writeln!(writer, "        {})", positionals.join("|"))?;
+        writeln!(writer, "            if [[ ${{cur}} != -* && ${{prev}} != -* ]]; then")?;
+        writeln!(writer, "                COMPREPLY+=({})", ids)?;
+        writeln!(writer, "            fi")?;
+        writeln!(writer, "            ;;")?;
+    }
+    writeln!(writer, "    esac")?;
+    writeln!(writer, "}}")?;
+    writeln!(writer)?;
+    writeln!(
+        writer,
+        "complete -F _{}_podcast_ids -o bashdefault -o default {}",
+        bin_name,
+        quote(bin_name)
+    )?;
+
+    Ok(())
+}
+
+pub fn zsh<W>(app: &App, bin_name: &str, mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    let commands = commands(app);
+    let (flags, positionals) = id_patterns(&commands, bin_name);
+    let ids = format!(
+        "podcasts=(${{(f)\"$({} {} 2>/dev/null | tr '\\t' ':')\"}})",
+        quote(bin_name),
+        COMPLETE_IDS
+    );
+    // The generated script completes right away when it's autoloaded from $fpath. the wrapper
+    // does that instead, below
+    let script = generate(Shell::Zsh, app, bin_name);
+    let call = format!("_{} \"$@\"", bin_name);
+    let script = script.trim_end().strip_suffix(&call).unwrap_or(&script);
+
+    write!(writer, "{}", script)?;
+    writeln!(writer)?;
+    writeln!(writer, "_{}_podcast_ids() {{", bin_name)?;
+    // $path is tied to $PATH in zsh
+    writeln!(writer, "    local command i")?;
+    writeln!(writer, "    local -a podcasts")?;
+    writeln!(writer, "    command={}", quote(bin_name))?;
+    writeln!(writer, "    for ((i = 2; i < CURRENT; i++)); do")?;
+    writeln!(writer, "        case \"${{command}}__${{words[i]}}\" in")?;
+    writeln!(
+        writer,
+        "            {}) command=\"${{command}}__${{words[i]}}\" ;;",
+        subcommand_paths(&commands, bin_name)
+    )?;
+    writeln!(writer, "        esac")?;
+    writeln!(writer, "    done")?;
+    writeln!(writer)?;
+    writeln!(writer, "    case \"${{command}}:${{words[CURRENT-1]}}\" in")?;
+    if !flags.is_empty() {
+        writeln!(writer, "        {})", flags.join("|"))?;
+        writeln!(writer, "            {}", ids)?;
+        writeln!(writer, "            _describe 'podcast' podcasts")?;
+        writeln!(writer, "            return")?;
+        writeln!(writer, "            ;;")?;
+    }
+    if !positionals.is_empty() {
+        writeln!(writer, "        {})", positionals.join("|"))?;
+        writeln!(
+            writer,
+            "            if [[ ${{words[CURRENT]}} != -* && ${{words[CURRENT-1]}} != -* ]]; then"
+        )?;
+        writeln!(writer, "                {}", ids)?;
+        writeln!(writer, "                _describe 'podcast' podcasts")?;
+        writeln!(writer, "            fi")?;
+        writeln!(writer, "            ;;")?;
+    }
+    writeln!(writer, "    esac")?;
+    writeln!(writer, "    {}", call)?;
+    writeln!(writer, "}}")?;
+    writeln!(writer)?;
+    writeln!(writer, "compdef _{}_podcast_ids {}", bin_name, quote(bin_name))?;
+    writeln!(writer, "if [ \"$funcstack[1]\" = \"_{}\" ]; then", bin_name)?;
+    writeln!(writer, "    _{}_podcast_ids \"$@\"", bin_name)?;
+    writeln!(writer, "fi")?;
+
+    Ok(())
+}
+
+pub fn fish<W>(app: &App, bin_name: &str, mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    let ids = format!("'({} {})'", bin_name, COMPLETE_IDS);
+
+    write!(writer, "{}", generate(Shell::Fish, app, bin_name))?;
+    for command in commands(app) {
+        // The same conditions as the generated lines, which tell the command apart from its
+        // parents and its subcommands
+        let condition = match command.names.is_empty() {
+            true if command.subcommands.is_empty() => String::new(),
+            true => " -n \"__fish_use_subcommand\"".to_string(),
+            false => format!(
+                " -n \"{}\"",
+                command
+                    .names
+                    .iter()
+                    .map(|name| format!("__fish_seen_subcommand_from {}", name))
+                    .chain(
+                        command
+                            .subcommands
+                            .iter()
+                            .map(|name| format!("not __fish_seen_subcommand_from {}", name))
+                    )
+                    .collect::<Vec<String>>()
+                    .join("; and ")
+            ),
+        };
+        for flag in &command.id_flags {
+            let flag = match flag.strip_prefix("--") {
+                Some(long) => format!("-l {}", long),
+                None => format!("-s {}", flag.trim_start_matches('-')),
+            };
+            writeln!(writer, "complete -c {}{} {} -x -a {}", bin_name, condition, flag, ids)?;
+        }
+        if command.id_positional {
+            writeln!(writer, "complete -c {}{} -f -a {}", bin_name, condition, ids)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub struct Completions<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+    app: &'a App<'static>,
+}
+
+impl<'a> Completions<'a> {
+    /// Constructs a new Completions struct which is used to work with the sub command
+    /// "completions" and the hidden "complete-ids"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config, app: &'a App<'static>) -> Self {
+        Self { matches, config, app }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        // Completes the name pcasts was run as, e.g when it's installed under another name
+        let bin_name = self.app.get_bin_name().unwrap_or_else(|| self.app.get_name());
+        let bin_name = bin_name.rsplit(std::path::MAIN_SEPARATOR).next().unwrap_or(bin_name);
+
+        let writer = std::io::stdout();
+        let writer = writer.lock();
+
+        match self.matches.value_of("shell") {
+            Some("zsh") => zsh(self.app, bin_name, writer),
+            Some("fish") => fish(self.app, bin_name, writer),
+            _ => bash(self.app, bin_name, writer),
+        }
+    }
+
+    /// Lists the IDs and names of the saved podcasts, separated by a tab
+    pub fn ids(&self) -> Result<(), Errors> {
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+
+        // Nothing to complete before the first podcast is added
+        for podcast in load_podcasts(self.config).unwrap_or_default() {
+            writeln!(writer, "{}\t{}", podcast.id, podcast.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{AppSettings, Arg};
+    use std::str::from_utf8;
+
+    #[test]
+    fn completions_scripts() {
+        let app = App::new("pcasts")
+            .arg(
+                Arg::with_name("quiet")
+                    .help("Log only errors")
+                    .short('q')
+                    .long("quiet")
+                    .global(true),
+            )
+            .subcommand(
+                App::new("podcasts")
+                    .about("Manage the podcasts")
+                    .arg(
+                        Arg::with_name("remove")
+                            .help("Remove a podcast")
+                            .short('r')
+                            .long("remove")
+                            .takes_value(true)
+                            .value_name(PODCAST_ID),
+                    )
+                    .subcommand(
+                        App::new("keep").about("Keep the newest downloads").arg(
+                            Arg::with_name("id")
+                                .help("Id of the podcast")
+                                .value_name(PODCAST_ID)
+                                .index(1),
+                        ),
+                    ),
+            )
+            .subcommand(
+                App::new("episodes").arg(
+                    // Not marked, even though its help mentions a podcast
+                    Arg::with_name("podcast")
+                        .help("ID of the podcast to list")
+                        .long("podcast")
+                        .takes_value(true),
+                ),
+            )
+            .subcommand(App::new(COMPLETE_IDS).setting(AppSettings::Hidden));
+
+        let commands = commands(&app);
+        let paths: Vec<String> = commands.iter().map(|command| command.path("pcasts")).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "pcasts",
+                "pcasts__podcasts",
+                "pcasts__podcasts__keep",
+                "pcasts__episodes"
+            ]
+        );
+        assert_eq!(commands[1].id_flags, vec!["-r", "--remove"]);
+        assert!(commands[2].id_positional);
+        assert!(!commands[1].id_positional);
+        assert!(commands[3].id_flags.is_empty());
+
+        let mut output = Vec::new();
+        bash(&app, "pcasts", &mut output).unwrap();
+        let script = from_utf8(&output).unwrap();
+        // The generated function, which the wrapper calls
+        assert!(script.starts_with("_pcasts() {\n"));
+        assert!(script.contains("'pcasts__podcasts'|'pcasts__podcasts__keep'|'pcasts__episodes') path="));
+        assert!(script.contains("        'pcasts__podcasts:-r'|'pcasts__podcasts:--remove')\n"));
+        assert!(script.contains("        'pcasts__podcasts__keep:'*)\n"));
+        assert!(script.ends_with("complete -F _pcasts_podcast_ids -o bashdefault -o default 'pcasts'\n"));
+
+        let mut output = Vec::new();
+        zsh(&app, "pcasts", &mut output).unwrap();
+        let script = from_utf8(&output).unwrap();
+        assert!(script.starts_with("#compdef pcasts\n"));
+        assert!(script.contains("_pcasts() {\n"));
+        assert!(script.contains("compdef _pcasts_podcast_ids 'pcasts'\n"));
+        // Only the wrapper completes when the script is autoloaded
+        assert_eq!(script.matches("_pcasts \"$@\"").count(), 1);
+
+        let mut output = Vec::new();
+        fish(&app, "pcasts", &mut output).unwrap();
+        let script = from_utf8(&output).unwrap();
+        assert!(script.contains(
+            "complete -c pcasts -n \"__fish_seen_subcommand_from podcasts; and not __fish_seen_subcommand_from keep\" -l remove -x -a '(pcasts complete-ids)'\n"
+        ));
+        assert!(script.contains(
+            "complete -c pcasts -n \"__fish_seen_subcommand_from podcasts; and __fish_seen_subcommand_from keep\" -f -a '(pcasts complete-ids)'\n"
+        ));
+        assert!(!script.contains("-l podcast -x"));
+    }
+}
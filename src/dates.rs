@@ -0,0 +1,144 @@
+//! Helpers for working with the dates of feeds and the times pcasts saves
+use chrono::DateTime;
+use std::convert::TryFrom;
+
+/// Current unix time in seconds
+#[cfg(not(test))]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// The tests use a fixed time so that their output doesn't change between runs
+#[cfg(test)]
+pub fn now() -> u64 {
+    1_600_000_000
+}
+
+/// Parses an RFC 2822 date, as used in the pubDate of RSS items, into unix time in seconds
+pub fn parse_pub_date(pub_date: &str) -> Option<u64> {
+    let date = DateTime::parse_from_rfc2822(pub_date.trim()).ok()?;
+
+    u64::try_from(date.timestamp()).ok()
+}
+
+/// Parses a date like "2020-07-22" into the unix time of its start in UTC
+pub fn parse_date(date: &str) -> Option<u64> {
+    let date = chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()?;
+
+    u64::try_from(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp()).ok()
+}
+
+/// Formats the passed unix time as a date, e.g "2020-07-22"
+pub fn format_date(time: u64) -> String {
+    match i64::try_from(time)
+        .ok()
+        .and_then(|time| DateTime::from_timestamp(time, 0))
+    {
+        Some(date) => date.format("%Y-%m-%d").to_string(),
+        None => String::new(),
+    }
+}
+
+/// Formats the passed unix time as the date of an HTTP header, e.g "Wed, 22 Jul 2020 13:00:00 GMT"
+pub fn format_http_date(time: u64) -> String {
+    match i64::try_from(time)
+        .ok()
+        .and_then(|time| DateTime::from_timestamp(time, 0))
+    {
+        Some(date) => date.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+        None => String::new(),
+    }
+}
+
+/// Parses a UTC time like "2020-07-22T13:00:00", as used by the gpodder API, into unix time in
+/// seconds
+pub fn parse_iso_time(time: &str) -> Option<u64> {
+    let time = chrono::NaiveDateTime::parse_from_str(time.trim(), "%Y-%m-%dT%H:%M:%S").ok()?;
+
+    u64::try_from(time.and_utc().timestamp()).ok()
+}
+
+/// Formats the passed unix time as a UTC time like "2020-07-22T13:00:00"
+pub fn format_iso_time(time: u64) -> String {
+    match i64::try_from(time)
+        .ok()
+        .and_then(|time| DateTime::from_timestamp(time, 0))
+    {
+        Some(date) => date.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        None => String::new(),
+    }
+}
+
+/// Formats the time since the passed unix time, e.g "2d ago"
+pub fn relative_age(time: u64, now: u64) -> String {
+    let seconds = now.saturating_sub(time);
+    let (value, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (seconds / 60, "m"),
+        3_600..=86_399 => (seconds / 3_600, "h"),
+        86_400..=2_591_999 => (seconds / 86_400, "d"),
+        2_592_000..=31_535_999 => (seconds / 2_592_000, "mo"),
+        _ => (seconds / 31_536_000, "y"),
+    };
+
+    format!("{}{} ago", value, unit)
+}
+
+/// Parses the duration of an episode in seconds. accepts "HH:MM:SS", "MM:SS" and plain seconds
+pub fn parse_clock(duration: &str) -> Option<u64> {
+    let parts = duration
+        .trim()
+        .split(':')
+        .map(|part| part.trim().parse::<u64>().ok())
+        .collect::<Option<Vec<u64>>>()?;
+
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
+
+    Some(parts.iter().fold(0, |total, part| total * 60 + part))
+}
+
+/// Formats a duration in seconds like the feeds do, e.g "1:01:48" or "55:09"
+pub fn format_duration(duration: u64) -> String {
+    let (hours, minutes, seconds) = (duration / 3_600, duration % 3_600 / 60, duration % 60);
+
+    match hours {
+        0 => format!("{}:{:02}", minutes, seconds),
+        _ => format!("{}:{:02}:{:02}", hours, minutes, seconds),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_relative_age() {
+        let pub_date = parse_pub_date("Wed, 22 Jul 2020 13:00:00 +0000").expect("Can't parse date");
+        assert_eq!(pub_date, 1_595_422_800);
+
+        assert_eq!(relative_age(now() - 30, now()), "just now");
+        assert_eq!(relative_age(now() - 7_200, now()), "2h ago");
+        assert_eq!(relative_age(now() - 2 * 86_400, now()), "2d ago");
+        assert_eq!(relative_age(pub_date, now()), "1mo ago");
+        assert!(parse_pub_date("yesterday").is_none());
+        assert_eq!(format_date(pub_date), "2020-07-22");
+        assert_eq!(parse_date("2020-07-22"), Some(1_595_376_000));
+        assert_eq!(parse_date("22/07/2020"), None);
+    }
+
+    #[test]
+    fn dates_parse_clock() {
+        assert_eq!(parse_clock("01:01:48"), Some(3_708));
+        assert_eq!(parse_clock("55:09"), Some(3_309));
+        assert_eq!(parse_clock("1800"), Some(1_800));
+        assert_eq!(parse_clock("1:2:3:4"), None);
+        assert_eq!(format_duration(3_708), "1:01:48");
+        assert_eq!(format_duration(3_309), "55:09");
+        assert_eq!(parse_clock("an hour"), None);
+    }
+}
@@ -0,0 +1,408 @@
+use crate::{
+    crash,
+    episodes::Episode,
+    feed,
+    file_system::{FilePermissions, FileSystem},
+    podcasts::{read_podcasts, Podcast},
+    timings::Phase,
+    web::Web,
+    Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use std::{collections::HashMap, fs, io::Write, path::Path, sync::Arc, time};
+
+/// The directory inside the app directory which holds the raw feeds saved by `episodes update`
+pub const FEEDS_DIRECTORY: &str = "feeds";
+
+/// The fields of a feed item which are compared between two versions of a feed
+#[derive(Debug, PartialEq)]
+struct FeedItem {
+    title: String,
+    pub_date: String,
+    link: String,
+    enclosure: String,
+}
+
+impl FeedItem {
+    fn from_item(item: &rss::Item) -> (String, Self) {
+        let title = item.title().unwrap_or("-").to_string();
+        let link = item.link().unwrap_or("-").to_string();
+        let key = match item.guid() {
+            Some(guid) => guid.value().to_string(),
+            None => format!("{} {}", title, link),
+        };
+
+        let feed_item = Self {
+            title,
+            pub_date: item.pub_date().unwrap_or("-").to_string(),
+            link,
+            enclosure: item
+                .enclosure()
+                .map(|enclosure| enclosure.url())
+                .unwrap_or("-")
+                .to_string(),
+        };
+
+        (key, feed_item)
+    }
+
+    /// Names and values of the fields which differ from the other item
+    fn changes<'a>(&'a self, other: &'a FeedItem) -> Vec<(&'static str, &'a str, &'a str)> {
+        let fields = vec![
+            ("title", &self.title, &other.title),
+            ("pub_date", &self.pub_date, &other.pub_date),
+            ("link", &self.link, &other.link),
+            ("enclosure", &self.enclosure, &other.enclosure),
+        ];
+
+        fields
+            .into_iter()
+            .filter(|(_name, old, new)| old != new)
+            .map(|(name, old, new)| (name, old.as_str(), new.as_str()))
+            .collect()
+    }
+}
+
+/// Generates a synthetic library with the passed amount of podcasts, each with the passed amount of
+/// episodes. used for measuring the performance of reading and writing large libraries
+pub fn generate_library(podcasts: usize, episodes: usize) -> Vec<(Podcast, Vec<Episode>)> {
+    (0..podcasts)
+        .map(|podcast_index| {
+            let podcast = Podcast::new(
+                format!("https://example.com/podcast-{}", podcast_index),
+                format!("https://example.com/podcast-{}/rss", podcast_index),
+                format!("Podcast {}", podcast_index),
+            );
+
+            let podcast_episodes = (0..episodes)
+                .rev()
+                .map(|episode_index| Episode {
+                    guid: format!("{}-{}", podcast.id, episode_index),
+                    title: format!("Episode {} - A synthetic episode", episode_index),
+                    pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+                    link: format!("{}/episodes/{}", podcast.url, episode_index),
+                    podcast: podcast.title.clone(),
+                    podcast_id: podcast.id,
+                    enclosure_url: format!("{}/episodes/{}.mp3", podcast.url, episode_index),
+                    enclosure_type: "audio/mpeg".to_string(),
+                    enclosure_length: 50_000_000,
+                    number: episode_index as u64 + 1,
+                    first_seen: 0,
+                    duration: 3_600,
+                    season: 0,
+                    episode_number: 0,
+                    episode_type: String::new(),
+                    explicit: false,
+                    description: String::new(),
+                    published_at: 0,
+                    alternates: String::new(),
+                    people: String::new(),
+                    location: String::new(),
+                    soundbites: String::new(),
+                    chapters: String::new(),
+                    transcript: String::new(),
+                })
+                .collect();
+
+            (podcast, podcast_episodes)
+        })
+        .collect()
+}
+
+pub struct Debugger<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Debugger<'a> {
+    /// Constructs a new Debugger struct which is used to work with the sub command "debug"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if let Some(matches) = self.matches.subcommand_matches("diff-feed") {
+            // Always present because it's a required argument
+            let url = matches.value_of("url").unwrap();
+
+            let podcasts_list = FileSystem::new(
+                &self.config.app_directory,
+                "podcast_list.csv",
+                vec![FilePermissions::Read],
+            )
+            .open()?;
+            let podcast = read_podcasts(&podcasts_list, self.config.strict)?
+                .into_iter()
+                .find(|podcast| podcast.rss_url == url)
+                .ok_or_else(|| Errors::MissingSnapshot(url.to_string()))?;
+
+            let snapshot_path = self
+                .config
+                .app_directory
+                .join(FEEDS_DIRECTORY)
+                .join(format!("{}.xml", podcast.id));
+            let snapshot = std::fs::read(snapshot_path).map_err(|_error| Errors::MissingSnapshot(url.to_string()))?;
+
+            let (_url, current) = self
+                .config
+                .timings
+                .measure(Phase::Fetch, || {
+                    Web::new(time::Duration::from_secs(10), &self.config.http)
+                        .stats(&self.config.network)
+                        .progress(Arc::clone(&self.config.progress))
+                        .jobs(self.config.jobs)
+                        .get(&[url])
+                })
+                .pop()
+                .ok_or_else(|| Errors::NotFound(url.to_string()))?;
+            let current = current?;
+
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.diff_feed(&snapshot[..], &current[..], writer);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("gen-library") {
+            // Always present because they have default values
+            let podcasts = matches.value_of("podcasts").unwrap().parse::<usize>()?;
+            let episodes = matches.value_of("episodes").unwrap().parse::<usize>()?;
+            let directory = Path::new(matches.value_of("out").unwrap());
+
+            self.gen_library(podcasts, episodes, directory)?;
+            println!(
+                "Generated {} podcasts with {} episodes each. Use it with PODCASTS_DIR={}",
+                podcasts,
+                episodes,
+                directory.display()
+            );
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("report") {
+            let path = crash::latest(&self.config.app_directory).ok_or(Errors::MissingCrashReport)?;
+            let report = fs::read_to_string(&path)?;
+
+            match matches.value_of("out") {
+                Some(out) => {
+                    self.bundle_report(&report, fs::File::create(out)?)?;
+                    println!("Saved the report to {}. Attach it to a new issue", out);
+                }
+                None => {
+                    let writer = std::io::stdout();
+                    self.bundle_report(&report, writer.lock())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wraps a crash report in the body of an issue
+    fn bundle_report<W>(&self, report: &str, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        writeln!(writer, "### What were you doing when pcasts crashed?\n")?;
+        writeln!(writer, "<!-- Describe the steps which lead to the crash -->\n")?;
+        writeln!(writer, "### Crash report\n")?;
+        writeln!(writer, "```\n{}```", report)?;
+
+        Ok(())
+    }
+
+    /// Writes a synthetic library in the same layout as the app directory. fails if the directory
+    /// is the app directory itself, whose library would be replaced
+    fn gen_library(&self, podcasts: usize, episodes: usize, directory: &Path) -> Result<(), Errors> {
+        // Either of them may not exist yet, in which case there's no library to replace
+        if let (Ok(directory), Ok(app_directory)) = (directory.canonicalize(), self.config.app_directory.canonicalize())
+        {
+            if directory == app_directory {
+                return Err(Errors::AppDirectory(directory.display().to_string()));
+            }
+        }
+        fs::create_dir_all(directory)?;
+
+        let library = generate_library(podcasts, episodes);
+        let file = FileSystem::new(directory, "podcast_list.csv", vec![]).atomic()?;
+        let mut podcasts_writer = csv::Writer::from_writer(file);
+        for (podcast, episodes) in library {
+            let file = FileSystem::new(directory, &podcast.id.to_string(), vec![]).atomic()?;
+            let mut episodes_writer = csv::Writer::from_writer(file);
+            for episode in episodes {
+                episodes_writer.serialize(episode)?;
+            }
+            episodes_writer
+                .into_inner()
+                .map_err(|error| Errors::IO(error.into_error()))?
+                .commit()?;
+
+            podcasts_writer.serialize(podcast)?;
+        }
+        let file = podcasts_writer
+            .into_inner()
+            .map_err(|error| Errors::IO(error.into_error()))?;
+
+        Ok(file.commit()?)
+    }
+
+    /// Prints the items which were added, removed or changed between the stored snapshot of a feed
+    /// and its current version
+    fn diff_feed<W>(&self, snapshot: &[u8], current: &[u8], mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let snapshot = feed::read(snapshot)?;
+        let current = feed::read(current)?;
+
+        let old_items: HashMap<String, FeedItem> = snapshot.items().iter().map(FeedItem::from_item).collect();
+        let new_items: Vec<(String, FeedItem)> = current.items().iter().map(FeedItem::from_item).collect();
+
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+        for (key, item) in new_items.iter() {
+            match old_items.get(key) {
+                None => {
+                    added += 1;
+                    writeln!(writer, "{} {} ({})", "+".green(), item.title, key)?;
+                }
+                Some(old_item) => {
+                    let changes = old_item.changes(item);
+                    if changes.is_empty() {
+                        continue;
+                    }
+
+                    changed += 1;
+                    writeln!(writer, "{} {} ({})", "~".yellow(), item.title, key)?;
+                    for (name, old, new) in changes {
+                        writeln!(writer, "    {}: {} -> {}", name, old, new)?;
+                    }
+                }
+            }
+        }
+
+        for (key, item) in snapshot.items().iter().map(FeedItem::from_item) {
+            if new_items.iter().all(|(new_key, _item)| *new_key != key) {
+                removed += 1;
+                writeln!(writer, "{} {} ({})", "-".red(), item.title, key)?;
+            }
+        }
+
+        writeln!(writer, "{} added, {} removed, {} changed", added, removed, changed)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, Application, ApplicationBuilder};
+    use std::str::from_utf8;
+
+    fn create_app() -> Application {
+        ApplicationBuilder::new(fixtures::config()).debug_subcommand().build()
+    }
+
+    fn create_feed(items: &[(&str, &str)]) -> String {
+        let items: String = items
+            .iter()
+            .map(|(guid, title)| {
+                format!(
+                    "<item><guid>{}</guid><title>{}</title><pubDate>Wed, 22 Jul 2020 13:00:00 +0000</pubDate></item>",
+                    guid, title
+                )
+            })
+            .collect();
+
+        format!(
+            "<rss version=\"2.0\"><channel><title>Feed</title><link>https://example.com</link>{}</channel></rss>",
+            items
+        )
+    }
+
+    #[test]
+    fn diff_feed() {
+        let app = create_app();
+        let config = fixtures::config();
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "debug", "diff-feed", "https://feed.syntax.fm/rss"]);
+        let debug_matches = args.subcommand_matches("debug").expect("No debug matches");
+        let debugger = Debugger::new(debug_matches, &config);
+
+        let snapshot = create_feed(&[("1", "First"), ("2", "Second")]);
+        let current = create_feed(&[("2", "Second (updated)"), ("3", "Third")]);
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+
+        debugger
+            .diff_feed(snapshot.as_bytes(), current.as_bytes(), &mut output)
+            .expect("Can't diff feeds");
+
+        let expected_output = r###"~ Second (updated) (2)
+    title: Second -> Second (updated)
++ Third (3)
+- First (1)
+1 added, 1 removed, 1 changed
+"###;
+        assert_eq!(from_utf8(&output).unwrap(), expected_output);
+    }
+
+    #[test]
+    fn bundle_report() {
+        let app = create_app();
+        let config = fixtures::config();
+        let args = app.app.get_matches_from(vec!["pcasts", "debug", "report"]);
+        let debug_matches = args.subcommand_matches("debug").expect("No debug matches");
+        let debugger = Debugger::new(debug_matches, &config);
+
+        let mut output = Vec::new();
+        debugger
+            .bundle_report("pcasts crash report\nVersion: 0.1.2\n", &mut output)
+            .expect("Can't bundle report");
+
+        let output = from_utf8(&output).unwrap();
+        assert!(output.starts_with("### What were you doing when pcasts crashed?"));
+        assert!(output.ends_with("```\npcasts crash report\nVersion: 0.1.2\n```\n"));
+    }
+
+    #[test]
+    fn generate_library_sizes() {
+        let library = generate_library(3, 5);
+        let ids: HashMap<u64, usize> = library
+            .iter()
+            .map(|(podcast, episodes)| (podcast.id, episodes.len()))
+            .collect();
+
+        assert_eq!(ids.len(), 3);
+        assert!(ids.values().all(|count| *count == 5));
+    }
+
+    #[test]
+    fn gen_library_app_directory() {
+        let app = create_app();
+        let config = fixtures::config();
+        let args = app.app.get_matches_from(vec!["pcasts", "debug", "gen-library"]);
+        let debug_matches = args.subcommand_matches("debug").expect("No debug matches");
+        let debugger = Debugger::new(debug_matches, &config);
+
+        fs::create_dir_all(&config.app_directory).unwrap();
+        let podcasts_list = config.app_directory.join("podcast_list.csv");
+        fs::write(&podcasts_list, "id,url,rss_url,title\n").unwrap();
+
+        // The same directory through another path is still the app directory
+        let other_path = config
+            .app_directory
+            .join("..")
+            .join(config.app_directory.file_name().unwrap());
+        match debugger.gen_library(2, 2, &other_path) {
+            Err(Errors::AppDirectory(_path)) => {}
+            result => panic!("Expected the app directory to be rejected, got {:?}", result),
+        }
+        assert_eq!(fs::read_to_string(&podcasts_list).unwrap(), "id,url,rss_url,title\n");
+
+        let out = config.app_directory.join("generated");
+        debugger.gen_library(2, 2, &out).expect("Can't generate library");
+        assert!(out.join("podcast_list.csv").exists());
+    }
+}
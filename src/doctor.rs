@@ -0,0 +1,195 @@
+use crate::{
+    episodes::Episode,
+    file_system::{self, FileSystem},
+    podcasts::{load_podcasts, Podcast},
+    records,
+    units::parse_duration,
+    Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, io::Write};
+
+pub struct Doctor<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Doctor<'a> {
+    /// Constructs a new Doctor struct which is used to work with the sub command "doctor"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Checks the podcasts list and the episodes of every podcast for rows which can't be read.
+    /// with --fix, the files are written again without them
+    pub fn run(&self) -> Result<(), Errors> {
+        let fix = self.matches.is_present("fix");
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+
+        if self.matches.is_present("clean-temp") {
+            self.clean_temp(&mut writer)?;
+        }
+
+        let (podcasts, mut problems) = self.check::<Podcast, _>("podcast_list.csv", fix, &mut writer)?;
+        for podcast in podcasts.iter() {
+            let (_episodes, invalid) = self.check::<Episode, _>(&podcast.id.to_string(), fix, &mut writer)?;
+            problems += invalid;
+        }
+
+        match (problems, fix) {
+            (0, _) => writeln!(writer, "No problems found")?,
+            (problems, true) => writeln!(writer, "Removed {} malformed rows", problems)?,
+            (problems, false) => writeln!(
+                writer,
+                "Found {} malformed rows. Run `pcasts doctor --fix` to remove them",
+                problems
+            )?,
+        }
+
+        Ok(())
+    }
+
+    /// Removes the temporary files older than --older-than and the empty podcast directories
+    fn clean_temp<W>(&self, writer: &mut W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let age = match self.matches.value_of("older-than") {
+            Some(age) => parse_duration(age)?,
+            None => file_system::STALE_AGE,
+        };
+
+        let mut files = Vec::new();
+        for directory in self.config.temporary_directories()? {
+            files.extend(file_system::remove_stale_files(&directory, age)?);
+        }
+        // Only the directories of the podcasts are removed, others in the download directory may
+        // be the user's
+        let mut directories = Vec::new();
+        for podcast in load_podcasts(self.config)? {
+            if podcast.download_directory.is_empty() {
+                directories.extend(file_system::remove_empty_directories(
+                    &self.config.podcast_directory(podcast.id),
+                )?);
+            }
+        }
+        for path in files.iter().chain(directories.iter()) {
+            writeln!(writer, "Removed {}", path.display())?;
+        }
+        writeln!(
+            writer,
+            "Removed {} temporary files and {} empty directories",
+            files.len(),
+            directories.len()
+        )?;
+
+        Ok(())
+    }
+
+    /// Reports the invalid rows of the file with the passed name in the app directory, and writes
+    /// it again without them if fix is set. returns the valid records and the number of invalid rows
+    fn check<T, W>(&self, name: &str, fix: bool, writer: &mut W) -> Result<(Vec<T>, usize), Errors>
+    where
+        T: DeserializeOwned + Serialize,
+        W: Write,
+    {
+        let path = self.config.app_directory.join(name);
+        let contents = match fs::read(&path) {
+            Ok(contents) => contents,
+            // Podcasts which were never updated don't have an episodes file
+            Err(_error) => return Ok((Vec::new(), 0)),
+        };
+
+        let (records, invalid) = self.diagnose(name, &contents[..], writer)?;
+        if fix && invalid > 0 {
+            // The original is kept in case the rows can be fixed by hand
+            fs::write(self.config.app_directory.join(format!("{}.bak", name)), &contents)?;
+
+            let file = FileSystem::new(&self.config.app_directory, name, vec![]).atomic()?;
+            let mut csv_writer = csv::Writer::from_writer(file);
+            for record in records.iter() {
+                csv_writer.serialize(record)?;
+            }
+            let file = csv_writer
+                .into_inner()
+                .map_err(|error| Errors::IO(error.into_error()))?;
+            file.commit()?;
+        }
+
+        Ok((records, invalid))
+    }
+
+    /// Writes a line for each invalid row of the passed CSV
+    fn diagnose<T, W>(&self, name: &str, contents: &[u8], writer: &mut W) -> Result<(Vec<T>, usize), Errors>
+    where
+        T: DeserializeOwned,
+        W: Write,
+    {
+        let (records, invalid) = records::read_all(contents);
+        for row in invalid.iter() {
+            writeln!(
+                writer,
+                "{} {}: {}",
+                name.red(),
+                format!("line {}", row.line).red(),
+                row.error
+            )?;
+        }
+
+        Ok((records, invalid.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, ApplicationBuilder};
+
+    #[test]
+    fn doctor_diagnose() {
+        let config = fixtures::config();
+        let app = ApplicationBuilder::new(fixtures::config()).doctor_subcommand().build();
+        let args = app.app.get_matches_from(vec!["pcasts", "doctor"]);
+        let doctor = Doctor::new(args.subcommand_matches("doctor").unwrap(), &config);
+
+        let input = "guid,title,pub_date,link,podcast,podcast_id\n\
+                     1,First,\"Wed, 22 Jul 2020 13:00:00 +0000\",-,Podcast,1\n\
+                     2,Second,\"Wed, 22 Jul 2020 13:00:00 +0000\",-,Podcast,one\n";
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+
+        let (episodes, invalid): (Vec<Episode>, usize) = doctor
+            .diagnose("1", input.as_bytes(), &mut output)
+            .expect("Can't diagnose episodes");
+        assert_eq!((episodes.len(), invalid), (1, 1));
+        assert!(std::str::from_utf8(&output).unwrap().starts_with("1 line 3: "));
+    }
+
+    #[test]
+    fn doctor_temporary_directories() {
+        let mut config = fixtures::config();
+        let custom = config.app_directory.join("custom");
+        let list = format!(
+            "id,url,rss_url,title,download_directory\n\
+             1,-,https://a.com/rss,A,\n\
+             2,-,https://b.com/rss,B,{}\n",
+            custom.display()
+        );
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(config.app_directory.join("podcast_list.csv"), &list).unwrap();
+        config.set_podcast_settings(&load_podcasts(&config).unwrap());
+
+        // The rest of the download directory may be shared with other programs
+        assert_eq!(
+            config.temporary_directories().unwrap(),
+            vec![
+                config.app_directory.clone(),
+                config.download_directory.join("1"),
+                custom
+            ]
+        );
+    }
+}
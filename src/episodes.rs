@@ -1,449 +1,3693 @@
 use crate::{
-    file_system::{FilePermissions, FileSystem},
-    podcasts::Podcast,
-    web::Web,
+    art, clips, crash, dates,
+    debug::FEEDS_DIRECTORY,
+    events::{self, Event, EventKind},
+    feed,
+    file_system::{self, FilePermissions, FileSystem},
+    freshness,
+    headers::HeaderList,
+    ignore::{IgnoreList, RuleKind},
+    interrupt,
+    journal::Journal,
+    notify,
+    played::{self, PlayedEntry, PlayedList},
+    playlist, podcast_index,
+    podcasts::{load_podcasts, read_podcasts, write_podcasts, Podcast},
+    progress::{NoProgress, ProgressSink},
+    queue::{self, QueueEntry, Status},
+    records, resolver,
+    table::{self, Table},
+    tags, text,
+    timings::Phase,
+    topics::{self, TopicList},
+    units::parse_size,
+    variants::{self, VariantList},
+    verify::{self, ManifestEntry},
+    web::{Bandwidth, Web},
     Config, Errors,
 };
 use bytes::{Buf, Bytes};
 use clap::{ArgMatches, Values};
 use colored::*;
-use csv;
-use rss;
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
 use std::{
     collections::{HashMap, HashSet},
     fmt,
-    io::{self, Read, Write},
+    io::{self, BufRead, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
     time,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Episode {
-    guid: String,
-    title: String,
-    pub_date: String,
-    link: String,
-    podcast: String,
-    podcast_id: u64,
+    pub guid: String,
+    pub title: String,
+    pub pub_date: String,
+    pub link: String,
+    pub podcast: String,
+    pub podcast_id: u64,
+    // The audio file of the episode. missing in files saved by older versions
+    #[serde(default)]
+    pub enclosure_url: String,
+    #[serde(default)]
+    pub enclosure_type: String,
+    #[serde(default)]
+    pub enclosure_length: u64,
+    // Short id which is unique per podcast and doesn't change between updates
+    #[serde(default)]
+    pub number: u64,
+    // Unix time of the update which found the episode. 0 for episodes saved by older versions
+    #[serde(default)]
+    pub first_seen: u64,
+    // Length of the episode in seconds, from itunes:duration. 0 if unknown
+    #[serde(default)]
+    pub duration: u64,
+    // From itunes:season and itunes:episode. 0 if the feed doesn't number its episodes
+    #[serde(default)]
+    pub season: u64,
+    #[serde(default)]
+    pub episode_number: u64,
+    // From itunes:episodeType, e.g "full", "trailer" or "bonus". empty if unknown
+    #[serde(default)]
+    pub episode_type: String,
+    #[serde(default)]
+    pub explicit: bool,
+    // The show notes of the episode, usually HTML
+    #[serde(default)]
+    pub description: String,
+    // Unix time of the pub_date, parsed by the update. 0 if it couldn't be parsed or for episodes
+    // saved by older versions
+    #[serde(default)]
+    pub published_at: u64,
+    // The other versions of the enclosure from <podcast:alternateEnclosure>, separated by spaces.
+    // each one is "bitrate;length;type;url"
+    #[serde(default)]
+    pub alternates: String,
+    // The people of the episode from <podcast:person>, separated by semicolons. each one is
+    // "role:name"
+    #[serde(default)]
+    pub people: String,
+    // From <podcast:location>, "latitude,longitude;name". the coordinates are empty if the feed
+    // only names the place
+    #[serde(default)]
+    pub location: String,
+    // The highlights from <podcast:soundbite>, separated by semicolons. each one is
+    // "start,duration,title" in seconds
+    #[serde(default)]
+    pub soundbites: String,
+    // URLs of the chapters and of the transcript of the episode, from the feed or the Podcast
+    // Index. empty if neither has them
+    #[serde(default)]
+    pub chapters: String,
+    #[serde(default)]
+    pub transcript: String,
 }
 
-impl fmt::Display for Episode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut str = format!("{:14}{}\n", "Title:".green(), self.title);
-        str.push_str(&format!("{:14}{}\n", "Release date:".green(), self.pub_date));
-        str.push_str(&format!("{:14}{}\n", "ID:".green(), self.guid));
-        str.push_str(&format!("{:14}{}\n", "Link:".green(), self.link));
-        str.push_str(&format!("{:14}{}\n", "Podcast:".green(), self.podcast));
-        str.push_str(&format!("{:14}{}\n", "Podcast ID:".green(), self.podcast_id));
-        write!(f, "{}", str)
-    }
+/// Joins the alternate enclosures of an item into the column of the episode
+fn encode_alternates(alternates: &[feed::Alternate]) -> String {
+    alternates
+        .iter()
+        .map(|alternate| {
+            format!(
+                "{};{};{};{}",
+                alternate.bitrate, alternate.length, alternate.mime_type, alternate.url
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
 }
 
-pub struct Episodes<'a> {
-    matches: &'a ArgMatches,
-    config: &'a Config,
+/// Joins the people of an item into the column of the episode
+fn encode_people(people: &[feed::Person]) -> String {
+    people
+        .iter()
+        .map(|person| format!("{}:{}", person.role.replace(':', " "), person.name.replace(';', ",")))
+        .collect::<Vec<String>>()
+        .join(";")
 }
 
-impl<'a> Episodes<'a> {
-    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
-        Self { matches, config }
+/// Joins the location of an item into the column of the episode
+fn encode_location(location: Option<&feed::Location>) -> String {
+    match location {
+        Some(location) => {
+            let coordinates = location
+                .coordinates
+                .map(|(latitude, longitude)| format!("{},{}", latitude, longitude))
+                .unwrap_or_default();
+            format!("{};{}", coordinates, location.name)
+        }
+        None => String::new(),
     }
+}
 
-    pub fn run(&self) -> Result<(), Errors> {
-        if let Some(matches) = self.matches.subcommand_matches("update") {
-            let podcasts_list = FileSystem::new(
-                &self.config.app_directory,
-                "podcast_list.csv",
-                vec![FilePermissions::Read],
+/// Joins the soundbites of an item into the column of the episode
+fn encode_soundbites(soundbites: &[feed::Soundbite]) -> String {
+    soundbites
+        .iter()
+        .map(|soundbite| {
+            format!(
+                "{},{},{}",
+                soundbite.start_time,
+                soundbite.duration,
+                soundbite.title.replace(';', ",")
             )
-            .open()?;
+        })
+        .collect::<Vec<String>>()
+        .join(";")
+}
 
-            if let Some(ids) = matches.values_of("id") {
-                let ids: HashSet<u64> = ids.flat_map(|id| id.parse::<u64>()).collect();
-                let mut reader = csv::Reader::from_reader(&podcasts_list);
-                let podcasts: Vec<Podcast> = reader
-                    .deserialize()
-                    .filter_map(|item: Result<Podcast, csv::Error>| item.ok())
-                    .filter(|podcast| ids.contains(&podcast.id))
-                    .collect();
+/// Sorts the episodes by their release, newest first like the feeds. the guid breaks ties, and
+/// episodes without a date are kept last so the order doesn't depend on the order of the file
+fn sort_newest_first(episodes: &mut [Episode]) {
+    episodes.sort_by(|first, second| {
+        let first_time = first.release_time();
+        let second_time = second.release_time();
+        match (first_time, second_time) {
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            _ => second_time.cmp(&first_time).then_with(|| first.guid.cmp(&second.guid)),
+        }
+    });
+}
 
-                let mut files = HashMap::new();
-                for podcast in podcasts.iter() {
-                    let file = FileSystem::new(
-                        &self.config.app_directory,
-                        &podcast.id.to_string(),
-                        vec![FilePermissions::Write],
-                    )
-                    .open();
+/// Orders the episodes for a listing by the passed --sort key. the default date order lists the
+/// newest episode last, right above the prompt. the ties of the other keys keep the date order
+fn listing_order(mut episodes: Vec<Episode>, sort: Option<&str>, reverse: bool) -> Vec<Episode> {
+    sort_newest_first(&mut episodes);
+    episodes.reverse();
 
-                    if let Err(error) = file {
-                        println!("Can't open file for podcast {}. {}", podcast.title, error);
-                        continue;
-                    }
+    match sort {
+        Some("title") => episodes.sort_by_cached_key(|episode| text::fold(&episode.title)),
+        Some("duration") => episodes.sort_by_key(|episode| episode.duration),
+        _ => {}
+    }
+    if reverse {
+        episodes.reverse();
+    }
 
-                    files.insert(podcast.id, file.unwrap());
-                }
+    episodes
+}
 
-                return self.update(&podcasts, &mut files);
-            }
-        }
+/// The URL a feed moved to, from its <itunes:new-feed-url> or else from permanent redirects. None
+/// if it stayed at the passed URL
+pub fn feed_move(rss_url: &str, new_feed_url: Option<&str>, redirected: Option<String>) -> Option<String> {
+    let new_feed_url = new_feed_url
+        .map(|url| url.trim())
+        .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+        .map(|url| url.to_string());
 
-        if let Some(matches) = self.matches.subcommand_matches("list") {
-            match matches.values_of("id") {
-                // Ids were passed as arguments to the list subcommand
-                Some(ids) => {
-                    let files: Vec<(u64, File)> = ids
-                        .flat_map(|id| {
-                            let file =
-                                FileSystem::new(&self.config.app_directory, id, vec![FilePermissions::Read]).open();
-                            let file_id = id.parse::<u64>();
-                            if file.is_err() || file_id.is_err() {
-                                return None;
-                            }
+    new_feed_url
+        .or(redirected)
+        .filter(|url| url.trim_end_matches('/') != rss_url.trim_end_matches('/'))
+}
 
-                            Some((file_id.unwrap(), file.unwrap()))
-                        })
-                        .collect();
+/// Maximum length in bytes of each value which is put in a file name
+const MAX_NAME_LENGTH: usize = 120;
 
-                    for file in files {
-                        let writer = std::io::stdout();
-                        let writer = writer.lock();
+/// Makes the passed value safe to use as part of a file name. replaces path separators and
+/// characters which aren't allowed on some file systems and shortens long values
+fn sanitize(value: &str) -> String {
+    let value: String = text::compose(value)
+        .chars()
+        .map(|char| match char {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            char if char.is_control() => '_',
+            char => char,
+        })
+        .collect();
 
-                        if let Err(error) = self.list(file.1, writer) {
-                            return Err(error);
-                        }
-                    }
-                }
-                // No Ids were passed. list all the episodes of all the saved podcasts
-                None => {
-                    let podcasts_list = FileSystem::new(
-                        &self.config.app_directory,
-                        "podcast_list.csv",
-                        vec![FilePermissions::Read],
-                    )
-                    .open()?;
-                    let mut reader = csv::Reader::from_reader(&podcasts_list);
-
-                    // The files with the same as id as the the passed id arguments
-                    let files: Vec<(u64, File)> = reader
-                        .deserialize()
-                        .filter_map(|item: Result<Podcast, csv::Error>| {
-                            if item.is_err() {
-                                return None;
-                            }
-                            let podcast = item.unwrap();
-                            let file = FileSystem::new(
-                                &self.config.app_directory,
-                                &podcast.id.to_string(),
-                                vec![FilePermissions::Read],
-                            )
-                            .open();
-                            if file.is_err() {
-                                return None;
-                            }
-                            Some((podcast.id, file.unwrap()))
-                        })
-                        .collect();
+    let mut length = 0;
+    let value: String = value
+        .trim()
+        .trim_start_matches('.')
+        .chars()
+        .take_while(|char| {
+            length += char.len_utf8();
+            length <= MAX_NAME_LENGTH
+        })
+        .collect();
+    let value = value.trim_end_matches(|char: char| char == '.' || char.is_whitespace());
 
-                    for file in files {
-                        let writer = std::io::stdout();
-                        let writer = writer.lock();
+    if value.is_empty() {
+        "_".to_string()
+    } else {
+        value.to_string()
+    }
+}
 
-                        return self.list(file.1, writer);
-                    }
-                }
-            }
-        }
+/// The file in the app directory which holds the time of the last `episodes list`
+const LAST_LISTED_FILE: &str = "last_listed";
 
-        if let Some(matches) = self.matches.subcommand_matches("download") {
-            // Always present because it's a required argument
-            let podcast_id = matches.value_of("id").unwrap();
-            let episodes_file =
-                FileSystem::new(&self.config.app_directory, podcast_id, vec![FilePermissions::Read]).open();
+/// Reads the saved episodes of a podcast. invalid rows are skipped with a warning unless strict
+/// is set
+pub fn read_episodes<R>(reader: R, strict: bool) -> Result<Vec<Episode>, Errors>
+where
+    R: Read,
+{
+    let (episodes, invalid): (Vec<Episode>, _) = records::read_all(reader);
+    // All the rows of a file belong to the same podcast
+    let name = match episodes.first() {
+        Some(episode) => format!("the episodes of {} ({})", episode.podcast, episode.podcast_id),
+        None => "an episodes file".to_string(),
+    };
+    records::check(invalid, &name, strict)?;
 
-            if episodes_file.is_err() {
-                return Err(Errors::WrongID(podcast_id.to_string()));
-            }
+    Ok(episodes)
+}
 
-            let episodes_file = episodes_file.unwrap();
-            match matches.values_of("episode-id") {
-                Some(ids) => {
-                    let files_data = self.download(Some(&ids), episodes_file, None)?;
-                    for (file_name, content) in files_data {
-                        let mut file = FileSystem::new(
-                            &self.config.download_directory,
-                            &file_name,
-                            vec![FilePermissions::Write],
-                        )
-                        .open()?;
-                        file.write_all(content.bytes())?;
-                    }
+/// The file in the app directory which marks that the downloads were moved to the directories of
+/// their podcasts
+const MIGRATED_DOWNLOADS_FILE: &str = "downloads_migrated";
+
+/// Moves the episodes which older versions downloaded right into the download directory to the
+/// directory of their podcast, where the commands look for them now. it runs once, and returns
+/// the number of the moved files
+pub(crate) fn migrate_flat_downloads(config: &Config) -> Result<usize, Errors> {
+    let marker = config.app_directory.join(MIGRATED_DOWNLOADS_FILE);
+    if marker.exists() {
+        return Ok(0);
+    }
+
+    let mut moved = 0;
+    // Nothing was downloaded without saved podcasts
+    if let Ok(contents) = fs::read(config.app_directory.join("podcast_list.csv")) {
+        // The podcasts which set their own download directory never had flat downloads
+        let podcasts = read_podcasts(&contents[..], false)?
+            .into_iter()
+            .filter(|podcast| podcast.download_directory.is_empty());
+        for podcast in podcasts {
+            let episodes = match fs::read(config.app_directory.join(podcast.id.to_string())) {
+                Ok(contents) => read_episodes(&contents[..], false)?,
+                // The podcast was never updated
+                Err(_error) => continue,
+            };
+
+            let podcast_directory = config.podcast_directory(podcast.id);
+            for episode in episodes {
+                let file_name = episode.file_name(config.podcast_file_template(podcast.id));
+                let old_path = config.download_directory.join(&file_name);
+                let new_path = podcast_directory.join(&file_name);
+                if !old_path.is_file() || new_path.exists() {
+                    continue;
                 }
-                // --list or --count arguments may be present
-                None => {
-                    let list_present = matches.is_present("list");
-                    let count = matches.value_of("count");
-                    let count = if count.is_none() {
-                        None
-                    } else {
-                        Some(count.unwrap().parse::<usize>()?)
-                    };
 
-                    match list_present {
-                        // List downloaded episodes for the podcast. use count to indicate how many episodes
-                        // to list
-                        true => {
-                            let dir_files =
-                                fs::read_dir(&self.config.download_directory).map_err(|error| Errors::IO(error))?;
-
-                            let mut downloaded_episodes = Vec::new();
-                            for dir_entry in dir_files {
-                                let path = dir_entry?.path();
-                                let entry = path
-                                    .file_name()
-                                    .ok_or(Errors::IO(io::Error::new(
-                                        io::ErrorKind::Other,
-                                        "Couldn't get file name",
-                                    )))?
-                                    .to_str();
-                                if let Some(entry) = entry {
-                                    downloaded_episodes.push(entry.to_string());
-                                }
-                            }
-                            let writer = std::io::stdout();
-                            let writer = writer.lock();
-                            return self.list_downloaded(episodes_file, downloaded_episodes, writer, count);
-                        }
-                        false => {
-                            let files_data = self.download(None, episodes_file, count)?;
-                            for (file_name, content) in files_data {
-                                let mut file = FileSystem::new(
-                                    &self.config.download_directory,
-                                    &file_name,
-                                    vec![FilePermissions::Write],
-                                )
-                                .open()?;
-                                file.write_all(content.bytes())?;
-                            }
-                        }
-                    }
+                // The template may put the file in a subdirectory
+                if let Some(directory) = new_path.parent() {
+                    fs::create_dir_all(directory)?;
                 }
+                fs::rename(old_path, new_path)?;
+                moved += 1;
             }
         }
+    }
 
-        Ok(())
+    fs::create_dir_all(&config.app_directory)?;
+    fs::write(marker, "")?;
+
+    Ok(moved)
+}
+
+/// The directory which holds the names of the removed downloads of each podcast, so `sync`
+/// doesn't download them again
+pub const ARCHIVE_DIRECTORY: &str = "archive";
+
+/// The subdirectory of a podcast which holds the enclosures that aren't audio, like the PDFs and
+/// images of bonus content
+const ATTACHMENTS_DIRECTORY: &str = "attachments";
+
+/// The directory in the podcast directory which the soundbites are cut out to
+const SOUNDBITES_DIRECTORY: &str = "soundbites";
+
+/// `episodes download --match` asks before downloading more than this many episodes, since a
+/// short query can match most of a podcast
+const CONFIRM_MATCHES: usize = 5;
+
+/// Returns the file extension for the passed MIME type of an enclosure
+fn mime_extension(mime_type: &str) -> Option<&'static str> {
+    let extension = match mime_type.split(';').next().unwrap_or("").trim() {
+        "audio/mpeg" | "audio/mp3" | "audio/mpeg3" | "audio/x-mpeg" => "mp3",
+        "audio/mp4" | "audio/m4a" | "audio/x-m4a" => "m4a",
+        "audio/aac" | "audio/x-aac" => "aac",
+        "audio/ogg" | "audio/vorbis" => "ogg",
+        "audio/opus" => "opus",
+        "audio/flac" | "audio/x-flac" => "flac",
+        "audio/wav" | "audio/x-wav" => "wav",
+        "video/mp4" => "mp4",
+        "video/x-m4v" => "m4v",
+        "video/quicktime" => "mov",
+        _ => return None,
+    };
+
+    Some(extension)
+}
+
+/// Returns the file extension for the passed MIME type of an enclosure which isn't audio
+fn attachment_extension(mime_type: &str) -> Option<&'static str> {
+    let extension = match mime_type.split(';').next().unwrap_or("").trim() {
+        "application/pdf" => "pdf",
+        "application/epub+zip" => "epub",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "text/plain" => "txt",
+        "text/html" => "html",
+        _ => return None,
+    };
+
+    Some(extension)
+}
+
+/// Detects the MIME type of downloaded content which isn't audio from its first bytes. feeds
+/// often label bonus content as audio/mpeg
+fn sniff_attachment(bytes: &[u8]) -> Option<&'static str> {
+    let mime_type = if bytes.starts_with(b"%PDF-") {
+        "application/pdf"
+    } else if bytes.starts_with(b"\x89PNG\r\n") {
+        "image/png"
+    } else if bytes.starts_with(b"\xFF\xD8\xFF") {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        "image/gif"
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(&b"WEBP"[..]) {
+        "image/webp"
+    } else {
+        return None;
+    };
+
+    Some(mime_type)
+}
+
+impl Episode {
+    /// Parses the episodes out of the passed RSS or Atom feed
+    pub fn parse_feed(bytes: &[u8], podcast_id: u64) -> Result<Vec<Episode>, Errors> {
+        let rss_channel = feed::read(bytes)?;
+
+        Ok(Self::from_channel(&rss_channel, bytes, podcast_id))
     }
 
-    pub fn update<T>(&self, podcasts: &Vec<Podcast>, writers: &mut HashMap<u64, T>) -> Result<(), Errors>
-    where
-        T: Write,
-    {
-        let urls_map: HashMap<&str, u64> = podcasts
-            .iter()
-            .map(|podcast| (podcast.rss_url.as_str(), podcast.id))
-            .collect();
+    /// The episodes of the channel which was read out of the passed feed, for the callers which
+    /// need the channel too
+    pub fn from_channel(rss_channel: &rss::Channel, bytes: &[u8], podcast_id: u64) -> Vec<Episode> {
+        let podcast_title = rss_channel.title();
+        let metadata = feed::item_metadata(bytes);
 
-        let urls: Vec<&str> = podcasts.iter().map(|podcast| podcast.rss_url.as_str()).collect();
+        // We collect guid, pub_date, title, link from the rss feed for each item
+        let episodes = rss_channel
+            .items()
+            .iter()
+            .filter_map(|item| {
+                let guid = item.guid();
+                let pub_date = item.pub_date();
+                let title = item.title();
+                let link = item.link();
+                let enclosure = item.enclosure();
 
-        for (url, bytes) in Web::new(time::Duration::from_secs(10)).get(&urls) {
-            let bytes = bytes?;
-            let rss_channel = rss::Channel::read_from(&bytes[..]);
-            if rss_channel.is_err() {
-                continue;
-            }
-            let rss_channel = rss_channel.unwrap();
+                match (guid, pub_date, title, link) {
+                    (Some(guid), Some(pub_date), Some(title), link) => {
+                        let metadata = metadata.get(guid.value()).cloned().unwrap_or_default();
+                        if enclosure.is_none() {
+                            tracing::debug!("{} of {} has no enclosure", title, podcast_title);
+                        }
 
-            let podcast_title = rss_channel.title();
-            let podcast_id = urls_map.get(url).ok_or(Errors::RSS)?;
-            // We collect guid, pub_date, title, link from the rss feed for each item
-            let items: Vec<Episode> = rss_channel
-                .items()
-                .iter()
-                .filter_map(|item| {
-                    let guid = item.guid();
-                    let pub_date = item.pub_date();
-                    let title = item.title();
-                    let link = item.link();
-
-                    match (guid, pub_date, title, link) {
-                        (Some(guid), Some(pub_date), Some(title), link) => Some(Episode {
+                        Some(Episode {
                             guid: guid.value().to_string(),
                             pub_date: pub_date.to_string(),
                             title: title.to_string(),
                             link: link.unwrap_or("-").to_string(),
                             podcast: podcast_title.to_string(),
-                            podcast_id: *podcast_id,
-                        }),
-                        _ => None,
+                            podcast_id,
+                            enclosure_url: enclosure.map(|enclosure| enclosure.url()).unwrap_or("").to_string(),
+                            enclosure_type: enclosure
+                                .map(|enclosure| enclosure.mime_type())
+                                .unwrap_or("")
+                                .to_string(),
+                            enclosure_length: enclosure
+                                .and_then(|enclosure| enclosure.length().trim().parse::<u64>().ok())
+                                .unwrap_or(0),
+                            number: 0,
+                            first_seen: 0,
+                            duration: item
+                                .itunes_ext()
+                                .and_then(|itunes| itunes.duration())
+                                .and_then(dates::parse_clock)
+                                .unwrap_or(0),
+                            season: metadata.season,
+                            episode_number: metadata.episode,
+                            episode_type: metadata.episode_type,
+                            explicit: item
+                                .itunes_ext()
+                                .and_then(|itunes| itunes.explicit())
+                                .is_some_and(|explicit| {
+                                    let explicit = explicit.trim().to_lowercase();
+                                    explicit == "yes" || explicit == "true" || explicit == "explicit"
+                                }),
+                            // content:encoded usually has the full show notes and the description a summary
+                            description: item
+                                .content()
+                                .or_else(|| item.description())
+                                .or_else(|| item.itunes_ext().and_then(|itunes| itunes.summary()))
+                                .unwrap_or("")
+                                .to_string(),
+                            published_at: dates::parse_pub_date(pub_date).unwrap_or(0),
+                            alternates: encode_alternates(&metadata.alternates),
+                            people: encode_people(&metadata.people),
+                            location: encode_location(metadata.location.as_ref()),
+                            soundbites: encode_soundbites(&metadata.soundbites),
+                            chapters: metadata.chapters,
+                            transcript: metadata.transcript,
+                        })
                     }
-                })
-                .collect();
+                    (guid, pub_date, _title, _link) => {
+                        tracing::debug!(
+                            "Skipped an item of {} without a {}",
+                            podcast_title,
+                            if guid.is_none() {
+                                "guid"
+                            } else if pub_date.is_none() {
+                                "publication date"
+                            } else {
+                                "title"
+                            },
+                        );
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<Episode>>();
+        tracing::debug!("Parsed {} episodes of {}", episodes.len(), podcast_title);
 
-            let writer = writers.get_mut(podcast_id).ok_or(Errors::RSS)?;
-            let mut csv_writer = csv::WriterBuilder::new().has_headers(true).from_writer(writer);
+        episodes
+    }
 
-            for item in items {
-                csv_writer.serialize(item)?;
-            }
+    /// Unix time of the release of the episode. None if the feed's date can't be parsed
+    pub fn release_time(&self) -> Option<u64> {
+        match self.published_at {
+            0 => dates::parse_pub_date(&self.pub_date),
+            published_at => Some(published_at),
+        }
+    }
 
-            csv_writer.flush()?;
+    /// Whether the passed id is the guid or the short id of the episode
+    pub fn matches_id(&self, id: &str) -> bool {
+        if id == self.guid {
+            return true;
         }
 
-        Ok(())
+        match id.parse::<u64>() {
+            Ok(number) => self.number > 0 && number == self.number,
+            Err(_error) => false,
+        }
     }
 
-    pub fn list<R, W>(&self, reader: R, mut writer: W) -> Result<(), Errors>
-    where
-        R: Read,
-        W: Write,
-    {
-        let mut csv_reader = csv::Reader::from_reader(reader);
-        let episodes: Vec<Episode> = csv_reader
-            .deserialize()
-            .filter_map(|item: Result<Episode, csv::Error>| item.ok())
-            .collect();
-        for episode in episodes.iter().rev() {
-            writeln!(writer, "{}", episode)?;
+    /// The URL of the audio file. episodes saved by older versions only have the link
+    pub fn download_url(&self) -> &str {
+        if self.enclosure_url.is_empty() {
+            &self.link
+        } else {
+            &self.enclosure_url
         }
+    }
 
-        Ok(())
+    /// The alternate enclosures of the episode. entries which can't be parsed are skipped
+    pub fn alternates(&self) -> Vec<feed::Alternate> {
+        self.alternates
+            .split_whitespace()
+            .filter_map(|alternate| {
+                let mut fields = alternate.splitn(4, ';');
+                Some(feed::Alternate {
+                    bitrate: fields.next()?.parse().ok()?,
+                    length: fields.next()?.parse().ok()?,
+                    mime_type: fields.next()?.to_string(),
+                    url: fields.next()?.to_string(),
+                })
+            })
+            .collect()
     }
 
-    pub fn download<R>(
-        &self,
-        ids: Option<&Values>,
-        reader: R,
-        count: Option<usize>,
-    ) -> Result<Vec<(String, Bytes)>, Errors>
-    where
-        R: Read,
-    {
-        let mut csv_reader = csv::Reader::from_reader(reader);
-        let episode_ids: Option<Vec<&str>> = if ids.is_none() {
-            None
-        } else {
-            Some(ids.unwrap().clone().collect())
-        };
+    /// The people of the episode, e.g its hosts and guests
+    pub fn people(&self) -> Vec<feed::Person> {
+        self.people
+            .split(';')
+            .filter_map(|person| {
+                let (role, name) = person.split_once(':')?;
+                Some(feed::Person {
+                    name: name.trim().to_string(),
+                    role: role.trim().to_string(),
+                })
+            })
+            .filter(|person| !person.name.is_empty())
+            .collect()
+    }
 
-        let episodes: Vec<Episode> = csv_reader
-            .deserialize()
-            .filter_map(|item: Result<Episode, csv::Error>| item.ok())
-            .filter(|episode| {
-                // Download all the episodes if no ids were provided
-                if episode_ids.is_none() {
-                    return true;
-                }
+    /// Where the episode takes place or what place it's about. None if the feed doesn't tell
+    pub fn location(&self) -> Option<feed::Location> {
+        let (coordinates, name) = self.location.split_once(';')?;
+        let coordinates = coordinates
+            .split_once(',')
+            .and_then(|(latitude, longitude)| Some((latitude.parse::<f64>().ok()?, longitude.parse::<f64>().ok()?)));
+
+        Some(feed::Location {
+            name: name.to_string(),
+            coordinates,
+        })
+    }
+
+    /// The highlighted moments of the episode, in the order of the feed
+    pub fn soundbites(&self) -> Vec<feed::Soundbite> {
+        self.soundbites
+            .split(';')
+            .filter_map(|soundbite| {
+                let (start_time, rest) = soundbite.split_once(',')?;
+                let (duration, title) = rest.split_once(',')?;
+                Some(feed::Soundbite {
+                    start_time: start_time.parse().ok()?,
+                    duration: duration.parse().ok()?,
+                    title: title.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Switches to the alternate enclosure which suits the connection. a metered connection takes
+    /// the smallest one and a max size the largest one under it. the enclosure of the feed is kept
+    /// when the episode has no alternates of the same kind or their sizes are unknown
+    pub fn select_enclosure(&mut self, bandwidth: Bandwidth, max_size: Option<u64>) {
+        if bandwidth == Bandwidth::Unmetered && max_size.is_none() {
+            return;
+        }
 
-                episode_ids.as_ref().unwrap().iter().any(|id| *id == episode.guid)
+        // Audio isn't swapped for video or the other way around
+        let kind = |mime_type: &str| mime_type.split('/').next().unwrap_or("").to_string();
+        let mut candidates: Vec<feed::Alternate> = self
+            .alternates()
+            .into_iter()
+            .filter(|alternate| {
+                self.enclosure_type.is_empty() || kind(&alternate.mime_type) == kind(&self.enclosure_type)
             })
             .collect();
-        let episodes_count = episodes.len();
+        if candidates.is_empty() {
+            return;
+        }
+        candidates.push(feed::Alternate {
+            url: self.enclosure_url.clone(),
+            mime_type: self.enclosure_type.clone(),
+            length: self.enclosure_length,
+            bitrate: 0,
+        });
 
-        // Take count amount of episodes if needed
-        let episodes_map: HashMap<String, Episode> = episodes
+        // The length, or an estimate from the bitrate and the duration
+        let size = |alternate: &feed::Alternate| match alternate.length {
+            0 if alternate.bitrate > 0 && self.duration > 0 => Some(alternate.bitrate * self.duration / 8),
+            0 => None,
+            length => Some(length),
+        };
+        let mut sized: Vec<(u64, feed::Alternate)> = candidates
             .into_iter()
-            .take(count.unwrap_or(episodes_count))
-            .map(|episode| (episode.link.clone(), episode))
+            .filter_map(|alternate| size(&alternate).map(|size| (size, alternate)))
             .collect();
-        let episode_urls: Vec<&str> = episodes_map.keys().map(|key| key.as_str()).collect();
+        sized.sort_by_key(|(size, _alternate)| *size);
 
-        let mut files_data = Vec::new();
-        for (url, bytes) in Web::new(time::Duration::from_secs(0)).get(&episode_urls) {
-            let bytes = bytes?;
-            let episode = episodes_map.get(url).unwrap();
-            let file_name = format!("{}_{}.mp3", episode.podcast, episode.title);
-            files_data.push((file_name, bytes));
+        let selected = match (bandwidth, max_size) {
+            (Bandwidth::Unmetered, Some(max_size)) => sized
+                .iter()
+                .rev()
+                .find(|(size, _alternate)| *size <= max_size)
+                .or_else(|| sized.first()),
+            _ => sized.first(),
+        };
+        if let Some((_size, alternate)) = selected {
+            self.enclosure_url = alternate.url.clone();
+            self.enclosure_type = alternate.mime_type.clone();
+            // The estimates aren't kept, the length is what `pcasts verify` expects the file to have
+            self.enclosure_length = alternate.length;
         }
+    }
 
-        Ok(files_data)
+    /// Whether the enclosure is bonus content, like a PDF or an image, instead of audio
+    pub fn is_attachment(&self) -> bool {
+        attachment_extension(&self.enclosure_type).is_some()
     }
 
-    fn list_downloaded<R, W>(
-        &self,
-        episodes: R,
-        downloaded_episodes: Vec<String>,
-        mut writer: W,
-        count: Option<usize>,
-    ) -> Result<(), Errors>
-    where
-        R: Read,
-        W: Write,
-    {
-        let mut csv_reader = csv::Reader::from_reader(episodes);
-        let episodes: Vec<Episode> = csv_reader
-            .deserialize()
-            .filter_map(|item: Result<Episode, csv::Error>| item.ok())
-            .filter(|episode| {
-                let file_name = format!("{}_{}.mp3", episode.podcast, episode.title);
-                downloaded_episodes.contains(&file_name)
-            })
-            .collect();
+    /// The file extension of the audio file, based on the MIME type of the enclosure. falls back
+    /// to the extension in the URL and then to mp3
+    pub fn extension(&self) -> String {
+        if let Some(extension) =
+            mime_extension(&self.enclosure_type).or_else(|| attachment_extension(&self.enclosure_type))
+        {
+            return extension.to_string();
+        }
 
-        for (index, episode) in episodes.iter().rev().enumerate() {
-            if let Some(count) = count {
-                if index < count {
-                    continue;
-                }
-            }
+        let url = self.download_url().split(['?', '#']).next().unwrap_or("");
+        match url.rsplit('/').next().and_then(|name| name.rsplit_once('.')) {
+            Some((_name, extension)) if !extension.is_empty() && extension.len() <= 4 => extension.to_lowercase(),
+            _ => "mp3".to_string(),
+        }
+    }
 
-            writeln!(writer, "{}", episode)?;
+    /// The duration in seconds. estimated from the size of the audio file at 128 kbps when the
+    /// feed doesn't have it
+    pub fn estimated_duration(&self) -> u64 {
+        if self.duration > 0 {
+            return self.duration;
         }
 
-        Ok(())
+        self.enclosure_length / 16_000
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{Application, ApplicationBuilder};
-    use clap::{App, Arg};
-    use std::path::PathBuf;
-    use std::str::from_utf8;
+    /// The path, relative to the download directory, which the episode is saved to. the
+    /// placeholders of the template are replaced by the sanitized values of the episode.
+    /// attachments are saved in their own subdirectory
+    pub fn file_name(&self, template: &str) -> String {
+        let pub_date = match dates::parse_pub_date(&self.pub_date) {
+            Some(pub_date) => dates::format_date(pub_date),
+            None => self.pub_date.clone(),
+        };
 
-    fn create_config() -> Config {
-        let app_directory = "/Users/dmitryshur/.podcasts";
-        let download_directory = "/Users/dmitryshur/.podcasts/downloads";
+        // Each part of the path is built separately, so values can't add directories
+        let path = template
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .map(|part| {
+                part.replace("{podcast}", &sanitize(&self.podcast))
+                    .replace("{title}", &sanitize(&self.title))
+                    .replace("{pub_date}", &sanitize(&pub_date))
+                    .replace("{guid}", &sanitize(&self.guid))
+                    .replace("{number}", &self.number.to_string())
+                    .replace("{season}", &format!("{:02}", self.season))
+                    .replace("{episode}", &format!("{:02}", self.episode_number))
+                    .replace("{ext}", &self.extension())
+            })
+            .collect::<Vec<String>>()
+            .join("/");
 
-        Config {
-            app_directory: PathBuf::from(app_directory),
-            download_directory: PathBuf::from(download_directory),
+        if self.is_attachment() {
+            return format!("{}/{}", ATTACHMENTS_DIRECTORY, path);
         }
-    }
 
-    fn create_app() -> Application {
-        let config = create_config();
-        ApplicationBuilder::new(config).episodes_subcommand().build()
+        path
     }
+}
 
-    #[test]
-    fn update() {
-        let app = create_app();
-        let config = create_config();
-        let args = app
-            .app
-            .get_matches_from(vec!["pcasts", "episodes", "update", "--id", "15913066141282366353"]);
-        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
-        let episodes = Episodes::new(&episodes_matches, &config);
-        let podcasts = vec![Podcast {
-            id: 15913066141282366353,
-            url: "https://syntax.fm".to_string(),
+impl fmt::Display for Episode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut str = format!("{:14}{}\n", "Title:".green(), self.title);
+        match dates::parse_pub_date(&self.pub_date) {
+            Some(pub_date) => str.push_str(&format!(
+                "{:14}{} ({})\n",
+                "Release date:".green(),
+                self.pub_date,
+                dates::relative_age(pub_date, dates::now())
+            )),
+            None => str.push_str(&format!("{:14}{}\n", "Release date:".green(), self.pub_date)),
+        }
+        str.push_str(&format!("{:14}{}\n", "ID:".green(), self.guid));
+        if self.number > 0 {
+            str.push_str(&format!("{:14}{}\n", "Short ID:".green(), self.number));
+        }
+        let mut numbering = Vec::new();
+        if self.season > 0 {
+            numbering.push(format!("season {}", self.season));
+        }
+        if self.episode_number > 0 {
+            numbering.push(format!("episode {}", self.episode_number));
+        }
+        // Most episodes are full ones, so only the other types are shown
+        if !self.episode_type.is_empty() && self.episode_type != "full" {
+            numbering.push(self.episode_type.clone());
+        }
+        if !numbering.is_empty() {
+            str.push_str(&format!("{:14}{}\n", "Episode:".green(), numbering.join(", ")));
+        }
+        if self.duration > 0 {
+            str.push_str(&format!(
+                "{:14}{}\n",
+                "Duration:".green(),
+                dates::format_duration(self.duration)
+            ));
+        }
+        if self.explicit {
+            str.push_str(&format!("{:14}{}\n", "Explicit:".green(), "yes"));
+        }
+        let people: Vec<String> = self
+            .people()
+            .iter()
+            .map(|person| format!("{} ({})", person.name, person.role))
+            .collect();
+        if !people.is_empty() {
+            str.push_str(&format!("{:14}{}\n", "People:".green(), people.join(", ")));
+        }
+        if let Some(location) = self.location() {
+            let place = match (location.name.is_empty(), location.coordinates) {
+                (false, Some((latitude, longitude))) => format!("{} ({}, {})", location.name, latitude, longitude),
+                (true, Some((latitude, longitude))) => format!("{}, {}", latitude, longitude),
+                (_, None) => location.name,
+            };
+            str.push_str(&format!("{:14}{}\n", "Location:".green(), place));
+        }
+        let soundbites = self.soundbites().len();
+        if soundbites > 0 {
+            str.push_str(&format!("{:14}{}\n", "Soundbites:".green(), soundbites));
+        }
+        if !self.chapters.is_empty() {
+            str.push_str(&format!("{:14}{}\n", "Chapters:".green(), self.chapters));
+        }
+        if !self.transcript.is_empty() {
+            str.push_str(&format!("{:14}{}\n", "Transcript:".green(), self.transcript));
+        }
+        str.push_str(&format!("{:14}{}\n", "Link:".green(), self.link));
+        if !self.enclosure_url.is_empty() {
+            let label = if self.is_attachment() { "Attachment:" } else { "Audio:" };
+            str.push_str(&format!(
+                "{:14}{} ({})\n",
+                label.green(),
+                self.enclosure_url,
+                self.enclosure_type
+            ));
+        }
+        str.push_str(&format!("{:14}{}\n", "Podcast:".green(), self.podcast));
+        str.push_str(&format!("{:14}{}\n", "Podcast ID:".green(), self.podcast_id));
+        write!(f, "{}", str)
+    }
+}
+
+/// What `episodes search` looks for in the titles and show notes of the episodes, and `episodes
+/// download --match` in their titles
+enum SearchQuery {
+    Text(String),
+    Regex(regex::Regex),
+}
+
+impl SearchQuery {
+    fn new(query: &str, is_regex: bool) -> Result<Self, Errors> {
+        match is_regex {
+            true => regex::RegexBuilder::new(query)
+                .case_insensitive(true)
+                .build()
+                .map(SearchQuery::Regex)
+                .map_err(|error| Errors::InvalidPattern(error.to_string())),
+            false => Ok(SearchQuery::Text(query.to_string())),
+        }
+    }
+
+    fn matches(&self, value: &str) -> bool {
+        match self {
+            SearchQuery::Text(query) => text::matches(value, query),
+            SearchQuery::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// The result of updating the episodes of a single podcast
+#[derive(Debug, PartialEq)]
+pub enum UpdateStatus {
+    New(usize),
+    Unchanged,
+    Failed(String),
+}
+
+/// The outcome of updating a podcast
+#[derive(Debug)]
+pub struct PodcastUpdate {
+    pub podcast: Podcast,
+    pub status: UpdateStatus,
+    // The new RSS URL of the podcast when its feed moved
+    pub moved_to: Option<String>,
+}
+
+impl fmt::Display for UpdateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UpdateStatus::New(count) => write!(f, "{}", format!("{} new episodes", count).green()),
+            UpdateStatus::Unchanged => write!(f, "unchanged"),
+            UpdateStatus::Failed(error) => write!(f, "{}", format!("failed: {}", error).red()),
+        }
+    }
+}
+
+/// An episode which was downloaded with the name of its file, or the error its download failed
+/// with
+pub type Download = (Episode, Result<String, Errors>);
+
+/// An episode with the name of its file and its content, or the error its download failed with
+type Fetched = (Episode, Result<(String, Bytes), Errors>);
+
+pub struct Episodes<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+    progress: Arc<dyn ProgressSink>,
+    // The lengths of the enclosures which were downloaded, by "podcast id/file name", recorded in
+    // the manifest when the files are saved
+    expected_sizes: Mutex<HashMap<String, u64>>,
+}
+
+impl<'a> Episodes<'a> {
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self {
+            matches,
+            config,
+            progress: Arc::clone(&config.progress),
+            expected_sizes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The matches of the subcommand of "episodes" with the passed name. None when the struct
+    /// works for another command, e.g sync, since clap panics in debug builds when it's asked for
+    /// a subcommand the matches don't know
+    fn subcommand_matches(&self, name: &str) -> Option<&ArgMatches> {
+        match self.matches.subcommand() {
+            Some((subcommand, matches)) if subcommand == name => Some(matches),
+            _ => None,
+        }
+    }
+
+    /// Doesn't report the progress of the requests, for interfaces which own the terminal
+    pub fn hide_progress(mut self) -> Self {
+        self.progress = Arc::new(NoProgress);
+        self
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if let Some(matches) = self.subcommand_matches("verify") {
+            return verify::Verifier::new(matches, self.config).run();
+        }
+
+        if let Some(matches) = self.subcommand_matches("check") {
+            return freshness::Checker::new(matches, self.config).run();
+        }
+
+        if let Some(matches) = self.subcommand_matches("topics") {
+            return topics::Analyzer::new(matches, self.config).run();
+        }
+
+        if let Some(matches) = self.subcommand_matches("update") {
+            // Update all the saved podcasts which aren't paused if no ids were passed
+            let ids: Option<HashSet<u64>> = match matches.values_of("id") {
+                Some(inputs) => {
+                    let podcasts = load_podcasts(self.config)?;
+                    let podcasts = resolver::podcasts(&podcasts, inputs)?;
+                    Some(podcasts.iter().map(|podcast| podcast.id).collect())
+                }
+                None => None,
+            };
+
+            return self.update_podcasts(ids.as_ref());
+        }
+
+        if let Some(matches) = self.subcommand_matches("list") {
+            let last_listed = self.last_listed();
+            // With --new only the episodes found by the last update of each podcast are listed
+            let last_updates: Option<HashMap<u64, u64>> = match matches.is_present("new") {
+                true => {
+                    let podcasts_list = FileSystem::new(
+                        &self.config.app_directory,
+                        "podcast_list.csv",
+                        vec![FilePermissions::Read],
+                    )
+                    .open()?;
+                    Some(
+                        read_podcasts(podcasts_list, self.config.strict)?
+                            .into_iter()
+                            .map(|podcast| (podcast.id, podcast.last_update))
+                            .collect(),
+                    )
+                }
+                false => None,
+            };
+            // Podcasts which were never updated by this version have no known new episodes
+            let since = |id: u64| match &last_updates {
+                Some(last_updates) => match last_updates.get(&id) {
+                    Some(last_update) if *last_update > 0 => *last_update,
+                    _ => u64::MAX,
+                },
+                None => 0,
+            };
+
+            match matches.values_of("id") {
+                // Ids were passed as arguments to the list subcommand
+                Some(ids) => {
+                    let podcasts = load_podcasts(self.config)?;
+                    // Podcasts which were never updated have no episodes file
+                    let mut files: Vec<(u64, File)> = Vec::new();
+                    for podcast in resolver::podcasts(&podcasts, ids)? {
+                        if let Some(file) = self.saved_episodes_file(podcast.id)? {
+                            files.push((podcast.id, file));
+                        }
+                    }
+
+                    for file in files {
+                        let writer = std::io::stdout();
+                        let writer = writer.lock();
+
+                        self.list(file.1, writer, last_listed, since(file.0))?;
+                    }
+                }
+                // No Ids were passed. list all the episodes of all the saved podcasts
+                None => {
+                    let tag = matches.value_of("tag");
+
+                    let mut files: Vec<(u64, File)> = Vec::new();
+                    for podcast in load_podcasts(self.config)? {
+                        if tag.is_some_and(|tag| !podcast.has_tag(tag)) {
+                            continue;
+                        }
+                        // Podcasts which were never updated have no episodes file
+                        if let Some(file) = self.saved_episodes_file(podcast.id)? {
+                            files.push((podcast.id, file));
+                        }
+                    }
+
+                    for file in files {
+                        let writer = std::io::stdout();
+                        let writer = writer.lock();
+
+                        self.list(file.1, writer, last_listed, since(file.0))?;
+                    }
+                }
+            }
+
+            // The episodes which were shown now are no longer new. listing doesn't take the lock, so
+            // two runs may race here. the file is replaced atomically and the later time wins,
+            // which at worst shows a few episodes as new once more
+            let mut file = FileSystem::new(&self.config.app_directory, LAST_LISTED_FILE, vec![]).atomic()?;
+            write!(file, "{}", dates::now())?;
+            file.commit()?;
+        }
+
+        if self.subcommand_matches("backlog").is_some() {
+            let podcasts_list = FileSystem::new(
+                &self.config.app_directory,
+                "podcast_list.csv",
+                vec![FilePermissions::Read],
+            )
+            .open()?;
+            let podcasts = read_podcasts(&podcasts_list, self.config.strict)?;
+
+            let mut files = Vec::new();
+            for podcast in podcasts {
+                // Podcasts which were never updated have no backlog
+                let file = match self.saved_episodes_file(podcast.id)? {
+                    Some(file) => file,
+                    None => continue,
+                };
+                let downloaded_episodes = self.downloaded_files(podcast.id)?;
+                files.push((podcast.name().to_string(), file, downloaded_episodes));
+            }
+
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.backlog(files, writer);
+        }
+
+        if let Some(matches) = self.subcommand_matches("download") {
+            if matches.is_present("resume") {
+                return self.resume_downloads();
+            }
+
+            if let Some(tag) = matches.value_of("podcast-tag") {
+                let count = match matches.value_of("count") {
+                    Some(count) => Some(count.parse::<usize>()?),
+                    None => None,
+                };
+
+                return self.download_tagged(tag, count);
+            }
+
+            // Always present because it's required without --podcast-tag
+            let podcast_id = resolver::podcast_id(self.config, matches.value_of("id").unwrap())?;
+            let podcast_id = podcast_id.as_str();
+            let episodes_file =
+                FileSystem::new(&self.config.app_directory, podcast_id, vec![FilePermissions::Read]).open();
+
+            if episodes_file.is_err() {
+                return Err(Errors::WrongID(podcast_id.to_string()));
+            }
+
+            let episodes_file = episodes_file.unwrap();
+            match matches.values_of("episode-id") {
+                Some(ids) => {
+                    let files_data = self.download(Some(&ids), episodes_file, None)?;
+                    self.store_downloads(podcast_id, files_data)?;
+                }
+                // --list or --count arguments may be present
+                None => {
+                    let list_present = matches.is_present("list");
+                    let count = match matches.value_of("count") {
+                        Some(count) => Some(count.parse::<usize>()?),
+                        None => None,
+                    };
+
+                    match list_present {
+                        // List downloaded episodes for the podcast. use count to indicate how many episodes
+                        // to list
+                        true => {
+                            let downloaded_episodes = self.downloaded_files(podcast_id)?;
+                            let writer = std::io::stdout();
+                            let writer = writer.lock();
+                            return self.list_downloaded(episodes_file, downloaded_episodes, writer, count);
+                        }
+                        false => {
+                            let files_data = self.download(None, episodes_file, count)?;
+                            self.store_downloads(podcast_id, files_data)?;
+
+                            if self.binge() {
+                                let season = match matches.value_of("season") {
+                                    Some(season) => Some(season.parse::<u64>()?),
+                                    None => None,
+                                };
+                                let podcast = load_podcasts(self.config)?
+                                    .into_iter()
+                                    .find(|podcast| podcast.id.to_string() == podcast_id)
+                                    .ok_or_else(|| Errors::WrongID(podcast_id.to_string()))?;
+                                self.binge_playlist(&podcast, season)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(matches) = self.subcommand_matches("ignore") {
+            let mut ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+
+            if matches.is_present("list") {
+                for rule in ignore_list.rules() {
+                    match rule.kind {
+                        RuleKind::Id => println!("{}", rule.value),
+                        RuleKind::Pattern => println!("{} {}", "pattern".green(), rule.value),
+                    }
+                }
+
+                return Ok(());
+            }
+
+            let ids = matches.values_of("id").into_iter().flatten();
+            let patterns = matches.values_of("pattern").into_iter().flatten();
+            let rules = ids
+                .map(|id| (RuleKind::Id, id))
+                .chain(patterns.map(|pattern| (RuleKind::Pattern, pattern)));
+            for (kind, value) in rules {
+                match ignore_list.add(kind, value)? {
+                    true => println!("Ignoring {}", value),
+                    false => println!("Already ignoring {}", value),
+                }
+            }
+
+            return ignore_list.save();
+        }
+
+        if let Some(matches) = self.subcommand_matches("search") {
+            let query = match matches.value_of("query") {
+                Some(query) => Some(SearchQuery::new(query, matches.is_present("regex"))?),
+                None => None,
+            };
+            let person = matches.value_of("person");
+
+            let writer = std::io::stdout();
+            let mut writer = writer.lock();
+            let mut found = 0;
+            for episodes_file in self.episodes_files(matches.values_of("id"))? {
+                found += self.search(episodes_file, query.as_ref(), person, &mut writer)?;
+            }
+
+            if found == 0 {
+                // Always present because the query is required without a person
+                println!("No episodes matched {}", matches.value_of("query").or(person).unwrap());
+            }
+
+            return Ok(());
+        }
+
+        if let Some(matches) = self.subcommand_matches("people") {
+            let mut episodes = Vec::new();
+            for episodes_file in self.episodes_files(matches.values_of("id"))? {
+                episodes.extend(read_episodes(episodes_file, self.config.strict)?);
+            }
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.people(&episodes, matches.value_of("name"), writer);
+        }
+
+        if let Some(matches) = self.subcommand_matches("soundbites") {
+            // Always present because it's a required argument
+            let guid = matches.value_of("guid").unwrap();
+            let mut episodes = Vec::new();
+            for episodes_file in self.episodes_files(None)? {
+                episodes.extend(read_episodes(episodes_file, self.config.strict)?);
+            }
+            let episode = resolver::episode(&episodes, guid)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.soundbites(episode, matches.is_present("extract"), writer);
+        }
+
+        if let Some(matches) = self.subcommand_matches("show") {
+            // Both are always present because they're required arguments
+            let podcast_id = resolver::podcast_id(self.config, matches.value_of("id").unwrap())?;
+            let podcast_id = podcast_id.as_str();
+            let episode_id = matches.value_of("episode-id").unwrap();
+            let episodes_file =
+                FileSystem::new(&self.config.app_directory, podcast_id, vec![FilePermissions::Read]).open();
+
+            if episodes_file.is_err() {
+                return Err(Errors::WrongID(podcast_id.to_string()));
+            }
+
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.show(episodes_file.unwrap(), episode_id, writer);
+        }
+
+        if let Some(matches) = self.subcommand_matches("playlist") {
+            // Always present because it's a required argument
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, matches.value_of("id").unwrap())?;
+            // Always present because it has a default value, and clap only accepts the known formats
+            let format = playlist::Format::parse(matches.value_of("format").unwrap()).unwrap();
+            let count = match matches.value_of("count") {
+                Some(count) => Some(count.parse::<usize>()?),
+                None => None,
+            };
+
+            let downloaded_episodes = self.downloaded_files(podcast.id)?;
+            let mut episodes: Vec<Episode> = self
+                .podcast_episodes(podcast.id)?
+                .into_iter()
+                .filter(|episode| {
+                    downloaded_episodes
+                        .contains(&episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+                })
+                .collect();
+            // The feed lists the newest episodes first
+            if let Some(count) = count {
+                episodes.truncate(count);
+            }
+
+            let podcast_directory = self.config.podcast_directory(podcast.id);
+            let tracks: Vec<(Episode, PathBuf)> = playlist::publication_order(episodes)
+                .into_iter()
+                .map(|episode| {
+                    let path = podcast_directory
+                        .join(episode.file_name(self.config.podcast_file_template(episode.podcast_id)));
+                    (episode, path)
+                })
+                .collect();
+
+            let path = match matches.value_of("output") {
+                Some(output) => PathBuf::from(output),
+                None => {
+                    self.config
+                        .download_directory
+                        .join(format!("{}.{}", sanitize(podcast.name()), format.extension()))
+                }
+            };
+            let directory = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let file_name = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .ok_or_else(|| Errors::IO(io::Error::other("The playlist needs a file name")))?;
+
+            let mut file = FileSystem::new(directory, file_name, vec![]).atomic()?;
+            playlist::write(format, podcast.name(), &tracks, directory, &mut file)?;
+            file.commit()?;
+            println!("Wrote {} episodes to {}", tracks.len(), path.display());
+        }
+
+        if let Some(matches) = self.subcommand_matches("prune") {
+            // Always present because it's a required argument
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, matches.value_of("id").unwrap())?;
+            let keep = match matches.value_of("keep") {
+                Some(keep) => keep.parse::<u64>()?,
+                None => podcast.keep,
+            };
+            if keep == 0 {
+                println!(
+                    "Keeping all the downloads of {}. Pass --keep or set it with `podcasts keep`",
+                    podcast.title
+                );
+                return Ok(());
+            }
+
+            self.prune(
+                podcast,
+                keep,
+                matches.value_of("move-to").map(Path::new),
+                matches.is_present("dry-run"),
+            )?;
+        }
+
+        if let Some(matches) = self.subcommand_matches("played") {
+            // Always present because they're required arguments
+            let podcast_id = resolver::podcast_id(self.config, matches.value_of("id").unwrap())?;
+            let episodes_file = FileSystem::new(&self.config.app_directory, &podcast_id, vec![FilePermissions::Read])
+                .open()
+                .map_err(|_error| Errors::WrongID(podcast_id.to_string()))?;
+
+            let episodes = read_episodes(episodes_file, self.config.strict)?;
+            let episodes = resolver::episodes(&episodes, matches.values_of("episode-id").unwrap())?;
+            let entries: Vec<PlayedEntry> = episodes.iter().map(|episode| PlayedEntry::finished(episode)).collect();
+            played::record(&self.config.app_directory, &entries)?;
+            for episode in episodes {
+                println!("Marked {} as played", episode.title);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(matches) = self.subcommand_matches("remove") {
+            // Always present because it's a required argument
+            let podcast_id = resolver::podcast_id(self.config, matches.value_of("id").unwrap())?;
+            let podcast_id = podcast_id.as_str();
+            let episodes_file =
+                FileSystem::new(&self.config.app_directory, podcast_id, vec![FilePermissions::Read]).open();
+
+            if episodes_file.is_err() {
+                return Err(Errors::WrongID(podcast_id.to_string()));
+            }
+
+            let downloaded_episodes = self.downloaded_files(podcast_id)?;
+            let file_names = self.remove(
+                matches.values_of("episode-id").as_ref(),
+                matches.value_of("name"),
+                episodes_file.unwrap(),
+                &downloaded_episodes,
+            )?;
+
+            let dry_run = matches.is_present("dry-run");
+            for file_name in file_names {
+                if dry_run {
+                    println!("Would remove {}", file_name);
+                    continue;
+                }
+
+                self.config.timings.measure(Phase::WriteFiles, || {
+                    FileSystem::new(&self.config.podcast_directory(podcast_id), &file_name, vec![]).remove()
+                })?;
+                self.archive(podcast_id, &file_name)?;
+                println!("Removed {}", file_name);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Updates the saved podcasts with the passed ids, or all the podcasts which aren't paused,
+    /// and prints the status of each one
+    pub fn update_podcasts(&self, ids: Option<&HashSet<u64>>) -> Result<(), Errors> {
+        let updates = self.refresh(ids)?;
+
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+        for update in updates.iter() {
+            writeln!(writer, "{:40} {}", update.podcast.name(), update.status)?;
+        }
+        for update in updates.iter() {
+            if let Some(new_url) = &update.moved_to {
+                writeln!(
+                    writer,
+                    "{} moved to {}. Updated its RSS URL",
+                    update.podcast.title, new_url
+                )?;
+            }
+        }
+
+        if let Some(body) = self.notification(&updates) {
+            // The update itself succeeded, so a missing notifier is only a warning
+            if let Err(error) = crash::extension("the notification", || notify::send("pcasts", &body)) {
+                notify::failed(&error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The body of the notification about the new episodes of the updates. None if there are none,
+    /// or notifications are off or skipped by --safe-mode
+    fn notification(&self, updates: &[PodcastUpdate]) -> Option<String> {
+        if !self.config.notify || self.config.safe_mode {
+            return None;
+        }
+
+        notify::summary(updates.iter().map(|update| match update.status {
+            UpdateStatus::New(count) => count,
+            _ => 0,
+        }))
+    }
+
+    /// Updates the saved podcasts with the passed ids, or all the podcasts which aren't paused.
+    /// returns the outcome of each one
+    pub fn refresh(&self, ids: Option<&HashSet<u64>>) -> Result<Vec<PodcastUpdate>, Errors> {
+        let podcasts_list = FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+
+        let mut all_podcasts = read_podcasts(&podcasts_list, self.config.strict)?;
+        let podcasts: Vec<Podcast> = all_podcasts
+            .iter()
+            .filter(|podcast| match ids {
+                Some(ids) => ids.contains(&podcast.id),
+                None => !podcast.paused,
+            })
+            .cloned()
+            .collect();
+
+        let mut buffers = HashMap::new();
+        let mut existing = HashMap::new();
+        for podcast in podcasts.iter() {
+            let file = FileSystem::new(
+                &self.config.app_directory,
+                &podcast.id.to_string(),
+                vec![FilePermissions::Read],
+            )
+            .open();
+
+            if let Err(error) = file {
+                tracing::warn!("Can't open file for podcast {}. {}", podcast.title, error);
+                continue;
+            }
+
+            // Remember the previously saved episodes so that the new ones can be counted
+            let file = file.unwrap();
+            let saved: HashMap<String, Episode> = read_episodes(&file, self.config.strict)?
+                .into_iter()
+                .map(|episode: Episode| (episode.guid.clone(), episode))
+                .collect();
+
+            existing.insert(podcast.id, saved);
+            buffers.insert(podcast.id, Vec::new());
+        }
+
+        let started = dates::now();
+        let (summary, moves) = self.update(&podcasts, &existing, &mut buffers)?;
+
+        // The episodes of all the podcasts and the podcasts list are replaced together
+        let mut journal = Journal::new(&self.config.app_directory);
+        for (podcast, status) in podcasts.iter().zip(summary.iter()) {
+            // The episodes are replaced only after the whole feed was stored, failed feeds keep the old ones
+            if let (UpdateStatus::New(_), Some(buffer)) | (UpdateStatus::Unchanged, Some(buffer)) =
+                (status, buffers.get(&podcast.id))
+            {
+                let mut file = FileSystem::new(&self.config.app_directory, &podcast.id.to_string(), vec![]).atomic()?;
+                file.write_all(buffer)?;
+                journal.stage(file)?;
+            }
+        }
+
+        // Count the consecutive failures, used by `podcasts cleanup` to find broken feeds
+        let statuses: HashMap<u64, &UpdateStatus> =
+            podcasts.iter().map(|podcast| podcast.id).zip(summary.iter()).collect();
+        for podcast in all_podcasts.iter_mut() {
+            match statuses.get(&podcast.id) {
+                Some(UpdateStatus::Failed(_)) => podcast.failures += 1,
+                // The episodes found by this update were first seen after it started
+                Some(_) => {
+                    podcast.failures = 0;
+                    podcast.last_update = started;
+                }
+                None => {}
+            }
+
+            // The id stays the same, so the saved episodes and downloads are kept
+            if let Some(new_url) = moves.get(&podcast.id) {
+                podcast.rss_url = new_url.clone();
+            }
+        }
+        let mut podcasts_list = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        write_podcasts(all_podcasts.iter(), &mut podcasts_list)?;
+        journal.stage(podcasts_list)?;
+        journal.commit()?;
+
+        for podcast in podcasts.iter() {
+            self.enforce_window(podcast)?;
+        }
+
+        // With the failures, update time and RSS URL as they were saved
+        let mut saved: HashMap<u64, Podcast> = all_podcasts.into_iter().map(|podcast| (podcast.id, podcast)).collect();
+        Ok(podcasts
+            .into_iter()
+            .zip(summary)
+            .map(|(podcast, status)| PodcastUpdate {
+                moved_to: moves.get(&podcast.id).cloned(),
+                podcast: saved.remove(&podcast.id).unwrap_or(podcast),
+                status,
+            })
+            .collect())
+    }
+
+    /// Fetches the RSS feeds of the passed podcasts and writes their episodes to the matching
+    /// writers. existing holds the previously saved episodes of each podcast by their guid.
+    /// returns the update status of each podcast, in the same order as the podcasts, and the new
+    /// RSS URLs of the podcasts whose feed moved
+    pub fn update<T>(
+        &self,
+        podcasts: &[Podcast],
+        existing: &HashMap<u64, HashMap<String, Episode>>,
+        writers: &mut HashMap<u64, T>,
+    ) -> Result<(Vec<UpdateStatus>, HashMap<u64, String>), Errors>
+    where
+        T: Write,
+    {
+        let urls_map: HashMap<&str, u64> = podcasts
+            .iter()
+            .map(|podcast| (podcast.rss_url.as_str(), podcast.id))
+            .collect();
+
+        let urls: Vec<&str> = podcasts.iter().map(|podcast| podcast.rss_url.as_str()).collect();
+        // Renamed podcasts keep their alias instead of the title of the feed
+        let aliases: HashMap<u64, &str> = podcasts
+            .iter()
+            .filter(|podcast| !podcast.alias.is_empty())
+            .map(|podcast| (podcast.id, podcast.alias.as_str()))
+            .collect();
+        let mut statuses: HashMap<u64, UpdateStatus> = HashMap::new();
+        let mut moves = HashMap::new();
+        let mut artwork = Vec::new();
+        let mut discovered = Vec::new();
+
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?
+            .by_url(podcasts.iter().map(|podcast| (podcast.rss_url.as_str(), podcast.id)));
+        let web = Web::new(time::Duration::from_secs(10), &self.config.http)
+            .stats(&self.config.network)
+            .progress(Arc::clone(&self.progress))
+            .jobs(self.config.jobs)
+            .headers(headers);
+        let responses = self.config.timings.measure(Phase::Fetch, || web.get(&urls));
+        let lookups = self.lookup_index(podcasts);
+
+        for (url, bytes) in responses {
+            let podcast_id = urls_map.get(url).ok_or(Errors::RSS)?;
+            let bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    tracing::debug!("Can't fetch {}. {}", url, error);
+                    self.config.outcome.failure(url, &error);
+                    statuses.insert(*podcast_id, UpdateStatus::Failed(error.to_string()));
+                    continue;
+                }
+            };
+            let mut items = match self
+                .config
+                .timings
+                .measure(Phase::Parse, || Episode::parse_feed(&bytes[..], *podcast_id))
+            {
+                Ok(items) => items,
+                Err(error) => {
+                    tracing::debug!("Can't parse {}. {}", url, error);
+                    self.config.outcome.failure(url, &error);
+                    statuses.insert(*podcast_id, UpdateStatus::Failed(error.to_string()));
+                    continue;
+                }
+            };
+
+            if let Some(alias) = aliases.get(podcast_id) {
+                for item in items.iter_mut() {
+                    item.podcast = alias.to_string();
+                }
+            }
+            if let Some(response) = lookups.get(podcast_id) {
+                crash::extension("the Podcast Index enrichment", || {
+                    podcast_index::enrich(&mut items, response)
+                });
+            }
+
+            let channel = feed::read(&bytes[..]).ok();
+            let new_feed_url = channel
+                .as_ref()
+                .and_then(|channel| channel.itunes_ext())
+                .and_then(|itunes| itunes.new_feed_url());
+            if let Some(new_url) = feed_move(url, new_feed_url, web.moved(url)) {
+                moves.insert(*podcast_id, new_url);
+            }
+
+            // Podcasts added by older versions have no artwork yet. the downloaded one is kept
+            if !art::path(&self.config.app_directory, *podcast_id).exists() {
+                if let Some(image_url) = channel.as_ref().and_then(art::image_url) {
+                    artwork.push((*podcast_id, image_url));
+                }
+            }
+
+            // Keep the raw feed so that later versions of it can be compared with `debug diff-feed`
+            let snapshot = FileSystem::new(
+                &self.config.app_directory.join(FEEDS_DIRECTORY),
+                &format!("{}.xml", podcast_id),
+                vec![],
+            )
+            .atomic();
+            if let Ok(mut snapshot) = snapshot {
+                snapshot.write_all(&bytes)?;
+                snapshot.commit()?;
+            }
+
+            let writer = match writers.get_mut(podcast_id) {
+                Some(writer) => writer,
+                None => continue,
+            };
+            let saved = existing.get(podcast_id);
+            let new_count = items
+                .iter()
+                .filter(|item| match saved {
+                    Some(saved) => !saved.contains_key(&item.guid),
+                    None => true,
+                })
+                .count();
+            // The episodes of the first update are the back catalogue and not news
+            if let Some(saved) = saved.filter(|saved| !saved.is_empty()) {
+                discovered.extend(
+                    items
+                        .iter()
+                        .filter(|item| !saved.contains_key(&item.guid))
+                        .map(|item| Event::new(EventKind::Discovered, *podcast_id, &item.guid, &item.title)),
+                );
+            }
+            let items = Self::merge_saved(items, saved, dates::now());
+
+            self.config.timings.measure(Phase::Store, || -> Result<(), Errors> {
+                let mut csv_writer = csv::WriterBuilder::new().has_headers(true).from_writer(writer);
+                for item in items {
+                    csv_writer.serialize(item)?;
+                }
+
+                csv_writer.flush()?;
+                Ok(())
+            })?;
+
+            let status = if new_count > 0 {
+                UpdateStatus::New(new_count)
+            } else {
+                UpdateStatus::Unchanged
+            };
+            self.config.outcome.success();
+            statuses.insert(*podcast_id, status);
+        }
+        art::download(self.config, &artwork)?;
+        events::record(&self.config.app_directory, &discovered)?;
+
+        let statuses = podcasts
+            .iter()
+            .map(|podcast| {
+                statuses
+                    .remove(&podcast.id)
+                    .unwrap_or_else(|| UpdateStatus::Failed("Can't open episodes file".to_string()))
+            })
+            .collect();
+
+        Ok((statuses, moves))
+    }
+
+    /// Writes the saved episodes which were first seen at or after since. the ones found after
+    /// the passed time of the last listing get a "NEW" badge
+    pub fn list<R, W>(&self, reader: R, mut writer: W, last_listed: u64, since: u64) -> Result<(), Errors>
+    where
+        R: Read,
+        W: Write,
+    {
+        let ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+        let list_matches = self.subcommand_matches("list");
+        let query = list_matches.and_then(|matches| matches.value_of("match"));
+        let json = list_matches.and_then(|matches| matches.value_of("output")) == Some("json");
+        let date = |name: &str| -> Result<Option<u64>, Errors> {
+            match list_matches.and_then(|matches| matches.value_of(name)) {
+                Some(date) => dates::parse_date(date)
+                    .map(Some)
+                    .ok_or_else(|| Errors::InvalidDate(date.to_string())),
+                None => Ok(None),
+            }
+        };
+        let (released_since, released_until) = (date("since")?, date("until")?);
+        let topic = list_matches.and_then(|matches| matches.value_of("topic"));
+        let topic_list = match topic {
+            Some(_topic) => TopicList::load(&self.config.app_directory)?,
+            None => TopicList::default(),
+        };
+        let count = match list_matches.and_then(|matches| matches.value_of("count")) {
+            Some(count) => Some(count.parse::<usize>()?),
+            None => None,
+        };
+
+        let mut episodes: Vec<Episode> = read_episodes(reader, self.config.strict)?
+            .into_iter()
+            .filter(|episode| !ignore_list.is_ignored(episode))
+            .filter(|episode: &Episode| episode.first_seen >= since)
+            .filter(|episode| match query {
+                Some(query) => text::matches(&episode.title, query),
+                None => true,
+            })
+            .filter(|episode| topic.is_none_or(|topic| topic_list.has(episode, topic)))
+            .filter(|episode| {
+                if released_since.is_none() && released_until.is_none() {
+                    return true;
+                }
+
+                // Episodes without a date can't be placed in the range
+                let release_time = match episode.release_time() {
+                    Some(release_time) => release_time,
+                    None => return false,
+                };
+                // --until includes the whole day
+                released_since.is_none_or(|released_since| release_time >= released_since)
+                    && released_until.is_none_or(|released_until| release_time < released_until + 86_400)
+            })
+            .collect();
+        sort_newest_first(&mut episodes);
+        if let Some(count) = count {
+            episodes.truncate(count);
+        }
+        let episodes = listing_order(
+            episodes,
+            list_matches.and_then(|matches| matches.value_of("sort")),
+            list_matches.is_some_and(|matches| matches.is_present("reverse")),
+        );
+
+        let table = list_matches.and_then(|matches| matches.value_of("format")) == Some("table");
+        if table && !json {
+            // The episodes of one podcast are listed at a time
+            let downloaded_episodes = match episodes.first() {
+                Some(episode) => self.downloaded_files(episode.podcast_id)?,
+                None => Vec::new(),
+            };
+            return Ok(self
+                .episodes_table(&episodes, &downloaded_episodes)
+                .write(writer, table::terminal_width())?);
+        }
+
+        for episode in episodes.iter() {
+            // One object per line, so the output of several podcasts can be concatenated
+            if json {
+                serde_json::to_writer(&mut writer, episode).map_err(io::Error::from)?;
+                writeln!(writer)?;
+                continue;
+            }
+
+            if episode.first_seen > last_listed {
+                writeln!(writer, "{}", " NEW ".black().on_yellow())?;
+            }
+            writeln!(writer, "{}", episode)?;
+        }
+
+        Ok(())
+    }
+
+    /// The episodes files of the podcasts with the passed ids, or of all the podcasts. podcasts
+    /// which were never updated have no episodes file and are skipped
+    fn episodes_files(&self, ids: Option<Values>) -> Result<Vec<File>, Errors> {
+        let podcasts = load_podcasts(self.config)?;
+        let podcast_ids: Vec<String> = match ids {
+            Some(ids) => resolver::podcasts(&podcasts, ids)?
+                .iter()
+                .map(|podcast| podcast.id.to_string())
+                .collect(),
+            None => podcasts.iter().map(|podcast| podcast.id.to_string()).collect(),
+        };
+
+        Ok(podcast_ids
+            .iter()
+            .filter_map(|podcast_id| {
+                FileSystem::new(&self.config.app_directory, podcast_id, vec![FilePermissions::Read])
+                    .open()
+                    .ok()
+            })
+            .collect())
+    }
+
+    /// Writes the episodes whose title or show notes match the query and which have a person of
+    /// the passed name. returns how many matched
+    fn search<R, W>(
+        &self,
+        reader: R,
+        query: Option<&SearchQuery>,
+        person: Option<&str>,
+        mut writer: W,
+    ) -> Result<usize, Errors>
+    where
+        R: Read,
+        W: Write,
+    {
+        let mut found = 0;
+        for episode in read_episodes(reader, self.config.strict)? {
+            // The markup of the show notes isn't searched
+            let matches_query = query.is_none_or(|query| {
+                query.matches(&episode.title) || query.matches(&text::html_to_text(&episode.description).0)
+            });
+            let matches_person =
+                person.is_none_or(|name| episode.people().iter().any(|person| text::matches(&person.name, name)));
+
+            if matches_query && matches_person {
+                writeln!(writer, "{}", episode)?;
+                found += 1;
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Writes the episodes a person of the passed name appears in, with their roles. without a name
+    /// writes everyone who appears in the episodes, the most frequent first
+    fn people<W>(&self, episodes: &[Episode], name: Option<&str>, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        if let Some(name) = name {
+            let mut found = 0;
+            for episode in episodes {
+                let roles: Vec<String> = episode
+                    .people()
+                    .into_iter()
+                    .filter(|person| text::matches(&person.name, name))
+                    .map(|person| format!("{} ({})", person.name, person.role))
+                    .collect();
+                if !roles.is_empty() {
+                    writeln!(writer, "{} {}", roles.join(", ").bold(), episode.podcast)?;
+                    writeln!(writer, "{}", episode)?;
+                    found += 1;
+                }
+            }
+            if found == 0 {
+                writeln!(writer, "No episodes with {}", name)?;
+            }
+
+            return Ok(());
+        }
+
+        // The same person may be spelled differently across feeds, so the names are folded. the
+        // first spelling is shown
+        let mut people: Vec<(String, usize, Vec<String>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for person in episodes.iter().flat_map(|episode| episode.people()) {
+            let position = *positions.entry(text::fold(&person.name)).or_insert_with(|| {
+                people.push((person.name.clone(), 0, Vec::new()));
+                people.len() - 1
+            });
+            let (_name, count, roles) = &mut people[position];
+            *count += 1;
+            if !roles.contains(&person.role) {
+                roles.push(person.role);
+            }
+        }
+        people.sort_by(|first, second| second.1.cmp(&first.1).then_with(|| first.0.cmp(&second.0)));
+
+        for (name, count, roles) in people {
+            let episodes = if count == 1 { "episode" } else { "episodes" };
+            writeln!(writer, "{:>4} {:8} {} ({})", count, episodes, name, roles.join(", "))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the soundbites of the episode. with extract, each one is also cut out of the
+    /// downloaded episode into the soundbites directory of its podcast
+    fn soundbites<W>(&self, episode: &Episode, extract: bool, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let soundbites = episode.soundbites();
+        if soundbites.is_empty() {
+            writeln!(writer, "{} has no soundbites", episode.title)?;
+            return Ok(());
+        }
+
+        let content = match extract {
+            true => {
+                // Other formats would have to be decoded, MP3 frames can be copied as they are
+                if episode.extension() != "mp3" {
+                    return Err(Errors::UnsupportedClip(episode.extension()));
+                }
+                let path = self
+                    .config
+                    .podcast_directory(episode.podcast_id)
+                    .join(episode.file_name(self.config.podcast_file_template(episode.podcast_id)));
+                Some(fs::read(&path).map_err(|_error| Errors::NotFound(path.display().to_string()))?)
+            }
+            false => None,
+        };
+        let directory = self
+            .config
+            .podcast_directory(episode.podcast_id)
+            .join(SOUNDBITES_DIRECTORY);
+
+        for (index, soundbite) in soundbites.iter().enumerate() {
+            let end = soundbite.start_time + soundbite.duration;
+            writeln!(
+                writer,
+                "{:>3}. {} - {} {}",
+                index + 1,
+                dates::format_duration(soundbite.start_time as u64),
+                dates::format_duration(end.round() as u64),
+                soundbite.title
+            )?;
+
+            if let Some(content) = &content {
+                let clip = clips::cut(content, soundbite.start_time, soundbite.duration);
+                if clip.is_empty() {
+                    writeln!(writer, "     The downloaded episode ends before the soundbite")?;
+                    continue;
+                }
+
+                let file_name = format!("{} - {}.mp3", sanitize(&episode.title), index + 1);
+                let mut file = FileSystem::new(&directory, &file_name, vec![]).atomic()?;
+                file.write_all(&clip)?;
+                file.commit()?;
+                writeln!(writer, "     Saved {}", directory.join(&file_name).display())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the details of the episode with the passed guid or short id followed by its show
+    /// notes as text and the links in them
+    fn show<R, W>(&self, reader: R, episode_id: &str, mut writer: W) -> Result<(), Errors>
+    where
+        R: Read,
+        W: Write,
+    {
+        let episodes = read_episodes(reader, self.config.strict)?;
+        let episode = resolver::episode(&episodes, episode_id)?;
+
+        write!(writer, "{}", episode)?;
+        let (notes, links) = text::html_to_text(&episode.description);
+        if !notes.is_empty() {
+            writeln!(writer, "\n{}\n{}", "Show notes:".green(), notes)?;
+        }
+        if !links.is_empty() {
+            writeln!(writer, "\n{}", "Links:".green())?;
+            for (index, link) in links.iter().enumerate() {
+                writeln!(writer, "{:>3}. {}", index + 1, link)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn download<R>(
+        &self,
+        ids: Option<&Values>,
+        reader: R,
+        count: Option<usize>,
+    ) -> Result<Vec<(String, Bytes)>, Errors>
+    where
+        R: Read,
+    {
+        let ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+        let download_matches = self.subcommand_matches("download");
+        let season = match download_matches.and_then(|matches| matches.value_of("season")) {
+            Some(season) => Some(season.parse::<u64>()?),
+            None => None,
+        };
+        let query = match download_matches.and_then(|matches| matches.value_of("match")) {
+            Some(query) => Some(SearchQuery::new(
+                query,
+                download_matches.is_some_and(|matches| matches.is_present("regex")),
+            )?),
+            None => None,
+        };
+        let since = match download_matches.and_then(|matches| matches.value_of("since")) {
+            Some(date) => Some(dates::parse_date(date).ok_or_else(|| Errors::InvalidDate(date.to_string()))?),
+            None => None,
+        };
+        let latest = download_matches.is_some_and(|matches| matches.is_present("latest"));
+
+        let episodes = read_episodes(reader, self.config.strict)?;
+        let episodes: Vec<Episode> = match ids {
+            Some(ids) => resolver::episodes(&episodes, ids.clone())?
+                .into_iter()
+                .cloned()
+                .collect(),
+            // Download all the episodes which aren't ignored if no ids were provided
+            None => episodes
+                .into_iter()
+                .filter(|episode| {
+                    !ignore_list.is_ignored(episode) && season.is_none_or(|season| episode.season == season)
+                })
+                .filter(|episode| query.as_ref().is_none_or(|query| query.matches(&episode.title)))
+                .filter(|episode| {
+                    since.is_none_or(|since| episode.release_time().is_some_and(|release_time| release_time >= since))
+                })
+                .collect(),
+        };
+        // The count is taken from the first episode of the season instead of the newest one
+        let episodes = match self.binge() {
+            true => self.binge_order(episodes),
+            false => episodes,
+        };
+        // Feeds don't always list the newest episode first
+        let (episodes, count) = match latest {
+            true => {
+                let mut episodes = episodes;
+                sort_newest_first(&mut episodes);
+                (episodes, Some(1))
+            }
+            false => (episodes, count),
+        };
+        let episodes_count = episodes.len();
+
+        // Take count amount of episodes if needed
+        let episodes: Vec<Episode> = episodes.into_iter().take(count.unwrap_or(episodes_count)).collect();
+        if query.is_some() {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            if !self.confirm_matches(&episodes, stdin.lock(), stdout.lock())? {
+                return Ok(Vec::new());
+            }
+        }
+
+        self.fetch(episodes)
+    }
+
+    /// Whether the episodes selected with --match are downloaded. more than a few are listed
+    /// first and downloaded only if that's confirmed, or with --yes
+    fn confirm_matches<R, W>(&self, episodes: &[Episode], mut input: R, mut writer: W) -> Result<bool, Errors>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let download_matches = self.subcommand_matches("download");
+        let query = download_matches
+            .and_then(|matches| matches.value_of("match"))
+            .unwrap_or("");
+        if episodes.is_empty() {
+            writeln!(writer, "No episodes matched {}", query)?;
+            return Ok(false);
+        }
+        if episodes.len() <= CONFIRM_MATCHES || download_matches.is_some_and(|matches| matches.is_present("yes")) {
+            return Ok(true);
+        }
+
+        writeln!(writer, "{} episodes match {}", episodes.len(), query.bold())?;
+        for episode in episodes {
+            writeln!(writer, "  {}", episode.title)?;
+        }
+        write!(writer, "Download all of them? [y/N] ")?;
+        writer.flush()?;
+
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+    }
+
+    /// Whether the episodes are downloaded in the order they're meant to be heard, with --ordered
+    /// or --sequential-names
+    fn binge(&self) -> bool {
+        self.subcommand_matches("download")
+            .is_some_and(|matches| matches.is_present("ordered") || matches.is_present("sequential-names"))
+    }
+
+    /// Orders the episodes of a season from its first one. with --sequential-names, the episodes
+    /// of feeds which don't number all of them are numbered by their place in their season, since
+    /// the number is in the names of their files
+    fn binge_order(&self, episodes: Vec<Episode>) -> Vec<Episode> {
+        let mut episodes = playlist::season_order(episodes);
+        let sequential_names = self
+            .subcommand_matches("download")
+            .is_some_and(|matches| matches.is_present("sequential-names"));
+        if sequential_names && episodes.iter().any(|episode| episode.episode_number == 0) {
+            let mut numbers: HashMap<u64, u64> = HashMap::new();
+            for episode in episodes.iter_mut() {
+                let number = numbers.entry(episode.season).or_insert(0);
+                *number += 1;
+                episode.episode_number = *number;
+            }
+        }
+
+        episodes
+    }
+
+    /// Writes a playlist of the downloaded episodes of the season in the order they're meant to be
+    /// heard, next to the directories of the podcasts
+    fn binge_playlist(&self, podcast: &Podcast, season: Option<u64>) -> Result<(), Errors> {
+        let downloaded_episodes = self.downloaded_files(podcast.id)?;
+        let episodes: Vec<Episode> = self
+            .podcast_episodes(podcast.id)?
+            .into_iter()
+            .filter(|episode| season.is_none_or(|season| episode.season == season))
+            .collect();
+        let podcast_directory = self.config.podcast_directory(podcast.id);
+        let tracks: Vec<(Episode, PathBuf)> = self
+            .binge_order(episodes)
+            .into_iter()
+            .filter(|episode| {
+                downloaded_episodes.contains(&episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            })
+            .map(|episode| {
+                let path =
+                    podcast_directory.join(episode.file_name(self.config.podcast_file_template(episode.podcast_id)));
+                (episode, path)
+            })
+            .collect();
+
+        let title = match season {
+            Some(season) => format!("{} - Season {}", podcast.name(), season),
+            None => podcast.name().to_string(),
+        };
+        let file_name = format!("{}.{}", sanitize(&title), playlist::Format::M3u.extension());
+        let mut file = FileSystem::new(&self.config.download_directory, &file_name, vec![]).atomic()?;
+        playlist::write(
+            playlist::Format::M3u,
+            &title,
+            &tracks,
+            &self.config.download_directory,
+            &mut file,
+        )?;
+        file.commit()?;
+        println!(
+            "Wrote {} episodes to {}",
+            tracks.len(),
+            self.config.download_directory.join(file_name).display()
+        );
+
+        Ok(())
+    }
+
+    /// Downloads the audio files of the passed episodes. returns the file name of each downloaded
+    /// episode together with its content. the failed ones are left out
+    pub fn fetch(&self, episodes: Vec<Episode>) -> Result<Vec<(String, Bytes)>, Errors> {
+        Ok(self
+            .fetch_episodes(episodes)?
+            .into_iter()
+            .filter_map(|(_episode, file)| file.ok())
+            .collect())
+    }
+
+    /// Downloads the passed episodes, which may belong to several podcasts, and saves the files of
+    /// each podcast. returns each episode with the name of its file, or why it wasn't downloaded
+    pub fn download_batch(&self, episodes: Vec<Episode>) -> Result<Vec<Download>, Errors> {
+        let mut podcasts: HashMap<u64, Vec<(String, Bytes)>> = HashMap::new();
+        let mut downloaded = Vec::new();
+        for (episode, file) in self.fetch_episodes(episodes)? {
+            match file {
+                Ok((file_name, bytes)) => {
+                    podcasts
+                        .entry(episode.podcast_id)
+                        .or_default()
+                        .push((file_name.clone(), bytes));
+                    downloaded.push((episode, Ok(file_name)));
+                }
+                Err(error) => downloaded.push((episode, Err(error))),
+            }
+        }
+
+        for (podcast_id, files_data) in podcasts {
+            self.store_downloads(&podcast_id.to_string(), files_data)?;
+        }
+
+        Ok(downloaded)
+    }
+
+    /// Records that the episode failed to download, so it's retried by `download --resume` and
+    /// counted by --fail-on
+    fn download_failed(&self, url: &str, episode: &Episode, error: &Errors) -> Result<(), Errors> {
+        let mut failed = QueueEntry::new(
+            episode.podcast_id,
+            &episode.guid,
+            &episode.file_name(self.config.podcast_file_template(episode.podcast_id)),
+            Status::Failed,
+        );
+        failed.error = error.to_string();
+        queue::record(&self.config.app_directory, &[failed])?;
+        self.config.outcome.failure(url, error);
+        tracing::warn!("Can't download {}. {}", episode.title, error);
+
+        Ok(())
+    }
+
+    /// Downloads the passed episodes in their order. returns each episode with the name of its
+    /// file and its content, or the error it failed with. one failed episode doesn't stop the
+    /// others
+    fn fetch_episodes(&self, episodes: Vec<Episode>) -> Result<Vec<Fetched>, Errors> {
+        let episodes: Vec<Episode> = self
+            .preferred_variants(episodes)?
+            .into_iter()
+            .map(|mut episode| {
+                episode.select_enclosure(self.config.bandwidth, self.config.max_size);
+                episode
+            })
+            .collect();
+        let urls: Vec<String> = episodes
+            .iter()
+            .map(|episode| episode.download_url().to_string())
+            .collect();
+        let episode_urls: Vec<&str> = urls.iter().map(|url| url.as_str()).collect();
+        let episodes_map: HashMap<String, Episode> = episodes
+            .into_iter()
+            .map(|episode| (episode.download_url().to_string(), episode))
+            .collect();
+        let requested: Vec<QueueEntry> = episodes_map
+            .values()
+            .map(|episode| {
+                let file_name = episode.file_name(self.config.podcast_file_template(episode.podcast_id));
+                QueueEntry::new(episode.podcast_id, &episode.guid, &file_name, Status::Requested)
+            })
+            .collect();
+        queue::request(&self.config.app_directory, &requested, self.config.strict)?;
+
+        let chunks = match self
+            .subcommand_matches("download")
+            .and_then(|matches| matches.value_of("chunks"))
+        {
+            Some(chunks) => chunks.parse::<usize>()?,
+            None => 1,
+        };
+        let limit_rate = match self
+            .subcommand_matches("download")
+            .and_then(|matches| matches.value_of("limit-rate"))
+        {
+            Some(limit_rate) => Some(parse_size(limit_rate)?),
+            None => self.config.limit_rate,
+        };
+
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?.by_url(
+            episodes_map
+                .iter()
+                .map(|(url, episode)| (url.as_str(), episode.podcast_id)),
+        );
+        let discard_partial = self
+            .subcommand_matches("download")
+            .map(|matches| matches.is_present("discard-partial"))
+            .unwrap_or(false);
+        let mut partials = HashMap::new();
+        for (url, episode) in episodes_map.iter() {
+            let path = self.config.podcast_directory(episode.podcast_id).join(format!(
+                "{}.part",
+                episode.file_name(self.config.podcast_file_template(episode.podcast_id))
+            ));
+            match discard_partial {
+                true if path.exists() => fs::remove_file(path)?,
+                true => {}
+                false => {
+                    partials.insert(url.clone(), path);
+                }
+            }
+        }
+
+        let mut files_data = Vec::new();
+        let responses = self.config.timings.measure(Phase::Fetch, || {
+            Web::new(time::Duration::from_secs(0), &self.config.http)
+                .stats(&self.config.network)
+                .jobs(self.config.jobs)
+                .headers(headers)
+                .partials(partials)
+                .limit_rate(limit_rate)
+                .progress(Arc::clone(&self.progress))
+                .deadline(self.config.deadline)
+                .get_chunked(&episode_urls, chunks)
+        });
+
+        let tag = !self.config.safe_mode
+            && (self.config.tag
+                || self
+                    .subcommand_matches("download")
+                    .map(|matches| matches.is_present("tag"))
+                    .unwrap_or(false));
+
+        let mut attachments = Vec::new();
+        let mut out_of_time = 0;
+        let mut interrupted = 0;
+        for (url, bytes) in responses {
+            let mut bytes = match bytes {
+                Ok(bytes) => bytes,
+                Err(Errors::OutOfTime(_url)) => {
+                    out_of_time += 1;
+                    continue;
+                }
+                // Ctrl-C was pressed. the finished downloads are still saved
+                Err(Errors::Interrupted(_url)) if interrupt::interrupted() => {
+                    interrupted += 1;
+                    continue;
+                }
+                Err(error) => {
+                    // Always present since the responses are for the URLs of the map
+                    let episode = episodes_map.get(url).unwrap();
+                    self.download_failed(url, episode, &error)?;
+                    files_data.push((episode.clone(), Err(error)));
+                    continue;
+                }
+            };
+            let episode = episodes_map.get(url).unwrap();
+
+            if tag && !episode.is_attachment() && episode.extension() == "mp3" && sniff_attachment(&bytes[..]).is_none()
+            {
+                let cover = fs::read(art::path(&self.config.app_directory, episode.podcast_id)).ok();
+                match crash::extension("the ID3 tagging", || tags::write(&bytes[..], episode, cover)) {
+                    Ok(tagged) => bytes = tagged,
+                    Err(error) => {
+                        self.download_failed(url, episode, &error)?;
+                        files_data.push((episode.clone(), Err(error)));
+                        continue;
+                    }
+                }
+            }
+
+            match sniff_attachment(&bytes[..]) {
+                Some(mime_type) if !episode.is_attachment() => {
+                    let mut attachment = episode.clone();
+                    attachment.enclosure_type = mime_type.to_string();
+                    let file_name = attachment.file_name(self.config.podcast_file_template(attachment.podcast_id));
+                    queue::record(
+                        &self.config.app_directory,
+                        &[QueueEntry::new(
+                            episode.podcast_id,
+                            &episode.guid,
+                            &file_name,
+                            Status::Requested,
+                        )],
+                    )?;
+                    files_data.push((episode.clone(), Ok((file_name, bytes))));
+                    attachments.push(attachment);
+                }
+                _ => files_data.push((
+                    episode.clone(),
+                    Ok((
+                        episode.file_name(self.config.podcast_file_template(episode.podcast_id)),
+                        bytes,
+                    )),
+                )),
+            }
+        }
+        self.record_attachments(attachments)?;
+        let mut downloaded = 0;
+        if let Ok(mut expected_sizes) = self.expected_sizes.lock() {
+            for (episode, file) in files_data.iter() {
+                if let Ok((file_name, _bytes)) = file {
+                    downloaded += 1;
+                    expected_sizes.insert(
+                        format!("{}/{}", episode.podcast_id, file_name),
+                        episode.enclosure_length,
+                    );
+                }
+            }
+        }
+        if out_of_time > 0 {
+            println!(
+                "Reached --max-time. Downloaded {} of {} episodes, the rest are left for the next run",
+                downloaded,
+                files_data.len() + out_of_time
+            );
+        }
+        if interrupted > 0 {
+            println!(
+                "Interrupted. Downloaded {} of {} episodes, {}",
+                downloaded,
+                files_data.len() + interrupted,
+                match discard_partial {
+                    true => "the partial downloads were removed",
+                    false => "the partial downloads are resumed by the next run",
+                }
+            );
+        }
+
+        Ok(files_data)
+    }
+
+    /// Replaces the enclosures of the episodes whose podcast has a variant preferred by the current
+    /// profile with the enclosures of the same episodes in the variant's feed. episodes which
+    /// aren't in that feed keep their own enclosure
+    fn preferred_variants(&self, episodes: Vec<Episode>) -> Result<Vec<Episode>, Errors> {
+        let variants = VariantList::load(&self.config.app_directory, self.config.strict)?;
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?;
+        let mut feeds: HashMap<u64, Vec<Episode>> = HashMap::new();
+
+        Ok(episodes
+            .into_iter()
+            .map(|mut episode| {
+                let variant = match variants.preferred(episode.podcast_id, &self.config.profile) {
+                    Some(variant) => variant,
+                    None => return episode,
+                };
+                let candidates = feeds.entry(episode.podcast_id).or_insert_with(|| {
+                    let response = Web::new(time::Duration::from_secs(10), &self.config.http)
+                        .stats(&self.config.network)
+                        .progress(Arc::clone(&self.progress))
+                        .headers(headers.by_url(vec![(variant.rss_url.as_str(), episode.podcast_id)]))
+                        .get(&[&variant.rss_url])
+                        .pop()
+                        .map(|(_url, response)| response)
+                        .unwrap_or_else(|| Err(Errors::NotFound(variant.rss_url.clone())));
+                    match response.and_then(|bytes| Episode::parse_feed(&bytes[..], episode.podcast_id)) {
+                        Ok(candidates) => candidates,
+                        Err(error) => {
+                            tracing::warn!(
+                                "Can't fetch the {} variant of {}. {}",
+                                variant.name,
+                                episode.podcast,
+                                error
+                            );
+                            Vec::new()
+                        }
+                    }
+                });
+
+                match variants::matching(&episode, candidates) {
+                    Some(candidate) => {
+                        episode.enclosure_url = candidate.enclosure_url.clone();
+                        episode.enclosure_type = candidate.enclosure_type.clone();
+                        episode.enclosure_length = candidate.enclosure_length;
+                        episode.alternates = candidate.alternates.clone();
+                    }
+                    None => tracing::info!(
+                        "{} isn't in the {} variant, downloading the main feed's enclosure",
+                        episode.title,
+                        variant.name
+                    ),
+                }
+
+                episode
+            })
+            .collect())
+    }
+
+    /// Saves the detected type of the enclosures which turned out to be attachments, so the later
+    /// commands find them in the attachments directory
+    fn record_attachments(&self, attachments: Vec<Episode>) -> Result<(), Errors> {
+        let mut podcasts_attachments: HashMap<u64, Vec<Episode>> = HashMap::new();
+        for attachment in attachments {
+            podcasts_attachments
+                .entry(attachment.podcast_id)
+                .or_default()
+                .push(attachment);
+        }
+
+        let mut journal = Journal::new(&self.config.app_directory);
+        for (podcast_id, attachments) in podcasts_attachments {
+            let episodes_file = FileSystem::new(
+                &self.config.app_directory,
+                &podcast_id.to_string(),
+                vec![FilePermissions::Read],
+            )
+            .open()?;
+            let mut episodes = read_episodes(episodes_file, self.config.strict)?;
+            for episode in episodes.iter_mut() {
+                if let Some(attachment) = attachments.iter().find(|attachment| attachment.guid == episode.guid) {
+                    episode.enclosure_type = attachment.enclosure_type.clone();
+                }
+            }
+
+            let file = FileSystem::new(&self.config.app_directory, &podcast_id.to_string(), vec![]).atomic()?;
+            let mut writer = csv::Writer::from_writer(file);
+            for episode in episodes {
+                writer.serialize(episode)?;
+            }
+            let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+            journal.stage(file)?;
+        }
+
+        journal.commit()
+    }
+
+    /// The responses of the Podcast Index for the podcasts with enrichment turned on, by their id.
+    /// empty without credentials, and the podcasts whose lookup failed are left out
+    fn lookup_index(&self, podcasts: &[Podcast]) -> HashMap<u64, Bytes> {
+        let enriched: HashMap<String, u64> = podcasts
+            .iter()
+            .filter(|podcast| podcast.enrich && !self.config.safe_mode)
+            .map(|podcast| (podcast_index::episodes_url(&podcast.rss_url), podcast.id))
+            .collect();
+        if enriched.is_empty() {
+            return HashMap::new();
+        }
+        let credentials = match &self.config.podcast_index {
+            Some(credentials) => credentials,
+            None => {
+                tracing::warn!("Skipping the Podcast Index. Set PODCASTS_INDEX_KEY and PODCASTS_INDEX_SECRET");
+                return HashMap::new();
+            }
+        };
+
+        let feed_urls = podcasts
+            .iter()
+            .filter(|podcast| podcast.enrich)
+            .map(|podcast| podcast.rss_url.as_str());
+        let web = Web::new(time::Duration::from_secs(10), &self.config.http)
+            .stats(&self.config.network)
+            .progress(Arc::clone(&self.progress))
+            .jobs(self.config.jobs)
+            .headers(podcast_index::headers(credentials, feed_urls));
+        let urls: Vec<&str> = enriched.keys().map(|url| url.as_str()).collect();
+
+        self.config
+            .timings
+            .measure(Phase::Fetch, || web.get(&urls))
+            .into_iter()
+            .filter_map(|(url, response)| match response {
+                Ok(bytes) => Some((enriched[url], bytes)),
+                Err(error) => {
+                    tracing::debug!("Can't look up {} in Podcast Index. {}", url, error);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Keeps the short ids and first seen times of the previously saved episodes. the new episodes
+    /// get the next ids and are marked as first seen now. the feed lists the newest episodes
+    /// first, so the oldest episode gets the smallest id
+    fn merge_saved(mut items: Vec<Episode>, saved: Option<&HashMap<String, Episode>>, now: u64) -> Vec<Episode> {
+        let mut next_number = saved
+            .and_then(|saved| saved.values().map(|episode| episode.number).max())
+            .unwrap_or(0)
+            + 1;
+
+        for item in items.iter_mut().rev() {
+            match saved.and_then(|saved| saved.get(&item.guid)) {
+                Some(episode) => {
+                    item.first_seen = episode.first_seen;
+
+                    // Attachments which were labeled as audio are only detected when they're downloaded
+                    if episode.is_attachment() && episode.enclosure_url == item.enclosure_url {
+                        item.enclosure_type = episode.enclosure_type.clone();
+                    }
+
+                    // What the Podcast Index filled in is kept while the feed still lacks it
+                    if item.duration == 0 {
+                        item.duration = episode.duration;
+                    }
+                    if item.chapters.is_empty() {
+                        item.chapters = episode.chapters.clone();
+                    }
+                    if item.transcript.is_empty() {
+                        item.transcript = episode.transcript.clone();
+                    }
+
+                    // Files saved by older versions have no short ids
+                    if episode.number > 0 {
+                        item.number = episode.number;
+                    } else {
+                        item.number = next_number;
+                        next_number += 1;
+                    }
+                }
+                None => {
+                    item.first_seen = now;
+                    item.number = next_number;
+                    next_number += 1;
+                }
+            }
+        }
+
+        items
+    }
+
+    /// Writes the number of unlistened episodes and the hours left of each podcast, largest
+    /// backlog first. downloaded episodes count as unlistened until they're removed or marked as
+    /// played
+    fn backlog<R, W>(&self, podcasts: Vec<(String, R, Vec<String>)>, mut writer: W) -> Result<(), Errors>
+    where
+        R: Read,
+        W: Write,
+    {
+        let ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+        let played_list = PlayedList::load(&self.config.app_directory);
+        let mut backlog: Vec<(String, usize, u64)> = Vec::new();
+        for (title, reader, downloaded_episodes) in podcasts {
+            let (count, seconds) = read_episodes(reader, self.config.strict)?
+                .into_iter()
+                .filter(|episode: &Episode| !ignore_list.is_ignored(episode) && !played_list.contains(episode))
+                .filter(|episode| {
+                    downloaded_episodes
+                        .contains(&episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+                })
+                .fold((0, 0), |(count, seconds), episode| {
+                    (count + 1, seconds + episode.estimated_duration())
+                });
+
+            if count > 0 {
+                backlog.push((title, count, seconds));
+            }
+        }
+        backlog.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then(b.2.cmp(&a.2))
+                .then_with(|| text::fold(&a.0).cmp(&text::fold(&b.0)))
+        });
+
+        writeln!(
+            writer,
+            "{:40} {:>8} {:>8}",
+            "Podcast".green(),
+            "Episodes".green(),
+            "Hours".green()
+        )?;
+        for (title, count, seconds) in backlog.iter() {
+            writeln!(writer, "{:40} {:>8} {:>8.1}", title, count, *seconds as f64 / 3600.0)?;
+        }
+
+        let (count, seconds) = backlog.iter().fold((0, 0), |(count, seconds), backlog| {
+            (count + backlog.1, seconds + backlog.2)
+        });
+        writeln!(writer, "{:40} {:>8} {:>8.1}", "Total", count, seconds as f64 / 3600.0)?;
+
+        Ok(())
+    }
+
+    /// Time of the last `episodes list`. 0 if the episodes were never listed
+    fn last_listed(&self) -> u64 {
+        fs::read_to_string(self.config.app_directory.join(LAST_LISTED_FILE))
+            .ok()
+            .and_then(|contents| contents.trim().parse::<u64>().ok())
+            .unwrap_or(0)
+    }
+
+    /// Writes the downloaded episodes to the directory of their podcast
+    pub fn save_files(&self, podcast_id: &str, files_data: Vec<(String, Bytes)>) -> Result<(), Errors> {
+        let podcast_directory = self.config.podcast_directory(podcast_id);
+
+        self.config.timings.measure(Phase::WriteFiles, || {
+            let mut entries = Vec::new();
+            let mut downloaded = Vec::new();
+            let mut completed = Vec::new();
+            for (path, content) in files_data {
+                // The template may put the file in a subdirectory
+                let (directory, file_name) = match path.rsplit_once('/') {
+                    Some((directory, file_name)) => (podcast_directory.join(directory), file_name),
+                    None => (podcast_directory.clone(), path.as_str()),
+                };
+                let mut file = FileSystem::new(&directory, file_name, vec![FilePermissions::Write]).open()?;
+                file.write_all(content.bytes())?;
+
+                // Recorded so that `pcasts verify` can find files which were corrupted later
+                if let Ok(podcast_id) = podcast_id.parse::<u64>() {
+                    downloaded.push(Event::new(EventKind::Downloaded, podcast_id, "", &path));
+                    completed.push(QueueEntry::new(podcast_id, "", &path, Status::Completed));
+                    entries.push(ManifestEntry {
+                        podcast_id,
+                        size: content.len() as u64,
+                        sha256: verify::checksum(content.bytes())?,
+                        expected_size: self
+                            .expected_sizes
+                            .lock()
+                            .ok()
+                            .and_then(|expected_sizes| expected_sizes.get(&format!("{}/{}", podcast_id, path)).copied())
+                            .unwrap_or(0),
+                        file_name: path,
+                    });
+                }
+            }
+
+            verify::record(&self.config.app_directory, &entries)?;
+            queue::record(&self.config.app_directory, &completed)?;
+            events::record(&self.config.app_directory, &downloaded)
+        })
+    }
+
+    /// Saves the downloaded episodes of the podcast and removes the ones which are now outside of
+    /// its window. shared by the commands and the TUI
+    pub fn store_downloads(&self, podcast_id: &str, files_data: Vec<(String, Bytes)>) -> Result<(), Errors> {
+        self.save_files(podcast_id, files_data)?;
+        self.enforce_podcast_window(podcast_id)
+    }
+
+    /// Downloads the episodes which the earlier downloads requested and didn't complete
+    fn resume_downloads(&self) -> Result<(), Errors> {
+        let pending = queue::pending(&self.config.app_directory, self.config.strict)?;
+        if pending.is_empty() {
+            println!("Nothing to resume, the last downloads completed");
+            return Ok(());
+        }
+
+        let mut podcasts_episodes: HashMap<u64, Vec<Episode>> = HashMap::new();
+        let mut episodes = Vec::new();
+        for download in pending.iter() {
+            // Podcasts which were removed since have no episodes
+            let podcast_episodes = podcasts_episodes
+                .entry(download.podcast_id)
+                .or_insert_with(|| self.podcast_episodes(download.podcast_id).unwrap_or_default());
+            match podcast_episodes.iter().find(|episode| episode.guid == download.guid) {
+                Some(episode) => episodes.push(episode.clone()),
+                None => tracing::info!("Skipping {}, its episode is gone", download.file_name),
+            }
+        }
+
+        println!("Resuming {} downloads", episodes.len());
+        self.download_batch(episodes)?;
+
+        Ok(())
+    }
+
+    /// Downloads the newest episodes of each podcast with the passed tag, or all the episodes
+    /// without a count. podcasts which were never updated are skipped
+    fn download_tagged(&self, tag: &str, count: Option<usize>) -> Result<(), Errors> {
+        let podcasts: Vec<Podcast> = load_podcasts(self.config)?
+            .into_iter()
+            .filter(|podcast| podcast.has_tag(tag))
+            .collect();
+        if podcasts.is_empty() {
+            println!("No podcasts are tagged {}", tag);
+            return Ok(());
+        }
+
+        for podcast in podcasts {
+            let podcast_id = podcast.id.to_string();
+            let episodes_file =
+                match FileSystem::new(&self.config.app_directory, &podcast_id, vec![FilePermissions::Read]).open() {
+                    Ok(file) => file,
+                    Err(error) => {
+                        tracing::debug!("Skipped podcast {} without episodes. {}", podcast_id, error);
+                        continue;
+                    }
+                };
+
+            let files_data = self.download(None, episodes_file, count)?;
+            self.store_downloads(&podcast_id, files_data)?;
+        }
+
+        Ok(())
+    }
+
+    /// The saved episodes of the podcast with the passed id, the newest first
+    pub fn podcast_episodes(&self, podcast_id: u64) -> Result<Vec<Episode>, Errors> {
+        let episodes_file = FileSystem::new(
+            &self.config.app_directory,
+            &podcast_id.to_string(),
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+
+        read_episodes(episodes_file, self.config.strict)
+    }
+
+    /// The saved episodes file of the podcast with the passed id, opened for reading only. none if
+    /// the podcast was never updated
+    fn saved_episodes_file(&self, podcast_id: u64) -> Result<Option<File>, Errors> {
+        match File::open(self.config.app_directory.join(podcast_id.to_string())) {
+            Ok(file) => Ok(Some(file)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(Errors::IO(error)),
+        }
+    }
+
+    /// Removes the downloaded episodes of the podcast which were released before its window
+    pub fn enforce_window(&self, podcast: &Podcast) -> Result<(), Errors> {
+        if podcast.window == 0 {
+            return Ok(());
+        }
+
+        let episodes_file = FileSystem::new(
+            &self.config.app_directory,
+            &podcast.id.to_string(),
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+        let downloaded_episodes = self.downloaded_files(podcast.id)?;
+
+        for file_name in self.expired_files(podcast.window, episodes_file, &downloaded_episodes, dates::now())? {
+            self.config.timings.measure(Phase::WriteFiles, || {
+                FileSystem::new(&self.config.podcast_directory(podcast.id), &file_name, vec![]).remove()
+            })?;
+            self.archive(podcast.id, &file_name)?;
+            println!("Removed {} (older than {} days)", file_name, podcast.window);
+        }
+
+        Ok(())
+    }
+
+    /// Enforces the window of the saved podcast with the passed id
+    pub fn enforce_podcast_window(&self, podcast_id: &str) -> Result<(), Errors> {
+        match load_podcasts(self.config)?
+            .into_iter()
+            .find(|podcast| podcast.id.to_string() == podcast_id)
+        {
+            Some(podcast) => self.enforce_window(&podcast),
+            None => Ok(()),
+        }
+    }
+
+    /// Removes the downloaded episodes of the podcast other than the newest ones, or moves them to
+    /// the passed directory. the removed episodes are archived so they aren't downloaded again
+    pub fn prune(&self, podcast: &Podcast, keep: u64, move_to: Option<&Path>, dry_run: bool) -> Result<(), Errors> {
+        let episodes_file = FileSystem::new(
+            &self.config.app_directory,
+            &podcast.id.to_string(),
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+        let downloaded_episodes = self.downloaded_files(podcast.id)?;
+
+        for file_name in self.pruned_files(keep, episodes_file, &downloaded_episodes)? {
+            if dry_run {
+                println!("Would prune {}", file_name);
+                continue;
+            }
+
+            let path = self.config.podcast_directory(podcast.id).join(&file_name);
+            match move_to {
+                Some(directory) => {
+                    let target = directory.join(&file_name);
+                    self.config.timings.measure(Phase::WriteFiles, || -> io::Result<()> {
+                        fs::create_dir_all(directory)?;
+                        // A rename fails across file systems, e.g to an external drive
+                        fs::rename(&path, &target)
+                            .or_else(|_error| fs::copy(&path, &target).and_then(|_size| fs::remove_file(&path)))
+                    })?;
+                    println!("Moved {} to {}", file_name, directory.display());
+                }
+                None => {
+                    self.config.timings.measure(Phase::WriteFiles, || {
+                        FileSystem::new(&self.config.podcast_directory(podcast.id), &file_name, vec![]).remove()
+                    })?;
+                    println!("Removed {} (keeping the newest {})", file_name, keep);
+                }
+            }
+            self.archive(podcast.id, &file_name)?;
+        }
+
+        // A directory of its own is kept, it may be one which the user created
+        if !dry_run && podcast.download_directory.is_empty() {
+            file_system::remove_empty_directories(&self.config.podcast_directory(podcast.id))?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the names of the downloaded episodes other than the passed number of the newest
+    /// ones, the oldest first
+    fn pruned_files<R>(&self, keep: u64, episodes: R, downloaded_episodes: &[String]) -> Result<Vec<String>, Errors>
+    where
+        R: Read,
+    {
+        let mut episodes: Vec<Episode> = read_episodes(episodes, self.config.strict)?
+            .into_iter()
+            .filter(|episode| {
+                downloaded_episodes.contains(&episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            })
+            .collect();
+        sort_newest_first(&mut episodes);
+
+        Ok(episodes
+            .into_iter()
+            .skip(keep as usize)
+            .rev()
+            .map(|episode| episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            .collect())
+    }
+
+    /// Returns the names of the downloaded episodes which were released more than the passed
+    /// number of days before now. episodes without a valid release date are kept
+    fn expired_files<R>(
+        &self,
+        window: u64,
+        episodes: R,
+        downloaded_episodes: &[String],
+        now: u64,
+    ) -> Result<Vec<String>, Errors>
+    where
+        R: Read,
+    {
+        let oldest = now.saturating_sub(window * 24 * 60 * 60);
+
+        Ok(read_episodes(episodes, self.config.strict)?
+            .into_iter()
+            .filter(|episode: &Episode| match dates::parse_pub_date(&episode.pub_date) {
+                Some(pub_date) => pub_date < oldest,
+                None => false,
+            })
+            .map(|episode| episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            .filter(|file_name| downloaded_episodes.contains(file_name))
+            .collect())
+    }
+
+    /// Returns the names of the downloaded episodes of the podcast which match either the passed
+    /// episode ids or the glob pattern of the file name
+    fn remove<R>(
+        &self,
+        ids: Option<&Values>,
+        pattern: Option<&str>,
+        episodes: R,
+        downloaded_episodes: &[String],
+    ) -> Result<Vec<String>, Errors>
+    where
+        R: Read,
+    {
+        let pattern = match pattern {
+            Some(pattern) => {
+                Some(glob::Pattern::new(pattern).map_err(|error| Errors::InvalidPattern(error.to_string()))?)
+            }
+            None => None,
+        };
+
+        let episodes = read_episodes(episodes, self.config.strict)?;
+        let episodes = match ids {
+            Some(ids) => resolver::episodes(&episodes, ids.clone())?,
+            None => episodes.iter().collect(),
+        };
+        let file_names: Vec<String> = episodes
+            .into_iter()
+            .map(|episode| episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            .filter(|file_name| match &pattern {
+                Some(pattern) => pattern.matches(file_name),
+                None => true,
+            })
+            .filter(|file_name| downloaded_episodes.contains(file_name))
+            .collect();
+
+        if file_names.is_empty() {
+            return Err(Errors::NoMatches);
+        }
+
+        Ok(file_names)
+    }
+
+    /// Remembers that the passed downloaded file of the podcast was removed
+    fn archive<T>(&self, podcast_id: T, file_name: &str) -> Result<(), Errors>
+    where
+        T: fmt::Display,
+    {
+        let mut file = FileSystem::new(
+            &self.config.app_directory.join(ARCHIVE_DIRECTORY),
+            &podcast_id.to_string(),
+            vec![FilePermissions::Append],
+        )
+        .open()?;
+        writeln!(file, "{}", file_name)?;
+
+        if let Ok(podcast_id) = podcast_id.to_string().parse::<u64>() {
+            events::record(
+                &self.config.app_directory,
+                &[Event::new(EventKind::Archived, podcast_id, "", file_name)],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Renames the podcast in its saved episodes and moves its downloads to the names the file
+    /// template gives them under the new name. the archive and the manifest follow, so removed
+    /// downloads aren't downloaded again and the checksums are still found. returns how many
+    /// downloads were moved
+    pub fn rename_podcast(&self, podcast: &Podcast) -> Result<usize, Errors> {
+        let episodes_file = FileSystem::new(
+            &self.config.app_directory,
+            &podcast.id.to_string(),
+            vec![FilePermissions::Read],
+        )
+        .open();
+        let mut episodes = match episodes_file {
+            Ok(episodes_file) => read_episodes(episodes_file, self.config.strict)?,
+            // The podcast was never updated
+            Err(_error) => return Ok(0),
+        };
+
+        let mut names = HashMap::new();
+        for episode in episodes.iter_mut() {
+            let old_name = episode.file_name(self.config.podcast_file_template(episode.podcast_id));
+            episode.podcast = podcast.name().to_string();
+            let new_name = episode.file_name(self.config.podcast_file_template(episode.podcast_id));
+            if old_name != new_name {
+                names.insert(old_name, new_name);
+            }
+        }
+
+        let mut journal = Journal::new(&self.config.app_directory);
+        let file = FileSystem::new(&self.config.app_directory, &podcast.id.to_string(), vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        for episode in episodes {
+            writer.serialize(episode)?;
+        }
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        journal.stage(file)?;
+
+        let archived = self.archived_files(podcast.id);
+        if !archived.is_empty() {
+            let mut file = FileSystem::new(
+                &self.config.app_directory.join(ARCHIVE_DIRECTORY),
+                &podcast.id.to_string(),
+                vec![],
+            )
+            .atomic()?;
+            for file_name in archived.iter() {
+                writeln!(file, "{}", names.get(file_name).unwrap_or(file_name))?;
+            }
+            journal.stage(file)?;
+        }
+        verify::stage_renames(
+            &self.config.app_directory,
+            podcast.id,
+            &names,
+            self.config.strict,
+            &mut journal,
+        )?;
+
+        let podcast_directory = self.config.podcast_directory(podcast.id);
+        let mut moved = 0;
+        for (old_name, new_name) in names.iter() {
+            let old_path = podcast_directory.join(old_name);
+            if !old_path.exists() {
+                continue;
+            }
+
+            let new_path = podcast_directory.join(new_name);
+            if let Some(directory) = new_path.parent() {
+                fs::create_dir_all(directory)?;
+            }
+            fs::rename(old_path, new_path)?;
+            moved += 1;
+        }
+
+        journal.commit()?;
+        Ok(moved)
+    }
+
+    /// The names of the downloaded files of the podcast which were removed
+    pub fn archived_files<T>(&self, podcast_id: T) -> Vec<String>
+    where
+        T: fmt::Display,
+    {
+        match fs::read_to_string(
+            self.config
+                .app_directory
+                .join(ARCHIVE_DIRECTORY)
+                .join(podcast_id.to_string()),
+        ) {
+            Ok(contents) => contents.lines().map(|line| line.to_string()).collect(),
+            Err(_error) => Vec::new(),
+        }
+    }
+
+    /// The newest episodes, up to the passed count, which aren't downloaded, ignored, played or
+    /// removed before. the feed lists the newest episodes first
+    pub fn sync_selection<R>(
+        &self,
+        episodes: R,
+        count: usize,
+        downloaded_episodes: &[String],
+        archived_episodes: &[String],
+    ) -> Result<Vec<Episode>, Errors>
+    where
+        R: Read,
+    {
+        let ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+        let played_list = PlayedList::load(&self.config.app_directory);
+
+        Ok(read_episodes(episodes, self.config.strict)?
+            .into_iter()
+            .filter(|episode: &Episode| !ignore_list.is_ignored(episode))
+            .take(count)
+            .filter(|episode| {
+                let file_name = episode.file_name(self.config.podcast_file_template(episode.podcast_id));
+                !downloaded_episodes.contains(&file_name)
+                    && !archived_episodes.contains(&file_name)
+                    && !played_list.contains(episode)
+            })
+            .collect())
+    }
+
+    /// Paths of all the files in the directory of the podcast and its subdirectories, relative to it
+    pub fn downloaded_files<T>(&self, podcast_id: T) -> Result<Vec<String>, Errors>
+    where
+        T: fmt::Display,
+    {
+        let podcast_directory = self.config.podcast_directory(podcast_id);
+        let mut downloaded_episodes = Vec::new();
+        let mut directories = vec![String::new()];
+
+        // Nothing was downloaded for the podcast yet
+        if !podcast_directory.exists() {
+            return Ok(downloaded_episodes);
+        }
+
+        while let Some(directory) = directories.pop() {
+            let dir_files = fs::read_dir(podcast_directory.join(&directory)).map_err(Errors::IO)?;
+
+            for dir_entry in dir_files {
+                let path = dir_entry?.path();
+                let entry = path
+                    .file_name()
+                    .ok_or_else(|| Errors::IO(io::Error::other("Couldn't get file name")))?
+                    .to_str();
+                let entry = match entry {
+                    Some(entry) if directory.is_empty() => entry.to_string(),
+                    Some(entry) => format!("{}/{}", directory, entry),
+                    None => continue,
+                };
+
+                if path.is_dir() {
+                    directories.push(entry);
+                } else {
+                    downloaded_episodes.push(entry);
+                }
+            }
+        }
+
+        Ok(downloaded_episodes)
+    }
+
+    fn list_downloaded<R, W>(
+        &self,
+        episodes: R,
+        downloaded_episodes: Vec<String>,
+        mut writer: W,
+        count: Option<usize>,
+    ) -> Result<(), Errors>
+    where
+        R: Read,
+        W: Write,
+    {
+        let download_matches = self.subcommand_matches("download");
+        let episodes: Vec<Episode> = read_episodes(episodes, self.config.strict)?
+            .into_iter()
+            .filter(|episode: &Episode| {
+                downloaded_episodes.contains(&episode.file_name(self.config.podcast_file_template(episode.podcast_id)))
+            })
+            .collect();
+        let episodes = listing_order(
+            episodes,
+            download_matches.and_then(|matches| matches.value_of("sort")),
+            download_matches.is_some_and(|matches| matches.is_present("reverse")),
+        );
+        let episodes: Vec<Episode> = episodes
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _episode)| count.is_none_or(|count| *index >= count))
+            .map(|(_index, episode)| episode)
+            .collect();
+
+        if download_matches.and_then(|matches| matches.value_of("format")) == Some("table") {
+            return Ok(self
+                .episodes_table(&episodes, &downloaded_episodes)
+                .write(writer, table::terminal_width())?);
+        }
+        for episode in episodes.iter() {
+            writeln!(writer, "{}", episode)?;
+        }
+
+        Ok(())
+    }
+
+    /// The episodes in columns, with whether each one was downloaded. the title is the column
+    /// which is cut to the width of the terminal
+    fn episodes_table(&self, episodes: &[Episode], downloaded_episodes: &[String]) -> Table {
+        let mut table = Table::new(vec!["ID", "DATE", "DURATION", "TITLE", "DOWNLOADED"], 3);
+        for episode in episodes {
+            let id = match episode.number {
+                0 => episode.guid.clone(),
+                number => number.to_string(),
+            };
+            let date = episode.release_time().map(dates::format_date).unwrap_or_default();
+            let duration = match episode.duration {
+                0 => "-".to_string(),
+                duration => dates::format_duration(duration),
+            };
+            let file_name = episode.file_name(self.config.podcast_file_template(episode.podcast_id));
+            let downloaded = match downloaded_episodes.contains(&file_name) {
+                true => "yes",
+                false => "",
+            };
+            table.push(vec![id, date, duration, episode.title.clone(), downloaded.to_string()]);
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, Application, ApplicationBuilder, DEFAULT_FILE_TEMPLATE};
+    use std::str::from_utf8;
+
+    fn create_config() -> Config {
+        fixtures::config()
+    }
+
+    fn create_app() -> Application {
+        let config = create_config();
+        ApplicationBuilder::new(config).episodes_subcommand().build()
+    }
+
+    #[test]
+    fn update() {
+        let app = create_app();
+        let config = create_config();
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "update", "--id", "15913066141282366353"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+        let podcasts = vec![Podcast {
+            id: 15913066141282366353,
+            url: "https://syntax.fm".to_string(),
             rss_url: "https://feed.syntax.fm/rss".to_string(),
             title: "Syntax - Tasty Web Development Treats".to_string(),
+            paused: false,
+            failures: 0,
+            window: 0,
+            sync_count: 0,
+            last_update: 0,
+            keep: 0,
+            priority: 0,
+            tags: String::new(),
+            alias: String::new(),
+            enrich: false,
+            download_directory: String::new(),
+            file_template: String::new(),
+            manual: false,
         }];
         let mut syntax_expected_output = String::new();
-        let mut file = File::open("src/test_files/syntax.csv").expect("Can't open syntax.csv");
+        let mut file = File::open(fixtures::path("syntax.csv")).expect("Can't open syntax.csv");
         file.read_to_string(&mut syntax_expected_output)
             .expect("Can't write syntax.csv");
 
-        let mut writers = HashMap::new();
-        writers.insert(15913066141282366353, Vec::new());
-        episodes.update(&podcasts, &mut writers);
+        let mut writers = HashMap::new();
+        writers.insert(15913066141282366353, Vec::new());
+        let (summary, moves) = episodes
+            .update(&podcasts, &HashMap::new(), &mut writers)
+            .expect("Can't update episodes");
+        assert!(moves.is_empty());
+
+        let syntax_output_string = from_utf8(writers.get(&15913066141282366353).unwrap()).unwrap();
+        let episodes_count = syntax_expected_output.trim().lines().count() - 1;
+
+        assert_eq!(syntax_output_string.trim(), syntax_expected_output.trim());
+        assert_eq!(summary, vec![UpdateStatus::New(episodes_count)]);
+    }
+
+    #[test]
+    fn update_all() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "update"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+        let podcasts = vec![
+            Podcast {
+                id: 15913066141282366353,
+                url: "https://syntax.fm".to_string(),
+                rss_url: "https://feed.syntax.fm/rss".to_string(),
+                title: "Syntax - Tasty Web Development Treats".to_string(),
+                paused: false,
+                failures: 0,
+                window: 0,
+                sync_count: 0,
+                last_update: 0,
+                keep: 0,
+                priority: 0,
+                tags: String::new(),
+                alias: String::new(),
+                enrich: false,
+                download_directory: String::new(),
+                file_template: String::new(),
+                manual: false,
+            },
+            Podcast {
+                id: 1,
+                url: "https://example.com".to_string(),
+                rss_url: "https://example.com/rss".to_string(),
+                title: "Missing".to_string(),
+                paused: false,
+                failures: 0,
+                window: 0,
+                sync_count: 0,
+                last_update: 0,
+                keep: 0,
+                priority: 0,
+                tags: String::new(),
+                alias: String::new(),
+                enrich: false,
+                download_directory: String::new(),
+                file_template: String::new(),
+                manual: false,
+            },
+        ];
+
+        // All the episodes of syntax were already saved
+        let mut expected_output = String::new();
+        let mut file = File::open(fixtures::path("syntax.csv")).expect("Can't open syntax.csv");
+        file.read_to_string(&mut expected_output)
+            .expect("Can't read syntax.csv");
+        let saved: HashMap<String, Episode> = csv::Reader::from_reader(expected_output.as_bytes())
+            .deserialize()
+            .filter_map(|item: Result<Episode, csv::Error>| item.ok())
+            .map(|episode| (episode.guid.clone(), episode))
+            .collect();
+        let mut existing = HashMap::new();
+        existing.insert(15913066141282366353, saved);
+
+        let mut writers = HashMap::new();
+        writers.insert(15913066141282366353, Vec::new());
+        writers.insert(1, Vec::new());
+        let (summary, _moves) = episodes
+            .update(&podcasts, &existing, &mut writers)
+            .expect("Can't update episodes");
+
+        assert_eq!(
+            summary,
+            vec![UpdateStatus::Unchanged, UpdateStatus::Failed(Errors::RSS.to_string())]
+        );
+        assert!(writers.get(&1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_people_feed() {
+        let feed = fixtures::read("people.xml").expect("Can't read fixture");
+        let parsed = Episode::parse_feed(&feed, 1).expect("Can't parse feed");
+        let people = |guid: &str| -> Vec<String> {
+            let episode = parsed.iter().find(|episode| episode.guid == guid).expect("No episode");
+            episode
+                .people()
+                .iter()
+                .map(|person| format!("{} ({})", person.name, person.role))
+                .collect()
+        };
+        // The roles are lowercased and the people without a name are dropped
+        assert_eq!(people("people-3"), vec!["Ada Lovelace (host)", "José Valim (guest)"]);
+        // The separator of the saved people can't be a part of a name
+        assert_eq!(people("people-2"), vec!["Knuth, Donald (guest)"]);
+        // Without people of its own the episode has the ones of the channel
+        assert_eq!(people("people-1"), vec!["Ada Lovelace (host)"]);
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for episode in parsed.iter() {
+            writer.serialize(episode).unwrap();
+        }
+        let saved = writer.into_inner().unwrap();
+
+        let args = create_app()
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "search", "--person", "ada"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let person = episodes_matches
+            .subcommand_matches("search")
+            .and_then(|matches| matches.value_of("person"));
+        assert_eq!(person, Some("ada"));
+        let config = create_config();
+        let episodes = Episodes::new(episodes_matches, &config);
+        let search = |query: Option<&str>, person: Option<&str>| {
+            let query = query.map(|query| SearchQuery::new(query, false).unwrap());
+            let mut output = Vec::new();
+            let found = episodes
+                .search(&saved[..], query.as_ref(), person, &mut output)
+                .unwrap();
+            (found, from_utf8(&output).unwrap().to_string())
+        };
+
+        assert_eq!(search(None, person).0, 2);
+        let (found, output) = search(None, Some("jose"));
+        assert_eq!(found, 1);
+        assert!(output.contains("Elixir with José"));
+        assert_eq!(search(Some("typesetting"), Some("knuth")).0, 1);
+        assert_eq!(search(Some("elixir"), Some("knuth")).0, 0);
+    }
+
+    #[test]
+    fn search_episodes() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "search", "css"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,description,people
+a,CSS Grid,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,,host:Wes Bos;guest:José Valim
+b,Layouts,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,<p>Flexbox and <b>CSS</b> grid</p>,host:Wes Bos
+c,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,<a href=""https://example.com/css"">Links</a>,
+"###;
+        let search_person = |query: Option<&str>, person: Option<&str>| {
+            let mut output = Vec::new();
+            let query = query.map(|query| SearchQuery::new(query, false).unwrap());
+            let found = episodes
+                .search(input.as_bytes(), query.as_ref(), person, &mut output)
+                .unwrap();
+            (found, from_utf8(&output).unwrap().to_string())
+        };
+        let search = |query: &str, is_regex: bool| {
+            let mut output = Vec::new();
+            let query = SearchQuery::new(query, is_regex).unwrap();
+            let found = episodes
+                .search(input.as_bytes(), Some(&query), None, &mut output)
+                .unwrap();
+            (found, from_utf8(&output).unwrap().to_string())
+        };
+
+        let (found, output) = search("css", false);
+        assert_eq!(found, 2);
+        assert!(output.contains("CSS Grid") && output.contains("Layouts"));
+        assert_eq!(search("^lay.*s$", true).0, 1);
+        assert_eq!(search("css grid", true).0, 2);
+        assert!(SearchQuery::new("(", true).is_err());
+
+        assert_eq!(search_person(None, Some("wes bos")).0, 2);
+        assert_eq!(search_person(Some("flexbox"), Some("Wes")).0, 1);
+        let (found, output) = search_person(None, Some("jose"));
+        assert_eq!(found, 1);
+        assert!(output.contains("José Valim (guest)"));
+
+        let episodes_list = read_episodes(input.as_bytes(), false).unwrap();
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        episodes.people(&episodes_list, None, &mut output).unwrap();
+        assert_eq!(
+            from_utf8(&output).unwrap(),
+            "   2 episodes Wes Bos (host)\n   1 episode  José Valim (guest)\n"
+        );
+    }
+
+    #[test]
+    fn rename_podcast() {
+        let app = create_app();
+        let config = create_config();
+        fs::create_dir_all(config.podcast_directory(1)).unwrap();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Syntax - Tasty Web Development Treats,1,https://example.com/a.mp3,audio/mpeg
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Syntax - Tasty Web Development Treats,1,https://example.com/b.mp3,audio/mpeg
+"###;
+        fs::write(config.app_directory.join("1"), input).unwrap();
+        let saved = read_episodes(input.as_bytes(), true).unwrap();
+        let old_names: Vec<String> = saved
+            .iter()
+            .map(|episode| episode.file_name(config.podcast_file_template(episode.podcast_id)))
+            .collect();
+        fs::write(config.podcast_directory(1).join(&old_names[0]), "rust").unwrap();
+        episodes.archive(1, &old_names[1]).unwrap();
+        verify::record(
+            &config.app_directory,
+            &[ManifestEntry {
+                podcast_id: 1,
+                file_name: old_names[0].clone(),
+                size: 4,
+                sha256: String::new(),
+                expected_size: 0,
+            }],
+        )
+        .unwrap();
+
+        let mut podcast = Podcast::new(
+            String::new(),
+            String::new(),
+            "Syntax - Tasty Web Development Treats".to_string(),
+        );
+        podcast.id = 1;
+        podcast.alias = "Syntax".to_string();
+        assert_eq!(episodes.rename_podcast(&podcast).unwrap(), 1);
+
+        let renamed = read_episodes(fs::File::open(config.app_directory.join("1")).unwrap(), true).unwrap();
+        assert!(renamed.iter().all(|episode| episode.podcast == "Syntax"));
+        assert!(config.podcast_directory(1).join("Syntax_Rust.mp3").exists());
+        assert!(!config.podcast_directory(1).join(&old_names[0]).exists());
+        assert_eq!(episodes.archived_files(1), vec!["Syntax_Go.mp3".to_string()]);
+        assert!(verify::read_manifest(&config.app_directory, true)
+            .unwrap()
+            .contains_key(&(1, "Syntax_Rust.mp3".to_string())));
+    }
+
+    #[test]
+    fn migrate_downloads() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        fs::create_dir_all(&config.download_directory).unwrap();
+        fs::write(
+            config.app_directory.join("podcast_list.csv"),
+            "id,url,rss_url,title\n1,-,https://example.com/rss,Syntax\n",
+        )
+        .unwrap();
+        fs::write(
+            config.app_directory.join("1"),
+            r###"guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Syntax,1,https://example.com/a.mp3,audio/mpeg
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Syntax,1,https://example.com/b.mp3,audio/mpeg
+"###,
+        )
+        .unwrap();
+        // Older versions saved every download right in the download directory
+        fs::write(config.download_directory.join("Syntax_Rust.mp3"), "rust").unwrap();
+        fs::write(config.download_directory.join("notes.txt"), "notes").unwrap();
+        assert!(episodes.downloaded_files(1).unwrap().is_empty());
+
+        assert_eq!(migrate_flat_downloads(&config).unwrap(), 1);
+        assert_eq!(
+            episodes.downloaded_files(1).unwrap(),
+            vec!["Syntax_Rust.mp3".to_string()]
+        );
+        assert_eq!(
+            fs::read_to_string(config.podcast_directory(1).join("Syntax_Rust.mp3")).unwrap(),
+            "rust"
+        );
+        assert!(config.download_directory.join("notes.txt").exists());
+
+        // It only runs once
+        fs::write(config.download_directory.join("Syntax_Go.mp3"), "go").unwrap();
+        assert_eq!(migrate_flat_downloads(&config).unwrap(), 0);
+        assert!(config.download_directory.join("Syntax_Go.mp3").exists());
+    }
+
+    #[test]
+    fn episode_soundbites() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "soundbites", "a"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type,soundbites
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/a.m4a,audio/x-m4a,"73,60.5,Why Rust?;3600.5,42,"
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/b.mp3,audio/mpeg,
+"###;
+        let episodes_list = read_episodes(input.as_bytes(), true).unwrap();
+        assert_eq!(episodes_list[0].soundbites().len(), 2);
+        assert_eq!(episodes_list[0].soundbites()[0].title, "Why Rust?");
+
+        let mut output = Vec::new();
+        episodes.soundbites(&episodes_list[0], false, &mut output).unwrap();
+        assert_eq!(
+            from_utf8(&output).unwrap(),
+            "  1. 1:13 - 2:14 Why Rust?\n  2. 1:00:00 - 1:00:43 \n"
+        );
+
+        let mut output = Vec::new();
+        episodes.soundbites(&episodes_list[1], true, &mut output).unwrap();
+        assert_eq!(from_utf8(&output).unwrap(), "Go has no soundbites\n");
+        assert!(matches!(
+            episodes.soundbites(&episodes_list[0], true, Vec::new()),
+            Err(Errors::UnsupportedClip(_))
+        ));
+    }
+
+    #[test]
+    fn show_episode() {
+        let app = create_app();
+        let config = create_config();
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "show", "--id", "1", "--episode-id", "2"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,number,duration,season,episode_number,episode_type,explicit,description
+a,Notes,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,2,3708,3,12,bonus,true,"<p>Links from <a href=""https://example.com"">the show</a>:</p><ul><li>One</li></ul>"
+"###;
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        episodes
+            .show(input.as_bytes(), "2", &mut output)
+            .expect("Can't show episode");
+
+        let expected_output = r###"Title:        Notes
+Release date: Wed, 22 Jul 2020 13:00:00 +0000 (1mo ago)
+ID:           a
+Short ID:     2
+Episode:      season 3, episode 12, bonus
+Duration:     1:01:48
+Explicit:     yes
+Link:         -
+Podcast:      Podcast
+Podcast ID:   1
 
-        let syntax_output_string = from_utf8(writers.get(&15913066141282366353).unwrap()).unwrap();
+Show notes:
+Links from the show:
 
-        assert_eq!(syntax_output_string.trim(), syntax_expected_output.trim());
+- One
+
+Links:
+  1. https://example.com
+"###;
+        assert_eq!(from_utf8(&output).unwrap(), expected_output);
+        assert!(episodes.show(input.as_bytes(), "b", Vec::new()).is_err());
+    }
+
+    #[test]
+    fn select_enclosure() {
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type,enclosure_length,duration,alternates
+a,Alternates,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/a.mp3,audio/mpeg,60000000,3600,64000;0;audio/opus;https://example.com/a.opus 0;900;video/mp4;https://example.com/a.mp4
+"###;
+        let episode: Episode = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(episode.alternates().len(), 2);
+        let selected = |bandwidth, max_size| {
+            let mut episode = episode.clone();
+            episode.select_enclosure(bandwidth, max_size);
+            episode.enclosure_url
+        };
+
+        // The opus version is estimated at 28.8MB from its bitrate, the video isn't considered
+        assert_eq!(selected(Bandwidth::Unmetered, None), "https://example.com/a.mp3");
+        assert_eq!(selected(Bandwidth::Metered, None), "https://example.com/a.opus");
+        assert_eq!(
+            selected(Bandwidth::Unmetered, Some(100_000_000)),
+            "https://example.com/a.mp3"
+        );
+        assert_eq!(
+            selected(Bandwidth::Unmetered, Some(50_000_000)),
+            "https://example.com/a.opus"
+        );
+        assert_eq!(selected(Bandwidth::Unmetered, Some(1000)), "https://example.com/a.opus");
+
+        let mut single = episode.clone();
+        single.alternates = String::new();
+        single.select_enclosure(Bandwidth::Metered, None);
+        assert_eq!(single.enclosure_url, "https://example.com/a.mp3");
+    }
+
+    #[test]
+    fn list_date_range() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "list",
+            "--since",
+            "2020-07-01",
+            "--until",
+            "2020-07-22",
+            "--count",
+            "2",
+            "--output",
+            "json",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        // Out of order like a feed which was edited, with an episode before and after the range
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+b,Second,"Mon, 13 Jul 2020 13:00:00 +0000",-,Podcast,1
+d,Fourth,"Thu, 23 Jul 2020 13:00:00 +0000",-,Podcast,1
+c,Third,"Wed, 22 Jul 2020 23:00:00 +0000",-,Podcast,1
+a,First,"Wed, 01 Jul 2020 13:00:00 +0000",-,Podcast,1
+x,Undated,yesterday,-,Podcast,1
+"###;
+        let mut output = Vec::new();
+        episodes
+            .list(input.as_bytes(), &mut output, 0, 0)
+            .expect("Can't list episodes");
+        let guids: Vec<String> = from_utf8(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<Episode>(line).unwrap().guid)
+            .collect();
+        assert_eq!(guids, ["b", "c"]);
+
+        let args = create_app()
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "list", "--since", "July"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        assert!(Episodes::new(episodes_matches, &config)
+            .list(input.as_bytes(), Vec::new(), 0, 0)
+            .is_err());
+    }
+
+    #[test]
+    fn list_sort_orders() {
+        let config = create_config();
+        // Out of order, with a tie in duration and an episode without a date
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,duration
+b,second,"Mon, 13 Jul 2020 13:00:00 +0000",https://example.com/b.mp3,Podcast,1,1800
+d,Fourth,"Thu, 23 Jul 2020 13:00:00 +0000",https://example.com/d.mp3,Podcast,1,600
+c,Third,"Wed, 22 Jul 2020 23:00:00 +0000",https://example.com/c.mp3,Podcast,1,1800
+a,First,"Wed, 01 Jul 2020 13:00:00 +0000",https://example.com/a.mp3,Podcast,1,3600
+x,Undated,yesterday,https://example.com/x.mp3,Podcast,1,0
+"###;
+        let list = |args: &[&str], json: bool| -> Vec<String> {
+            let mut command = vec!["pcasts", "episodes", "list"];
+            command.extend(args);
+            if json {
+                command.extend(["--output", "json"]);
+            }
+            let args = create_app().app.get_matches_from(command);
+            let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+            let mut output = Vec::new();
+            Episodes::new(episodes_matches, &config)
+                .list(input.as_bytes(), &mut output, 0, 0)
+                .expect("Can't list episodes");
+            let output = from_utf8(&output).unwrap();
+            match json {
+                true => output
+                    .lines()
+                    .map(|line| serde_json::from_str::<Episode>(line).unwrap().guid)
+                    .collect(),
+                false => output
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("ID:"))
+                    .map(|guid| guid.trim().to_string())
+                    .collect(),
+            }
+        };
+
+        // The newest episode is last by default, the undated one before the oldest
+        assert_eq!(list(&[], true), ["x", "a", "b", "c", "d"]);
+        assert_eq!(list(&["--sort", "date"], true), ["x", "a", "b", "c", "d"]);
+        assert_eq!(list(&["--reverse"], true), ["d", "c", "b", "a", "x"]);
+        // Titles are compared without case
+        assert_eq!(list(&["--sort", "title"], true), ["a", "d", "b", "c", "x"]);
+        assert_eq!(list(&["--sort", "title", "--reverse"], true), ["x", "c", "b", "d", "a"]);
+        // The episodes of the same duration stay in the date order
+        assert_eq!(list(&["--sort", "duration"], true), ["x", "d", "b", "c", "a"]);
+        assert_eq!(
+            list(&["--sort", "duration", "--reverse"], true),
+            ["a", "c", "b", "d", "x"]
+        );
+        // The human output is in the same order as the JSON
+        assert_eq!(list(&["--sort", "duration"], false), ["x", "d", "b", "c", "a"]);
+
+        // `download --list` orders only the downloaded episodes
+        let downloaded: Vec<String> = read_episodes(input.as_bytes(), false)
+            .unwrap()
+            .iter()
+            .filter(|episode| episode.guid != "c")
+            .map(|episode| episode.file_name(&config.file_template))
+            .collect();
+        let args = create_app().app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "download",
+            "--id",
+            "1",
+            "--list",
+            "--sort",
+            "duration",
+            "--reverse",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let mut output = Vec::new();
+        Episodes::new(episodes_matches, &config)
+            .list_downloaded(input.as_bytes(), downloaded, &mut output, None)
+            .expect("Can't list downloaded episodes");
+        let guids: Vec<&str> = from_utf8(&output)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.strip_prefix("ID:"))
+            .map(str::trim)
+            .collect();
+        assert_eq!(guids, ["a", "b", "d", "x"]);
     }
 
     #[test]
@@ -452,7 +3696,7 @@ mod tests {
         let config = create_config();
         let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list"]);
         let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
-        let episodes = Episodes::new(&episodes_matches, &config);
+        let episodes = Episodes::new(episodes_matches, &config);
 
         let input = r###"guid,title,pub_date,link,podcast,podcast_id
 272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
@@ -463,12 +3707,144 @@ mod tests {
             pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
             link: "https://traffic.libsyn.com/secure/syntax/Syntax268.mp3".to_string(),
             podcast: "Syntax - Tasty Web Development Treats".to_string(),
-            podcast_id: 15913066141282366353
+            podcast_id: 15913066141282366353,
+            enclosure_url: String::new(),
+            enclosure_type: String::new(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
         };
         let expected_output = episode.to_string();
         let mut output = Vec::new();
-        episodes.list(input, &mut output).expect("Can't list episodes");
+        colored::control::set_override(false);
+        episodes.list(input, &mut output, 0, 0).expect("Can't list episodes");
         assert_eq!(from_utf8(&output).unwrap().trim(), expected_output.trim());
+
+        // Episodes found after the last listing get a badge
+        let input = format!(
+            "guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type,enclosure_length,number,first_seen\n\
+             1,New,\"Wed, 22 Jul 2020 13:00:00 +0000\",-,Podcast,1,,,0,1,{}\n",
+            dates::now()
+        );
+        let mut output = Vec::new();
+        episodes
+            .list(input.as_bytes(), &mut output, dates::now() - 60, 0)
+            .expect("Can't list episodes");
+        let output = from_utf8(&output).unwrap();
+        assert!(output.starts_with(" NEW \nTitle:"));
+        assert!(output.contains("Wed, 22 Jul 2020 13:00:00 +0000 (1mo ago)"));
+
+        // Only the episodes found by the last update are listed with --new
+        let mut output = Vec::new();
+        episodes
+            .list(input.as_bytes(), &mut output, 0, dates::now() + 1)
+            .expect("Can't list episodes");
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn list_new() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list", "--new"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+        colored::control::set_override(false);
+
+        // The last update found Go and Rust, and the episodes were listed before it
+        let last_update = dates::now() - 100;
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(
+            config.app_directory.join("podcast_list.csv"),
+            format!(
+                "id,url,rss_url,title,last_update\n1,-,https://example.com/rss,Podcast,{}\n",
+                last_update
+            ),
+        )
+        .unwrap();
+        let input = format!(
+            "guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type,enclosure_length,number,first_seen\n\
+             a,Java,\"Mon, 20 Jul 2020 13:00:00 +0000\",-,Podcast,1,,,0,1,{}\n\
+             b,Go,\"Tue, 21 Jul 2020 13:00:00 +0000\",-,Podcast,1,,,0,2,{}\n\
+             c,Rust,\"Wed, 22 Jul 2020 13:00:00 +0000\",-,Podcast,1,,,0,3,{}\n",
+            last_update - 1_000,
+            last_update,
+            last_update
+        );
+        fs::write(config.app_directory.join("1"), &input).unwrap();
+        fs::write(
+            config.app_directory.join(LAST_LISTED_FILE),
+            (last_update - 500).to_string(),
+        )
+        .unwrap();
+
+        let list = || {
+            let mut output = Vec::new();
+            episodes
+                .list(input.as_bytes(), &mut output, episodes.last_listed(), last_update)
+                .expect("Can't list episodes");
+            String::from_utf8(output).unwrap()
+        };
+        let output = list();
+        assert!(!output.contains("Java"));
+        assert!(output.contains("Go") && output.contains("Rust"));
+        assert_eq!(output.matches(" NEW ").count(), 2);
+
+        // Listing records the time, so the same episodes are listed without the badges next time
+        episodes.run().expect("Can't list episodes");
+        assert!(episodes.last_listed() >= dates::now() - 1);
+        let output = list();
+        assert!(output.contains("Go") && output.contains("Rust"));
+        assert!(!output.contains(" NEW "));
+    }
+
+    #[test]
+    fn list_strict() {
+        let app = create_app();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+
+        // The second podcast was never updated, and the third row is malformed
+        let config = create_config();
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(
+            config.app_directory.join("podcast_list.csv"),
+            "id,url,rss_url,title\n\
+             1,-,https://example.com/rss,Podcast\n\
+             2,-,https://example.com/other,Other\n\
+             three,-,https://example.com/broken,Broken\n",
+        )
+        .unwrap();
+        fs::write(
+            config.app_directory.join("1"),
+            "guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type,enclosure_length,number\n\
+             a,Java,\"Mon, 20 Jul 2020 13:00:00 +0000\",-,Podcast,1,,,0,1\n",
+        )
+        .unwrap();
+
+        Episodes::new(episodes_matches, &config)
+            .run()
+            .expect("Can't list episodes");
+        assert!(!config.app_directory.join("2").exists());
+
+        let config = config.with_strict(true);
+        match Episodes::new(episodes_matches, &config).run() {
+            Err(Errors::InvalidRecord(line, _error)) => assert_eq!(line, 4),
+            result => panic!("Expected an invalid record, got {:?}", result),
+        }
     }
 
     #[test]
@@ -479,8 +3855,10 @@ mod tests {
             .app
             .get_matches_from(vec!["pcasts", "episodes", "download", "--id", "15913066141282366353"]);
         let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
-        let episode_id = episodes_matches.values_of("episode-id");
-        let episodes = Episodes::new(&episodes_matches, &config);
+        let episode_id = episodes_matches
+            .subcommand_matches("download")
+            .and_then(|matches| matches.values_of("episode-id"));
+        let episodes = Episodes::new(episodes_matches, &config);
 
         let input = r###"guid,title,pub_date,link,podcast,podcast_id
 272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
@@ -492,4 +3870,691 @@ mod tests {
 
         assert_eq!(output, expected_output);
     }
+
+    #[test]
+    fn download_match() {
+        colored::control::set_override(false);
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "download",
+            "--id",
+            "15913066141282366353",
+            "--match",
+            "webpack",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353
+50f59fbe-93db-4acf-b6db-8f9a078d315a,Hasty Treat - Turbolinks,"Mon, 20 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax267.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
+        let output = episodes
+            .download(None, input.as_bytes(), None)
+            .expect("Can't download episodes");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].0.contains("Webpack"));
+
+        let matching: Vec<Episode> = read_episodes(input.as_bytes(), false).unwrap();
+        let many: Vec<Episode> = matching.iter().cycle().take(CONFIRM_MATCHES + 1).cloned().collect();
+        let mut prompt = Vec::new();
+        assert!(episodes.confirm_matches(&many, &b"y\n"[..], &mut prompt).unwrap());
+        assert!(from_utf8(&prompt).unwrap().starts_with("6 episodes match webpack\n"));
+        assert!(!episodes.confirm_matches(&many, &b"\n"[..], Vec::new()).unwrap());
+        assert!(episodes.confirm_matches(&matching[..1], &b""[..], Vec::new()).unwrap());
+        assert!(!episodes.confirm_matches(&[], &b""[..], Vec::new()).unwrap());
+    }
+
+    #[test]
+    fn download_latest_since() {
+        let app = create_app();
+        let config = create_config();
+        // The older episode is first, as in the feeds which list them in order
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+50f59fbe-93db-4acf-b6db-8f9a078d315a,Hasty Treat - Turbolinks,"Mon, 20 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax267.mp3,Syntax - Tasty Web Development Treats,15913066141282366353
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
+
+        let args = app.app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "download",
+            "--id",
+            "15913066141282366353",
+            "--latest",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let output = Episodes::new(episodes_matches, &config)
+            .download(None, input.as_bytes(), None)
+            .expect("Can't download episodes");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].0.contains("Potluck"));
+
+        let app = create_app();
+        let args = app.app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "download",
+            "--id",
+            "15913066141282366353",
+            "--since",
+            "2020-07-21",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+        let output = episodes
+            .download(None, input.as_bytes(), None)
+            .expect("Can't download episodes");
+        assert_eq!(output.len(), 1);
+        assert!(output[0].0.contains("Potluck"));
+    }
+
+    #[test]
+    fn download_sniff_attachments() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "list"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        // The fixtures of the URLs are a PDF, a PNG and the start of an MP3
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,enclosure_url,enclosure_type
+a,Notes,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/pdf.mp3,audio/mpeg
+b,Cover,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/png.m4a,
+c,Poster,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/audio.png,image/png
+d,Audio,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,https://example.com/audio.mp3,audio/mpeg
+"###;
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(config.app_directory.join("1"), input).unwrap();
+
+        let mut file_names: Vec<String> = episodes
+            .fetch(read_episodes(input.as_bytes(), false).unwrap())
+            .expect("Can't download episodes")
+            .into_iter()
+            .map(|(file_name, _bytes)| file_name)
+            .collect();
+        file_names.sort();
+        assert_eq!(
+            file_names,
+            vec![
+                // The content wins over the type in the feed and over the extension of the URL
+                "Podcast_Audio.mp3",
+                "attachments/Podcast_Cover.png",
+                "attachments/Podcast_Notes.pdf",
+                // Enclosures the feed labels as attachments are saved as such, whatever their content
+                "attachments/Podcast_Poster.png",
+            ]
+        );
+
+        // The sniffed types are saved, so the next downloads name the files the same way
+        let saved = read_episodes(&fs::read(config.app_directory.join("1")).unwrap()[..], false).unwrap();
+        let types: Vec<&str> = saved.iter().map(|episode| episode.enclosure_type.as_str()).collect();
+        assert_eq!(types, vec!["application/pdf", "image/png", "image/png", "audio/mpeg"]);
+    }
+
+    /// Answers the URLs of the fixtures with them and the others with a 404
+    #[derive(Debug)]
+    struct MissingClient;
+
+    impl crate::web::HttpClient for MissingClient {
+        fn fetch(&self, request: &crate::web::Request) -> Result<crate::web::Response, Errors> {
+            match fixtures::lookup(request.url) {
+                Some(_bytes) => fixtures::FixtureClient.fetch(request),
+                None => Ok(crate::web::Response {
+                    status: 404,
+                    length: None,
+                    accepts_ranges: false,
+                    last_modified: None,
+                    body: Box::new(io::Cursor::new(Vec::new())),
+                }),
+            }
+        }
+    }
+
+    #[test]
+    fn download_batch_failures() {
+        let app = create_app();
+        let config = create_config().with_http_client(Arc::new(MissingClient));
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "download", "--id", "15913066141282366353"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353
+50f59fbe-93db-4acf-b6db-8f9a078d315a,Hasty Treat - Turbolinks,"Mon, 20 Jul 2020 13:00:00 +0000",https://example.com/missing.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
+        let batch = read_episodes(input.as_bytes(), false).unwrap();
+
+        // The missing episode doesn't stop the other one from being saved
+        let downloaded = episodes.download_batch(batch).expect("Can't download episodes");
+        assert_eq!(downloaded.len(), 2);
+        let (saved, failed): (Vec<_>, Vec<_>) = downloaded.iter().partition(|(_episode, file_name)| file_name.is_ok());
+        assert_eq!(saved.len(), 1);
+        assert!(saved[0].0.title.starts_with("Potluck"));
+        assert!(matches!(&failed[0].1, Err(Errors::NotFound(_))));
+        if let Ok(file_name) = &saved[0].1 {
+            assert!(config
+                .podcast_directory(15913066141282366353u64)
+                .join(file_name)
+                .exists());
+        }
+
+        assert_eq!(config.outcome.failed(crate::FailOn::Any), 1);
+        assert_eq!(config.outcome.failed(crate::FailOn::None), 0);
+    }
+
+    #[test]
+    fn download_batch_max_time() {
+        let app = create_app();
+        let mut config = create_config();
+        // --max-time ran out before the downloads started
+        config.deadline = Some(time::Instant::now());
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "download", "--id", "15913066141282366353"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax - Tasty Web Development Treats,15913066141282366353"###;
+        let batch = read_episodes(input.as_bytes(), false).unwrap();
+
+        // The episodes which ran out of time aren't failures, they're left for `download --resume`
+        let downloaded = episodes.download_batch(batch).expect("Can't download episodes");
+        assert!(downloaded.is_empty());
+        assert_eq!(config.outcome.failed(crate::FailOn::Any), 0);
+        let pending = queue::pending(&config.app_directory, false).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].guid, "272eca72-476b-4633-864c-a9fffa3f5976");
+        assert_eq!(pending[0].status, Status::Requested);
+        assert!(!config.podcast_directory(15913066141282366353u64).exists());
+    }
+
+    /// Answers with the fixtures like `FixtureClient`, but sends a byte every 20ms
+    #[derive(Debug)]
+    struct SlowClient;
+
+    impl crate::web::HttpClient for SlowClient {
+        fn fetch(&self, request: &crate::web::Request) -> Result<crate::web::Response, Errors> {
+            struct SlowBody(Box<dyn Read + Send>);
+
+            impl Read for SlowBody {
+                fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                    std::thread::sleep(time::Duration::from_millis(20));
+                    let length = buf.len().min(1);
+                    self.0.read(&mut buf[..length])
+                }
+            }
+
+            let mut response = fixtures::FixtureClient.fetch(request)?;
+            response.body = Box::new(SlowBody(response.body));
+            Ok(response)
+        }
+    }
+
+    #[test]
+    fn download_batch_max_time_in_flight() {
+        let app = create_app();
+        let mut config = create_config().with_http_client(Arc::new(SlowClient));
+        // --max-time runs out while the episode is downloading
+        config.deadline = Some(time::Instant::now() + time::Duration::from_millis(100));
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "download", "--id", "15913066141282366353"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax,15913066141282366353"###;
+        let batch = read_episodes(input.as_bytes(), false).unwrap();
+        let file_name = batch[0].file_name(config.podcast_file_template(15913066141282366353));
+
+        // The download is stopped, kept to be resumed and left for `download --resume`
+        let downloaded = episodes.download_batch(batch).expect("Can't download episodes");
+        assert!(downloaded.is_empty());
+        assert_eq!(config.outcome.failed(crate::FailOn::Any), 0);
+        let pending = queue::pending(&config.app_directory, false).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].status, Status::Requested);
+        let directory = config.podcast_directory(15913066141282366353u64);
+        assert!(!directory.join(&file_name).exists());
+        assert!(
+            fs::metadata(directory.join(format!("{}.part", file_name)))
+                .unwrap()
+                .len()
+                > 0
+        );
+    }
+
+    #[test]
+    fn safe_mode_skips_extensions() {
+        let app = create_app();
+        let args = app
+            .app
+            .get_matches_from(vec!["pcasts", "episodes", "download", "--id", "15913066141282366353"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax,15913066141282366353"###;
+        let mut podcast = Podcast::new(
+            String::new(),
+            "https://feed.syntax.fm/rss".to_string(),
+            "Syntax".to_string(),
+        );
+        podcast.id = 15913066141282366353;
+        podcast.enrich = true;
+        let updates = vec![PodcastUpdate {
+            podcast: podcast.clone(),
+            status: UpdateStatus::New(2),
+            moved_to: None,
+        }];
+        let audio = fixtures::read("syntax_268.mp3").unwrap();
+
+        for safe_mode in [false, true] {
+            let mut config = create_config()
+                .with_tag(true)
+                .with_notify(true)
+                .with_podcast_index("key".to_string(), "secret".to_string());
+            config.safe_mode = safe_mode;
+            config.app_directory = fixtures::scratch().join(safe_mode.to_string());
+            config.download_directory = config.app_directory.join("downloads");
+            let episodes = Episodes::new(episodes_matches, &config);
+
+            assert_eq!(episodes.notification(&updates).is_none(), safe_mode);
+            assert_eq!(episodes.lookup_index(&[podcast.clone()]).is_empty(), safe_mode);
+
+            let batch = read_episodes(input.as_bytes(), false).unwrap();
+            let file_name = batch[0].file_name(config.podcast_file_template(podcast.id));
+            episodes.download_batch(batch).expect("Can't download episodes");
+            let saved = fs::read(config.podcast_directory(podcast.id).join(file_name)).unwrap();
+            // The tags are written in front of the audio
+            assert_eq!(saved == audio, safe_mode);
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "remove",
+            "--id",
+            "15913066141282366353",
+            "--name",
+            "*Potluck*",
+        ]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let remove_matches = episodes_matches.subcommand_matches("remove").unwrap();
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id
+272eca72-476b-4633-864c-a9fffa3f5976,Potluck - Beating Procrastination,"Wed, 22 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax,15913066141282366353
+50f59fbe-93db-4acf-b6db-8f9a078d315a,Hasty Treat - Turbolinks,"Mon, 20 Jul 2020 13:00:00 +0000",https://traffic.libsyn.com/secure/syntax/Syntax267.mp3,Syntax,15913066141282366353"###;
+        let downloaded_episodes = vec![
+            "Syntax_Potluck - Beating Procrastination.mp3".to_string(),
+            "Syntax_Hasty Treat - Turbolinks.mp3".to_string(),
+            "Other_Potluck - Something else.mp3".to_string(),
+        ];
+
+        let by_name = episodes
+            .remove(
+                None,
+                remove_matches.value_of("name"),
+                input.as_bytes(),
+                &downloaded_episodes,
+            )
+            .expect("Can't remove episodes");
+        assert_eq!(
+            by_name,
+            vec!["Syntax_Potluck - Beating Procrastination.mp3".to_string()]
+        );
+
+        let args = create_app().app.get_matches_from(vec![
+            "pcasts",
+            "episodes",
+            "remove",
+            "--id",
+            "15913066141282366353",
+            "--episode-id",
+            "50f59fbe-93db-4acf-b6db-8f9a078d315a",
+        ]);
+        let remove_matches = args
+            .subcommand_matches("episodes")
+            .and_then(|matches| matches.subcommand_matches("remove"))
+            .unwrap();
+        let by_id = episodes
+            .remove(
+                remove_matches.values_of("episode-id").as_ref(),
+                None,
+                input.as_bytes(),
+                &downloaded_episodes,
+            )
+            .expect("Can't remove episodes");
+        assert_eq!(by_id, vec!["Syntax_Hasty Treat - Turbolinks.mp3".to_string()]);
+
+        let nothing = episodes.remove(None, Some("*Missing*"), input.as_bytes(), &downloaded_episodes);
+        assert!(matches!(nothing, Err(Errors::NoMatches)));
+    }
+
+    #[test]
+    fn enclosure_file_name() {
+        let mut episode = Episode {
+            guid: "1".to_string(),
+            title: "Episode".to_string(),
+            pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+            link: "https://example.com/episodes/1".to_string(),
+            podcast: "Podcast".to_string(),
+            podcast_id: 1,
+            enclosure_url: "https://cdn.example.com/1.m4a?source=rss".to_string(),
+            enclosure_type: String::new(),
+            enclosure_length: 0,
+            number: 3,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        };
+
+        assert!(episode.matches_id("3"));
+        assert!(episode.matches_id("1"));
+        assert!(!episode.matches_id("2"));
+        assert_eq!(episode.download_url(), "https://cdn.example.com/1.m4a?source=rss");
+        assert_eq!(episode.file_name(DEFAULT_FILE_TEMPLATE), "Podcast_Episode.m4a");
+
+        episode.enclosure_type = "audio/ogg".to_string();
+        assert_eq!(episode.file_name(DEFAULT_FILE_TEMPLATE), "Podcast_Episode.ogg");
+
+        episode.enclosure_url = String::new();
+        episode.enclosure_type = String::new();
+        assert_eq!(episode.download_url(), "https://example.com/episodes/1");
+        assert_eq!(episode.file_name(DEFAULT_FILE_TEMPLATE), "Podcast_Episode.mp3");
+    }
+
+    #[test]
+    fn file_name_template() {
+        let episode = Episode {
+            guid: "1".to_string(),
+            title: "Episode 12: TCP/IP <explained>...".to_string(),
+            pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+            link: "https://example.com/episodes/1".to_string(),
+            podcast: "../Podcast".to_string(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 3,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        };
+
+        assert_eq!(
+            episode.file_name(DEFAULT_FILE_TEMPLATE),
+            "_Podcast_Episode 12_ TCP_IP _explained_.mp3"
+        );
+        assert_eq!(
+            episode.file_name("{podcast}/{pub_date}_{number}_{title}.{ext}"),
+            "_Podcast/2020-07-22_3_Episode 12_ TCP_IP _explained_.mp3"
+        );
+
+        let attachment = Episode {
+            enclosure_type: "application/pdf".to_string(),
+            ..episode.clone()
+        };
+        assert_eq!(
+            attachment.file_name(DEFAULT_FILE_TEMPLATE),
+            "attachments/_Podcast_Episode 12_ TCP_IP _explained_.pdf"
+        );
+        assert_eq!(sniff_attachment(b"%PDF-1.4\n"), Some("application/pdf"));
+        assert_eq!(sniff_attachment(b"ID3\x04\x00"), None);
+
+        let long_title = "é".repeat(200);
+        assert_eq!(sanitize(&long_title).len(), MAX_NAME_LENGTH);
+        assert_eq!(sanitize(" .. "), "_");
+    }
+
+    #[test]
+    fn update_merge_saved() {
+        let create_episode = |guid: &str, number: u64, first_seen: u64| Episode {
+            guid: guid.to_string(),
+            title: "Episode".to_string(),
+            pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+            link: "-".to_string(),
+            podcast: "Podcast".to_string(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: String::new(),
+            enclosure_length: 0,
+            number,
+            first_seen,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        };
+
+        // The feed lists the newest episodes first
+        let items = vec![
+            create_episode("c", 0, 0),
+            create_episode("b", 0, 0),
+            create_episode("a", 0, 0),
+        ];
+        let mut saved = HashMap::new();
+        saved.insert("a".to_string(), create_episode("a", 1, 100));
+        saved.insert("b".to_string(), create_episode("b", 2, 200));
+
+        let merged: Vec<(u64, u64)> = Episodes::merge_saved(items, Some(&saved), 300)
+            .iter()
+            .map(|episode| (episode.number, episode.first_seen))
+            .collect();
+        assert_eq!(merged, vec![(3, 300), (2, 200), (1, 100)]);
+
+        let items = vec![create_episode("b", 0, 0), create_episode("a", 0, 0)];
+        let merged: Vec<(u64, u64)> = Episodes::merge_saved(items, None, 300)
+            .iter()
+            .map(|episode| (episode.number, episode.first_seen))
+            .collect();
+        assert_eq!(merged, vec![(2, 300), (1, 300)]);
+
+        // What the Podcast Index filled in outlives updates whose feed still lacks it
+        let mut enriched = create_episode("a", 1, 100);
+        enriched.duration = 3600;
+        enriched.chapters = "https://example.com/a.json".to_string();
+        saved.insert("a".to_string(), enriched);
+        let merged = Episodes::merge_saved(vec![create_episode("a", 0, 0)], Some(&saved), 300);
+        assert_eq!(merged[0].duration, 3600);
+        assert_eq!(merged[0].chapters, "https://example.com/a.json");
+    }
+
+    #[test]
+    fn backlog() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "backlog"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let syntax = fixtures::read("syntax.csv").expect("Can't read syntax.csv");
+        let http_203 = fixtures::read("http_203.csv").expect("Can't read http_203.csv");
+        let syntax_downloaded = vec![
+            "Syntax - Tasty Web Development Treats_Hasty Treat - Turbolinks + Server Generated HTML + JS Sprinkles.mp3"
+                .to_string(),
+            "Syntax - Tasty Web Development Treats_Video for the Web 2020 and Beyond.mp3".to_string(),
+        ];
+        let http_203_downloaded = vec!["HTTP 203_The big build-tool bonanza.m4a".to_string()];
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+
+        episodes
+            .backlog(
+                vec![
+                    ("HTTP 203".to_string(), &http_203[..], http_203_downloaded),
+                    ("Syntax".to_string(), &syntax[..], syntax_downloaded),
+                ],
+                &mut output,
+            )
+            .expect("Can't show backlog");
+
+        let output = from_utf8(&output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("Syntax "));
+        assert!(lines[1].contains(" 2 "));
+        assert!(lines[2].starts_with("HTTP 203 "));
+        assert!(lines[3].starts_with("Total "));
+        assert!(lines[3].contains(" 3 "));
+    }
+
+    #[test]
+    fn backlog_never_updated() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "backlog"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(
+            config.app_directory.join("podcast_list.csv"),
+            "id,url,rss_url,title\n1,-,https://example.com/rss,Podcast\n",
+        )
+        .unwrap();
+
+        // Showing the backlog doesn't create the episodes file of a podcast which was never updated
+        episodes.run().expect("Can't show backlog");
+        assert!(!config.app_directory.join("1").exists());
+    }
+
+    #[test]
+    fn window_expired_files() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "update"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let syntax = fixtures::read("syntax.csv").expect("Can't read syntax.csv");
+        let hasty_treat =
+            "Syntax - Tasty Web Development Treats_Hasty Treat - Turbolinks + Server Generated HTML + JS Sprinkles.mp3";
+        let downloaded = vec![
+            hasty_treat.to_string(),
+            "Syntax - Tasty Web Development Treats_Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!.mp3".to_string(),
+            "notes.txt".to_string(),
+        ];
+        // A day after the release of the newest episode
+        let now = dates::parse_pub_date("Thu, 23 Jul 2020 13:00:00 +0000").unwrap();
+
+        let expired = episodes.expired_files(2, &syntax[..], &downloaded, now).unwrap();
+        assert_eq!(expired, vec![hasty_treat.to_string()]);
+        assert!(episodes
+            .expired_files(30, &syntax[..], &downloaded, now)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn update_feed_move() {
+        let rss_url = "http://feeds.feedburner.com/syntax";
+        assert_eq!(feed_move(rss_url, None, None), None);
+        assert_eq!(
+            feed_move(rss_url, None, Some("https://feed.syntax.fm/rss".to_string())),
+            Some("https://feed.syntax.fm/rss".to_string())
+        );
+        // The tag of the publisher wins over the redirects
+        assert_eq!(
+            feed_move(
+                rss_url,
+                Some(" https://syntax.fm/rss "),
+                Some("https://feed.syntax.fm/rss".to_string())
+            ),
+            Some("https://syntax.fm/rss".to_string())
+        );
+        assert_eq!(
+            feed_move(rss_url, Some("http://feeds.feedburner.com/syntax/"), None),
+            None
+        );
+        assert_eq!(feed_move(rss_url, Some("not a url"), None), None);
+    }
+
+    #[test]
+    fn prune_pruned_files() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "update"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let syntax = fixtures::read("syntax.csv").expect("Can't read syntax.csv");
+        let hasty_treat =
+            "Syntax - Tasty Web Development Treats_Hasty Treat - Turbolinks + Server Generated HTML + JS Sprinkles.mp3";
+        let downloaded = vec![
+            "Syntax - Tasty Web Development Treats_Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!.mp3".to_string(),
+            hasty_treat.to_string(),
+            "notes.txt".to_string(),
+        ];
+
+        let pruned = episodes.pruned_files(1, &syntax[..], &downloaded).unwrap();
+        assert_eq!(pruned, vec![hasty_treat.to_string()]);
+        assert!(episodes.pruned_files(2, &syntax[..], &downloaded).unwrap().is_empty());
+        assert_eq!(episodes.pruned_files(0, &syntax[..], &downloaded).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn sync_selection() {
+        let app = create_app();
+        let config = create_config();
+        let args = app.app.get_matches_from(vec!["pcasts", "episodes", "update"]);
+        let episodes_matches = args.subcommand_matches("episodes").expect("No episodes matches");
+        let episodes = Episodes::new(episodes_matches, &config);
+
+        let syntax = fixtures::read("syntax.csv").expect("Can't read syntax.csv");
+        let downloaded = vec![
+            "Syntax - Tasty Web Development Treats_Potluck - Beating Procrastination × Rollup vs Webpack × Leadership × Code Planning × Styled Components × More!.mp3".to_string(),
+        ];
+        let archived = vec![
+            "Syntax - Tasty Web Development Treats_Hasty Treat - Turbolinks + Server Generated HTML + JS Sprinkles.mp3"
+                .to_string(),
+        ];
+
+        let selection = episodes.sync_selection(&syntax[..], 3, &downloaded, &archived).unwrap();
+        let titles: Vec<&str> = selection.iter().map(|episode| episode.title.as_str()).collect();
+        assert_eq!(titles, vec!["Video for the Web 2020 and Beyond"]);
+        assert!(episodes
+            .sync_selection(&syntax[..], 1, &downloaded, &[])
+            .unwrap()
+            .is_empty());
+    }
 }
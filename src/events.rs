@@ -0,0 +1,248 @@
+//! A log of what happened to the library: the podcasts which were added and removed, and the
+//! episodes which were found, downloaded and removed. `pcasts log` prints it and `stats library`
+//! sums it up
+use crate::{
+    dates,
+    file_system::{FilePermissions, FileSystem},
+    podcasts::load_podcasts,
+    records, units, Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::HashMap, fmt, fs, io::Write, path::Path};
+
+pub const EVENTS_FILE: &str = "events.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    Subscribed,
+    Unsubscribed,
+    // A new episode was found by an update
+    Discovered,
+    Downloaded,
+    // A downloaded episode was removed, by hand or by the window and keep policies
+    Archived,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 5] = [
+        EventKind::Subscribed,
+        EventKind::Unsubscribed,
+        EventKind::Discovered,
+        EventKind::Downloaded,
+        EventKind::Archived,
+    ];
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self {
+            EventKind::Subscribed => "subscribed",
+            EventKind::Unsubscribed => "unsubscribed",
+            EventKind::Discovered => "discovered",
+            EventKind::Downloaded => "downloaded",
+            EventKind::Archived => "archived",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    pub time: u64,
+    pub kind: EventKind,
+    pub podcast_id: u64,
+    // The guid of the discovered episode. empty for the other events
+    pub guid: String,
+    // The title of the podcast or of the episode, or the name of the downloaded file
+    pub subject: String,
+}
+
+impl Event {
+    /// An event which happens now
+    pub fn new(kind: EventKind, podcast_id: u64, guid: &str, subject: &str) -> Self {
+        Self {
+            time: dates::now(),
+            kind,
+            podcast_id,
+            guid: guid.to_string(),
+            subject: subject.to_string(),
+        }
+    }
+}
+
+/// Appends the passed events to the log
+pub fn record(app_directory: &Path, events: &[Event]) -> Result<(), Errors> {
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let file = FileSystem::new(app_directory, EVENTS_FILE, vec![FilePermissions::Append]).open()?;
+    let is_empty = file.metadata()?.len() == 0;
+
+    let mut writer = csv::WriterBuilder::new().has_headers(is_empty).from_writer(file);
+    for event in events {
+        writer.serialize(event)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The events logged since the passed unix time, oldest first
+pub fn read(app_directory: &Path, since: u64, strict: bool) -> Result<Vec<Event>, Errors> {
+    let contents = match fs::read(app_directory.join(EVENTS_FILE)) {
+        Ok(contents) => contents,
+        // Nothing happened yet
+        Err(_error) => return Ok(Vec::new()),
+    };
+
+    Ok(records::read::<Event, _>(&contents[..], EVENTS_FILE, strict)?
+        .into_iter()
+        .filter(|event| event.time >= since)
+        .collect())
+}
+
+/// The names of the podcasts of the events. the removed podcasts are named by the events which
+/// added or removed them
+fn podcast_names(events: &[Event], config: &Config) -> HashMap<u64, String> {
+    let mut names: HashMap<u64, String> = events
+        .iter()
+        .filter(|event| event.kind == EventKind::Subscribed || event.kind == EventKind::Unsubscribed)
+        .map(|event| (event.podcast_id, event.subject.clone()))
+        .collect();
+    for podcast in load_podcasts(config).unwrap_or_default() {
+        names.insert(podcast.id, podcast.name().to_string());
+    }
+
+    names
+}
+
+/// The unix time of the passed span before now, e.g "7d". 0 without a span
+pub fn since(span: Option<&str>) -> Result<u64, Errors> {
+    match span {
+        Some(span) => Ok(dates::now().saturating_sub(units::parse_duration(span)?.as_secs())),
+        None => Ok(0),
+    }
+}
+
+pub struct Logger<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Logger<'a> {
+    /// Constructs a new Logger struct which is used to work with the sub command "log"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        let since = since(self.matches.value_of("since"))?;
+        let events = read(&self.config.app_directory, since, self.config.strict)?;
+        let names = podcast_names(&events, self.config);
+        let json = self.matches.value_of("output") == Some("json");
+
+        let writer = std::io::stdout();
+        let writer = writer.lock();
+
+        write_events(&events, &names, json, writer)
+    }
+}
+
+/// Writes the events, one line each. json writes an object per line
+fn write_events<W>(events: &[Event], names: &HashMap<u64, String>, json: bool, mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    if events.is_empty() && !json {
+        writeln!(writer, "Nothing happened yet")?;
+        return Ok(());
+    }
+
+    for event in events {
+        let podcast = names.get(&event.podcast_id).map(String::as_str).unwrap_or("");
+        if json {
+            let object = json!({
+                "time": event.time,
+                "kind": event.kind,
+                "podcast_id": event.podcast_id,
+                "podcast": podcast,
+                "guid": event.guid,
+                "subject": event.subject,
+            });
+            writeln!(writer, "{}", object)?;
+            continue;
+        }
+
+        let subject = match event.kind {
+            EventKind::Subscribed | EventKind::Unsubscribed => event.subject.clone(),
+            _ => format!("{} {}", podcast.bold(), event.subject),
+        };
+        writeln!(
+            writer,
+            "{} {:12} {}",
+            dates::format_date(event.time),
+            event.kind.to_string().green(),
+            subject
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::str::from_utf8;
+
+    #[test]
+    fn events_log() {
+        let directory = fixtures::scratch();
+
+        let mut old = Event::new(EventKind::Subscribed, 1, "", "Syntax");
+        old.time = 1_595_419_200;
+        record(&directory, &[old]).unwrap();
+        record(
+            &directory,
+            &[
+                Event::new(EventKind::Discovered, 1, "a", "Episode 1"),
+                Event::new(EventKind::Downloaded, 1, "", "Syntax_Episode 1.mp3"),
+                Event::new(EventKind::Unsubscribed, 2, "", "HTTP 203"),
+            ],
+        )
+        .unwrap();
+        record(&directory, &[]).unwrap();
+
+        let events = read(&directory, 0, true).unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[1].guid, "a");
+        let recent = read(&directory, since(Some("7d")).unwrap(), true).unwrap();
+        assert_eq!(recent.len(), 3);
+
+        let mut names = HashMap::new();
+        names.insert(1, "Syntax".to_string());
+
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        write_events(&events[..2], &names, false, &mut output).unwrap();
+        assert_eq!(
+            from_utf8(&output).unwrap(),
+            format!(
+                "2020-07-22 subscribed   Syntax\n{} discovered   Syntax Episode 1\n",
+                dates::format_date(events[1].time)
+            )
+        );
+
+        let mut output = Vec::new();
+        write_events(&events[3..], &names, true, &mut output).unwrap();
+        let object: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(object["kind"], "unsubscribed");
+        assert_eq!(object["podcast"], "");
+        assert_eq!(object["subject"], "HTTP 203");
+    }
+}
@@ -0,0 +1,130 @@
+//! Exports the saved episodes into formats other tools understand
+use crate::{
+    episodes::{read_episodes, Episode},
+    file_system::{FilePermissions, FileSystem},
+    podcasts::load_podcasts,
+    resolver, Config, Errors,
+};
+use clap::ArgMatches;
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+pub struct Exporter<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+/// A GeoJSON feature collection with a point for each episode which has coordinates. episodes
+/// which only name their location are left out
+fn geojson(episodes: &[Episode]) -> Value {
+    let features: Vec<Value> = episodes
+        .iter()
+        .filter_map(|episode| {
+            let location = episode.location()?;
+            let (latitude, longitude) = location.coordinates?;
+
+            // GeoJSON positions are longitude first
+            Some(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [longitude, latitude],
+                },
+                "properties": {
+                    "title": episode.title,
+                    "podcast": episode.podcast,
+                    "podcast_id": episode.podcast_id,
+                    "guid": episode.guid,
+                    "pub_date": episode.pub_date,
+                    "link": episode.link,
+                    "location": location.name,
+                },
+            }))
+        })
+        .collect();
+
+    json!({
+        "type": "FeatureCollection",
+        "features": features,
+    })
+}
+
+impl<'a> Exporter<'a> {
+    /// Constructs a new Exporter struct which is used to work with the sub command "export"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if let Some(matches) = self.matches.subcommand_matches("geojson") {
+            let episodes = self.episodes(matches)?;
+            let collection = geojson(&episodes);
+
+            let mut writer: Box<dyn Write> = match matches.value_of("output") {
+                Some(path) => Box::new(File::create(path)?),
+                None => Box::new(io::stdout()),
+            };
+            serde_json::to_writer_pretty(&mut writer, &collection).map_err(io::Error::from)?;
+            writeln!(writer)?;
+
+            if let Some(path) = matches.value_of("output") {
+                // Always an array, see `geojson`
+                let count = collection["features"].as_array().map(Vec::len).unwrap_or(0);
+                println!("Exported {} episodes with a location to {}", count, path);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The saved episodes of the podcasts passed with --id, or of all the podcasts. podcasts
+    /// which were never updated have no episodes
+    fn episodes(&self, matches: &ArgMatches) -> Result<Vec<Episode>, Errors> {
+        let podcasts = load_podcasts(self.config)?;
+        let podcast_ids: Vec<String> = match matches.values_of("id") {
+            Some(ids) => resolver::podcasts(&podcasts, ids)?
+                .iter()
+                .map(|podcast| podcast.id.to_string())
+                .collect(),
+            None => podcasts.iter().map(|podcast| podcast.id.to_string()).collect(),
+        };
+
+        let mut episodes = Vec::new();
+        for podcast_id in podcast_ids {
+            let episodes_file =
+                match FileSystem::new(&self.config.app_directory, &podcast_id, vec![FilePermissions::Read]).open() {
+                    Ok(file) => file,
+                    Err(_error) => continue,
+                };
+            episodes.extend(read_episodes(episodes_file, self.config.strict)?);
+        }
+
+        Ok(episodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_geojson() {
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,location
+a,Berlin,"Wed, 22 Jul 2020 13:00:00 +0000",-,Travels,1,"52.52,13.405;Berlin"
+b,Somewhere,"Wed, 22 Jul 2020 13:00:00 +0000",-,Travels,1,;Somewhere
+c,Nowhere,"Wed, 22 Jul 2020 13:00:00 +0000",-,Travels,1,
+"###;
+        let episodes = read_episodes(input.as_bytes(), true).unwrap();
+        let collection = geojson(&episodes);
+
+        assert_eq!(collection["type"], "FeatureCollection");
+        let features = collection["features"].as_array().unwrap();
+        assert_eq!(features.len(), 1);
+        assert_eq!(features[0]["geometry"]["coordinates"], json!([13.405, 52.52]));
+        assert_eq!(features[0]["properties"]["location"], "Berlin");
+        assert_eq!(features[0]["properties"]["guid"], "a");
+    }
+}
@@ -0,0 +1,564 @@
+//! Reads the feeds of the podcasts. RSS 2.0 feeds are read by the rss crate and Atom feeds are
+//! converted into the same channel, so adding and updating podcasts handles both alike
+use crate::Errors;
+use chrono::DateTime;
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use rss::{Channel, Enclosure, Guid, Image, Item};
+use std::collections::HashMap;
+
+/// Parses the passed RSS or Atom feed
+pub fn read(bytes: &[u8]) -> Result<Channel, Errors> {
+    match Channel::read_from(bytes) {
+        Ok(channel) => Ok(channel),
+        Err(_error) => read_atom(bytes).ok_or(Errors::RSS),
+    }
+}
+
+/// An other version of the enclosure of an item, e.g with a lower bitrate or in another codec
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Alternate {
+    pub url: String,
+    pub mime_type: String,
+    pub length: u64,
+    // In bits per second. 0 if the feed doesn't tell
+    pub bitrate: u64,
+}
+
+/// Someone who takes part in an episode, from <podcast:person>
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Person {
+    pub name: String,
+    // E.g "host" or "guest". the podcast namespace defaults to "host"
+    pub role: String,
+}
+
+/// Where an episode takes place or is about, from <podcast:location>
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Location {
+    pub name: String,
+    // Latitude and longitude from the geo attribute. None if the feed only names the place
+    pub coordinates: Option<(f64, f64)>,
+}
+
+/// A highlighted moment of an episode, from <podcast:soundbite>
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Soundbite {
+    // Both in seconds from the start of the enclosure
+    pub start_time: f64,
+    pub duration: f64,
+    pub title: String,
+}
+
+/// Parses the coordinates of a geo URI, e.g "geo:30.2672,-97.7431". the altitude and the
+/// parameters after them are ignored
+pub fn parse_geo(geo: &str) -> Option<(f64, f64)> {
+    let coordinates = geo.trim().strip_prefix("geo:")?;
+    let coordinates = coordinates.split(';').next()?;
+    let mut values = coordinates.split(',').map(|value| value.trim().parse::<f64>());
+    let latitude = values.next()?.ok()?;
+    let longitude = values.next()?.ok()?;
+
+    match latitude.abs() <= 90.0 && longitude.abs() <= 180.0 {
+        true => Some((latitude, longitude)),
+        false => None,
+    }
+}
+
+/// The iTunes tags of an item which the rss crate doesn't keep
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ItemMetadata {
+    pub season: u64,
+    pub episode: u64,
+    pub episode_type: String,
+    pub alternates: Vec<Alternate>,
+    pub people: Vec<Person>,
+    pub location: Option<Location>,
+    pub soundbites: Vec<Soundbite>,
+    // URLs of the <podcast:chapters> and of the first <podcast:transcript>. empty if the feed has
+    // none
+    pub chapters: String,
+    pub transcript: String,
+}
+
+/// The name of a namespaced tag without its prefix. empty for tags without a namespace
+fn local_name(name: &[u8]) -> &[u8] {
+    match name.iter().position(|byte| *byte == b':') {
+        Some(position) => &name[position + 1..],
+        None => &b""[..],
+    }
+}
+
+/// Reads the season, episode number and episode type of the items of an RSS feed, keyed by their
+/// guid. the podcast namespace tags of the same name are read as well, together with the HTTP
+/// sources of <podcast:alternateEnclosure>, the <podcast:person> tags, <podcast:location>, the
+/// <podcast:soundbite> tags and the URLs of the chapters and the transcript. the people and the location of the channel are the ones of the items
+/// which don't have their own
+pub fn item_metadata(bytes: &[u8]) -> HashMap<String, ItemMetadata> {
+    let mut reader = Reader::from_reader(bytes);
+    reader.trim_text(true);
+
+    let mut items = HashMap::new();
+    let mut item: Option<(String, ItemMetadata)> = None;
+    // The tags of the channel which the items inherit
+    let mut channel = ItemMetadata::default();
+    let mut element: Vec<u8> = Vec::new();
+    let mut buffer = Vec::new();
+
+    loop {
+        match reader.read_event(&mut buffer) {
+            Ok(Event::Start(start)) => {
+                if start.name() == b"item" {
+                    item = Some((String::new(), ItemMetadata::default()));
+                }
+                let metadata = match item.as_mut() {
+                    Some((_guid, metadata)) => metadata,
+                    None => &mut channel,
+                };
+                read_element(&start, &reader, metadata);
+                element = start.name().to_vec();
+            }
+            Ok(Event::Empty(empty)) => {
+                let metadata = match item.as_mut() {
+                    Some((_guid, metadata)) => metadata,
+                    None => &mut channel,
+                };
+                read_element(&empty, &reader, metadata);
+            }
+            Ok(Event::Text(text)) | Ok(Event::CData(text)) => {
+                let value = text.unescape_and_decode(&reader).unwrap_or_default();
+                let metadata = match item.as_mut() {
+                    Some((_guid, metadata)) => metadata,
+                    None => &mut channel,
+                };
+                match local_name(&element) {
+                    b"person" => {
+                        if let Some(person) = metadata.people.last_mut() {
+                            person.name = value.trim().to_string();
+                        }
+                    }
+                    b"location" => {
+                        if let Some(location) = metadata.location.as_mut() {
+                            location.name = value.trim().to_string();
+                        }
+                    }
+                    b"soundbite" => {
+                        if let Some(soundbite) = metadata.soundbites.last_mut() {
+                            soundbite.title = value.trim().to_string();
+                        }
+                    }
+                    _ => {}
+                }
+
+                if let Some((guid, metadata)) = item.as_mut() {
+                    if element == b"guid" {
+                        *guid = value;
+                        buffer.clear();
+                        continue;
+                    }
+
+                    // Only the namespaced tags, since <episode> alone isn't part of RSS
+                    match local_name(&element) {
+                        b"season" => metadata.season = value.trim().parse().unwrap_or(0),
+                        b"episode" => metadata.episode = value.trim().parse().unwrap_or(0),
+                        b"episodeType" => metadata.episode_type = value.trim().to_lowercase(),
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::End(end)) => {
+                if end.name() == b"item" {
+                    if let Some((guid, mut metadata)) = item.take() {
+                        // Only the sources which can be downloaded over HTTP, not torrents or IPFS
+                        metadata
+                            .alternates
+                            .retain(|alternate| alternate.url.starts_with("http"));
+                        metadata.people.retain(|person| !person.name.is_empty());
+                        if metadata.people.is_empty() {
+                            metadata.people = channel
+                                .people
+                                .iter()
+                                .filter(|person| !person.name.is_empty())
+                                .cloned()
+                                .collect();
+                        }
+                        metadata.location = metadata
+                            .location
+                            .filter(|location| !location.name.is_empty() || location.coordinates.is_some())
+                            .or_else(|| channel.location.clone());
+                        items.insert(guid, metadata);
+                    }
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buffer.clear();
+    }
+
+    items
+}
+
+/// Adds an <podcast:alternateEnclosure>, a <podcast:person>, a <podcast:location> or a
+/// <podcast:soundbite> to the metadata, or sets the URL of the last alternate enclosure from its
+/// first <podcast:source>. the names and the titles are set once their text is read
+fn read_element(element: &BytesStart, reader: &Reader<&[u8]>, metadata: &mut ItemMetadata) {
+    let attributes: Vec<(Vec<u8>, String)> = element
+        .attributes()
+        .flatten()
+        .map(|attribute| {
+            let value = attribute.unescape_and_decode_value(reader).unwrap_or_default();
+            (attribute.key.to_vec(), value)
+        })
+        .collect();
+    let attribute = |key: &[u8]| {
+        attributes
+            .iter()
+            .find(|(name, _value)| name == key)
+            .map(|(_name, value)| value.trim())
+    };
+
+    match local_name(element.name()) {
+        b"alternateEnclosure" => metadata.alternates.push(Alternate {
+            url: String::new(),
+            mime_type: attribute(b"type").unwrap_or("").to_string(),
+            length: attribute(b"length").and_then(|length| length.parse().ok()).unwrap_or(0),
+            bitrate: attribute(b"bitrate")
+                .and_then(|bitrate| bitrate.parse::<f64>().ok())
+                .map(|bitrate| bitrate as u64)
+                .unwrap_or(0),
+        }),
+        b"source" => {
+            if let (Some(alternate), Some(uri)) = (metadata.alternates.last_mut(), attribute(b"uri")) {
+                if alternate.url.is_empty() && uri.starts_with("http") {
+                    alternate.url = uri.to_string();
+                }
+            }
+        }
+        b"person" => metadata.people.push(Person {
+            name: String::new(),
+            role: attribute(b"role")
+                .map(|role| role.to_lowercase())
+                .unwrap_or_else(|| "host".to_string()),
+        }),
+        b"location" => {
+            metadata.location = Some(Location {
+                name: String::new(),
+                coordinates: attribute(b"geo").and_then(parse_geo),
+            })
+        }
+        b"chapters" if metadata.chapters.is_empty() => {
+            metadata.chapters = attribute(b"url").unwrap_or("").to_string();
+        }
+        b"transcript" if metadata.transcript.is_empty() => {
+            metadata.transcript = attribute(b"url").unwrap_or("").to_string();
+        }
+        b"soundbite" => {
+            let seconds = |key: &[u8]| attribute(key).and_then(|value| value.parse::<f64>().ok());
+            // Soundbites without a start or a length can't be played
+            if let (Some(start_time), Some(duration)) = (seconds(b"startTime"), seconds(b"duration")) {
+                if start_time >= 0.0 && duration > 0.0 {
+                    metadata.soundbites.push(Soundbite {
+                        start_time,
+                        duration,
+                        title: String::new(),
+                    });
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Atom dates are RFC 3339, while the rest of pcasts reads the RFC 2822 dates of RSS
+fn rfc2822_date(date: &str) -> String {
+    match DateTime::parse_from_rfc3339(date.trim()) {
+        Ok(date) => date.to_rfc2822(),
+        Err(_error) => date.to_string(),
+    }
+}
+
+/// A `<link>` of the feed or of an entry
+struct Link {
+    rel: String,
+    href: String,
+    mime_type: String,
+    length: String,
+}
+
+impl Link {
+    fn parse<B>(element: &quick_xml::events::BytesStart, reader: &Reader<B>) -> Self
+    where
+        B: std::io::BufRead,
+    {
+        let mut link = Link {
+            // Links without a rel are alternate links
+            rel: "alternate".to_string(),
+            href: String::new(),
+            mime_type: String::new(),
+            length: String::new(),
+        };
+
+        for attribute in element.attributes().flatten() {
+            let value = attribute.unescape_and_decode_value(reader).unwrap_or_default();
+            match attribute.key {
+                b"rel" => link.rel = value,
+                b"href" => link.href = value,
+                b"type" => link.mime_type = value,
+                b"length" => link.length = value,
+                _ => {}
+            }
+        }
+
+        link
+    }
+}
+
+/// Converts an Atom feed into a channel. the id, published date and enclosure link of each entry
+/// become the guid, pubDate and enclosure of an item. returns None if it isn't an Atom feed
+fn read_atom(bytes: &[u8]) -> Option<Channel> {
+    let mut reader = Reader::from_reader(bytes);
+    reader.trim_text(true);
+
+    let mut channel = Channel::default();
+    let mut items = Vec::new();
+    let mut item: Option<Item> = None;
+    // The local names of the open elements
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut buffer = Vec::new();
+
+    loop {
+        let (element, is_start) = match reader.read_event(&mut buffer).ok()? {
+            Event::Start(element) => (element.into_owned(), true),
+            Event::Empty(element) => (element.into_owned(), false),
+            Event::Text(text) | Event::CData(text) => {
+                let value = text.unescape_and_decode(&reader).unwrap_or_default();
+                let name = path.last().map(|name| name.as_slice()).unwrap_or_default();
+                let parent = match path.len() {
+                    0 | 1 => &b""[..],
+                    length => path[length - 2].as_slice(),
+                };
+
+                match (parent, item.as_mut()) {
+                    (b"entry", Some(item)) => match name {
+                        b"id" => {
+                            let mut guid = Guid::default();
+                            guid.set_value(value);
+                            guid.set_permalink(false);
+                            item.set_guid(guid);
+                        }
+                        b"title" => item.set_title(value),
+                        b"published" => item.set_pub_date(rfc2822_date(&value)),
+                        // Entries which were never republished may only have the updated date
+                        b"updated" if item.pub_date().is_none() => item.set_pub_date(rfc2822_date(&value)),
+                        b"summary" if item.description().is_none() => item.set_description(value),
+                        b"content" => item.set_description(value),
+                        _ => {}
+                    },
+                    (b"feed", None) => match name {
+                        b"title" => channel.set_title(value),
+                        b"subtitle" => channel.set_description(value),
+                        b"logo" | b"icon" if channel.image().is_none() => {
+                            let mut image = Image::default();
+                            image.set_url(value);
+                            channel.set_image(image);
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                buffer.clear();
+                continue;
+            }
+            Event::End(_) => {
+                if path.pop().as_deref() == Some(&b"entry"[..]) {
+                    items.extend(item.take());
+                }
+                buffer.clear();
+                continue;
+            }
+            Event::Eof => break,
+            _ => {
+                buffer.clear();
+                continue;
+            }
+        };
+
+        let name = element.local_name().to_vec();
+        if path.is_empty() && name != b"feed" {
+            return None;
+        }
+
+        if name == b"entry" {
+            item = Some(Item::default());
+        } else if name == b"link" {
+            let link = Link::parse(&element, &reader);
+            match (item.as_mut(), link.rel.as_str()) {
+                (Some(item), "enclosure") => {
+                    let mut enclosure = Enclosure::default();
+                    enclosure.set_url(link.href);
+                    enclosure.set_mime_type(link.mime_type);
+                    enclosure.set_length(link.length);
+                    item.set_enclosure(enclosure);
+                }
+                (Some(item), "alternate") => item.set_link(link.href),
+                (None, "alternate") => channel.set_link(link.href),
+                _ => {}
+            }
+        }
+
+        if is_start {
+            path.push(name);
+        }
+        buffer.clear();
+    }
+
+    // An empty document isn't a feed
+    if channel.title().is_empty() && items.is_empty() {
+        return None;
+    }
+    channel.set_items(items);
+
+    Some(channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::episodes::Episode;
+
+    #[test]
+    fn feed_atom() {
+        let feed = r###"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Atom &amp; Eve</title>
+  <link href="https://example.com/"/>
+  <link rel="self" href="https://example.com/feed.atom"/>
+  <logo>https://example.com/logo.png</logo>
+  <updated>2020-07-27T13:00:00Z</updated>
+  <entry>
+    <title>Second</title>
+    <id>urn:uuid:2</id>
+    <updated>2020-07-27T13:00:00+02:00</updated>
+    <link rel="alternate" href="https://example.com/2"/>
+    <link rel="enclosure" type="audio/mpeg" length="1234" href="https://example.com/2.mp3"/>
+    <summary>The <![CDATA[second]]> episode</summary>
+  </entry>
+  <entry>
+    <title type="html">First</title>
+    <id>urn:uuid:1</id>
+    <published>2020-07-22T13:00:00Z</published>
+    <updated>2020-07-25T13:00:00Z</updated>
+  </entry>
+</feed>"###;
+        let channel = read(feed.as_bytes()).unwrap();
+        assert_eq!(channel.title(), "Atom & Eve");
+        assert_eq!(channel.link(), "https://example.com/");
+        assert_eq!(
+            channel.image().map(|image| image.url()),
+            Some("https://example.com/logo.png")
+        );
+
+        let episodes = Episode::parse_feed(feed.as_bytes(), 1).unwrap();
+        assert_eq!(episodes.len(), 2);
+        assert_eq!(episodes[0].guid, "urn:uuid:2");
+        assert_eq!(episodes[0].pub_date, "Mon, 27 Jul 2020 13:00:00 +0200");
+        assert_eq!(episodes[0].link, "https://example.com/2");
+        assert_eq!(episodes[0].enclosure_url, "https://example.com/2.mp3");
+        assert_eq!(episodes[0].enclosure_length, 1234);
+        assert_eq!(episodes[0].podcast, "Atom & Eve");
+        assert_eq!(episodes[1].pub_date, "Wed, 22 Jul 2020 13:00:00 +0000");
+        assert_eq!(episodes[1].link, "-");
+
+        assert!(read(b"<html><body>Not a feed</body></html>").is_err());
+    }
+
+    #[test]
+    fn feed_item_metadata() {
+        let feed = r###"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel>
+<title>Feed</title><link>https://example.com</link>
+<podcast:person>Jane Doe</podcast:person><podcast:location geo="geo:52.52,13.405">Berlin</podcast:location>
+<item><title>Trailer</title><guid>1</guid><itunes:episodeType>Trailer</itunes:episodeType></item>
+<item><title>Second</title><guid isPermaLink="false">2</guid><itunes:season>3</itunes:season>
+<itunes:episode>12</itunes:episode><episode>7</episode>
+<podcast:alternateEnclosure type="audio/opus" length="1200" bitrate="32000.5">
+<podcast:source uri="ipfs://QmHash"/><podcast:source uri="https://example.com/2.opus"/>
+</podcast:alternateEnclosure>
+<podcast:alternateEnclosure type="audio/mpeg"><podcast:source uri="magnet:?xt=1"/></podcast:alternateEnclosure>
+<podcast:person role="Guest" href="https://example.com/john">John Smith</podcast:person>
+<podcast:person role="host"> Jane Doe </podcast:person><podcast:person role="guest"></podcast:person>
+<podcast:location geo="geo:30.2672,-97.7431;u=350" osm="R113314">Austin, TX</podcast:location>
+<podcast:soundbite startTime="73.0" duration="60.5">Why Rust?</podcast:soundbite>
+<podcast:soundbite startTime="1234.5" duration="42.25"/><podcast:soundbite startTime="10"/>
+<podcast:chapters url="https://example.com/2.json" type="application/json+chapters"/>
+<podcast:transcript url="https://example.com/2.vtt" type="text/vtt"/>
+<podcast:transcript url="https://example.com/2.srt" type="application/srt"/>
+</item>
+</channel></rss>"###;
+        let metadata = item_metadata(feed.as_bytes());
+        assert_eq!(metadata.len(), 2);
+        assert_eq!(metadata["1"].episode_type, "trailer");
+        assert_eq!(
+            metadata["1"].people,
+            vec![Person {
+                name: "Jane Doe".to_string(),
+                role: "host".to_string(),
+            }]
+        );
+        assert_eq!(
+            metadata["2"],
+            ItemMetadata {
+                season: 3,
+                episode: 12,
+                episode_type: String::new(),
+                alternates: vec![Alternate {
+                    url: "https://example.com/2.opus".to_string(),
+                    mime_type: "audio/opus".to_string(),
+                    length: 1200,
+                    bitrate: 32000,
+                }],
+                people: vec![
+                    Person {
+                        name: "John Smith".to_string(),
+                        role: "guest".to_string(),
+                    },
+                    Person {
+                        name: "Jane Doe".to_string(),
+                        role: "host".to_string(),
+                    },
+                ],
+                location: Some(Location {
+                    name: "Austin, TX".to_string(),
+                    coordinates: Some((30.2672, -97.7431)),
+                }),
+                soundbites: vec![
+                    Soundbite {
+                        start_time: 73.0,
+                        duration: 60.5,
+                        title: "Why Rust?".to_string(),
+                    },
+                    Soundbite {
+                        start_time: 1234.5,
+                        duration: 42.25,
+                        title: String::new(),
+                    },
+                ],
+                chapters: "https://example.com/2.json".to_string(),
+                transcript: "https://example.com/2.vtt".to_string(),
+            }
+        );
+        assert_eq!(
+            metadata["1"]
+                .location
+                .as_ref()
+                .and_then(|location| location.coordinates),
+            Some((52.52, 13.405))
+        );
+        assert_eq!(parse_geo("geo:-33.87,151.21,12"), Some((-33.87, 151.21)));
+        assert_eq!(parse_geo("geo:91,0"), None);
+        assert_eq!(parse_geo("30.2,97.7"), None);
+    }
+}
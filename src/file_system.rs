@@ -1,4 +1,14 @@
-use std::{fmt, fs, io, path::Path};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+/// Temporary files which weren't written to for this long were left behind by processes which
+/// didn't finish. a week, so an interrupted download can still be resumed for a while
+pub const STALE_AGE: Duration = Duration::from_secs(7 * 86_400);
 
 #[derive(Debug)]
 pub enum FileSystemErrors {
@@ -6,6 +16,7 @@ pub enum FileSystemErrors {
     CreateFile(io::Error),
     Rename(io::Error),
     Remove(io::Error),
+    Sync(io::Error),
 }
 
 impl fmt::Display for FileSystemErrors {
@@ -15,6 +26,7 @@ impl fmt::Display for FileSystemErrors {
             FileSystemErrors::CreateFile(error) => format!("Can't create file. {}", error),
             FileSystemErrors::Rename(error) => format!("Can't rename file, {}", error),
             FileSystemErrors::Remove(error) => format!("Can't remove file. {}", error),
+            FileSystemErrors::Sync(error) => format!("Can't sync file to disk. {}", error),
         };
 
         write!(f, "{}", message)
@@ -45,7 +57,7 @@ impl<'a, 'b> FileSystem<'a, 'b> {
     }
 
     pub fn open(&self) -> Result<fs::File, FileSystemErrors> {
-        let file_path = format!("{}/{}", self.directory.display(), self.file_name);
+        let file_path = self.directory.join(self.file_name);
         let mut file = fs::OpenOptions::new();
 
         for permission in &self.permissions {
@@ -81,28 +93,295 @@ impl<'a, 'b> FileSystem<'a, 'b> {
             .create(true)
             .read(true)
             .write(true)
+            .truncate(false)
             .open(&file_path)
-            .map_err(|error| FileSystemErrors::CreateFile(error))
+            .map_err(FileSystemErrors::CreateFile)
     }
 
     #[allow(dead_code)]
     pub fn rename(&mut self, new_name: &'static str) -> Result<(), FileSystemErrors> {
-        let old_path = format!("{}/{}", self.directory.display(), self.file_name);
-        let new_path = format!("{}/{}", self.directory.display(), new_name);
+        let old_path = self.directory.join(self.file_name);
+        let new_path = self.directory.join(new_name);
 
-        return match fs::rename(old_path, new_path) {
+        match fs::rename(old_path, new_path) {
             Ok(_) => {
                 self.file_name = new_name;
                 Ok(())
             }
             Err(error) => Err(FileSystemErrors::Rename(error)),
-        };
+        }
+    }
+
+    /// Opens a temporary file next to the file which replaces it once `AtomicFile::commit` is
+    /// called, so that an interrupted write never leaves a partially written file behind
+    pub fn atomic(&self) -> Result<AtomicFile, FileSystemErrors> {
+        self.atomic_with(fs::OpenOptions::new().write(true).create(true).truncate(true))
+    }
+
+    /// Like `atomic`, but only the user can read and write the file, from the moment it's
+    /// created. for files which hold secrets, e.g passwords
+    pub fn atomic_private(&self) -> Result<AtomicFile, FileSystemErrors> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        // A temporary file left behind by a process with the same PID would keep its permissions
+        let _ = fs::remove_file(self.temp_path());
+        self.atomic_with(&options)
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.directory
+            .join(format!(".{}.tmp-{}", self.file_name, process::id()))
+    }
+
+    fn atomic_with(&self, options: &fs::OpenOptions) -> Result<AtomicFile, FileSystemErrors> {
+        fs::create_dir_all(self.directory).map_err(FileSystemErrors::CreateDirectory)?;
+
+        let path = self.directory.join(self.file_name);
+        let temp_path = self.temp_path();
+        let file = options.open(&temp_path).map_err(FileSystemErrors::CreateFile)?;
+
+        Ok(AtomicFile {
+            path,
+            temp_path,
+            file: Some(file),
+        })
     }
 
-    #[allow(dead_code)]
     pub fn remove(self) -> Result<(), FileSystemErrors> {
-        let path = format!("{}/{}", self.directory.display(), self.file_name);
+        let path = self.directory.join(self.file_name);
+
+        fs::remove_file(path).map_err(FileSystemErrors::Remove)
+    }
+}
+
+/// Whether the file name is of a partial download or of an `AtomicFile` which wasn't committed,
+/// i.e `<episode>.part` or `.<file>.tmp-<pid>`
+fn is_temporary(file_name: &str) -> bool {
+    if file_name.len() > ".part".len() && file_name.ends_with(".part") {
+        return true;
+    }
+
+    match file_name.strip_prefix('.').and_then(|name| name.rsplit_once(".tmp-")) {
+        Some((name, pid)) => !name.is_empty() && !pid.is_empty() && pid.bytes().all(|byte| byte.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Removes the temporary files in the directory and its subdirectories which weren't modified for
+/// longer than the passed age. returns their paths
+pub fn remove_stale_files(directory: &Path, age: Duration) -> Result<Vec<PathBuf>, FileSystemErrors> {
+    let mut removed = Vec::new();
+    let mut directories = vec![directory.to_path_buf()];
+
+    while let Some(directory) = directories.pop() {
+        // Nothing was saved there yet
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(_error) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            // Symbolic links aren't followed, they may point outside the directory or at a parent
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                directories.push(path);
+                continue;
+            }
+
+            let is_stale = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .is_some_and(|elapsed| elapsed > age);
+            if is_stale && is_temporary(&entry.file_name().to_string_lossy()) {
+                fs::remove_file(&path).map_err(FileSystemErrors::Remove)?;
+                removed.push(path);
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Removes the directory of a podcast when its downloads were all removed, together with its
+/// empty subdirectories, e.g of seasons. returns their paths
+pub fn remove_empty_directories(directory: &Path) -> Result<Vec<PathBuf>, FileSystemErrors> {
+    let mut removed = Vec::new();
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_error) => return Ok(removed),
+    };
+
+    // The subdirectories go first, so the directories which only held empty ones are removed too.
+    // symbolic links aren't followed, they may point outside the directory or at a parent
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            removed.extend(remove_empty_directories(&entry.path())?);
+        }
+    }
+    let is_empty = fs::read_dir(directory).is_ok_and(|mut entries| entries.next().is_none());
+    if is_empty {
+        fs::remove_dir(directory).map_err(FileSystemErrors::Remove)?;
+        removed.push(directory.to_path_buf());
+    }
+
+    Ok(removed)
+}
+
+/// A file which is written to a temporary location and moved over the destination on commit.
+/// Dropping it without committing removes the temporary file and leaves the destination untouched
+pub struct AtomicFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: Option<fs::File>,
+}
+
+impl AtomicFile {
+    /// Flushes the written content to disk and renames the temporary file over the destination
+    pub fn commit(self) -> Result<(), FileSystemErrors> {
+        let (temp_path, path) = self.prepare()?;
+
+        fs::rename(temp_path, path).map_err(FileSystemErrors::Rename)
+    }
+
+    /// Flushes the written content to disk without replacing the destination. returns the path of
+    /// the temporary file and of the destination, which is left to the caller to rename
+    pub fn prepare(mut self) -> Result<(PathBuf, PathBuf), FileSystemErrors> {
+        // Always present until the file is committed
+        let mut file = self.file.take().unwrap();
+        file.flush().map_err(FileSystemErrors::Sync)?;
+        file.sync_all().map_err(FileSystemErrors::Sync)?;
+
+        Ok((self.temp_path.clone(), self.path.clone()))
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Always present until the file is committed
+        self.file.as_mut().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().unwrap().flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn atomic_file() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("list.csv"), "old").unwrap();
+        let file_system = FileSystem::new(&directory, "list.csv", vec![]);
+
+        // An uncommitted write keeps the previous content and doesn't leave the temporary file
+        let mut file = file_system.atomic().expect("Can't open atomic file");
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        assert_eq!(fs::read_to_string(directory.join("list.csv")).unwrap(), "old");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+
+        let mut file = file_system.atomic().expect("Can't open atomic file");
+        file.write_all(b"new").unwrap();
+        file.commit().expect("Can't commit atomic file");
+        assert_eq!(fs::read_to_string(directory.join("list.csv")).unwrap(), "new");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = fixtures::scratch();
+        let file_system = FileSystem::new(&directory, "account.csv", vec![]);
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        // A leftover temporary file doesn't pass its permissions on
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(file_system.temp_path(), "old").unwrap();
+        fs::set_permissions(file_system.temp_path(), fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut file = file_system.atomic_private().expect("Can't create atomic file");
+        assert_eq!(mode(&file.temp_path), 0o600);
+        write!(file, "password").unwrap();
+        file.commit().expect("Can't commit atomic file");
+        assert_eq!(mode(&directory.join("account.csv")), 0o600);
+        assert_eq!(fs::read_to_string(directory.join("account.csv")).unwrap(), "password");
+    }
+
+    #[test]
+    fn remove_stale_and_empty() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(directory.join("1/Season 01")).unwrap();
+        fs::create_dir_all(directory.join("2")).unwrap();
+        fs::create_dir_all(directory.join("3")).unwrap();
+        fs::write(directory.join("2/episode.mp3.part"), "01").unwrap();
+        fs::write(directory.join("2/.podcast_list.csv.tmp-1"), "01").unwrap();
+        fs::write(directory.join("3/episode.mp3"), "0123").unwrap();
+        // Only the names of the temporary files pcasts writes itself
+        fs::write(directory.join("3/.part"), "01").unwrap();
+        fs::write(directory.join("3/.session.tmp-a1"), "01").unwrap();
+        fs::write(directory.join("3/cache.tmp-1"), "01").unwrap();
+
+        // The files were just written, so they may still be in use
+        assert!(remove_stale_files(&directory, STALE_AGE).unwrap().is_empty());
+        std::thread::sleep(Duration::from_millis(20));
+        let mut stale = remove_stale_files(&directory, Duration::from_millis(10)).unwrap();
+        stale.sort();
+        assert_eq!(
+            stale,
+            vec![
+                directory.join("2/.podcast_list.csv.tmp-1"),
+                directory.join("2/episode.mp3.part")
+            ]
+        );
+        assert!(directory.join("3/episode.mp3").exists());
+        assert_eq!(fs::read_dir(directory.join("3")).unwrap().count(), 4);
+
+        let mut empty = remove_empty_directories(&directory.join("1")).unwrap();
+        empty.sort();
+        assert_eq!(empty, vec![directory.join("1"), directory.join("1/Season 01")]);
+        assert!(remove_empty_directories(&directory.join("3")).unwrap().is_empty());
+        assert!(directory.join("2").exists() && directory.join("3").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn remove_without_following_links() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(directory.join("1")).unwrap();
+        fs::create_dir_all(directory.join("outside/empty")).unwrap();
+        fs::write(directory.join("outside/episode.mp3.part"), "01").unwrap();
+        std::os::unix::fs::symlink(directory.join("1"), directory.join("1/loop")).unwrap();
+        std::os::unix::fs::symlink(directory.join("outside"), directory.join("1/outside")).unwrap();
 
-        fs::remove_file(path).map_err(|error| FileSystemErrors::Remove(error))
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(remove_stale_files(&directory.join("1"), Duration::from_millis(10))
+            .unwrap()
+            .is_empty());
+        assert!(remove_empty_directories(&directory.join("1")).unwrap().is_empty());
+        assert!(directory.join("outside/empty").exists());
+        assert!(directory.join("outside/episode.mp3.part").exists());
     }
 }
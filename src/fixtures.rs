@@ -0,0 +1,206 @@
+//! Test data which lives in the "fixtures" directory at the root of the crate. every fixture is
+//! registered in "fixtures/index.csv" together with the URL it answers for, so `FixtureClient`
+//! can serve it instead of hitting the network
+use crate::{
+    file_system::FileSystem,
+    web::{ClientOptions, HttpClient, Request, Response, Web},
+    Errors,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time,
+};
+
+const INDEX_FILE: &str = "index.csv";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Fixture {
+    pub url: String,
+    pub file: String,
+}
+
+/// The directory which holds all the fixtures, located relative to the crate and not to the
+/// current working directory
+pub fn directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+}
+
+/// Full path of the fixture with the passed file name
+pub fn path(file: &str) -> PathBuf {
+    directory().join(file)
+}
+
+/// Reads the contents of the fixture with the passed file name
+pub fn read(file: &str) -> Result<Vec<u8>, Errors> {
+    fs::read(path(file)).map_err(Errors::IO)
+}
+
+/// All the registered fixtures
+pub fn list() -> Result<Vec<Fixture>, Errors> {
+    let mut reader = csv::Reader::from_path(directory().join(INDEX_FILE))?;
+    let fixtures = reader.deserialize().collect::<Result<Vec<Fixture>, csv::Error>>()?;
+
+    Ok(fixtures)
+}
+
+/// Returns the contents of the fixture registered for the passed URL
+pub fn lookup(url: &str) -> Option<Vec<u8>> {
+    let fixtures = list().ok()?;
+    let fixture = fixtures.iter().find(|fixture| fixture.url == url)?;
+
+    read(&fixture.file).ok()
+}
+
+/// Answers the requests with the registered fixtures. the URLs without one answer with an empty
+/// body, and ranges aren't supported
+#[derive(Debug, Default)]
+pub struct FixtureClient;
+
+impl HttpClient for FixtureClient {
+    fn fetch(&self, request: &Request) -> Result<Response, Errors> {
+        let bytes = lookup(request.url).unwrap_or_default();
+
+        Ok(Response {
+            status: 200,
+            length: Some(bytes.len() as u64),
+            accepts_ranges: false,
+            last_modified: None,
+            body: Box::new(io::Cursor::new(bytes)),
+        })
+    }
+}
+
+/// Downloads the passed URL and registers the response as a new fixture. if no file name is
+/// passed, one is derived from the URL. returns the path of the saved fixture
+pub fn record(url: &str, file: Option<&str>) -> Result<PathBuf, Errors> {
+    let file = match file {
+        Some(file) => file.to_string(),
+        None => file_name(url),
+    };
+
+    let (_url, response) = Web::new(time::Duration::from_secs(30), &ClientOptions::default())
+        .get(&[url])
+        .pop()
+        .ok_or_else(|| Errors::NotFound(url.to_string()))?;
+    let bytes = response?;
+    let mut fixture = FileSystem::new(&directory(), &file, vec![]).atomic()?;
+    fixture.write_all(&bytes[..])?;
+    fixture.commit()?;
+
+    // Replace a previous registration of the same URL
+    let mut fixtures: Vec<Fixture> = list()?.into_iter().filter(|fixture| fixture.url != url).collect();
+    fixtures.push(Fixture {
+        url: url.to_string(),
+        file: file.clone(),
+    });
+
+    let index = FileSystem::new(&directory(), INDEX_FILE, vec![]).atomic()?;
+    let mut writer = csv::Writer::from_writer(index);
+    for fixture in fixtures {
+        writer.serialize(fixture)?;
+    }
+    writer
+        .into_inner()
+        .map_err(|error| Errors::IO(error.into_error()))?
+        .commit()?;
+
+    Ok(path(&file))
+}
+
+/// Turns a URL into a file name, e.g "https://feed.syntax.fm/rss" -> "feed.syntax.fm_rss.xml"
+fn file_name(url: &str) -> String {
+    let address = url.split("://").last().unwrap_or(url);
+    let name: String = address
+        .chars()
+        .map(|char| {
+            if char.is_ascii_alphanumeric() || char == '.' {
+                char
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_matches('_');
+
+    // Feeds usually don't have an extension in their path, e.g "/rss"
+    let has_extension = match address.rsplit_once('/') {
+        Some((_, last_segment)) => last_segment.contains('.'),
+        None => false,
+    };
+
+    if has_extension {
+        name.to_string()
+    } else {
+        format!("{}.xml", name)
+    }
+}
+
+/// The scratch directory of the test which runs on the thread. it's removed when the thread ends
+#[cfg(test)]
+struct Scratch(PathBuf);
+
+#[cfg(test)]
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    static SCRATCH: Scratch = {
+        // The test harness runs each test on a thread named after it
+        let test = std::thread::current().name().unwrap_or("main").replace("::", "-");
+        let path = std::env::temp_dir()
+            .join("pcasts-tests")
+            .join(format!("{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&path);
+
+        Scratch(path)
+    };
+}
+
+/// A scratch directory for the running test inside the system temp directory. it's named after
+/// the process and the test, so the tests which run at the same time never share one, and it's
+/// removed once the test finishes, whether it passed or not
+#[cfg(test)]
+pub(crate) fn scratch() -> PathBuf {
+    SCRATCH.with(|scratch| scratch.0.clone())
+}
+
+/// A config which points to the scratch directory of the running test
+#[cfg(test)]
+pub(crate) fn config() -> crate::Config {
+    let app_directory = scratch();
+    let download_directory = app_directory.join("downloads");
+
+    crate::Config::new(app_directory, download_directory)
+        .with_http_client(std::sync::Arc::new(FixtureClient))
+        .with_progress(std::sync::Arc::new(crate::NoProgress))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_registered() {
+        let fixtures = list().expect("Can't read fixtures index");
+
+        for fixture in fixtures {
+            assert!(path(&fixture.file).exists(), "Missing fixture {}", fixture.file);
+        }
+    }
+
+    #[test]
+    fn fixtures_file_name() {
+        assert_eq!(file_name("https://feed.syntax.fm/rss"), "feed.syntax.fm_rss.xml");
+        assert_eq!(
+            file_name("https://traffic.libsyn.com/secure/syntax/Syntax268.mp3"),
+            "traffic.libsyn.com_secure_syntax_Syntax268.mp3"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! A quick guess of whether the feeds have new episodes, for shell prompts and status bars which
+//! can't wait for an update. each feed gets a HEAD request, conditional on the time of the last
+//! update of its podcast, and nothing is saved
+use crate::{
+    dates,
+    headers::HeaderList,
+    podcasts::load_podcasts,
+    web::{Response, Web},
+    Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use std::{collections::HashSet, fmt, io::Write, time};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Freshness {
+    New,
+    Unchanged,
+    // The server didn't say when the feed changed, or it couldn't be reached
+    Unknown,
+}
+
+impl fmt::Display for Freshness {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Freshness::New => write!(f, "{}", "new episodes likely".green()),
+            Freshness::Unchanged => write!(f, "unchanged"),
+            Freshness::Unknown => write!(f, "{}", "unknown".yellow()),
+        }
+    }
+}
+
+impl Freshness {
+    /// Whether the feed changed since the passed time, judging by the response to its HEAD request
+    fn of(response: &Response, last_update: u64) -> Self {
+        match response.status {
+            304 => Freshness::Unchanged,
+            // Some hosts don't answer HEAD requests
+            status if status >= 400 => Freshness::Unknown,
+            _ => match response.last_modified.as_deref().and_then(dates::parse_pub_date) {
+                Some(modified) if modified > last_update => Freshness::New,
+                Some(_modified) => Freshness::Unchanged,
+                None => Freshness::Unknown,
+            },
+        }
+    }
+}
+
+pub struct Checker<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Checker<'a> {
+    /// Constructs a new Checker struct which is used to work with the sub command "episodes check"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Checks the podcasts with the passed ids, or all the podcasts which aren't paused
+    pub fn run(&self) -> Result<(), Errors> {
+        let ids: Option<HashSet<&str>> = self.matches.values_of("podcast").map(|ids| ids.collect());
+        let podcasts: Vec<_> = load_podcasts(self.config)?
+            .into_iter()
+            .filter(|podcast| match &ids {
+                Some(ids) => ids.contains(podcast.id.to_string().as_str()),
+                None => !podcast.paused,
+            })
+            .collect();
+        if let (Some(ids), true) = (&ids, podcasts.is_empty()) {
+            let ids: Vec<&str> = ids.iter().copied().collect();
+            return Err(Errors::WrongID(ids.join(", ")));
+        }
+
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?
+            .by_url(podcasts.iter().map(|podcast| (podcast.rss_url.as_str(), podcast.id)));
+        // Without the network statistics, which would be saved
+        let web = Web::new(time::Duration::from_secs(10), &self.config.http)
+            .jobs(self.config.jobs)
+            .headers(headers);
+        let requests: Vec<(&str, Vec<(String, String)>)> = podcasts
+            .iter()
+            .map(|podcast| {
+                let conditions = match podcast.last_update {
+                    0 => vec![],
+                    last_update => vec![("If-Modified-Since".to_string(), dates::format_http_date(last_update))],
+                };
+                (podcast.rss_url.as_str(), conditions)
+            })
+            .collect();
+        let responses = web.head(&requests);
+
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+        for (podcast, (url, response)) in podcasts.iter().zip(responses) {
+            let freshness = match response {
+                Ok(response) => Freshness::of(&response, podcast.last_update),
+                Err(error) => {
+                    tracing::debug!("Can't check {}. {}", url, error);
+                    Freshness::Unknown
+                }
+            };
+            writeln!(writer, "{:40} {}", podcast.name(), freshness)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn create_response(status: u16, last_modified: Option<&str>) -> Response {
+        Response {
+            status,
+            length: None,
+            accepts_ranges: false,
+            last_modified: last_modified.map(|last_modified| last_modified.to_string()),
+            body: Box::new(io::empty()),
+        }
+    }
+
+    #[test]
+    fn freshness_of_response() {
+        let last_update = dates::parse_pub_date("Wed, 22 Jul 2020 13:00:00 GMT").unwrap();
+        assert_eq!(dates::format_http_date(last_update), "Wed, 22 Jul 2020 13:00:00 GMT");
+
+        let freshness = |status, last_modified| Freshness::of(&create_response(status, last_modified), last_update);
+        assert_eq!(freshness(304, None), Freshness::Unchanged);
+        assert_eq!(freshness(200, Some("Thu, 23 Jul 2020 08:00:00 GMT")), Freshness::New);
+        assert_eq!(
+            freshness(200, Some("Wed, 22 Jul 2020 13:00:00 GMT")),
+            Freshness::Unchanged
+        );
+        assert_eq!(freshness(200, Some("yesterday")), Freshness::Unknown);
+        assert_eq!(freshness(200, None), Freshness::Unknown);
+        assert_eq!(
+            freshness(405, Some("Thu, 23 Jul 2020 08:00:00 GMT")),
+            Freshness::Unknown
+        );
+    }
+}
@@ -0,0 +1,559 @@
+//! Keeps the subscriptions and the played episodes in sync with the other devices of a gpodder.net
+//! account, or of any server with its API, e.g the one a phone app syncs with. `gpodder login`
+//! saves the account and registers pcasts as a device of it, and `gpodder sync` exchanges what
+//! changed on each side since the last sync. the first sync merges the subscriptions of both
+use crate::{
+    dates,
+    episodes::{Episode, Episodes},
+    events::{self, Event, EventKind},
+    file_system::{FilePermissions, FileSystem},
+    played::{self, PlayedEntry, PlayedList},
+    podcasts::{load_podcasts, subscribe, write_podcasts, Podcast},
+    progress::NoProgress,
+    records,
+    web::Web,
+    Config, Errors,
+};
+use bytes::Bytes;
+use clap::ArgMatches;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::Path,
+    sync::Arc,
+    time,
+};
+
+/// The file inside the app directory which holds the account
+pub const GPODDER_FILE: &str = "gpodder.csv";
+
+/// The name pcasts registers itself with when no device is passed
+const DEFAULT_DEVICE: &str = "pcasts";
+
+/// Stands for the device of the played episodes which came from a server that didn't say
+const UNKNOWN_DEVICE: &str = "gpodder";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub server: String,
+    pub username: String,
+    // Empty when it's passed with PODCASTS_GPODDER_PASSWORD instead
+    pub password: String,
+    pub device: String,
+    // The times of the server which the next sync asks for the changes since
+    pub subscriptions_since: u64,
+    pub actions_since: u64,
+    // When the last sync finished. the episodes marked as played here after it are sent
+    pub synced_at: u64,
+    // The RSS URLs of the podcasts at the last sync, separated by spaces, which tell the podcasts
+    // which were added and removed here since
+    pub subscriptions: String,
+}
+
+impl Account {
+    /// The saved account. None before `gpodder login`
+    fn load(app_directory: &Path) -> Result<Option<Self>, Errors> {
+        match fs::read(app_directory.join(GPODDER_FILE)) {
+            Ok(contents) => Ok(records::read(&contents[..], GPODDER_FILE, true)?.pop()),
+            Err(_error) => Ok(None),
+        }
+    }
+
+    /// Saves the account. only the user can read the file, since it may hold the password
+    fn save(&self, app_directory: &Path) -> Result<(), Errors> {
+        let file = FileSystem::new(app_directory, GPODDER_FILE, vec![]).atomic_private()?;
+        let mut writer = csv::Writer::from_writer(file);
+        writer.serialize(self)?;
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        file.commit()?;
+
+        Ok(())
+    }
+
+    /// The URL of the passed path of the API
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/2/{}", self.server.trim_end_matches('/'), path)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SubscriptionChanges {
+    #[serde(default)]
+    add: Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+    timestamp: u64,
+}
+
+/// The playback of an episode on a device, or another change of it like its download
+#[derive(Debug, Serialize, Deserialize)]
+struct EpisodeAction {
+    podcast: String,
+    // The URL of the audio file
+    episode: String,
+    action: String,
+    #[serde(default)]
+    timestamp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+    #[serde(default)]
+    device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpisodeActions {
+    #[serde(default)]
+    actions: Vec<EpisodeAction>,
+    timestamp: u64,
+}
+
+/// The body of a response of the API
+fn parse<T>(bytes: &Bytes, url: &str) -> Result<T, Errors>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_slice(&bytes[..]).map_err(|error| {
+        tracing::debug!("Can't parse the response of {}. {}", url, error);
+        Errors::InvalidResponse(url.to_string())
+    })
+}
+
+pub struct Gpodder<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Gpodder<'a> {
+    /// Constructs a new Gpodder struct which is used to work with the sub command "gpodder"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if let Some(matches) = self.matches.subcommand_matches("login") {
+            return self.login(matches);
+        }
+
+        if self.matches.subcommand_matches("logout").is_some() {
+            if Account::load(&self.config.app_directory)?.is_none() {
+                return Err(Errors::NotLoggedIn);
+            }
+            fs::remove_file(self.config.app_directory.join(GPODDER_FILE))?;
+            println!("Logged out. The subscriptions and played episodes are kept");
+            return Ok(());
+        }
+
+        if self.matches.subcommand_matches("sync").is_some() {
+            let account = Account::load(&self.config.app_directory)?.ok_or(Errors::NotLoggedIn)?;
+            return self.sync(account);
+        }
+
+        Ok(())
+    }
+
+    /// The password of PODCASTS_GPODDER_PASSWORD, or else the saved one
+    fn password(&self, account: &Account) -> Result<String, Errors> {
+        match &self.config.gpodder_password {
+            Some(password) => Ok(password.clone()),
+            None if !account.password.is_empty() => Ok(account.password.clone()),
+            None => Err(Errors::MissingPassword),
+        }
+    }
+
+    /// Sends the requests to the passed URLs with the credentials of the account
+    fn web(&self, account: &Account, urls: &[&str]) -> Result<Web, Errors> {
+        let credentials = base64::encode(format!("{}:{}", account.username, self.password(account)?));
+        let headers = urls
+            .iter()
+            .map(|url| {
+                let authorization = ("Authorization".to_string(), format!("Basic {}", credentials));
+                (url.to_string(), vec![authorization])
+            })
+            .collect();
+
+        Ok(Web::new(time::Duration::from_secs(30), &self.config.http)
+            .progress(Arc::new(NoProgress))
+            .headers(headers))
+    }
+
+    /// Saves the account and registers pcasts as a device of it, which checks the credentials
+    fn login(&self, matches: &ArgMatches) -> Result<(), Errors> {
+        // Always present because they're required arguments
+        let account = Account {
+            server: matches.value_of("server").unwrap().trim_end_matches('/').to_string(),
+            username: matches.value_of("username").unwrap().to_string(),
+            password: matches.value_of("password").unwrap_or_default().to_string(),
+            device: matches.value_of("device").unwrap_or(DEFAULT_DEVICE).to_string(),
+            subscriptions_since: 0,
+            actions_since: 0,
+            synced_at: 0,
+            subscriptions: String::new(),
+        };
+
+        let url = account.url(&format!("devices/{}/{}.json", account.username, account.device));
+        let body = json!({ "caption": "pcasts", "type": "desktop" }).to_string();
+        self.web(&account, &[&url])?.post(&url, body.as_bytes())?;
+        account.save(&self.config.app_directory)?;
+        println!(
+            "Logged in to {} as {} with the device {}",
+            account.server, account.username, account.device
+        );
+
+        Ok(())
+    }
+
+    /// Exchanges the subscriptions and the played episodes which changed since the last sync
+    fn sync(&self, mut account: Account) -> Result<(), Errors> {
+        let subscriptions_url = account.url(&format!("subscriptions/{}/{}.json", account.username, account.device));
+        let changes_url = format!("{}?since={}", subscriptions_url, account.subscriptions_since);
+        let actions_url = account.url(&format!("episodes/{}.json", account.username));
+        let new_actions_url = format!("{}?since={}", actions_url, account.actions_since);
+        let web = self.web(
+            &account,
+            &[&subscriptions_url, &changes_url, &actions_url, &new_actions_url],
+        )?;
+        let get = |url: &str| -> Result<Bytes, Errors> {
+            web.get(&[url])
+                .pop()
+                .map(|(_url, response)| response)
+                .unwrap_or_else(|| Err(Errors::NotFound(url.to_string())))
+        };
+
+        // The changes of the other devices are applied unless they're undone here
+        let local: HashSet<String> = load_podcasts(self.config)?
+            .into_iter()
+            .map(|podcast| podcast.rss_url)
+            .collect();
+        let synced: HashSet<String> = account.subscriptions.split_whitespace().map(String::from).collect();
+        let mut added: Vec<&String> = local.difference(&synced).collect();
+        let mut removed: Vec<&String> = synced.difference(&local).collect();
+        added.sort();
+        removed.sort();
+        let changes: SubscriptionChanges = parse(&get(&changes_url)?, &changes_url)?;
+        let remote_added: Vec<&str> = changes
+            .add
+            .iter()
+            .filter(|url| !local.contains(*url) && !removed.contains(url))
+            .map(|url| url.as_str())
+            .collect();
+        let remote_removed: HashSet<&str> = changes
+            .remove
+            .iter()
+            .filter(|url| local.contains(*url) && !added.contains(url))
+            .map(|url| url.as_str())
+            .collect();
+        let subscribed = self.subscribe(&remote_added)?;
+        self.unsubscribe(&remote_removed)?;
+        if !added.is_empty() || !removed.is_empty() {
+            let body = json!({ "add": added, "remove": removed }).to_string();
+            web.post(&subscriptions_url, body.as_bytes())?;
+        }
+
+        // The played episodes of the new podcasts can only be matched once their episodes are saved
+        let episodes = Episodes::new(self.matches, self.config);
+        if !subscribed.is_empty() {
+            episodes.refresh(Some(&subscribed))?;
+        }
+        let podcasts = load_podcasts(self.config)?;
+        let mut saved_episodes: HashMap<u64, Vec<Episode>> = HashMap::new();
+
+        let actions: EpisodeActions = parse(&get(&new_actions_url)?, &new_actions_url)?;
+        let by_url: HashMap<&str, &Podcast> = podcasts
+            .iter()
+            .map(|podcast| (podcast.rss_url.as_str(), podcast))
+            .collect();
+        let mut pulled = Vec::new();
+        for action in actions.actions.iter().filter(|action| action.device != account.device) {
+            let (position, total) = match action.action.as_str() {
+                "play" => (action.position.unwrap_or(0), action.total.unwrap_or(0)),
+                "new" => (0, 0),
+                _ => continue,
+            };
+            let podcast = match by_url.get(action.podcast.as_str()) {
+                Some(podcast) => podcast,
+                None => continue,
+            };
+            let episode = saved_episodes
+                .entry(podcast.id)
+                .or_insert_with(|| episodes.podcast_episodes(podcast.id).unwrap_or_default())
+                .iter()
+                .find(|episode| episode.enclosure_url == action.episode || action.guid.as_ref() == Some(&episode.guid));
+            if let Some(episode) = episode {
+                pulled.push(PlayedEntry {
+                    podcast_id: podcast.id,
+                    guid: episode.guid.clone(),
+                    position,
+                    total,
+                    time: dates::parse_iso_time(&action.timestamp).unwrap_or_else(dates::now),
+                    device: match action.device.as_str() {
+                        "" => UNKNOWN_DEVICE.to_string(),
+                        device => device.to_string(),
+                    },
+                });
+            }
+        }
+        played::record(&self.config.app_directory, &pulled)?;
+
+        let by_id: HashMap<u64, &Podcast> = podcasts.iter().map(|podcast| (podcast.id, podcast)).collect();
+        let played_list = PlayedList::load(&self.config.app_directory);
+        let mut pushed = Vec::new();
+        for entry in played_list.changed_since(account.synced_at) {
+            let podcast = match by_id.get(&entry.podcast_id) {
+                Some(podcast) => podcast,
+                None => continue,
+            };
+            if let Some(episode) = saved_episodes
+                .entry(podcast.id)
+                .or_insert_with(|| episodes.podcast_episodes(podcast.id).unwrap_or_default())
+                .iter()
+                .find(|episode| episode.guid == entry.guid)
+            {
+                let played = entry.total > 0;
+                pushed.push(EpisodeAction {
+                    podcast: podcast.rss_url.clone(),
+                    episode: episode.enclosure_url.clone(),
+                    action: if played { "play" } else { "new" }.to_string(),
+                    timestamp: dates::format_iso_time(entry.time),
+                    position: Some(entry.position).filter(|_position| played),
+                    total: Some(entry.total).filter(|_total| played),
+                    device: account.device.clone(),
+                    guid: Some(episode.guid.clone()),
+                });
+            }
+        }
+        if !pushed.is_empty() {
+            let body = serde_json::to_string(&pushed).map_err(|error| Errors::IO(error.into()))?;
+            web.post(&actions_url, body.as_bytes())?;
+        }
+
+        // The changes sent by this sync come back with the next one, where they change nothing
+        let mut subscriptions: Vec<String> = load_podcasts(self.config)?
+            .into_iter()
+            .map(|podcast| podcast.rss_url)
+            .collect();
+        subscriptions.sort();
+        account.subscriptions = subscriptions.join(" ");
+        account.subscriptions_since = changes.timestamp;
+        account.actions_since = actions.timestamp;
+        account.synced_at = dates::now();
+        account.save(&self.config.app_directory)?;
+
+        println!(
+            "Sent {} subscription changes and {} played episodes. Received {} subscription changes and {} played episodes",
+            added.len() + removed.len(),
+            pushed.len(),
+            remote_added.len() + remote_removed.len(),
+            pulled.len()
+        );
+
+        Ok(())
+    }
+
+    /// Saves the podcasts of the passed RSS URLs. returns the ids of the saved ones
+    fn subscribe(&self, urls: &[&str]) -> Result<HashSet<u64>, Errors> {
+        if urls.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let mut contents = String::new();
+        FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?
+        .read_to_string(&mut contents)?;
+        let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        let results = subscribe(self.config, urls, &[], contents.as_bytes(), &mut writer_file)?;
+        writer_file.commit()?;
+
+        let mut ids = HashSet::new();
+        for (url, podcast) in results {
+            match podcast {
+                Ok(podcast) => {
+                    println!("Added podcast {}", url);
+                    ids.insert(podcast.id);
+                }
+                Err(error) => tracing::warn!("Can't add podcast {}. {}", url, error),
+            }
+        }
+
+        Ok(ids)
+    }
+
+    /// Removes the podcasts of the passed RSS URLs. their saved episodes and downloads are kept
+    fn unsubscribe(&self, urls: &HashSet<&str>) -> Result<(), Errors> {
+        if urls.is_empty() {
+            return Ok(());
+        }
+
+        let (removed, kept): (Vec<Podcast>, Vec<Podcast>) = load_podcasts(self.config)?
+            .into_iter()
+            .partition(|podcast| urls.contains(podcast.rss_url.as_str()));
+        let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        write_podcasts(&kept, &mut writer_file)?;
+        writer_file.commit()?;
+
+        let unsubscribed: Vec<Event> = removed
+            .iter()
+            .map(|podcast| Event::new(EventKind::Unsubscribed, podcast.id, "", podcast.name()))
+            .collect();
+        events::record(&self.config.app_directory, &unsubscribed)?;
+        for podcast in removed.iter() {
+            println!("Removed {}", podcast.name());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixtures::{self, FixtureClient},
+        web::{HttpClient, Request, Response},
+        Application, ApplicationBuilder,
+    };
+    use std::{io, sync::Mutex};
+
+    /// A gpodder server where another device subscribed to Syntax and played one of its episodes
+    #[derive(Debug, Default)]
+    struct ServerClient {
+        posts: Mutex<Vec<(String, String)>>,
+    }
+
+    impl HttpClient for ServerClient {
+        fn fetch(&self, request: &Request) -> Result<Response, Errors> {
+            let body = if request.url.contains("/api/2/subscriptions/") {
+                json!({ "add": ["https://feed.syntax.fm/rss"], "remove": [], "timestamp": 12 })
+            } else if request.url.contains("/api/2/episodes/") {
+                json!({
+                    "actions": [{
+                        "podcast": "https://feed.syntax.fm/rss",
+                        "episode": "https://traffic.libsyn.com/secure/syntax/Syntax268.mp3?dest-id=532671",
+                        "action": "play",
+                        "timestamp": "2020-07-23T08:00:00",
+                        "position": 3600,
+                        "total": 3610,
+                        "device": "phone"
+                    }],
+                    "timestamp": 12
+                })
+            } else {
+                return FixtureClient.fetch(request);
+            };
+
+            Ok(Response {
+                status: 200,
+                length: None,
+                accepts_ranges: false,
+                last_modified: None,
+                body: Box::new(io::Cursor::new(body.to_string().into_bytes())),
+            })
+        }
+
+        fn post(&self, request: &Request, body: &[u8]) -> Result<Response, Errors> {
+            let body = String::from_utf8_lossy(body).to_string();
+            self.posts.lock().unwrap().push((request.url.to_string(), body));
+
+            Ok(Response {
+                status: 200,
+                length: None,
+                accepts_ranges: false,
+                last_modified: None,
+                body: Box::new(io::Cursor::new(br#"{"timestamp":13}"#.to_vec())),
+            })
+        }
+    }
+
+    fn create_app() -> Application {
+        ApplicationBuilder::new(fixtures::config()).gpodder_subcommand().build()
+    }
+
+    #[test]
+    fn gpodder_sync() {
+        let app = create_app();
+        let client = Arc::new(ServerClient::default());
+        let config = fixtures::config().with_http_client(client.clone());
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(config.app_directory.join("podcast_list.csv"), "").unwrap();
+
+        let args = app.app.get_matches_from(vec!["pcasts", "gpodder", "sync"]);
+        let gpodder_matches = args.subcommand_matches("gpodder").expect("No gpodder matches");
+        let gpodder = Gpodder::new(gpodder_matches, &config);
+
+        // A podcast which was added and an episode which was played here before the first sync
+        let ids = gpodder
+            .subscribe(&["http://feeds.feedburner.com/Http203Podcast"])
+            .unwrap();
+        let episodes = Episodes::new(gpodder_matches, &config);
+        episodes.refresh(Some(&ids)).unwrap();
+        let http_203 = *ids.iter().next().unwrap();
+        let played_here = episodes.podcast_episodes(http_203).unwrap().remove(0);
+        played::record(&config.app_directory, &[PlayedEntry::finished(&played_here)]).unwrap();
+
+        assert!(matches!(gpodder.run(), Err(Errors::NotLoggedIn)));
+        let account = Account {
+            server: "https://gpodder.example/".to_string(),
+            username: "user".to_string(),
+            password: "secret".to_string(),
+            device: DEFAULT_DEVICE.to_string(),
+            subscriptions_since: 0,
+            actions_since: 0,
+            synced_at: 0,
+            subscriptions: String::new(),
+        };
+        assert_eq!(
+            account.url("episodes/user.json"),
+            "https://gpodder.example/api/2/episodes/user.json"
+        );
+        account.save(&config.app_directory).unwrap();
+        gpodder.run().expect("Can't sync");
+
+        let posts = client.posts.lock().unwrap();
+        assert_eq!(posts.len(), 2);
+        assert_eq!(
+            posts[0],
+            (
+                "https://gpodder.example/api/2/subscriptions/user/pcasts.json".to_string(),
+                r#"{"add":["http://feeds.feedburner.com/Http203Podcast"],"remove":[]}"#.to_string()
+            )
+        );
+        let pushed: Vec<EpisodeAction> = serde_json::from_str(&posts[1].1).unwrap();
+        assert_eq!(pushed.len(), 1);
+        assert_eq!(pushed[0].action, "play");
+        assert_eq!(pushed[0].episode, played_here.enclosure_url);
+        assert_eq!(pushed[0].device, DEFAULT_DEVICE);
+
+        // The podcast subscribed on the phone was added, with its played episode
+        let podcasts = load_podcasts(&config).unwrap();
+        let syntax = podcasts
+            .iter()
+            .find(|podcast| podcast.rss_url == "https://feed.syntax.fm/rss")
+            .expect("Syntax wasn't added");
+        let played_list = PlayedList::load(&config.app_directory);
+        let syntax_episodes = episodes.podcast_episodes(syntax.id).unwrap();
+        let played_there = syntax_episodes
+            .iter()
+            .find(|episode| episode.guid == "272eca72-476b-4633-864c-a9fffa3f5976")
+            .unwrap();
+        assert!(played_list.contains(played_there));
+        assert!(played_list.contains(&played_here));
+        assert_eq!(played_list.changed_since(0).len(), 1);
+
+        let account = Account::load(&config.app_directory).unwrap().unwrap();
+        assert_eq!((account.subscriptions_since, account.actions_since), (12, 12));
+        assert_eq!(
+            account.subscriptions,
+            "http://feeds.feedburner.com/Http203Podcast https://feed.syntax.fm/rss"
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Extra HTTP headers sent with the requests of a podcast, e.g the credentials of a premium feed
+//! or a cookie its host requires. they're sent with the requests of the feed and of the episodes
+use crate::{file_system::FileSystem, records, Errors};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const HEADERS_FILE: &str = "headers.csv";
+
+/// Sent by `--agent browser`, for hosts which only answer browsers
+pub const BROWSER_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+/// The User-Agent of the passed value of --agent. "browser" is the one of a common browser
+pub fn user_agent(value: &str) -> String {
+    match value.trim() {
+        "browser" => BROWSER_USER_AGENT.to_string(),
+        value => value.to_string(),
+    }
+}
+
+/// The Referer of the passed value of --referrer. "site" is the website of the podcast, which CDNs
+/// of shows commonly expect
+pub fn referrer(value: &str, website: &str) -> String {
+    match value.trim() {
+        "site" => website.to_string(),
+        value => value.to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PodcastHeader {
+    pub podcast_id: u64,
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Default)]
+pub struct HeaderList {
+    directory: PathBuf,
+    headers: Vec<PodcastHeader>,
+}
+
+impl HeaderList {
+    /// Loads the headers saved in the app directory. an empty list if none were set yet
+    pub fn load(app_directory: &Path, strict: bool) -> Result<Self, Errors> {
+        let headers = match fs::read(app_directory.join(HEADERS_FILE)) {
+            Ok(contents) => records::read(&contents[..], HEADERS_FILE, strict)?,
+            Err(_error) => Vec::new(),
+        };
+
+        Ok(Self {
+            directory: app_directory.to_path_buf(),
+            headers,
+        })
+    }
+
+    /// The headers of the podcast with the passed id
+    pub fn headers(&self, podcast_id: u64) -> impl Iterator<Item = &PodcastHeader> {
+        self.headers
+            .iter()
+            .filter(move |header| header.podcast_id == podcast_id)
+    }
+
+    /// Sets a header of the podcast, replacing its previous value. header names aren't case
+    /// sensitive
+    pub fn set(&mut self, podcast_id: u64, name: &str, value: &str) {
+        self.unset(podcast_id, name);
+        self.headers.push(PodcastHeader {
+            podcast_id,
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    /// Removes a header of the podcast. returns false if the podcast has no such header
+    pub fn unset(&mut self, podcast_id: u64, name: &str) -> bool {
+        let count = self.headers.len();
+        self.headers
+            .retain(|header| header.podcast_id != podcast_id || !header.name.eq_ignore_ascii_case(name));
+
+        self.headers.len() < count
+    }
+
+    /// The headers of the requests of each passed URL, by the id of the podcast it belongs to.
+    /// URLs of podcasts without headers are left out
+    pub fn by_url<'a, I>(&self, urls: I) -> HashMap<String, Vec<(String, String)>>
+    where
+        I: IntoIterator<Item = (&'a str, u64)>,
+    {
+        urls.into_iter()
+            .filter_map(|(url, podcast_id)| {
+                let headers: Vec<(String, String)> = self
+                    .headers(podcast_id)
+                    .map(|header| (header.name.clone(), header.value.clone()))
+                    .collect();
+                match headers.is_empty() {
+                    true => None,
+                    false => Some((url.to_string(), headers)),
+                }
+            })
+            .collect()
+    }
+
+    pub fn save(&self) -> Result<(), Errors> {
+        let file = FileSystem::new(&self.directory, HEADERS_FILE, vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        for header in self.headers.iter() {
+            writer.serialize(header)?;
+        }
+
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        Ok(file.commit()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn headers_list() {
+        let directory = fixtures::scratch();
+
+        let mut list = HeaderList::load(&directory, false).expect("Can't load headers");
+        list.set(1, "Authorization", "Bearer old");
+        list.set(1, "authorization", "Bearer new");
+        list.set(1, "Cookie", "session=1; theme=dark");
+        list.set(2, "Referer", "https://example.com");
+        list.save().expect("Can't save headers");
+
+        let mut list = HeaderList::load(&directory, false).expect("Can't load headers");
+        assert_eq!(list.headers(1).count(), 2);
+        assert!(list.unset(1, "COOKIE"));
+        assert!(!list.unset(1, "Cookie"));
+
+        let headers = list.by_url(vec![
+            ("https://example.com/rss", 1),
+            ("https://cdn.example.com/1.mp3", 1),
+            ("https://other.com/rss", 3),
+        ]);
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            headers["https://cdn.example.com/1.mp3"],
+            vec![("authorization".to_string(), "Bearer new".to_string())]
+        );
+
+        assert_eq!(user_agent("browser"), BROWSER_USER_AGENT);
+        assert_eq!(user_agent("curl/8.0"), "curl/8.0");
+        assert_eq!(referrer("site", "https://example.com"), "https://example.com");
+        assert_eq!(
+            referrer("https://other.com/", "https://example.com"),
+            "https://other.com/"
+        );
+
+        // A malformed row is skipped, unless strict mode is on
+        let mut contents = fs::read(directory.join(HEADERS_FILE)).unwrap();
+        contents.extend_from_slice(b"two,Cookie,session=2\n");
+        fs::write(directory.join(HEADERS_FILE), contents).unwrap();
+        let list = HeaderList::load(&directory, false).expect("Can't load headers");
+        assert_eq!(list.headers(1).count(), 2);
+        assert!(matches!(
+            HeaderList::load(&directory, true),
+            Err(Errors::InvalidRecord(..))
+        ));
+    }
+}
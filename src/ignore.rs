@@ -0,0 +1,140 @@
+//! Episodes which are never shown or downloaded automatically, e.g cross posted episodes of other
+//! feeds and republished trailers
+use crate::{episodes::Episode, file_system::FileSystem, records, Errors};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const IGNORE_FILE: &str = "ignore_list.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// The guid or the audio URL of a single episode
+    Id,
+    /// A glob pattern which is matched against the guid, the audio URL and the title
+    Pattern,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IgnoreRule {
+    pub kind: RuleKind,
+    pub value: String,
+}
+
+#[derive(Debug, Default)]
+pub struct IgnoreList {
+    directory: PathBuf,
+    rules: Vec<IgnoreRule>,
+    ids: HashSet<String>,
+    patterns: Vec<glob::Pattern>,
+}
+
+impl IgnoreList {
+    /// Loads the ignore list saved in the app directory. an empty list if nothing was ignored yet
+    pub fn load(app_directory: &Path, strict: bool) -> Result<Self, Errors> {
+        let rules: Vec<IgnoreRule> = match fs::read(app_directory.join(IGNORE_FILE)) {
+            Ok(contents) => records::read(&contents[..], IGNORE_FILE, strict)?,
+            Err(_error) => Vec::new(),
+        };
+
+        let mut list = Self {
+            directory: app_directory.to_path_buf(),
+            ..Self::default()
+        };
+        for rule in rules {
+            // Invalid patterns can't be added, so they're skipped if the file was edited by hand
+            let _ = list.insert(rule);
+        }
+
+        Ok(list)
+    }
+
+    fn insert(&mut self, rule: IgnoreRule) -> Result<bool, Errors> {
+        if self.rules.contains(&rule) {
+            return Ok(false);
+        }
+
+        match rule.kind {
+            RuleKind::Id => {
+                self.ids.insert(rule.value.clone());
+            }
+            RuleKind::Pattern => self
+                .patterns
+                .push(glob::Pattern::new(&rule.value).map_err(|error| Errors::InvalidPattern(error.to_string()))?),
+        }
+        self.rules.push(rule);
+
+        Ok(true)
+    }
+
+    /// Adds a rule to the list. returns false if the list already has it
+    pub fn add(&mut self, kind: RuleKind, value: &str) -> Result<bool, Errors> {
+        self.insert(IgnoreRule {
+            kind,
+            value: value.to_string(),
+        })
+    }
+
+    pub fn rules(&self) -> &[IgnoreRule] {
+        &self.rules
+    }
+
+    /// Whether the passed episode matches any of the rules
+    pub fn is_ignored(&self, episode: &Episode) -> bool {
+        if self.ids.contains(&episode.guid) || self.ids.contains(episode.download_url()) {
+            return true;
+        }
+
+        self.patterns.iter().any(|pattern| {
+            pattern.matches(&episode.guid) || pattern.matches(episode.download_url()) || pattern.matches(&episode.title)
+        })
+    }
+
+    pub fn save(&self) -> Result<(), Errors> {
+        let file = FileSystem::new(&self.directory, IGNORE_FILE, vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        for rule in self.rules.iter() {
+            writer.serialize(rule)?;
+        }
+
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        Ok(file.commit()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{episodes::read_episodes, fixtures};
+
+    #[test]
+    fn ignore_list() {
+        let directory = fixtures::scratch();
+
+        let syntax = fixtures::read("syntax.csv").expect("Can't read syntax.csv");
+        let episodes = read_episodes(&syntax[..], true).expect("Can't read syntax.csv");
+
+        let mut list = IgnoreList::load(&directory, false).expect("Can't load ignore list");
+        assert!(!episodes.iter().any(|episode| list.is_ignored(episode)));
+
+        assert!(list.add(RuleKind::Id, "50f59fbe-93db-4acf-b6db-8f9a078d315a").unwrap());
+        assert!(list.add(RuleKind::Pattern, "Potluck*").unwrap());
+        assert!(!list.add(RuleKind::Pattern, "Potluck*").unwrap());
+        assert!(list.add(RuleKind::Pattern, "[").is_err());
+        list.save().expect("Can't save ignore list");
+
+        let list = IgnoreList::load(&directory, false).expect("Can't load ignore list");
+        assert_eq!(list.rules().len(), 2);
+        let ignored: Vec<&str> = episodes
+            .iter()
+            .filter(|episode| list.is_ignored(episode))
+            .map(|episode| episode.guid.as_str())
+            .collect();
+        assert!(ignored.contains(&"50f59fbe-93db-4acf-b6db-8f9a078d315a"));
+        assert!(ignored.contains(&"272eca72-476b-4633-864c-a9fffa3f5976"));
+    }
+}
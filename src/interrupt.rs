@@ -0,0 +1,35 @@
+//! Ctrl-C handling. the first Ctrl-C cancels the transfers in flight, so the finished downloads
+//! are still saved and the episodes files written, and pcasts exits with `EXIT_CODE`. a second
+//! one exits at once
+use signal_hook::{consts::SIGINT, consts::SIGTERM, flag};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, OnceLock,
+};
+
+/// The exit code after an interruption, like the shells use for SIGINT
+pub const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+fn flag() -> &'static Arc<AtomicBool> {
+    INTERRUPTED.get_or_init(|| Arc::new(AtomicBool::new(false)))
+}
+
+/// Handles Ctrl-C and SIGTERM instead of exiting. a failure to install the handler leaves the
+/// default one, which exits at once
+pub fn install() {
+    for signal in [SIGINT, SIGTERM].iter() {
+        // The order matters, the shutdown only happens if the flag was set by an earlier signal
+        let result = flag::register_conditional_shutdown(*signal, EXIT_CODE, Arc::clone(flag()))
+            .and_then(|_id| flag::register(*signal, Arc::clone(flag())));
+        if let Err(error) = result {
+            tracing::debug!("Can't handle the signal {}. {}", signal, error);
+        }
+    }
+}
+
+/// Whether pcasts was asked to stop
+pub fn interrupted() -> bool {
+    flag().load(Ordering::Relaxed)
+}
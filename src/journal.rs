@@ -0,0 +1,160 @@
+//! A write-ahead journal for changes which span several files of the episode store, like an
+//! update which rewrites the episodes of every podcast and then the podcasts list. the new
+//! contents are staged next to the files and the journal records which of them replace which
+//! files, so a process which dies in the middle leaves either the old or the new state
+use crate::{file_system::AtomicFile, Errors};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+pub const JOURNAL_FILE: &str = "journal";
+
+/// The last line of a journal whose changes can be applied
+const COMMIT_MARKER: &str = "commit";
+
+pub struct Journal {
+    path: PathBuf,
+    renames: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Journal {
+    pub fn new(app_directory: &Path) -> Self {
+        Self {
+            path: app_directory.join(JOURNAL_FILE),
+            renames: Vec::new(),
+        }
+    }
+
+    /// Adds the written file to the changes. it replaces its destination only when the journal
+    /// is committed
+    pub fn stage(&mut self, file: AtomicFile) -> Result<(), Errors> {
+        let (temp_path, path) = file.prepare()?;
+        self.renames.push((temp_path, path));
+
+        Ok(())
+    }
+
+    /// Records the staged changes in the journal and applies them
+    pub fn commit(mut self) -> Result<(), Errors> {
+        // From here on the staged files are kept for the recovery if applying them fails
+        let renames = std::mem::take(&mut self.renames);
+        if renames.is_empty() {
+            return Ok(());
+        }
+
+        let mut file = fs::File::create(&self.path)?;
+        for (temp_path, path) in renames.iter() {
+            writeln!(file, "{}\t{}", temp_path.display(), path.display())?;
+        }
+        writeln!(file, "{}", COMMIT_MARKER)?;
+        file.sync_all()?;
+
+        apply(&renames)?;
+        fs::remove_file(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Finishes the changes of a process which died while applying them, or removes the staged
+    /// files of one which died before committing. returns whether there was anything to recover
+    pub fn recover(app_directory: &Path) -> Result<bool, Errors> {
+        let path = app_directory.join(JOURNAL_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(Errors::IO(error)),
+        };
+
+        let lines: Vec<&str> = contents.lines().collect();
+        let committed = lines.last() == Some(&COMMIT_MARKER);
+        let renames: Vec<(PathBuf, PathBuf)> = lines
+            .iter()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(temp_path, path)| (PathBuf::from(temp_path), PathBuf::from(path)))
+            .collect();
+
+        if committed {
+            apply(&renames)?;
+        } else {
+            for (temp_path, _path) in renames.iter() {
+                let _ = fs::remove_file(temp_path);
+            }
+        }
+        fs::remove_file(&path)?;
+
+        Ok(true)
+    }
+}
+
+/// Moves the staged files over their destinations. the ones which were already moved before the
+/// previous process died are skipped
+fn apply(renames: &[(PathBuf, PathBuf)]) -> Result<(), Errors> {
+    for (temp_path, path) in renames {
+        match fs::rename(temp_path, path) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(Errors::IO(error)),
+        }
+    }
+
+    Ok(())
+}
+
+impl Drop for Journal {
+    /// Staged files of a journal which wasn't committed are never applied
+    fn drop(&mut self) {
+        for (temp_path, _path) in self.renames.iter() {
+            let _ = fs::remove_file(temp_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_system::FileSystem;
+    use crate::fixtures;
+
+    #[test]
+    fn journal_recover() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("1"), "old").unwrap();
+        fs::write(directory.join("2"), "old").unwrap();
+
+        let stage = |name: &str| {
+            let mut file = FileSystem::new(&directory, name, vec![]).atomic().unwrap();
+            file.write_all(b"new").unwrap();
+            file.prepare().unwrap()
+        };
+
+        // A process which died after committing the journal and moving only the first file
+        let renames = [stage("1"), stage("2")];
+        fs::rename(&renames[0].0, &renames[0].1).unwrap();
+        let journal: String = renames
+            .iter()
+            .map(|(temp_path, path)| format!("{}\t{}\n", temp_path.display(), path.display()))
+            .collect();
+        fs::write(directory.join(JOURNAL_FILE), format!("{}{}\n", journal, COMMIT_MARKER)).unwrap();
+
+        assert!(Journal::recover(&directory).unwrap());
+        assert_eq!(fs::read_to_string(directory.join("1")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(directory.join("2")).unwrap(), "new");
+        assert!(!Journal::recover(&directory).unwrap());
+
+        // A process which died before committing keeps the old files
+        let (temp_path, path) = stage("1");
+        fs::write(
+            directory.join(JOURNAL_FILE),
+            format!("{}\t{}\n", temp_path.display(), path.display()),
+        )
+        .unwrap();
+        fs::write(&path, "old").unwrap();
+
+        assert!(Journal::recover(&directory).unwrap());
+        assert_eq!(fs::read_to_string(directory.join("1")).unwrap(), "old");
+        assert!(!temp_path.exists());
+    }
+}
@@ -1,24 +1,122 @@
-use clap::{self, App, Arg};
-use csv;
-use reqwest;
-use std::{fmt, io, num, path::PathBuf};
+use clap::{App, AppSettings, Arg, ArgMatches};
+use lock::{LockPolicy, ProcessLock};
+use stats::NetworkStats;
+use std::{collections::HashMap, fmt, io, num, path::PathBuf, sync::Arc, time};
+use timings::Timings;
 
+mod art;
+mod backfill;
+mod backup;
+mod cache;
+mod clips;
+mod color;
+mod compare;
+mod compat;
+mod completions;
 mod consts;
+mod crash;
+mod dates;
+mod debug;
+mod doctor;
 mod episodes;
+mod events;
+mod export;
+mod feed;
 mod file_system;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+mod freshness;
+mod gpodder;
+mod headers;
+mod ignore;
+mod interrupt;
+mod journal;
+mod library;
+mod lock;
+mod logging;
+mod notify;
+mod open;
+mod outcome;
+mod paths;
+mod played;
+mod playlist;
+mod podcast_index;
 mod podcasts;
+mod power;
+mod progress;
+mod qr;
+mod queue;
+mod records;
+mod resolver;
+mod resume;
+mod rules;
+mod schedule;
+mod snapshot;
+mod stats;
+mod sync;
+mod table;
+mod tags;
+mod text;
+mod thumbnail;
+mod timings;
+mod topics;
+mod tui;
+mod units;
+mod variants;
+mod verify;
 mod web;
 
+pub use debug::generate_library;
+pub use episodes::{Episode, PodcastUpdate, UpdateStatus};
+pub use library::Library;
+pub use outcome::FailOn;
+pub use paths::{default_app_directory, default_download_directory};
+pub use podcasts::{Podcast, Subscription};
+pub use progress::{JsonProgress, NoProgress, ProgressSink, TerminalProgress};
+pub use units::{parse_duration, parse_size};
+pub use web::{parse_proxy, Bandwidth, HttpClient, Request, Response};
+
 #[derive(Debug)]
 pub enum Errors {
     RSS,
     WrongID(String),
+    AmbiguousID(String, Vec<String>),
     Parse(num::ParseIntError),
     IO(io::Error),
     CSV(csv::Error),
     Timeout(String),
     NotFound(String),
+    HttpStatus(u16, String),
     Network(reqwest::Error),
+    Interrupted(String),
+    Cancelled,
+    Locked(u32),
+    MissingSnapshot(String),
+    InvalidBackup(String),
+    MissingTopics,
+    InvalidPattern(String),
+    NoMatches,
+    MissingCrashReport,
+    InvalidRules(String),
+    InvalidRecord(u64, String),
+    InvalidSize(String, String),
+    ChecksumMismatch(usize),
+    UnhealthyFeeds(usize),
+    PartialFailure(usize),
+    Tag(String),
+    InvalidDate(String),
+    InvalidDuration(String, String),
+    OutOfTime(String),
+    UnknownVariant(String),
+    TooLongForQr(String),
+    InvalidProxy(String),
+    InvalidHeader(String),
+    UnsupportedClip(String),
+    InvalidSetting(String),
+    NotLoggedIn,
+    MissingPassword,
+    InvalidResponse(String),
+    AppDirectory(String),
 }
 
 impl fmt::Display for Errors {
@@ -26,12 +124,88 @@ impl fmt::Display for Errors {
         match *self {
             Errors::RSS => write!(f, "Couldn't parse RSS feed"),
             Errors::WrongID(ref id) => write!(f, "Invalid ID: {}", id),
+            Errors::AmbiguousID(ref input, ref candidates) => {
+                write!(f, "{} matches {}. Pass one of their IDs:", input, candidates.len())?;
+                for candidate in candidates {
+                    write!(f, "\n  {}", candidate)?;
+                }
+                Ok(())
+            }
             Errors::Parse(ref e) => write!(f, "Couldn't parse string as number: {}", e),
             Errors::IO(ref e) => write!(f, "IO error: {}", e),
             Errors::CSV(ref e) => write!(f, "CSV error: {}", e),
             Errors::Timeout(ref url) => write!(f, "Network timeout for {}", url),
             Errors::NotFound(ref url) => write!(f, "Resource not found {}", url),
+            Errors::HttpStatus(403, ref url) => write!(
+                f,
+                "HTTP 403 from {}. Some hosts only answer browsers, see podcasts headers --agent and --referrer",
+                url
+            ),
+            Errors::HttpStatus(status, ref url) => write!(f, "HTTP {} from {}", status, url),
             Errors::Network(ref e) => write!(f, "Network error {}", e),
+            Errors::Interrupted(ref url) => write!(f, "The download of {} was interrupted", url),
+            Errors::Cancelled => write!(f, "Interrupted before the command finished"),
+            Errors::Locked(pid) => write!(
+                f,
+                "Another pcasts process (PID {}) is modifying the library. Use --on-lock wait to wait for it",
+                pid
+            ),
+            Errors::InvalidPattern(ref e) => write!(f, "Invalid pattern: {}", e),
+            Errors::NoMatches => write!(f, "No downloaded episodes matched"),
+            Errors::MissingCrashReport => write!(f, "No crash reports were found"),
+            Errors::InvalidRules(ref e) => write!(f, "Invalid rules file: {}", e),
+            Errors::InvalidRecord(line, ref e) => write!(f, "Invalid row at line {}: {}", line, e),
+            Errors::InvalidSize(ref size, ref reason) => write!(f, "Invalid size {}. {}", size, reason),
+            Errors::Tag(ref e) => write!(f, "Can't write ID3 tags: {}", e),
+            Errors::InvalidDate(ref date) => write!(f, "Invalid date {}. Use e.g 2020-07-22", date),
+            Errors::InvalidDuration(ref duration, ref reason) => write!(f, "Invalid duration {}. {}", duration, reason),
+            Errors::OutOfTime(ref url) => write!(f, "Out of time while downloading {}", url),
+            Errors::UnknownVariant(ref name) => write!(f, "No variant named {}", name),
+            Errors::TooLongForQr(ref url) => write!(f, "{} is too long for a QR code", url),
+            Errors::InvalidProxy(ref url) => write!(f, "Invalid proxy URL {}. Use e.g http://proxy:8080", url),
+            Errors::InvalidHeader(ref header) => {
+                write!(f, "Invalid header {}. Use e.g \"Authorization: Bearer token\"", header)
+            }
+            Errors::InvalidSetting(ref setting) => write!(f, "Invalid setting {}", setting),
+            Errors::UnsupportedClip(ref extension) => {
+                write!(f, "Can't cut soundbites out of {} episodes, only out of MP3", extension)
+            }
+            Errors::NotLoggedIn => write!(f, "Not logged in to a gpodder server. Use gpodder login first"),
+            Errors::MissingPassword => write!(
+                f,
+                "No password for the gpodder server. Pass --password to gpodder login or set PODCASTS_GPODDER_PASSWORD"
+            ),
+            Errors::InvalidResponse(ref url) => write!(f, "Unexpected response from {}", url),
+            Errors::AppDirectory(ref path) => write!(
+                f,
+                "{} is the app directory, writing there would replace the library. Pass another --out",
+                path
+            ),
+            Errors::ChecksumMismatch(count) => write!(f, "{} downloaded files don't match the manifest", count),
+            Errors::UnhealthyFeeds(count) => write!(f, "{} feeds can't be updated", count),
+            Errors::PartialFailure(count) => write!(
+                f,
+                "{} of the feeds and downloads failed. Use --summary for the details",
+                count
+            ),
+            Errors::MissingSnapshot(ref url) => {
+                write!(f, "No saved snapshot for {}. Run episodes update first", url)
+            }
+            Errors::InvalidBackup(ref path) => write!(f, "{} isn't a backup of a pcasts library", path),
+            Errors::MissingTopics => write!(
+                f,
+                "The topics of the episodes weren't found yet. Run episodes topics first"
+            ),
+        }
+    }
+}
+
+impl Errors {
+    /// The exit code of pcasts when a command fails with the error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Errors::Cancelled => interrupt::EXIT_CODE,
+            _ => 1,
         }
     }
 }
@@ -49,6 +223,7 @@ impl From<file_system::FileSystemErrors> for Errors {
             file_system::FileSystemErrors::CreateDirectory(e) => Errors::IO(e),
             file_system::FileSystemErrors::Rename(e) => Errors::IO(e),
             file_system::FileSystemErrors::Remove(e) => Errors::IO(e),
+            file_system::FileSystemErrors::Sync(e) => Errors::IO(e),
         }
     }
 }
@@ -65,10 +240,42 @@ impl From<std::num::ParseIntError> for Errors {
     }
 }
 
+/// The names of the downloaded files when no template is configured
+pub const DEFAULT_FILE_TEMPLATE: &str = "{podcast}_{title}.{ext}";
+
+/// The template of `episodes download --sequential-names`, which sorts the files of a season in
+/// the order they're meant to be heard
+pub const SEQUENTIAL_FILE_TEMPLATE: &str = "Season {season}/{episode} - {title}.{ext}";
+
 #[derive(Debug)]
 pub struct Config {
     app_directory: PathBuf,
     download_directory: PathBuf,
+    timings: Timings,
+    network: NetworkStats,
+    file_template: String,
+    // The download directories and file templates which podcasts override with podcasts --set
+    podcast_directories: HashMap<u64, PathBuf>,
+    file_templates: HashMap<u64, String>,
+    jobs: usize,
+    strict: bool,
+    limit_rate: Option<u64>,
+    tag: bool,
+    notify: bool,
+    safe_mode: bool,
+    snapshots: usize,
+    min_battery: u8,
+    deadline: Option<time::Instant>,
+    profile: String,
+    bandwidth: web::Bandwidth,
+    max_size: Option<u64>,
+    http: web::ClientOptions,
+    podcast_index: Option<podcast_index::Credentials>,
+    // Used instead of the password saved by gpodder login
+    gpodder_password: Option<String>,
+    progress: Arc<dyn ProgressSink>,
+    outcome: outcome::Outcome,
+    fail_on: FailOn,
 }
 
 impl Config {
@@ -76,6 +283,207 @@ impl Config {
         Self {
             app_directory,
             download_directory,
+            timings: Timings::new(),
+            network: NetworkStats::new(),
+            file_template: DEFAULT_FILE_TEMPLATE.to_string(),
+            podcast_directories: HashMap::new(),
+            file_templates: HashMap::new(),
+            jobs: web::DEFAULT_JOBS,
+            strict: false,
+            limit_rate: None,
+            tag: false,
+            notify: false,
+            safe_mode: false,
+            snapshots: snapshot::DEFAULT_SNAPSHOTS,
+            min_battery: 0,
+            deadline: None,
+            profile: variants::DEFAULT_PROFILE.to_string(),
+            bandwidth: web::Bandwidth::Unmetered,
+            max_size: None,
+            http: web::ClientOptions::default(),
+            podcast_index: None,
+            gpodder_password: None,
+            progress: Arc::new(TerminalProgress::new()),
+            outcome: outcome::Outcome::new(),
+            fail_on: FailOn::None,
+        }
+    }
+
+    /// Makes the commands fail when items of their batches fail, e.g a feed an update can't
+    /// fetch. by default only the errors which stop the whole command do
+    pub fn with_fail_on(mut self, fail_on: FailOn) -> Self {
+        self.fail_on = fail_on;
+        self
+    }
+
+    /// Reports the progress of the downloads to the passed sink instead of drawing progress bars
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressSink>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Sends all the requests with the passed client instead of reqwest, e.g to serve them from
+    /// recorded responses
+    pub fn with_http_client(mut self, client: Arc<dyn HttpClient>) -> Self {
+        self.http.client = Some(client);
+        self
+    }
+
+    /// Sends all the requests through the proxy with the passed URL
+    pub fn with_proxy(mut self, proxy: String) -> Self {
+        self.http.proxy = Some(proxy);
+        self
+    }
+
+    /// Sets the User-Agent header of all the requests, for hosts which block the default one
+    pub fn with_user_agent(mut self, user_agent: String) -> Self {
+        self.http.user_agent = user_agent;
+        self
+    }
+
+    /// Sends the passed password to the gpodder server instead of the one saved by gpodder login
+    pub fn with_gpodder_password(mut self, password: String) -> Self {
+        self.gpodder_password = Some(password);
+        self
+    }
+
+    /// Looks up the podcasts with enrichment turned on in the Podcast Index with the passed
+    /// credentials
+    pub fn with_podcast_index(mut self, key: String, secret: String) -> Self {
+        self.podcast_index = Some(podcast_index::Credentials { key, secret });
+        self
+    }
+
+    /// Sets the number of daily snapshots of the saved podcasts and episodes which are kept. 0
+    /// disables them
+    pub fn with_snapshots(mut self, snapshots: usize) -> Self {
+        self.snapshots = snapshots;
+        self
+    }
+
+    /// Writes the ID3 tags of the downloaded MP3 files from the feed of their podcast
+    pub fn with_tag(mut self, tag: bool) -> Self {
+        self.tag = tag;
+        self
+    }
+
+    /// Skips the extensions, i.e the ID3 tags, the artwork embedded in them, the Podcast Index
+    /// lookups and the notifications, to tell a bug of pcasts from one of an extension
+    pub fn with_safe_mode(mut self, safe_mode: bool) -> Self {
+        self.safe_mode = safe_mode;
+        self
+    }
+
+    /// Shows a desktop notification when updates find new episodes
+    pub fn with_notify(mut self, notify: bool) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    /// Makes sync defer the downloads while the machine runs on a battery charged below the passed
+    /// percent. 0 downloads regardless of the power source
+    pub fn with_min_battery(mut self, min_battery: u8) -> Self {
+        self.min_battery = min_battery;
+        self
+    }
+
+    /// Sets the profile of the machine, which picks the preferred variants of the podcasts
+    pub fn with_profile(mut self, profile: String) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    /// Picks the smallest alternate enclosures of the episodes on metered connections
+    pub fn with_bandwidth(mut self, bandwidth: web::Bandwidth) -> Self {
+        self.bandwidth = bandwidth;
+        self
+    }
+
+    /// Picks the largest alternate enclosures of the episodes which aren't larger than the passed
+    /// number of bytes
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Limits the combined speed of the episode downloads, in bytes per second
+    pub fn with_limit_rate(mut self, limit_rate: u64) -> Self {
+        self.limit_rate = Some(limit_rate);
+        self
+    }
+
+    /// Fails on rows of the saved podcasts and episodes which don't match the expected columns,
+    /// instead of skipping them
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Sets the number of simultaneous feed fetches and episode downloads
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Sets the template of the downloaded files' names. the placeholders {podcast}, {title},
+    /// {pub_date}, {guid}, {number} and {ext} are replaced by the values of each episode. a "/"
+    /// in the template puts the files in subdirectories
+    pub fn with_file_template(mut self, file_template: String) -> Self {
+        self.file_template = file_template;
+        self
+    }
+
+    /// Uses the download directories and file templates which the passed podcasts set instead of
+    /// the global ones
+    fn set_podcast_settings(&mut self, podcasts: &[Podcast]) {
+        for podcast in podcasts {
+            if !podcast.download_directory.is_empty() {
+                self.podcast_directories
+                    .insert(podcast.id, PathBuf::from(&podcast.download_directory));
+            }
+            if !podcast.file_template.is_empty() {
+                self.file_templates.insert(podcast.id, podcast.file_template.clone());
+            }
+        }
+    }
+
+    /// The directory which holds the downloaded episodes of a podcast
+    fn podcast_directory<T>(&self, podcast_id: T) -> PathBuf
+    where
+        T: fmt::Display,
+    {
+        let podcast_id = podcast_id.to_string();
+        let directory = podcast_id
+            .parse::<u64>()
+            .ok()
+            .and_then(|id| self.podcast_directories.get(&id));
+
+        match directory {
+            Some(directory) => directory.clone(),
+            None => self.download_directory.join(podcast_id),
+        }
+    }
+
+    /// The directories where pcasts leaves its temporary files, the app directory and the ones
+    /// which hold the downloads of the saved podcasts. the rest of the download directory isn't
+    /// included, it may be shared with other programs
+    fn temporary_directories(&self) -> Result<Vec<PathBuf>, Errors> {
+        let mut directories = vec![self.app_directory.clone()];
+        for podcast in podcasts::load_podcasts(self)? {
+            let directory = self.podcast_directory(podcast.id);
+            if !directories.contains(&directory) {
+                directories.push(directory);
+            }
+        }
+
+        Ok(directories)
+    }
+
+    /// The template of the names of the downloaded episodes of a podcast
+    fn podcast_file_template(&self, podcast_id: u64) -> &str {
+        match self.file_templates.get(&podcast_id) {
+            Some(file_template) => file_template,
+            None => &self.file_template,
         }
     }
 }
@@ -91,7 +499,165 @@ impl ApplicationBuilder {
         let app = App::new("pcasts")
             .version("1.0.0")
             .author("Dmitry S. <dimashur@gmail.com>")
-            .about("CLI util for downloading podcasts");
+            .about("CLI util for downloading podcasts")
+            .arg(
+                // What to do when another pcasts process is already modifying the saved state
+                Arg::with_name("on-lock")
+                    .help("Wait for or fail when another pcasts process holds the lock")
+                    .long("--on-lock")
+                    .takes_value(true)
+                    .possible_values(["wait", "fail"])
+                    .default_value("fail")
+                    .global(true),
+            )
+            .arg(
+                // Bounds commands which are run from cron. downloads which didn't finish when the
+                // time is up are left for the next run, which resumes the ones that started
+                Arg::with_name("max-time")
+                    .help("Stop the downloads after this long, e.g 90s, 10m or 1h")
+                    .long("--max-time")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_JOBS environment variable. slow connections do better
+                // with a single download at a time
+                Arg::with_name("jobs")
+                    .help("Number of simultaneous feed fetches and episode downloads")
+                    .short('j')
+                    .long("--jobs")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_STRICT environment variable. by default invalid rows
+                // of the saved files are skipped
+                Arg::with_name("strict")
+                    .help("Fail on invalid rows of the saved podcasts and episodes")
+                    .long("--strict")
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_BANDWIDTH environment variable. only matters for the
+                // episodes whose feed offers alternate enclosures
+                Arg::with_name("bandwidth")
+                    .help("Kind of the connection, metered downloads the smallest versions of the episodes")
+                    .long("--bandwidth")
+                    .takes_value(true)
+                    .possible_values(["wifi", "metered"])
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_MAX_SIZE environment variable
+                Arg::with_name("max-size")
+                    .help("Download the largest version of the episodes up to this size, e.g 50M")
+                    .long("--max-size")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_PROFILE environment variable. each profile, e.g a phone
+                // or a car, downloads its preferred variant of the podcasts
+                Arg::with_name("profile")
+                    .help("Name of the profile which picks the preferred variants of the podcasts")
+                    .long("--profile")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_HTTP_PROXY environment variable
+                Arg::with_name("proxy")
+                    .help("URL of a proxy for all the requests, e.g http://proxy:8080")
+                    .long("--proxy")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_USER_AGENT environment variable. some hosts block
+                // clients which don't look like a browser or a podcast app
+                Arg::with_name("user-agent")
+                    .help("User-Agent header of all the requests")
+                    .long("--user-agent")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_NOTIFY environment variable. meant for syncs which run
+                // unattended, e.g from a systemd timer
+                Arg::with_name("notify")
+                    .help("Show a desktop notification when updates find new episodes")
+                    .long("--notify")
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_SAFE_MODE environment variable. the crash reports suggest
+                // it when an extension panics
+                Arg::with_name("safe-mode")
+                    .help("Skip the ID3 tags, the Podcast Index lookups and the notifications")
+                    .long("--safe-mode")
+                    .global(true),
+            )
+            .arg(
+                // json writes an object per line to stderr, for programs which run pcasts
+                Arg::with_name("progress")
+                    .help("How to show the progress of the downloads")
+                    .long("--progress")
+                    .takes_value(true)
+                    .possible_values(["bars", "json", "none"])
+                    .global(true),
+            )
+            .arg(
+                // -v shows what pcasts does, -vv also every request and skipped feed item
+                Arg::with_name("verbose")
+                    .help("Log more details to stderr, repeat for even more")
+                    .short('v')
+                    .long("--verbose")
+                    .multiple_occurrences(true)
+                    .global(true),
+            )
+            .arg(
+                // Also hides the progress bars, unless --progress is passed
+                Arg::with_name("quiet")
+                    .help("Log only errors")
+                    .short('q')
+                    .long("--quiet")
+                    .conflicts_with("verbose")
+                    .global(true),
+            )
+            .arg(
+                // auto colors only the output to a terminal, and nothing when NO_COLOR is set
+                Arg::with_name("color")
+                    .help("When to color the output")
+                    .long("--color")
+                    .takes_value(true)
+                    .possible_values(["auto", "always", "never"])
+                    .default_value("auto")
+                    .global(true),
+            )
+            .arg(
+                // Overrides the PODCASTS_FAIL_ON environment variable
+                Arg::with_name("fail-on")
+                    .help("Which failed feeds and downloads make the command fail")
+                    .long("--fail-on")
+                    .takes_value(true)
+                    .possible_values(["any", "network", "none"])
+                    .global(true),
+            )
+            .arg(
+                // Written even when the command fails, - writes it to stdout
+                Arg::with_name("summary")
+                    .help("Write what succeeded and failed as JSON to this file")
+                    .long("--summary")
+                    .takes_value(true)
+                    .global(true),
+            )
+            .arg(
+                // Prints how long the major phases of the command took once it's done
+                Arg::with_name("timings")
+                    .help("Print a timing breakdown of the command")
+                    .long("--timings")
+                    .global(true),
+            );
 
         Self {
             config,
@@ -106,16 +672,31 @@ impl ApplicationBuilder {
                 .arg(
                     // Lists all the previously added podcasts with the add command
                     Arg::with_name("list")
-                        .about("Show a list of previously added RSS feeds")
+                        .help("Show a list of previously added RSS feeds")
                         .short('l')
                         .long("--list")
                         .conflicts_with_all(&["add", "remove"]),
                 )
+                .arg(
+                    // One line per podcast in columns, cut to the width of the terminal
+                    Arg::with_name("format")
+                        .help("Show the listed podcasts as full records or as a table")
+                        .long("--format")
+                        .takes_value(true)
+                        .possible_values(["full", "table"])
+                        .default_value("full"),
+                )
+                .arg(
+                    Arg::with_name("art")
+                        .help("Show the path of the downloaded artwork of each podcast")
+                        .long("--art")
+                        .requires("list"),
+                )
                 .arg(
                     // Adds a new podcasts with the provided RSS feed. doesn't do anything if the
                     // podcast already exists in the list
                     Arg::with_name("add")
-                        .about("Add new RSS feed")
+                        .help("Add new RSS feed or the number of a search result")
                         .short('a')
                         .long("--add")
                         .takes_value(true)
@@ -123,14 +704,257 @@ impl ApplicationBuilder {
                         .conflicts_with_all(&["list", "remove"]),
                 )
                 .arg(
-                    // Removes a previously added podcast from the list of saved podcasts
+                    // Removes a previously added podcast from the list of saved podcasts. the
+                    // value is its ID, its RSS URL or a part of its title
                     Arg::with_name("remove")
-                        .about("Remove an existing RSS feed")
+                        .help("Remove a podcast by ID, RSS URL or title")
+                        .value_name(completions::PODCAST_ID)
                         .short('r')
                         .long("--remove")
                         .takes_value(true)
                         .multiple(true)
                         .conflicts_with_all(&["list", "add"]),
+                )
+                .arg(
+                    // The alias is shown instead of the title of the feed and names the downloads.
+                    // an empty alias goes back to the title
+                    Arg::with_name("rename")
+                        .help("Give the podcast with the ID a shorter name")
+                        .long("--rename")
+                        .takes_value(true)
+                        .number_of_values(2)
+                        .value_names(&["id", "alias"])
+                        .conflicts_with_all(&["list", "add", "remove"]),
+                )
+                .arg(
+                    // Settings stored with the podcast, which the downloads of episodes download
+                    // and sync use instead of the global ones
+                    Arg::with_name("set")
+                        .help("Change settings of the podcast with the ID: download-dir, file-template, keep, auto-download or tags")
+                        .long("--set")
+                        .takes_value(true)
+                        .multiple(true)
+                        .min_values(2)
+                        .conflicts_with_all(&["list", "add", "remove", "rename"]),
+                )
+                .arg(
+                    Arg::with_name("purge")
+                        .help("Also delete the saved episodes and the downloads of the removed podcasts")
+                        .long("--purge")
+                        .requires("remove"),
+                )
+                .arg(
+                    // Tags group the podcasts, e.g to sync only the news in the morning
+                    Arg::with_name("tag")
+                        .help("Tag the added podcasts, or list only the podcasts with the tag")
+                        .long("--tag")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .takes_value(true),
+                )
+                .arg(
+                    // Fetches every feed and reports the ones which are gone, moved or broken,
+                    // instead of leaving them to fail quietly on every update
+                    Arg::with_name("check")
+                        .help("Check that the feed of every podcast still works")
+                        .long("--check")
+                        .conflicts_with_all(&["list", "add", "remove", "rename"]),
+                )
+                .arg(
+                    // Searches the iTunes directory. the results can be added by their number
+                    // with the add command
+                    Arg::with_name("search")
+                        .help("Search for podcasts by name")
+                        .short('s')
+                        .long("--search")
+                        .takes_value(true)
+                        .conflicts_with_all(&["list", "add", "remove"]),
+                )
+                .arg(
+                    // Search responses are cached for a day to avoid the rate limits of the API
+                    Arg::with_name("no-cache")
+                        .help("Ignore the cached search results")
+                        .long("--no-cache")
+                        .requires("search"),
+                )
+                .subcommand(
+                    // Goes over podcasts without new episodes in a year, without downloads or
+                    // whose feed keeps failing, and asks whether to pause, remove or keep each one
+                    App::new("cleanup").about("Interactively pause or remove stale podcasts"),
+                )
+                .subcommand(
+                    // Daily shows are rarely listened to after a few days. the downloads older
+                    // than the window are removed after every update and download
+                    App::new("window")
+                        .about("Keep only the downloads of the last days of a podcast")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("days")
+                                .help("Number of days to keep, or e.g 2w. 0 keeps all the downloads")
+                                .index(2)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    // Overrides the --count of the sync command for a single podcast
+                    App::new("sync-count")
+                        .about("Set the number of the newest episodes sync downloads for a podcast")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("count")
+                                .help("Number of episodes. 0 uses the --count of sync")
+                                .index(2)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    // Applied by sync after downloading, and by `episodes prune` without --keep
+                    App::new("keep")
+                        .about("Set the number of the newest downloads kept on disk for a podcast")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("count")
+                                .help("Number of downloads. 0 keeps all of them")
+                                .index(2)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    App::new("tag")
+                        .about("Add tags to a podcast or remove them")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("tags")
+                                .help("Tags, e.g tech or news")
+                                .index(2)
+                                .multiple(true)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("remove")
+                                .help("Remove the tags instead of adding them")
+                                .long("--remove"),
+                        ),
+                )
+                .subcommand(
+                    // Needs the credentials of a Podcast Index account in PODCASTS_INDEX_KEY and
+                    // PODCASTS_INDEX_SECRET
+                    App::new("enrich")
+                        .about("Fill in the durations, chapters and transcripts of a podcast from the Podcast Index")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("off")
+                                .help("Take the episodes from the feed only again")
+                                .long("--off"),
+                        ),
+                )
+                .subcommand(
+                    // When sync or the TUI download the episodes of several podcasts, the
+                    // podcasts take turns and each turn downloads this many of their episodes
+                    App::new("priority")
+                        .about("Set the share of a podcast in downloads which span several podcasts")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("weight")
+                                .help("Number of episodes per turn. Defaults to 1")
+                                .index(2)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    // Shows which publish a feed per language or quality are subscribed to once.
+                    // without arguments lists the variants, marking the one of the current profile
+                    App::new("variants")
+                        .about("Manage the alternative feeds of a podcast and which one this profile downloads")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("add")
+                                .help("Name of a new variant")
+                                .long("--add")
+                                .takes_value(true)
+                                .requires("url")
+                                .conflicts_with_all(&["remove", "prefer"]),
+                        )
+                        .arg(
+                            Arg::with_name("url")
+                                .help("RSS URL of the new variant")
+                                .long("--url")
+                                .takes_value(true)
+                                .requires("add"),
+                        )
+                        .arg(
+                            Arg::with_name("remove")
+                                .help("Name of the variant to remove")
+                                .long("--remove")
+                                .takes_value(true)
+                                .conflicts_with("prefer"),
+                        )
+                        .arg(
+                            // "main" is the feed the podcast was added with
+                            Arg::with_name("prefer")
+                                .help("Name of the variant the current --profile downloads")
+                                .long("--prefer")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Sent with the requests of the feed and of the episodes, e.g for feeds which
+                    // need credentials. without arguments lists the headers of the podcast
+                    App::new("headers")
+                        .about("Manage the extra HTTP headers of the requests of a podcast")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("set")
+                                .help("Header to send, e.g \"Authorization: Bearer token\"")
+                                .long("--set")
+                                .multiple(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("unset")
+                                .help("Name of a header to stop sending")
+                                .long("--unset")
+                                .multiple(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // Some CDNs reject the requests of anything but a browser
+                            Arg::with_name("agent")
+                                .help("User-Agent of the podcast's requests, or browser for the one of a browser")
+                                .long("--agent")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("referrer")
+                                .help("Referer of the podcast's requests, or site for the podcast's website")
+                                .long("--referrer")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Scanned with a phone to subscribe to the podcast, or to stream an episode
+                    App::new("qr")
+                        .about("Show the RSS URL of a podcast, or the URL of an episode, as a QR code")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("episode-id")
+                                .help("ID or short ID of an episode to stream instead")
+                                .long("--episode-id")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    App::new("info")
+                        .about("Show the details of a podcast with a preview of its artwork")
+                        .arg(Arg::with_name("id").help("Id of the podcast").value_name(completions::PODCAST_ID).index(1).required(true))
+                        .arg(
+                            Arg::with_name("art")
+                                .help("How to draw the artwork. auto picks the protocol of the terminal")
+                                .long("--art")
+                                .takes_value(true)
+                                .possible_values(["auto", "kitty", "iterm", "sixel", "ascii", "none"])
+                                .default_value("auto"),
+                        ),
                 ),
         );
 
@@ -148,23 +972,103 @@ impl ApplicationBuilder {
                             // The id of the podcast for which we want to list the episodes. if not
                             // provided, lists the episodes of all the podcasts
                             Arg::with_name("id")
-                                .about("Id of the podcast to list")
+                                .help("Id of the podcast to list")
+                                .value_name(completions::PODCAST_ID)
                                 .long("--id")
                                 .takes_value(true)
                                 .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("tag")
+                                .help("List only the episodes of the podcasts with the tag")
+                                .long("--tag")
+                                .takes_value(true)
+                                .conflicts_with("id"),
+                        )
+                        .arg(
+                            // Uses the time of the last update of each podcast, so it shows what
+                            // changed even after the episodes were listed
+                            Arg::with_name("new")
+                                .help("List only the episodes found by the last update")
+                                .long("--new"),
+                        )
+                        .arg(
+                            Arg::with_name("match")
+                                .help("List only the episodes whose title contains the text. accents and case are ignored")
+                                .long("--match")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // The topics are found by episodes topics
+                            Arg::with_name("topic")
+                                .help("List only the episodes about the topic, e.g rust")
+                                .long("--topic")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("since")
+                                .help("List only the episodes released on or after the date, e.g 2020-07-22")
+                                .long("--since")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("until")
+                                .help("List only the episodes released on or before the date")
+                                .long("--until")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("count")
+                                .help("Number of the newest matching episodes to list for each podcast")
+                                .long("--count")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("sort")
+                                .help("Order of the listed episodes")
+                                .long("--sort")
+                                .takes_value(true)
+                                .possible_values(["date", "title", "duration"])
+                                .default_value("date"),
+                        )
+                        .arg(
+                            Arg::with_name("reverse")
+                                .help("List the episodes in the reverse order")
+                                .long("--reverse"),
+                        )
+                        .arg(
+                            // One line per episode in columns, cut to the width of the terminal
+                            Arg::with_name("format")
+                                .help("Show the listed episodes as full records or as a table")
+                                .long("--format")
+                                .takes_value(true)
+                                .possible_values(["full", "table"])
+                                .default_value("full"),
+                        )
+                        .arg(
+                            // json writes one object per episode and line
+                            Arg::with_name("output")
+                                .help("Output format")
+                                .long("--output")
+                                .takes_value(true)
+                                .possible_values(["text", "json"])
+                                .default_value("text"),
                         ),
                 )
                 .subcommand(
                     // Updates the list of episodes for the podcast
-                    App::new("update").arg(
-                        // The id of the podcast for which we wish to update the list of existing
-                        // episodes
-                        Arg::with_name("id")
-                            .about("ID of the podcast to update")
-                            .long("--id")
-                            .multiple(true)
-                            .takes_value(true),
-                    ),
+                    App::new("update")
+                        .about("Update episodes. By default updates the episodes of all the podcasts")
+                        .arg(
+                            // The id of the podcast for which we wish to update the list of existing
+                            // episodes. if not provided, updates all the podcasts
+                            Arg::with_name("id")
+                                .help("ID of the podcast to update")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .multiple(true)
+                                .takes_value(true),
+                        ),
                 )
                 .subcommand(
                     // Download episodes for a particular podcast
@@ -172,63 +1076,1126 @@ impl ApplicationBuilder {
                         .arg(
                             // The id of the podcast for which we wish to download a new episode.
                             Arg::with_name("id")
-                                .about("ID of the podcast")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
                                 .long("--id")
-                                .required(true)
+                                .required_unless_one(["podcast-tag", "resume"])
                                 .takes_value(true),
                         )
+                        .arg(
+                            // Not --tag, which writes the ID3 tags of the downloaded files
+                            Arg::with_name("podcast-tag")
+                                .help("Download the episodes of all the podcasts with the tag")
+                                .long("--podcast-tag")
+                                .takes_value(true)
+                                .conflicts_with_all(&["id", "episode-id", "list"]),
+                        )
                         .arg(
                             // The ids of the episodes we wish to download. if not provided, downloads
                             // all the existing episodes for the podcast
                             Arg::with_name("episode-id")
-                                .about("IDs of the episodes to download")
+                                .help("IDs or short IDs of the episodes to download")
                                 .long("--episode-id")
                                 .multiple(true)
                                 .takes_value(true),
                         )
+                        .arg(
+                            // Large files from hosts which support ranged requests are split into
+                            // this many parallel requests
+                            Arg::with_name("chunks")
+                                .help("Number of parallel connections for each large file")
+                                .long("--chunks")
+                                .takes_value(true)
+                                .default_value("1"),
+                        )
+                        .arg(
+                            // Overrides the PODCASTS_LIMIT_RATE environment variable. the limit is
+                            // shared by all the parallel downloads
+                            Arg::with_name("limit-rate")
+                                .help("Maximum download speed, e.g 500k or 2M bytes per second")
+                                .long("--limit-rate")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // The queue of the downloads is kept in the app directory
+                            Arg::with_name("resume")
+                                .help("Download the episodes the last downloads requested and didn't complete")
+                                .long("--resume")
+                                .conflicts_with_all(&["id", "podcast-tag", "episode-id", "count", "list", "season"]),
+                        )
+                        .arg(
+                            // The partial files are kept next to the downloads, named like them
+                            // with ".part" appended
+                            Arg::with_name("discard-partial")
+                                .help("Remove what was received of interrupted downloads instead of resuming from it")
+                                .long("--discard-partial"),
+                        )
+                        .arg(
+                            // Overrides the PODCASTS_TAG environment variable
+                            Arg::with_name("tag")
+                                .help("Write the title, podcast, date, number and artwork of the episodes into their ID3 tags")
+                                .long("--tag"),
+                        )
                         .arg(
                             // The number of episodes to download if no episode id's were provided
                             Arg::with_name("count")
-                                .about("Number of episodes to download starting from the most recent one")
+                                .help("Number of episodes to download starting from the most recent one")
                                 .long("--count")
                                 .conflicts_with("episode-id")
                                 .takes_value(true),
                         )
                         .arg(
-                            // The list of downloaded episodes for a particular podcast
-                            Arg::with_name("list")
-                                .about("List the downloaded episodes of the provided podcast")
-                                .short('l')
-                                .long("--list")
-                                .conflicts_with("episode-id"),
-                        ),
-                ),
-        );
-
-        self
-    }
-
-    pub fn build(self) -> Application {
-        let app = self.app.clone().subcommands(self.subcommands);
-
-        Application::new(self.config, app)
-    }
-}
-
-#[derive(Debug)]
-pub struct Application {
-    app: App<'static>,
-    config: Config,
+                            Arg::with_name("latest")
+                                .help("Download only the newest episode")
+                                .long("--latest")
+                                .conflicts_with_all(&["episode-id", "count", "list"]),
+                        )
+                        .arg(
+                            // Episodes without a date can't be placed after it and aren't downloaded
+                            Arg::with_name("since")
+                                .help("Download the episodes released on or after the date, e.g 2024-06-01")
+                                .long("--since")
+                                .takes_value(true)
+                                .conflicts_with_all(&["episode-id", "list"]),
+                        )
+                        .arg(
+                            // More than a few matches are listed and confirmed before downloading
+                            Arg::with_name("match")
+                                .help("Download the episodes whose title contains this text")
+                                .long("--match")
+                                .takes_value(true)
+                                .conflicts_with_all(&["episode-id", "podcast-tag", "list"]),
+                        )
+                        .arg(
+                            Arg::with_name("regex")
+                                .help("Treat the match as a case insensitive regular expression")
+                                .long("--regex")
+                                .requires("match"),
+                        )
+                        .arg(
+                            Arg::with_name("yes")
+                                .help("Download all the matching episodes without asking")
+                                .short('y')
+                                .long("--yes")
+                                .requires("match"),
+                        )
+                        .arg(
+                            // Feeds which don't number their seasons have no episodes in any season
+                            Arg::with_name("season")
+                                .help("Download only the episodes of the season")
+                                .long("--season")
+                                .conflicts_with("episode-id")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // For shows which are meant to be heard from the start, like fiction
+                            // released a season at a time. a playlist of the season is written too
+                            Arg::with_name("ordered")
+                                .help("Download the episodes from the first one of the season, and write a playlist of them in that order")
+                                .long("--ordered")
+                                .conflicts_with_all(&["episode-id", "list"]),
+                        )
+                        .arg(
+                            Arg::with_name("sequential-names")
+                                .help("Name the files by their season and episode number, e.g \"Season 02/01 - Title.mp3\". implies --ordered")
+                                .long("--sequential-names")
+                                .conflicts_with_all(&["episode-id", "list"]),
+                        )
+                        .arg(
+                            Arg::with_name("sort")
+                                .help("Order of the listed episodes")
+                                .long("--sort")
+                                .takes_value(true)
+                                .possible_values(["date", "title", "duration"])
+                                .default_value("date"),
+                        )
+                        .arg(
+                            Arg::with_name("reverse")
+                                .help("List the episodes in the reverse order")
+                                .long("--reverse"),
+                        )
+                        .arg(
+                            // One line per episode in columns, cut to the width of the terminal
+                            Arg::with_name("format")
+                                .help("Show the listed episodes as full records or as a table")
+                                .long("--format")
+                                .takes_value(true)
+                                .possible_values(["full", "table"])
+                                .default_value("full"),
+                        )
+                        .arg(
+                            // The list of downloaded episodes for a particular podcast
+                            Arg::with_name("list")
+                                .help("List the downloaded episodes of the provided podcast")
+                                .short('l')
+                                .long("--list")
+                                .conflicts_with("episode-id"),
+                        ),
+                )
+                .subcommand(
+                    // Summarizes the downloaded episodes which are still waiting to be listened to
+                    App::new("backlog").about("Show the unlistened episodes and hours left of each podcast"),
+                )
+                .subcommand(
+                    // Searches the saved episodes, so it works offline and finds old episodes
+                    // which are no longer in the feeds
+                    App::new("search")
+                        .about("Search the titles and show notes of the saved episodes")
+                        .arg(
+                            Arg::with_name("query")
+                                .help("Text to search for. accents and case are ignored")
+                                .index(1)
+                                .required_unless("person"),
+                        )
+                        .arg(
+                            // Searches the episodes of all the podcasts if not provided
+                            Arg::with_name("id")
+                                .help("IDs of the podcasts to search")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .multiple(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("regex")
+                                .help("Treat the query as a case insensitive regular expression")
+                                .long("--regex"),
+                        )
+                        .arg(
+                            // From the <podcast:person> tags of the feeds
+                            Arg::with_name("person")
+                                .help("Only the episodes with a host or guest of this name")
+                                .long("--person")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Without a name lists everyone with their number of episodes
+                    App::new("people")
+                        .about("List the hosts and guests of the saved episodes, or the episodes of one of them")
+                        .arg(
+                            Arg::with_name("name")
+                                .help("Name of the person. accents and case are ignored")
+                                .index(1),
+                        )
+                        .arg(
+                            Arg::with_name("id")
+                                .help("IDs of the podcasts to list")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .multiple(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Extracting needs the episode to be downloaded first
+                    App::new("soundbites")
+                        .about("List the highlights of an episode from its feed, or cut them out of the download")
+                        .arg(
+                            Arg::with_name("guid")
+                                .help("ID of the episode")
+                                .index(1)
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("extract")
+                                .help("Save each soundbite as an MP3 file next to the downloaded episode")
+                                .long("--extract"),
+                        ),
+                )
+                .subcommand(
+                    // Shows the show notes of an episode before it's downloaded
+                    App::new("show")
+                        .about("Show the details and show notes of an episode")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("episode-id")
+                                .help("ID or short ID of the episode")
+                                .long("--episode-id")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Writes a playlist of the downloaded episodes of a podcast, oldest first
+                    App::new("playlist")
+                        .about("Write a playlist of the downloaded episodes of a podcast")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("count")
+                                .help("Number of the most recent downloaded episodes to include")
+                                .long("--count")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("format")
+                                .help("Format of the playlist")
+                                .long("--format")
+                                .takes_value(true)
+                                .possible_values(["m3u", "xspf"])
+                                .default_value("m3u"),
+                        )
+                        .arg(
+                            // Defaults to a file named after the podcast in the download directory
+                            Arg::with_name("output")
+                                .help("Path of the playlist file")
+                                .long("--output")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Delete downloaded episodes of a particular podcast
+                    App::new("remove")
+                        .about("Remove downloaded episodes")
+                        .arg(
+                            // The id of the podcast whose downloaded episodes we wish to remove
+                            Arg::with_name("id")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("episode-id")
+                                .help("IDs or short IDs of the episodes to remove")
+                                .long("--episode-id")
+                                .multiple(true)
+                                .takes_value(true)
+                                .required_unless("name"),
+                        )
+                        .arg(
+                            // A glob pattern which is matched against the names of the downloaded files
+                            Arg::with_name("name")
+                                .help("Remove the episodes whose file name matches the glob pattern")
+                                .long("--name")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // Only print the files which would have been removed
+                            Arg::with_name("dry-run")
+                                .help("Show what would be removed without removing anything")
+                                .long("--dry-run"),
+                        ),
+                )
+                .subcommand(
+                    // Removes the oldest downloads of a podcast, keeping the newest ones. the
+                    // removed episodes aren't downloaded again by sync
+                    App::new("prune")
+                        .about("Remove the oldest downloaded episodes of a podcast")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("keep")
+                                .help("Number of the newest downloads to keep. Defaults to the keep setting of the podcast")
+                                .long("--keep")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("move-to")
+                                .help("Move the pruned files to this directory instead of deleting them")
+                                .long("--move-to")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .help("Show what would be pruned without changing anything")
+                                .long("--dry-run"),
+                        ),
+                )
+                .subcommand(
+                    // Ignored episodes don't show up in list, backlog and the automatic downloads
+                    App::new("ignore")
+                        .about("Ignore episodes of all the podcasts")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("Guids or audio URLs of the episodes to ignore")
+                                .multiple(true)
+                                .required_unless_one(["pattern", "list"]),
+                        )
+                        .arg(
+                            // Matched against the guid, the audio URL and the title of each episode
+                            Arg::with_name("pattern")
+                                .help("Ignore the episodes which match the glob pattern")
+                                .long("--pattern")
+                                .multiple(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("list")
+                                .help("Show the ignored episodes and patterns")
+                                .long("--list")
+                                .conflicts_with_all(&["id", "pattern"]),
+                        ),
+                )
+                .subcommand(
+                    // The whole library is analyzed each time. nothing leaves the machine, other
+                    // than the requests for the transcripts
+                    App::new("topics")
+                        .about("Find the topics of the episodes, for episodes list --topic")
+                        .arg(
+                            Arg::with_name("transcripts")
+                                .help("Fetch the transcripts of the episodes which have one and analyze them too")
+                                .long("--transcripts"),
+                        ),
+                )
+                .subcommand(
+                    // Only asks the servers when the feeds changed, so it's fast enough for a
+                    // shell prompt. nothing is saved, the next update fetches the feeds
+                    App::new("check")
+                        .about("Check whether the feeds likely have new episodes, without updating them")
+                        .arg(
+                            Arg::with_name("podcast")
+                                .help("IDs of the podcasts. Defaults to all the podcasts which aren't paused")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--podcast")
+                                .multiple(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Played episodes are left out of the backlog and aren't downloaded by sync.
+                    // with a gpodder account the marks are sent to the other devices by gpodder sync
+                    App::new("played")
+                        .about("Mark episodes as played")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("ID of the podcast")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("episode-id")
+                                .help("IDs or short IDs of the played episodes")
+                                .index(1)
+                                .multiple(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    // The same as the top level verify, for the downloads of a single podcast
+                    App::new("verify")
+                        .about("Find the downloaded episodes which are truncated or corrupted")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("ID of the podcast. Defaults to all the podcasts")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("repair")
+                                .help("Download the mismatched and truncated files again")
+                                .long("--repair"),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn debug_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("debug")
+                .about("Tools for diagnosing problems with feeds and the saved data")
+                .subcommand(
+                    // Compares the current remote feed with the snapshot saved by the last update
+                    App::new("diff-feed")
+                        .about("Show the items which changed since the last update of a feed")
+                        .arg(
+                            Arg::with_name("url")
+                                .help("RSS URL of a saved podcast")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    // Writes a synthetic library which can be used for measuring performance
+                    App::new("gen-library")
+                        .about("Generate a large synthetic library")
+                        .arg(
+                            Arg::with_name("podcasts")
+                                .help("Number of podcasts to generate")
+                                .long("--podcasts")
+                                .takes_value(true)
+                                .default_value("200"),
+                        )
+                        .arg(
+                            Arg::with_name("episodes")
+                                .help("Number of episodes to generate for each podcast")
+                                .long("--episodes")
+                                .takes_value(true)
+                                .default_value("1000"),
+                        )
+                        .arg(
+                            // Never the app directory, so that the real library isn't overwritten
+                            Arg::with_name("out")
+                                .help("Directory to write the library to")
+                                .long("--out")
+                                .takes_value(true)
+                                .default_value("generated-library"),
+                        ),
+                )
+                .subcommand(
+                    // Prints the latest crash report in a form which can be pasted into an issue
+                    App::new("report")
+                        .about("Bundle the latest crash report for an issue")
+                        .arg(
+                            Arg::with_name("out")
+                                .help("File to write the bundled report to instead of stdout")
+                                .long("--out")
+                                .takes_value(true),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn open_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("open")
+                .about("Open a directory, file or web page with the default application")
+                .subcommand(App::new("downloads").about("Open the download directory"))
+                .subcommand(App::new("config").about("Open the directory which holds the saved data"))
+                .subcommand(
+                    App::new("podcast").about("Open the web page of a podcast").arg(
+                        Arg::with_name("id")
+                            .help("ID of the podcast")
+                            .value_name(completions::PODCAST_ID)
+                            .required(true)
+                            .takes_value(true),
+                    ),
+                )
+                .subcommand(
+                    // Opens the downloaded file if it exists, otherwise the episode's web page
+                    App::new("episode")
+                        .about("Open a downloaded episode or its web page")
+                        .arg(
+                            Arg::with_name("guid")
+                                .help("ID of the episode")
+                                .required(true)
+                                .takes_value(true),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn export_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("export")
+                .about("Export the saved episodes for other tools")
+                .subcommand(
+                    // For exploring travel and history podcasts on a map. only the episodes whose
+                    // feed has <podcast:location> coordinates are exported
+                    App::new("geojson")
+                        .about("Export the episodes which have a location as GeoJSON")
+                        .arg(
+                            Arg::with_name("id")
+                                .help("IDs of the podcasts to export. defaults to all of them")
+                                .value_name(completions::PODCAST_ID)
+                                .long("--id")
+                                .multiple(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("output")
+                                .help("Path of the GeoJSON file. printed if not provided")
+                                .long("--output")
+                                .short('o')
+                                .takes_value(true),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn stats_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("stats")
+                .about("Statistics collected across runs")
+                .subcommand(
+                    // The per host statistics are also used to choose the number of parallel downloads
+                    App::new("network").about("Show the latency and download speed of each host"),
+                )
+                .subcommand(
+                    App::new("library")
+                        .about("Count the subscriptions, new episodes, downloads and removals")
+                        .arg(
+                            Arg::with_name("since")
+                                .help("Only count the events of this long ago or later, e.g 30d")
+                                .long("--since")
+                                .takes_value(true),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn config_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("config")
+                .about("Share the rules and policies of the library")
+                .subcommand(
+                    // Writes the ignore list and the download policies of the podcasts, without
+                    // the podcasts and episodes themselves
+                    App::new("export-rules")
+                        .about("Export the ignore list and the podcast policies to a TOML file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("Path of the rules file")
+                                .index(1)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    // Adds the ignore rules and applies the policies of podcasts which are
+                    // already subscribed to
+                    App::new("import-rules")
+                        .about("Import the ignore list and the podcast policies from a TOML file")
+                        .arg(
+                            Arg::with_name("file")
+                                .help("Path of the rules file")
+                                .index(1)
+                                .required(true),
+                        ),
+                ),
+        );
+
+        self
+    }
+
+    pub fn doctor_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Rows which can't be read are skipped by the other commands, which shows up as
+            // missing podcasts or episodes
+            App::new("doctor")
+                .about("Find the malformed rows of the saved podcasts and episodes")
+                .arg(
+                    Arg::with_name("fix")
+                        .help("Remove the malformed rows. the original files are kept with a .bak extension")
+                        .long("--fix"),
+                )
+                .arg(
+                    // Also done after each command which changes the saved podcasts or episodes,
+                    // with the default age
+                    Arg::with_name("clean-temp")
+                        .help(
+                            "Remove the stale partial downloads and temporary files, and the empty podcast directories",
+                        )
+                        .long("--clean-temp"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only remove the temporary files older than this, e.g 2d. Defaults to a week")
+                        .long("--older-than")
+                        .takes_value(true)
+                        .requires("clean-temp"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn verify_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // The checksums are recorded in the manifest when the episodes are downloaded. the
+            // files are hashed in parallel, limited by the global --jobs
+            App::new("verify")
+                .about("Compare the downloaded episodes with the checksums recorded when they were downloaded")
+                .arg(
+                    Arg::with_name("id")
+                        .help("ID of the podcast. Defaults to all the podcasts")
+                        .value_name(completions::PODCAST_ID)
+                        .long("--id")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("repair")
+                        .help("Download the mismatched and truncated files again")
+                        .long("--repair"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn backup_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Restored with pcasts restore <file>, e.g on another machine
+            App::new("backup")
+                .about("Save the podcasts, episodes, settings and downloads to a compressed archive")
+                .arg(
+                    Arg::with_name("out")
+                        .help("Path of the archive, e.g library.tar.gz")
+                        .long("--out")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("exclude-audio")
+                        .help("Leave out the downloaded episodes")
+                        .long("--exclude-audio"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn restore_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // A snapshot is saved before the first command of each day which changes the podcasts
+            // or episodes
+            App::new("restore")
+                .about("Restore the podcasts and episodes from a daily snapshot or a backup")
+                .arg(
+                    Arg::with_name("snapshot")
+                        .help("Date of the snapshot, e.g 2020-07-22")
+                        .long("--snapshot")
+                        .takes_value(true)
+                        .required_unless_one(["list", "file"]),
+                )
+                .arg(
+                    // Replaces the whole library, and adds the downloaded episodes of the backup
+                    Arg::with_name("file")
+                        .help("Path of a backup saved with pcasts backup")
+                        .index(1)
+                        .conflicts_with_all(&["snapshot", "list"]),
+                )
+                .arg(
+                    Arg::with_name("list")
+                        .help("List the dates of the saved snapshots")
+                        .long("--list")
+                        .conflicts_with("snapshot"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn log_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("log")
+                .about("Show what happened to the library, oldest first")
+                .arg(
+                    Arg::with_name("since")
+                        .help("Only the events of this long ago or later, e.g 7d or 12h")
+                        .long("--since")
+                        .takes_value(true),
+                )
+                .arg(
+                    // json writes one object per event and line
+                    Arg::with_name("output")
+                        .help("Output format")
+                        .long("--output")
+                        .takes_value(true)
+                        .possible_values(["text", "json"])
+                        .default_value("text"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn completions_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // e.g `source <(pcasts completions bash)` in ~/.bashrc
+            App::new("completions")
+                .about("Print the shell completion script for pcasts")
+                .arg(
+                    Arg::with_name("shell")
+                        .help("Shell to complete in")
+                        .index(1)
+                        .required(true)
+                        .possible_values(["bash", "zsh", "fish"]),
+                ),
+        );
+        // Lists the IDs of the podcasts for the completion scripts
+        self.subcommands
+            .push(App::new(completions::COMPLETE_IDS).setting(AppSettings::Hidden));
+
+        self
+    }
+
+    pub fn backfill_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Episodes saved by older versions lack the fields which were added since. updates
+            // only rewrite the episodes which are still in the feed
+            App::new("backfill")
+                .about("Fill in the metadata the saved episodes lack by fetching their feeds again")
+                .arg(
+                    Arg::with_name("fields")
+                        .help("Fields to fill in, separated by commas")
+                        .long("--fields")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .possible_values(["duration", "description", "artwork"])
+                        .default_value("duration,description,artwork"),
+                )
+                .arg(
+                    Arg::with_name("id")
+                        .help("ID of the podcast to backfill. backfills all the podcasts if not provided")
+                        .value_name(completions::PODCAST_ID)
+                        .long("--id")
+                        .multiple(true)
+                        .takes_value(true),
+                ),
+        );
+
+        self
+    }
+
+    pub fn continue_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Resumes the playback saved by gpodder sync or the episodes played here. the default
+            // application starts from the beginning, so the position is printed
+            App::new("continue")
+                .about("Open the episode which was left in the middle most recently, downloading it if needed"),
+        );
+
+        self
+    }
+
+    pub fn compare_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            App::new("compare")
+                .about("Compare the release cadence, episode length, people and topics of two podcasts")
+                .arg(
+                    Arg::with_name("first")
+                        .help("Id of the podcast to compare")
+                        .value_name(completions::PODCAST_ID)
+                        .index(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("second")
+                        .help("Id of the podcast to compare it with")
+                        .value_name(completions::PODCAST_ID)
+                        .index(2)
+                        .required(true),
+                ),
+        );
+
+        self
+    }
+
+    pub fn gpodder_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Works with gpodder.net and the servers which implement its API, e.g nextcloud-gpodder.
+            // the account is saved in gpodder.csv in the app directory
+            App::new("gpodder")
+                .about("Sync the subscriptions and played episodes with a gpodder server")
+                .subcommand(
+                    App::new("login")
+                        .about("Register this device with a gpodder account")
+                        .arg(
+                            Arg::with_name("server")
+                                .help("URL of the server, e.g https://gpodder.net")
+                                .long("--server")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("username")
+                                .help("Name of the account")
+                                .long("--username")
+                                .required(true)
+                                .takes_value(true),
+                        )
+                        .arg(
+                            // Without it the password is read from PODCASTS_GPODDER_PASSWORD on
+                            // each sync and isn't saved
+                            Arg::with_name("password")
+                                .help("Password of the account. Saved in the app directory")
+                                .long("--password")
+                                .takes_value(true),
+                        )
+                        .arg(
+                            Arg::with_name("device")
+                                .help("ID of this device in the account. Defaults to pcasts")
+                                .long("--device")
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(App::new("logout").about("Forget the gpodder account"))
+                .subcommand(
+                    App::new("sync")
+                        .about("Exchange the subscription changes and played episodes with the other devices"),
+                ),
+        );
+
+        self
+    }
+
+    pub fn tui_subcommand(mut self) -> Self {
+        self.subcommands
+            .push(App::new("tui").about("Browse the podcasts and episodes and download episodes interactively"));
+
+        self
+    }
+
+    pub fn sync_subcommand(mut self) -> Self {
+        self.subcommands.push(
+            // Updates all the podcasts which aren't paused and downloads their newest episodes.
+            // meant to be run periodically, e.g from cron
+            App::new("sync")
+                .about("Update all the podcasts and download their newest episodes")
+                .arg(
+                    Arg::with_name("count")
+                        .help("Number of the newest episodes to keep downloaded for each podcast")
+                        .short('c')
+                        .long("--count")
+                        .takes_value(true)
+                        .default_value("1"),
+                )
+                .arg(
+                    // Overrides the PODCASTS_MIN_BATTERY environment variable
+                    Arg::with_name("min-battery")
+                        .help("Defer the downloads while on battery below this percent")
+                        .long("--min-battery")
+                        .takes_value(true),
+                )
+                .arg(
+                    // Used for the podcasts with a keep setting, see `podcasts keep`
+                    Arg::with_name("move-to")
+                        .help("Move the pruned downloads to this directory instead of deleting them")
+                        .long("--move-to")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Sync only the podcasts with the tag")
+                        .long("--tag")
+                        .takes_value(true),
+                ),
+        );
+
+        self
+    }
+
+    pub fn build(self) -> Application {
+        let app = self.app.clone().subcommands(self.subcommands);
+
+        Application::new(self.config, app)
+    }
+}
+
+#[derive(Debug)]
+pub struct Application {
+    app: App<'static>,
+    config: Config,
+    // Whether the output should end with "Done". not for the output shells read
+    reports_done: bool,
 }
 
 impl Application {
     pub fn new(config: Config, app: App<'static>) -> Self {
-        Self { config, app }
+        Self {
+            config,
+            app,
+            reports_done: true,
+        }
+    }
+
+    /// Whether "Done" should be printed once `run` returns
+    pub fn reports_done(&self) -> bool {
+        self.reports_done
     }
 
     pub fn run(&mut self) -> Result<(), Errors> {
-        let matches = self.app.get_matches_mut();
+        crash::install(&self.config.app_directory, &self.config.timings);
+        interrupt::install();
+
+        let matches = self
+            .app
+            .try_get_matches_from_mut(compat::args(&self.config.app_directory))
+            .unwrap_or_else(|error| error.exit());
+        if let Some(name) = matches.subcommand_name() {
+            crash::log(format!("Running {}", name));
+        }
+        self.reports_done = !matches!(
+            matches.subcommand_name(),
+            Some("completions") | Some(completions::COMPLETE_IDS)
+        );
+        let color = color::ColorChoice::parse(matches.value_of("color").unwrap_or("auto"));
+        colored::control::set_override(color.stdout());
+        logging::init(
+            logging::level(matches.occurrences_of("verbose"), matches.is_present("quiet")),
+            color.stderr(),
+        );
+        if matches.is_present("strict") {
+            self.config.strict = true;
+        }
+        if matches.is_present("notify") {
+            self.config.notify = true;
+        }
+        if matches.is_present("safe-mode") {
+            self.config.safe_mode = true;
+        }
+        // The settings of each podcast apply to every command. a library which can't be read
+        // fails in the command instead
+        if let Ok(contents) = std::fs::read(self.config.app_directory.join("podcast_list.csv")) {
+            let podcasts = podcasts::read_podcasts(&contents[..], false).unwrap_or_default();
+            self.config.set_podcast_settings(&podcasts);
+        }
+        let sequential_names = matches
+            .subcommand_matches("episodes")
+            .and_then(|matches| matches.subcommand_matches("download"))
+            .is_some_and(|matches| matches.is_present("sequential-names"));
+        if sequential_names {
+            self.config.file_template = SEQUENTIAL_FILE_TEMPLATE.to_string();
+            self.config.file_templates.clear();
+        }
+        match matches.value_of("progress") {
+            Some("json") => self.config.progress = Arc::new(JsonProgress::new(io::stderr())),
+            Some("none") => self.config.progress = Arc::new(NoProgress),
+            Some(_bars) => {}
+            None if matches.is_present("quiet") => self.config.progress = Arc::new(NoProgress),
+            None => {}
+        }
+        if let Some(jobs) = matches.value_of("jobs") {
+            self.config.jobs = jobs.parse::<usize>()?.max(1);
+        }
+        if let Some(bandwidth) = matches.value_of("bandwidth").and_then(web::Bandwidth::parse) {
+            self.config.bandwidth = bandwidth;
+        }
+        if let Some(max_size) = matches.value_of("max-size") {
+            self.config.max_size = Some(parse_size(max_size)?);
+        }
+        if let Some(profile) = matches.value_of("profile") {
+            self.config.profile = profile.to_string();
+        }
+        if let Some(proxy) = matches.value_of("proxy") {
+            self.config.http.proxy = Some(parse_proxy(proxy)?);
+        }
+        if let Some(user_agent) = matches.value_of("user-agent") {
+            self.config.http.user_agent = user_agent.to_string();
+        }
+        if let Some(max_time) = matches.value_of("max-time") {
+            self.config.deadline = Some(time::Instant::now() + parse_duration(max_time)?);
+        }
+        if let Some(fail_on) = matches.value_of("fail-on").and_then(FailOn::parse) {
+            self.config.fail_on = fail_on;
+        }
+
+        // The network statistics are only an optimization, so failing to read or write them isn't an
+        // error, except for the malformed rows strict mode fails on
+        if let Err(error @ Errors::InvalidRecord(..)) =
+            self.config.network.load(&self.config.app_directory, self.config.strict)
+        {
+            return Err(error);
+        }
+        let result = self.run_command(&matches);
+
+        if matches.is_present("timings") {
+            let writer = io::stderr();
+            let writer = writer.lock();
+            self.config.timings.report(writer)?;
+        }
+
+        if let Some(path) = matches.value_of("summary") {
+            let writer: Box<dyn io::Write> = match path {
+                "-" => Box::new(io::stdout()),
+                path => Box::new(std::fs::File::create(path)?),
+            };
+            let command = matches.subcommand_name().unwrap_or("");
+            self.config
+                .outcome
+                .report(command, self.config.fail_on, result.as_ref().err(), writer)?;
+        }
+
+        // The command finished what it was doing, but not all of it
+        if interrupt::interrupted() {
+            return Err(Errors::Cancelled);
+        }
+        result?;
+        match self.config.outcome.failed(self.config.fail_on) {
+            0 => Ok(()),
+            failed => Err(Errors::PartialFailure(failed)),
+        }
+    }
+
+    fn run_command(&self, matches: &ArgMatches) -> Result<(), Errors> {
+        // Only one process at a time is allowed to modify the saved podcasts and episodes
+        let lock = if Self::mutates_state(matches) {
+            let policy = match matches.value_of("on-lock") {
+                Some("wait") => LockPolicy::Wait,
+                _ => LockPolicy::Fail,
+            };
+            let lock = ProcessLock::acquire(&self.config.app_directory, policy)?;
 
+            if journal::Journal::recover(&self.config.app_directory)? {
+                tracing::warn!("Recovered the changes of a pcasts process which didn't finish");
+            }
+            match episodes::migrate_flat_downloads(&self.config) {
+                Ok(0) => {}
+                Ok(moved) => tracing::warn!(
+                    "Moved {} downloaded episodes to the directories of their podcasts",
+                    moved
+                ),
+                Err(error) => tracing::warn!(
+                    "Can't move the downloaded episodes to the directories of their podcasts. {}",
+                    error
+                ),
+            }
+            // A missing snapshot shouldn't prevent the command from running
+            if let Err(error) = snapshot::rotate(&self.config.app_directory, self.config.snapshots) {
+                tracing::warn!("Can't save a snapshot of the podcasts. {}", error);
+            }
+            Some(lock)
+        } else {
+            None
+        };
+
+        let result = self.dispatch(matches);
+        // While the lock is still held, so no other process is writing the files
+        let lock = match lock {
+            Some(lock) => {
+                self.clean_up();
+                Some(lock)
+            }
+            // The commands which don't take the lock take it only to save the network statistics,
+            // and don't save them if another process holds it
+            None if !self.config.network.is_empty() => {
+                ProcessLock::acquire(&self.config.app_directory, LockPolicy::Fail).ok()
+            }
+            None => None,
+        };
+        if lock.is_some() {
+            let _ = self.config.network.save(&self.config.app_directory);
+        }
+
+        result
+    }
+
+    /// Runs the passed sub command
+    fn dispatch(&self, matches: &ArgMatches) -> Result<(), Errors> {
         if let Some(matches) = matches.subcommand_matches("podcasts") {
             return podcasts::Podcasts::new(matches, &self.config).run();
         }
@@ -237,6 +2204,185 @@ impl Application {
             return episodes::Episodes::new(matches, &self.config).run();
         }
 
+        if let Some(matches) = matches.subcommand_matches("debug") {
+            return debug::Debugger::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("open") {
+            return open::Opener::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("config") {
+            return rules::Configurator::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("doctor") {
+            return doctor::Doctor::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("sync") {
+            return sync::Syncer::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("stats") {
+            return stats::Stats::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("export") {
+            return export::Exporter::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("verify") {
+            return verify::Verifier::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("restore") {
+            return snapshot::Restorer::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("backup") {
+            return backup::Backup::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("tui") {
+            return tui::Browser::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("log") {
+            return events::Logger::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("compare") {
+            return compare::Comparer::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("gpodder") {
+            return gpodder::Gpodder::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("backfill") {
+            return backfill::Backfiller::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("continue") {
+            return resume::Continuer::new(matches, &self.config).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("completions") {
+            return completions::Completions::new(matches, &self.config, &self.app).run();
+        }
+
+        if let Some(matches) = matches.subcommand_matches(completions::COMPLETE_IDS) {
+            return completions::Completions::new(matches, &self.config, &self.app).ids();
+        }
+
         Ok(())
     }
+
+    /// Removes the temporary files left behind by the processes which didn't finish. failing to
+    /// isn't an error of the command
+    fn clean_up(&self) {
+        let directories = match self.config.temporary_directories() {
+            Ok(directories) => directories,
+            Err(error) => {
+                tracing::warn!("Can't remove the temporary files. {}", error);
+                return;
+            }
+        };
+
+        for directory in &directories {
+            if let Err(error) = file_system::remove_stale_files(directory, file_system::STALE_AGE) {
+                tracing::warn!("Can't remove the temporary files in {}. {}", directory.display(), error);
+            }
+        }
+    }
+
+    /// Whether the passed command writes to the app or download directories
+    fn mutates_state(matches: &ArgMatches) -> bool {
+        if let Some(matches) = matches.subcommand_matches("podcasts") {
+            return matches.is_present("add")
+                || matches.is_present("remove")
+                || matches.is_present("rename")
+                || matches.is_present("set")
+                || matches.subcommand_matches("cleanup").is_some()
+                || matches.subcommand_matches("window").is_some()
+                || matches.subcommand_matches("sync-count").is_some()
+                || matches.subcommand_matches("keep").is_some()
+                || matches.subcommand_matches("priority").is_some()
+                || matches.subcommand_matches("tag").is_some()
+                || matches.subcommand_matches("enrich").is_some()
+                || matches.subcommand_matches("variants").is_some_and(|matches| {
+                    matches.is_present("add") || matches.is_present("remove") || matches.is_present("prefer")
+                })
+                || matches.subcommand_matches("headers").is_some_and(|matches| {
+                    ["set", "unset", "agent", "referrer"]
+                        .iter()
+                        .any(|change| matches.is_present(change))
+                });
+        }
+
+        if matches.subcommand_matches("sync").is_some()
+            || matches.subcommand_matches("tui").is_some()
+            || matches.subcommand_matches("backfill").is_some()
+            || matches.subcommand_matches("gpodder").is_some()
+            || matches.subcommand_matches("continue").is_some()
+        {
+            return true;
+        }
+
+        if let Some(matches) = matches.subcommand_matches("config") {
+            return matches.subcommand_matches("import-rules").is_some();
+        }
+
+        if let Some(matches) = matches.subcommand_matches("doctor") {
+            return matches.is_present("fix") || matches.is_present("clean-temp");
+        }
+
+        if let Some(matches) = matches.subcommand_matches("restore") {
+            return !matches.is_present("list");
+        }
+
+        if let Some(matches) = matches.subcommand_matches("verify") {
+            return matches.is_present("repair");
+        }
+
+        if let Some(matches) = matches.subcommand_matches("episodes") {
+            if matches.subcommand_matches("update").is_some()
+                || matches.subcommand_matches("played").is_some()
+                || matches.subcommand_matches("topics").is_some()
+            {
+                return true;
+            }
+
+            if let Some(matches) = matches.subcommand_matches("download") {
+                return !matches.is_present("list");
+            }
+
+            if let Some(matches) = matches.subcommand_matches("remove") {
+                return !matches.is_present("dry-run");
+            }
+
+            if let Some(matches) = matches.subcommand_matches("prune") {
+                return !matches.is_present("dry-run");
+            }
+
+            if let Some(matches) = matches.subcommand_matches("ignore") {
+                return !matches.is_present("list");
+            }
+
+            if let Some(matches) = matches.subcommand_matches("verify") {
+                return matches.is_present("repair");
+            }
+
+            if matches.subcommand_matches("playlist").is_some() {
+                return true;
+            }
+
+            if let Some(matches) = matches.subcommand_matches("soundbites") {
+                return matches.is_present("extract");
+            }
+        }
+
+        false
+    }
 }
@@ -0,0 +1,164 @@
+//! The operations of pcasts for programs which embed it. they return what happened instead of
+//! printing it, and take the same `Config` as the CLI
+use crate::{
+    episodes::{Episode, Episodes, PodcastUpdate},
+    events::{self, Event, EventKind},
+    file_system::{FilePermissions, FileSystem},
+    journal::Journal,
+    lock::{LockPolicy, ProcessLock},
+    podcasts::{load_podcasts, subscribe, write_podcasts, Podcast, Subscription},
+    Config, Errors,
+};
+use clap::ArgMatches;
+use std::{collections::HashSet, io::Read, path::PathBuf};
+
+pub struct Library {
+    config: Config,
+    // The commands read their options from the matches, so the library passes none
+    matches: ArgMatches,
+}
+
+impl Library {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            matches: ArgMatches::default(),
+        }
+    }
+
+    /// Takes the lock the CLI takes before modifying the library. fails while a pcasts process holds it
+    fn lock(&self) -> Result<ProcessLock, Errors> {
+        let lock = ProcessLock::acquire(&self.config.app_directory, LockPolicy::Fail)?;
+        Journal::recover(&self.config.app_directory)?;
+
+        Ok(lock)
+    }
+
+    /// The saved podcasts, in the order they were added
+    pub fn podcasts(&self) -> Result<Vec<Podcast>, Errors> {
+        load_podcasts(&self.config)
+    }
+
+    /// The saved episodes of the podcast with the passed id, the newest first
+    pub fn episodes(&self, podcast_id: u64) -> Result<Vec<Episode>, Errors> {
+        Episodes::new(&self.matches, &self.config).podcast_episodes(podcast_id)
+    }
+
+    /// Fetches the feeds of the passed RSS URLs and saves their podcasts with the passed tags.
+    /// returns the podcast or the error of each URL which wasn't saved yet
+    pub fn add_podcasts(&self, rss_urls: &[&str], tags: &[&str]) -> Result<Vec<Subscription>, Errors> {
+        let _lock = self.lock()?;
+        let mut contents = String::new();
+        FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?
+        .read_to_string(&mut contents)?;
+
+        let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        let results = subscribe(&self.config, rss_urls, tags, contents.as_bytes(), &mut writer_file)?;
+        writer_file.commit()?;
+
+        Ok(results)
+    }
+
+    /// Fetches the feed of the passed RSS URL and saves its podcast. returns the saved podcast
+    /// when it was added before
+    pub fn add_podcast(&self, rss_url: &str) -> Result<Podcast, Errors> {
+        match self.add_podcasts(&[rss_url], &[])?.pop() {
+            Some((_url, podcast)) => podcast,
+            None => self
+                .podcasts()?
+                .into_iter()
+                .find(|podcast| podcast.rss_url == rss_url.trim())
+                .ok_or_else(|| Errors::WrongID(rss_url.to_string())),
+        }
+    }
+
+    /// Removes the podcast with the passed id. its saved episodes and downloads are kept
+    pub fn remove_podcast(&self, podcast_id: u64) -> Result<Podcast, Errors> {
+        let _lock = self.lock()?;
+        let podcasts = self.podcasts()?;
+        let removed = podcasts
+            .iter()
+            .find(|podcast| podcast.id == podcast_id)
+            .cloned()
+            .ok_or_else(|| Errors::WrongID(podcast_id.to_string()))?;
+
+        let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        write_podcasts(
+            podcasts.iter().filter(|podcast| podcast.id != podcast_id),
+            &mut writer_file,
+        )?;
+        writer_file.commit()?;
+        events::record(
+            &self.config.app_directory,
+            &[Event::new(EventKind::Unsubscribed, removed.id, "", removed.name())],
+        )?;
+
+        Ok(removed)
+    }
+
+    /// Fetches the feeds of the podcasts with the passed ids, or of all the podcasts which aren't
+    /// paused, and saves their episodes. returns the outcome of each podcast
+    pub fn update(&self, podcast_ids: Option<&[u64]>) -> Result<Vec<PodcastUpdate>, Errors> {
+        let _lock = self.lock()?;
+        let ids: Option<HashSet<u64>> = podcast_ids.map(|ids| ids.iter().copied().collect());
+
+        Episodes::new(&self.matches, &self.config).refresh(ids.as_ref())
+    }
+
+    /// Downloads the passed episodes, which may belong to several podcasts. returns each
+    /// downloaded episode with the path of its file, the failed ones are in the outcome
+    pub fn download_episodes(&self, episodes: Vec<Episode>) -> Result<Vec<(Episode, PathBuf)>, Errors> {
+        let _lock = self.lock()?;
+        let downloaded = Episodes::new(&self.matches, &self.config).download_batch(episodes)?;
+
+        Ok(downloaded
+            .into_iter()
+            .filter_map(|(episode, file_name)| {
+                let path = self.config.podcast_directory(episode.podcast_id).join(file_name.ok()?);
+                Some((episode, path))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn library_podcasts() {
+        let library = Library::new(fixtures::config());
+
+        let results = library
+            .add_podcasts(
+                &["https://feed.syntax.fm/rss", "https://example.com/missing.xml"],
+                &["tech"],
+            )
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        let podcast = results[0].1.as_ref().unwrap();
+        assert_eq!(podcast.title, "Syntax - Tasty Web Development Treats");
+        assert!(podcast.has_tag("tech"));
+        assert!(results[1].1.is_err());
+
+        // Adding it again returns the saved podcast
+        let id = library.add_podcast("https://feed.syntax.fm/rss").unwrap().id;
+        assert_eq!(id, podcast.id);
+        assert_eq!(library.podcasts().unwrap().len(), 1);
+
+        let updates = library.update(None).unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].podcast.last_update, crate::dates::now());
+        assert!(!library.episodes(id).unwrap().is_empty());
+
+        assert_eq!(library.remove_podcast(id).unwrap().id, id);
+        assert!(library.podcasts().unwrap().is_empty());
+        assert!(library.remove_podcast(id).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::Errors;
+use fs2::FileExt;
+use std::{
+    fs,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    process,
+};
+
+pub const LOCK_FILE: &str = "pcasts.lock";
+
+/// What to do when another process already holds the lock. pcasts has no daemon which could run
+/// the work of the later commands, so they can't be queued and either wait for the lock or fail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockPolicy {
+    Wait,
+    Fail,
+}
+
+/// An advisory lock on a file in the app directory, held by the process that is allowed to mutate
+/// the saved state. the file holds its PID for the messages of the others. the system releases
+/// the lock when the process exits, even when it's killed, so a lock is never left behind. the
+/// file itself is never removed, since a process which opened it before the removal would lock a
+/// file the next ones don't see
+#[derive(Debug)]
+pub struct ProcessLock {
+    file: fs::File,
+}
+
+impl ProcessLock {
+    pub fn acquire(directory: &Path, policy: LockPolicy) -> Result<Self, Errors> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(LOCK_FILE);
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => {}
+            Err(error) if error.raw_os_error() == fs2::lock_contended_error().raw_os_error() => {
+                let pid = Self::holder(&path);
+                if policy == LockPolicy::Fail {
+                    return Err(Errors::Locked(pid));
+                }
+
+                tracing::warn!("Waiting for another pcasts process (PID {}) to finish", pid);
+                file.lock_exclusive()?;
+            }
+            Err(error) => return Err(Errors::IO(error)),
+        }
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", process::id())?;
+        Ok(Self { file })
+    }
+
+    /// Reads the PID stored in the lock file. returns 0 if it's unreadable, e.g the holder didn't
+    /// write it yet
+    fn holder(path: &Path) -> u32 {
+        let mut contents = String::new();
+        if let Ok(mut file) = fs::File::open(path) {
+            let _ = file.read_to_string(&mut contents);
+        }
+
+        contents.trim().parse::<u32>().unwrap_or(0)
+    }
+}
+
+impl Drop for ProcessLock {
+    fn drop(&mut self) {
+        // The PID of a process which exited isn't shown to the next ones. the lock is released
+        // when the file is closed
+        let _ = self.file.set_len(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+    };
+
+    #[test]
+    fn lock_fails_when_held() {
+        let directory = fixtures::scratch();
+        let lock = ProcessLock::acquire(&directory, LockPolicy::Fail).expect("Can't acquire lock");
+
+        match ProcessLock::acquire(&directory, LockPolicy::Fail) {
+            Err(Errors::Locked(pid)) => assert_eq!(pid, process::id()),
+            _ => panic!("Second lock shouldn't be acquired"),
+        }
+
+        drop(lock);
+        assert!(ProcessLock::acquire(&directory, LockPolicy::Fail).is_ok());
+    }
+
+    #[test]
+    fn lock_ignores_stale() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(&directory).unwrap();
+        // PIDs are never that large, so the process can't be running
+        fs::write(directory.join(LOCK_FILE), u32::MAX.to_string()).unwrap();
+
+        // Both see the file of the exited process at the same time, only one of them gets the lock
+        let barrier = Arc::new(Barrier::new(2));
+        let acquirers: Vec<_> = (0..2)
+            .map(|_index| {
+                let directory = directory.clone();
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let lock = ProcessLock::acquire(&directory, LockPolicy::Fail);
+                    // The lock is held until both tried
+                    barrier.wait();
+                    lock.is_ok()
+                })
+            })
+            .collect();
+        let acquired: Vec<bool> = acquirers.into_iter().map(|acquirer| acquirer.join().unwrap()).collect();
+        assert_eq!(acquired.iter().filter(|acquired| **acquired).count(), 1);
+
+        let lock = ProcessLock::acquire(&directory, LockPolicy::Fail).expect("Can't acquire stale lock");
+        assert_eq!(ProcessLock::holder(&directory.join(LOCK_FILE)), process::id());
+
+        drop(lock);
+    }
+}
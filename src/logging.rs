@@ -0,0 +1,57 @@
+//! Diagnostics written to stderr, e.g why a feed produced no episodes. warnings are shown by
+//! default, -v adds what pcasts does, -vv the details of every request and item and --quiet
+//! leaves only the errors
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+/// The most detailed level which is shown, by the number of -v flags
+pub fn level(verbose: u64, quiet: bool) -> LevelFilter {
+    if quiet {
+        return LevelFilter::ERROR;
+    }
+
+    match verbose {
+        0 => LevelFilter::WARN,
+        1 => LevelFilter::INFO,
+        2 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    }
+}
+
+/// Writes the events of the passed level and above to stderr for the rest of the process, colored
+/// if ansi is set. the events of the dependencies, e.g every connection reqwest opens, are only
+/// shown with -vvv
+pub fn init(level: LevelFilter, ansi: bool) {
+    let dependencies = if level == LevelFilter::TRACE {
+        LevelFilter::TRACE
+    } else {
+        LevelFilter::WARN.min(level)
+    };
+    let targets = Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_default(dependencies);
+
+    // Fails only when it was already initialized, e.g by a program which embeds pcasts
+    let _ = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(ansi)
+        .without_time()
+        .with_target(false)
+        .finish()
+        .with(targets)
+        .try_init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logging_level() {
+        assert_eq!(level(0, false), LevelFilter::WARN);
+        assert_eq!(level(2, false), LevelFilter::DEBUG);
+        assert_eq!(level(5, false), LevelFilter::TRACE);
+        assert_eq!(level(2, true), LevelFilter::ERROR);
+    }
+}
@@ -1,27 +1,120 @@
 use podcasts::{ApplicationBuilder, Config};
-use rayon;
 use std::{env, path::PathBuf};
 
 fn main() {
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(4)
-        .build_global()
-        .expect("Couldn't create rayon thread pool");
+    let app_directory = match env::var_os("PODCASTS_DIR") {
+        Some(app_directory) => PathBuf::from(app_directory),
+        None => podcasts::default_app_directory().expect("Can't find the home directory. Set PODCASTS_DIR"),
+    };
+    let download_directory = match env::var_os("PODCASTS_DOWNLOAD_DIR") {
+        Some(download_directory) => PathBuf::from(download_directory),
+        None => podcasts::default_download_directory(&app_directory),
+    };
 
-    let home_directory = env::var("HOME").expect("Can't find $HOME dir variable");
-    let app_directory = env::var("PODCASTS_DIR").unwrap_or(format!("{}/{}", home_directory.clone(), ".podcasts"));
-    let download_directory = env::var("PODCASTS_DOWNLOAD_DIR").unwrap_or(format!("{}/episodes", app_directory));
-
-    let config = Config::new(PathBuf::from(app_directory), PathBuf::from(download_directory));
+    let mut config = Config::new(app_directory, download_directory);
+    if let Ok(file_template) = env::var("PODCASTS_FILE_TEMPLATE") {
+        config = config.with_file_template(file_template);
+    }
+    if let Ok(strict) = env::var("PODCASTS_STRICT") {
+        config = config.with_strict(strict == "1" || strict == "true");
+    }
+    if let Ok(snapshots) = env::var("PODCASTS_SNAPSHOTS") {
+        match snapshots.parse::<usize>() {
+            Ok(snapshots) => config = config.with_snapshots(snapshots),
+            Err(error) => eprintln!("Ignoring PODCASTS_SNAPSHOTS. {}", error),
+        }
+    }
+    if let Ok(min_battery) = env::var("PODCASTS_MIN_BATTERY") {
+        match min_battery.parse::<u8>() {
+            Ok(min_battery) => config = config.with_min_battery(min_battery),
+            Err(error) => eprintln!("Ignoring PODCASTS_MIN_BATTERY. {}", error),
+        }
+    }
+    if let Ok(bandwidth) = env::var("PODCASTS_BANDWIDTH") {
+        match podcasts::Bandwidth::parse(&bandwidth) {
+            Some(bandwidth) => config = config.with_bandwidth(bandwidth),
+            None => eprintln!("Ignoring PODCASTS_BANDWIDTH. It should be wifi or metered"),
+        }
+    }
+    if let Ok(max_size) = env::var("PODCASTS_MAX_SIZE") {
+        match podcasts::parse_size(&max_size) {
+            Ok(max_size) => config = config.with_max_size(max_size),
+            Err(error) => eprintln!("Ignoring PODCASTS_MAX_SIZE. {}", error),
+        }
+    }
+    if let Ok(profile) = env::var("PODCASTS_PROFILE") {
+        config = config.with_profile(profile);
+    }
+    if let Ok(proxy) = env::var("PODCASTS_HTTP_PROXY") {
+        match podcasts::parse_proxy(&proxy) {
+            Ok(proxy) => config = config.with_proxy(proxy),
+            Err(error) => eprintln!("Ignoring PODCASTS_HTTP_PROXY. {}", error),
+        }
+    }
+    if let Ok(user_agent) = env::var("PODCASTS_USER_AGENT") {
+        config = config.with_user_agent(user_agent);
+    }
+    if let (Ok(key), Ok(secret)) = (env::var("PODCASTS_INDEX_KEY"), env::var("PODCASTS_INDEX_SECRET")) {
+        config = config.with_podcast_index(key, secret);
+    }
+    if let Ok(password) = env::var("PODCASTS_GPODDER_PASSWORD") {
+        config = config.with_gpodder_password(password);
+    }
+    if let Ok(tag) = env::var("PODCASTS_TAG") {
+        config = config.with_tag(tag == "1" || tag == "true");
+    }
+    if let Ok(notify) = env::var("PODCASTS_NOTIFY") {
+        config = config.with_notify(notify == "1" || notify == "true");
+    }
+    if let Ok(safe_mode) = env::var("PODCASTS_SAFE_MODE") {
+        config = config.with_safe_mode(safe_mode == "1" || safe_mode == "true");
+    }
+    if let Ok(fail_on) = env::var("PODCASTS_FAIL_ON") {
+        match podcasts::FailOn::parse(&fail_on) {
+            Some(fail_on) => config = config.with_fail_on(fail_on),
+            None => eprintln!("Ignoring PODCASTS_FAIL_ON. It should be any, network or none"),
+        }
+    }
+    if let Ok(limit_rate) = env::var("PODCASTS_LIMIT_RATE") {
+        match podcasts::parse_size(&limit_rate) {
+            Ok(limit_rate) => config = config.with_limit_rate(limit_rate),
+            Err(error) => eprintln!("Ignoring PODCASTS_LIMIT_RATE. {}", error),
+        }
+    }
+    if let Ok(jobs) = env::var("PODCASTS_JOBS") {
+        match jobs.parse::<usize>() {
+            Ok(jobs) => config = config.with_jobs(jobs),
+            Err(error) => eprintln!("Ignoring PODCASTS_JOBS. {}", error),
+        }
+    }
     let mut app = ApplicationBuilder::new(config)
         .podcasts_subcommand()
         .episodes_subcommand()
+        .debug_subcommand()
+        .open_subcommand()
+        .continue_subcommand()
+        .stats_subcommand()
+        .export_subcommand()
+        .sync_subcommand()
+        .config_subcommand()
+        .doctor_subcommand()
+        .verify_subcommand()
+        .backup_subcommand()
+        .restore_subcommand()
+        .tui_subcommand()
+        .log_subcommand()
+        .backfill_subcommand()
+        .compare_subcommand()
+        .gpodder_subcommand()
+        .completions_subcommand()
         .build();
 
     if let Err(error) = app.run() {
         eprintln!("{}", error);
-        std::process::exit(1);
+        std::process::exit(error.exit_code());
     }
 
-    println!("Done");
+    if app.reports_done() {
+        println!("Done");
+    }
 }
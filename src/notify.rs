@@ -0,0 +1,67 @@
+//! Desktop notifications about the new episodes updates find, for when sync runs unattended, e.g
+//! from a systemd timer. shown by the notification server of the desktop on Linux and the BSDs,
+//! and by the notification centers of macOS and Windows
+use crate::Errors;
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether a notification failed already in this run
+static FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Summarizes the new episodes of an update, e.g "3 new episodes in 2 podcasts". takes the number
+/// of new episodes of each podcast. None if there are none
+pub fn summary<I>(new_counts: I) -> Option<String>
+where
+    I: IntoIterator<Item = usize>,
+{
+    let counts: Vec<usize> = new_counts.into_iter().filter(|count| *count > 0).collect();
+    let episodes: usize = counts.iter().sum();
+    if episodes == 0 {
+        return None;
+    }
+
+    let plural = |count: usize, word: &str| match count {
+        1 => format!("1 {}", word),
+        count => format!("{} {}s", count, word),
+    };
+    Some(format!(
+        "{} in {}",
+        plural(episodes, "new episode"),
+        plural(counts.len(), "podcast")
+    ))
+}
+
+/// Shows a desktop notification
+pub fn send(title: &str, body: &str) -> Result<(), Errors> {
+    notify_rust::Notification::new()
+        .appname("pcasts")
+        .summary(title)
+        .body(body)
+        .show()
+        .map(|_handle| ())
+        .map_err(|error| Errors::IO(io::Error::other(error.to_string())))
+}
+
+/// Warns that a notification can't be shown, e.g when no notification server runs. only the
+/// first failure is a warning, since a system without one fails every notification
+pub fn failed(error: &Errors) {
+    match FAILED.swap(true, Ordering::Relaxed) {
+        false => tracing::warn!("Can't show a notification. {}", error),
+        true => tracing::debug!("Can't show a notification. {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notify_summary() {
+        assert_eq!(summary(vec![2, 0, 1]), Some("3 new episodes in 2 podcasts".to_string()));
+        assert_eq!(summary(vec![1]), Some("1 new episode in 1 podcast".to_string()));
+        assert_eq!(summary(vec![0, 0]), None);
+        assert_eq!(summary(Vec::new()), None);
+    }
+}
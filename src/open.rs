@@ -0,0 +1,143 @@
+use crate::{
+    episodes::{read_episodes, Episode},
+    file_system::{FilePermissions, FileSystem},
+    podcasts::{read_podcasts, Podcast},
+    resolver, Config, Errors,
+};
+use clap::ArgMatches;
+use std::{fs, io::Read, process::Command};
+
+/// The command which opens files, directories and URLs with their default application
+#[cfg(target_os = "macos")]
+const OPENER: (&str, &[&str]) = ("open", &[]);
+#[cfg(target_os = "windows")]
+const OPENER: (&str, &[&str]) = ("cmd", &["/C", "start", ""]);
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const OPENER: (&str, &[&str]) = ("xdg-open", &[]);
+
+/// Opens the passed path or URL with its default application, without waiting for it to close
+pub fn launch(target: &str) -> Result<(), Errors> {
+    let (program, args) = OPENER;
+    Command::new(program).args(args).arg(target).spawn()?;
+
+    Ok(())
+}
+
+pub struct Opener<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Opener<'a> {
+    /// Constructs a new Opener struct which is used to work with the sub command "open"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        let target = self.target()?;
+
+        println!("Opening {}", target);
+        launch(&target)
+    }
+
+    /// The path or URL which corresponds to the passed location
+    fn target(&self) -> Result<String, Errors> {
+        if self.matches.subcommand_matches("downloads").is_some() {
+            fs::create_dir_all(&self.config.download_directory)?;
+            return Ok(self.config.download_directory.display().to_string());
+        }
+
+        if self.matches.subcommand_matches("config").is_some() {
+            fs::create_dir_all(&self.config.app_directory)?;
+            return Ok(self.config.app_directory.display().to_string());
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("podcast") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+
+            return Ok(resolver::podcast(&self.podcasts()?, id)?.url.clone());
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("episode") {
+            // Always present because it's a required argument
+            let guid = matches.value_of("guid").unwrap();
+
+            for podcast in self.podcasts()? {
+                let episodes_file = FileSystem::new(
+                    &self.config.app_directory,
+                    &podcast.id.to_string(),
+                    vec![FilePermissions::Read],
+                )
+                .open()?;
+
+                if let Some(episode) = self.find_episode(episodes_file, guid)? {
+                    // Prefer the downloaded file over the episode's web page
+                    let path = self
+                        .config
+                        .podcast_directory(podcast.id)
+                        .join(episode.file_name(self.config.podcast_file_template(episode.podcast_id)));
+                    if path.exists() {
+                        return Ok(path.display().to_string());
+                    }
+
+                    return Ok(episode.link);
+                }
+            }
+
+            return Err(Errors::WrongID(guid.to_string()));
+        }
+
+        Err(Errors::NotFound("location".to_string()))
+    }
+
+    fn podcasts(&self) -> Result<Vec<Podcast>, Errors> {
+        let podcasts_list = FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+
+        read_podcasts(&podcasts_list, self.config.strict)
+    }
+
+    fn find_episode<R>(&self, episodes: R, guid: &str) -> Result<Option<Episode>, Errors>
+    where
+        R: Read,
+    {
+        Ok(read_episodes(episodes, self.config.strict)?
+            .into_iter()
+            .find(|episode: &Episode| episode.guid == guid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, ApplicationBuilder};
+
+    #[test]
+    fn open_targets() {
+        let config = fixtures::config();
+        let app = ApplicationBuilder::new(fixtures::config()).open_subcommand().build();
+        let args = app.app.get_matches_from(vec!["pcasts", "open", "downloads"]);
+        let opener = Opener::new(args.subcommand_matches("open").unwrap(), &config);
+        assert_eq!(
+            opener.target().unwrap(),
+            config.download_directory.display().to_string()
+        );
+
+        let episodes = fixtures::read("syntax.csv").expect("Can't read fixture");
+        let episode = opener
+            .find_episode(&episodes[..], "50f59fbe-93db-4acf-b6db-8f9a078d315a")
+            .unwrap()
+            .expect("Episode not found");
+        assert_eq!(
+            episode.title,
+            "Hasty Treat - Turbolinks + Server Generated HTML + JS Sprinkles"
+        );
+        assert!(opener.find_episode(&episodes[..], "missing").unwrap().is_none());
+    }
+}
@@ -0,0 +1,199 @@
+//! What succeeded and what failed while a command ran, e.g the feeds an update couldn't fetch.
+//! `--fail-on` picks which failures make pcasts exit with an error and `--summary` writes all of
+//! them as JSON, so scripts run from cron can tell a flaky network from a broken library
+use crate::Errors;
+use serde_json::json;
+use std::{
+    fmt,
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    // The host couldn't be reached or answered with an error
+    Network,
+    Other,
+}
+
+impl FailureKind {
+    pub fn of(error: &Errors) -> Self {
+        match error {
+            Errors::Network(_)
+            | Errors::Interrupted(_)
+            | Errors::Timeout(_)
+            | Errors::NotFound(_)
+            | Errors::HttpStatus(_, _)
+            | Errors::OutOfTime(_) => FailureKind::Network,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailureKind::Network => write!(f, "network"),
+            FailureKind::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Which failures of the items of a batch make the command fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailOn {
+    Any,
+    Network,
+    // Only the errors which stop the whole command
+    None,
+}
+
+impl FailOn {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "any" => Some(FailOn::Any),
+            "network" => Some(FailOn::Network),
+            "none" => Some(FailOn::None),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FailOn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FailOn::Any => write!(f, "any"),
+            FailOn::Network => write!(f, "network"),
+            FailOn::None => write!(f, "none"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub kind: FailureKind,
+    // What failed, e.g the URL of a feed
+    pub subject: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct Items {
+    succeeded: usize,
+    failures: Vec<Failure>,
+}
+
+/// Collects the outcome of each item of the batches a command runs. cloning it shares the
+/// collected outcomes
+#[derive(Debug, Clone, Default)]
+pub struct Outcome {
+    items: Arc<Mutex<Items>>,
+}
+
+impl Outcome {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn success(&self) {
+        if let Ok(mut items) = self.items.lock() {
+            items.succeeded += 1;
+        }
+    }
+
+    pub fn failure(&self, subject: &str, error: &Errors) {
+        if let Ok(mut items) = self.items.lock() {
+            items.failures.push(Failure {
+                kind: FailureKind::of(error),
+                subject: subject.to_string(),
+                message: error.to_string(),
+            });
+        }
+    }
+
+    pub fn failures(&self) -> Vec<Failure> {
+        self.items
+            .lock()
+            .map(|items| items.failures.clone())
+            .unwrap_or_default()
+    }
+
+    /// The number of failures which make the command fail with the passed policy
+    pub fn failed(&self, fail_on: FailOn) -> usize {
+        self.failures()
+            .iter()
+            .filter(|failure| match fail_on {
+                FailOn::Any => true,
+                FailOn::Network => failure.kind == FailureKind::Network,
+                FailOn::None => false,
+            })
+            .count()
+    }
+
+    /// Writes the outcome as a JSON object. error is the error which stopped the command, if any
+    pub fn report<W>(&self, command: &str, fail_on: FailOn, error: Option<&Errors>, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let succeeded = self.items.lock().map(|items| items.succeeded).unwrap_or(0);
+        let failures = self.failures();
+        let count = |kind: FailureKind| failures.iter().filter(|failure| failure.kind == kind).count();
+        let failed = self.failed(fail_on);
+
+        let summary = json!({
+            "command": command,
+            "fail_on": fail_on.to_string(),
+            "exit_code": if error.is_some() || failed > 0 { 1 } else { 0 },
+            "error": error.map(|error| error.to_string()),
+            "succeeded": succeeded,
+            "failed": {
+                "network": count(FailureKind::Network),
+                "other": count(FailureKind::Other),
+            },
+            "failures": failures
+                .iter()
+                .map(|failure| json!({
+                    "kind": failure.kind.to_string(),
+                    "subject": failure.subject,
+                    "message": failure.message,
+                }))
+                .collect::<Vec<serde_json::Value>>(),
+        });
+        writeln!(writer, "{}", summary)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome_fail_on() {
+        let outcome = Outcome::new();
+        outcome.success();
+        outcome.clone().failure(
+            "https://example.com/feed.xml",
+            &Errors::NotFound("https://example.com/feed.xml".to_string()),
+        );
+        outcome.failure("https://example.com/other.xml", &Errors::RSS);
+
+        assert_eq!(outcome.failed(FailOn::Any), 2);
+        assert_eq!(outcome.failed(FailOn::Network), 1);
+        assert_eq!(outcome.failed(FailOn::None), 0);
+        assert_eq!(FailOn::parse("network"), Some(FailOn::Network));
+        assert_eq!(FailOn::parse("some"), None);
+
+        let mut output = Vec::new();
+        outcome.report("sync", FailOn::None, None, &mut output).unwrap();
+        let summary: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(summary["exit_code"], 0);
+        assert_eq!(summary["succeeded"], 1);
+        assert_eq!(summary["failed"]["network"], 1);
+        assert_eq!(summary["failed"]["other"], 1);
+        assert_eq!(summary["failures"][0]["kind"], "network");
+        assert_eq!(summary["failures"][1]["subject"], "https://example.com/other.xml");
+        assert!(summary["error"].is_null());
+    }
+}
@@ -0,0 +1,80 @@
+//! The default locations of the app and download directories on each platform, e.g
+//! ~/.local/share/pcasts on Linux, ~/Library/Application Support/pcasts on macOS and
+//! %APPDATA%\pcasts\data on Windows. PODCASTS_DIR and PODCASTS_DOWNLOAD_DIR override them
+use directories::{BaseDirs, ProjectDirs, UserDirs};
+use std::path::{Path, PathBuf};
+
+/// The directory the app used before the platform directories, which is kept when it exists
+const LEGACY_DIRECTORY: &str = ".podcasts";
+
+/// The directory of the downloads inside the app directory, where they were always saved
+const DOWNLOADS_DIRECTORY: &str = "episodes";
+
+/// The directory of the downloads inside the audio directory of the user
+const AUDIO_DIRECTORY: &str = "Podcasts";
+
+/// The home directory of the user, on any platform
+pub fn home_directory() -> Option<PathBuf> {
+    BaseDirs::new().map(|directories| directories.home_dir().to_path_buf())
+}
+
+/// The legacy directory if it exists, or else the data directory of the platform
+fn choose_app_directory(legacy: Option<PathBuf>, data: Option<PathBuf>) -> Option<PathBuf> {
+    match legacy {
+        Some(legacy) if legacy.is_dir() => Some(legacy),
+        legacy => data.or(legacy),
+    }
+}
+
+/// The directory which holds the saved podcasts and episodes. None if the platform has no home
+/// directory
+pub fn default_app_directory() -> Option<PathBuf> {
+    let legacy = home_directory().map(|home| home.join(LEGACY_DIRECTORY));
+
+    choose_app_directory(legacy, data_directory())
+}
+
+/// The data directory of the platform
+fn data_directory() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "pcasts").map(|directories| directories.data_dir().to_path_buf())
+}
+
+/// The directory the episodes are downloaded to. with the data directory of the platform it's the
+/// audio directory of the user, e.g ~/Music/Podcasts. the legacy app directory and the ones set
+/// with PODCASTS_DIR keep the downloads inside them
+pub fn default_download_directory(app_directory: &Path) -> PathBuf {
+    let audio = UserDirs::new().and_then(|directories| directories.audio_dir().map(Path::to_path_buf));
+    match (audio, data_directory()) {
+        (Some(audio), Some(data)) if app_directory == data => audio.join(AUDIO_DIRECTORY),
+        _ => app_directory.join(DOWNLOADS_DIRECTORY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::fs;
+
+    #[test]
+    fn paths_app_directory() {
+        let directory = fixtures::scratch();
+        let legacy = directory.join(LEGACY_DIRECTORY);
+        let data = directory.join("data").join("pcasts");
+
+        assert_eq!(
+            choose_app_directory(Some(legacy.clone()), Some(data.clone())),
+            Some(data.clone())
+        );
+        assert_eq!(choose_app_directory(Some(legacy.clone()), None), Some(legacy.clone()));
+        assert_eq!(choose_app_directory(None, None), None);
+
+        // Existing libraries stay where they are, with their downloads
+        fs::create_dir_all(&legacy).unwrap();
+        assert_eq!(
+            choose_app_directory(Some(legacy.clone()), Some(data)),
+            Some(legacy.clone())
+        );
+        assert_eq!(default_download_directory(&legacy), legacy.join(DOWNLOADS_DIRECTORY));
+    }
+}
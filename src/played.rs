@@ -0,0 +1,183 @@
+//! The episodes which were played, marked with `episodes played` or by the other devices of a
+//! gpodder account. played episodes are left out of the backlog and aren't downloaded by sync
+use crate::{
+    dates,
+    episodes::Episode,
+    file_system::{FilePermissions, FileSystem},
+    records, Errors,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+
+/// The file inside the app directory which holds the played episodes
+pub const PLAYED_FILE: &str = "played.csv";
+
+/// An episode stopped this close to its end was played, since the credits are often skipped
+const FINISHED_MARGIN: u64 = 60;
+
+/// A change of the playback of an episode. the latest one of each episode is its state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayedEntry {
+    pub podcast_id: u64,
+    pub guid: String,
+    // Seconds into the episode where the playback stopped, and the length of the episode. both
+    // are 0 for the episodes which were marked as new again
+    pub position: u64,
+    pub total: u64,
+    pub time: u64,
+    // The gpodder device which played it. empty for the episodes marked here
+    pub device: String,
+}
+
+impl PlayedEntry {
+    /// The episode was played to its end now
+    pub fn finished(episode: &Episode) -> Self {
+        // Episodes of unknown length still need one to count as played
+        let total = episode.estimated_duration().max(1);
+        Self {
+            podcast_id: episode.podcast_id,
+            guid: episode.guid.clone(),
+            position: total,
+            total,
+            time: dates::now(),
+            device: String::new(),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.total > 0 && self.position + FINISHED_MARGIN >= self.total
+    }
+}
+
+/// Appends the passed entries to the played episodes
+pub fn record(app_directory: &Path, entries: &[PlayedEntry]) -> Result<(), Errors> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let file = FileSystem::new(app_directory, PLAYED_FILE, vec![FilePermissions::Append]).open()?;
+    let is_empty = file.metadata()?.len() == 0;
+
+    let mut writer = csv::WriterBuilder::new().has_headers(is_empty).from_writer(file);
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// The latest playback state of each episode
+#[derive(Debug, Default)]
+pub struct PlayedList {
+    entries: HashMap<(u64, String), PlayedEntry>,
+}
+
+impl PlayedList {
+    /// The saved states. the rows which can't be read are skipped
+    pub fn load(app_directory: &Path) -> Self {
+        let entries: Vec<PlayedEntry> = match fs::read(app_directory.join(PLAYED_FILE)) {
+            Ok(contents) => records::read(&contents[..], PLAYED_FILE, false).unwrap_or_default(),
+            Err(_error) => Vec::new(),
+        };
+
+        let mut list = Self::default();
+        for entry in entries {
+            let key = (entry.podcast_id, entry.guid.clone());
+            // The states which come from other devices may be older than the saved ones
+            match list.entries.get(&key) {
+                Some(saved) if saved.time > entry.time => {}
+                _ => {
+                    list.entries.insert(key, entry);
+                }
+            }
+        }
+
+        list
+    }
+
+    /// Whether the episode was played to its end
+    pub fn contains(&self, episode: &Episode) -> bool {
+        self.entries
+            .get(&(episode.podcast_id, episode.guid.clone()))
+            .is_some_and(PlayedEntry::is_finished)
+    }
+
+    /// The episodes whose playback stopped before their end, the most recently played first
+    pub fn in_progress(&self) -> Vec<&PlayedEntry> {
+        let mut entries: Vec<&PlayedEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.position > 0 && !entry.is_finished())
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.time));
+
+        entries
+    }
+
+    /// The latest states of the episodes which changed here after the passed time
+    pub fn changed_since(&self, time: u64) -> Vec<&PlayedEntry> {
+        let mut changed: Vec<&PlayedEntry> = self
+            .entries
+            .values()
+            .filter(|entry| entry.device.is_empty() && entry.time > time)
+            .collect();
+        changed.sort_by_key(|entry| entry.time);
+
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn entry(guid: &str, position: u64, total: u64, time: u64) -> PlayedEntry {
+        PlayedEntry {
+            podcast_id: 1,
+            guid: guid.to_string(),
+            position,
+            total,
+            time,
+            device: String::new(),
+        }
+    }
+
+    #[test]
+    fn played_finished() {
+        assert!(entry("a", 3_600, 3_600, 0).is_finished());
+        assert!(entry("a", 3_600 - FINISHED_MARGIN, 3_600, 0).is_finished());
+        assert!(!entry("a", 3_600 - FINISHED_MARGIN - 1, 3_600, 0).is_finished());
+        // Marked as new again
+        assert!(!entry("a", 0, 0, 0).is_finished());
+    }
+
+    #[test]
+    fn played_latest_wins() {
+        let directory = fixtures::scratch();
+
+        // The entry of another device arrives after a newer one was saved
+        record(
+            &directory,
+            &[
+                entry("a", 600, 3_600, 10),
+                entry("b", 3_600, 3_600, 10),
+                entry("a", 3_600, 3_600, 30),
+                entry("b", 0, 0, 20),
+                entry("a", 1_200, 3_600, 20),
+            ],
+        )
+        .expect("Can't record played episodes");
+
+        let list = PlayedList::load(&directory);
+        let in_progress: Vec<&str> = list.in_progress().iter().map(|entry| entry.guid.as_str()).collect();
+        assert!(in_progress.is_empty());
+        let changed: Vec<(&str, u64)> = list
+            .changed_since(0)
+            .iter()
+            .map(|entry| (entry.guid.as_str(), entry.time))
+            .collect();
+        assert_eq!(changed, vec![("b", 20), ("a", 30)]);
+    }
+}
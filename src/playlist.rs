@@ -0,0 +1,236 @@
+//! Playlists of the downloaded episodes, for players and DAPs which don't know about podcasts
+use crate::{dates, episodes::Episode, Errors};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    M3u,
+    Xspf,
+}
+
+impl Format {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format {
+            "m3u" => Some(Format::M3u),
+            "xspf" => Some(Format::Xspf),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::M3u => "m3u",
+            Format::Xspf => "xspf",
+        }
+    }
+}
+
+/// Orders the downloaded episodes by their publication date, oldest first. episodes without a
+/// date keep the order of the feed, which lists the newest episodes first
+pub fn publication_order(mut episodes: Vec<Episode>) -> Vec<Episode> {
+    episodes.reverse();
+    episodes.sort_by_key(|episode| dates::parse_pub_date(&episode.pub_date).unwrap_or(0));
+
+    episodes
+}
+
+/// Orders the episodes of a show which is meant to be heard from the start, like fiction released
+/// a season at a time: by season and episode number when the feed numbers all of them, and else by
+/// their publication date
+pub fn season_order(mut episodes: Vec<Episode>) -> Vec<Episode> {
+    if episodes.iter().all(|episode| episode.episode_number > 0) {
+        episodes.sort_by_key(|episode| (episode.season, episode.episode_number));
+        return episodes;
+    }
+
+    publication_order(episodes)
+}
+
+/// The path which is written in the playlist. files under the directory of the playlist are
+/// relative to it, so the playlist keeps working when both are copied to another device
+fn entry_path(path: &Path, playlist_directory: &Path) -> PathBuf {
+    path.strip_prefix(playlist_directory).unwrap_or(path).to_path_buf()
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent encodes a path for the location of an XSPF track, which is a URI
+fn encode_location(path: &Path) -> String {
+    let mut location = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => location.push(byte as char),
+            _ => location.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    match path.is_absolute() {
+        true => format!("file://{}", location),
+        false => location,
+    }
+}
+
+/// Writes a playlist of the passed episodes and the paths of their files
+pub fn write<W>(
+    format: Format,
+    title: &str,
+    tracks: &[(Episode, PathBuf)],
+    playlist_directory: &Path,
+    mut writer: W,
+) -> Result<(), Errors>
+where
+    W: Write,
+{
+    match format {
+        Format::M3u => {
+            writeln!(writer, "#EXTM3U")?;
+            writeln!(writer, "#PLAYLIST:{}", title)?;
+            for (episode, path) in tracks {
+                // -1 tells the player the length is unknown
+                let duration = match episode.duration {
+                    0 => -1,
+                    duration => duration as i64,
+                };
+                writeln!(writer, "#EXTINF:{},{} - {}", duration, episode.podcast, episode.title)?;
+                writeln!(writer, "{}", entry_path(path, playlist_directory).display())?;
+            }
+        }
+        Format::Xspf => {
+            writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            writeln!(writer, r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#)?;
+            writeln!(writer, "  <title>{}</title>", escape_xml(title))?;
+            writeln!(writer, "  <trackList>")?;
+            for (episode, path) in tracks {
+                writeln!(writer, "    <track>")?;
+                writeln!(
+                    writer,
+                    "      <location>{}</location>",
+                    escape_xml(&encode_location(&entry_path(path, playlist_directory)))
+                )?;
+                writeln!(writer, "      <title>{}</title>", escape_xml(&episode.title))?;
+                writeln!(writer, "      <creator>{}</creator>", escape_xml(&episode.podcast))?;
+                if episode.duration > 0 {
+                    writeln!(writer, "      <duration>{}</duration>", episode.duration * 1000)?;
+                }
+                writeln!(writer, "    </track>")?;
+            }
+            writeln!(writer, "  </trackList>")?;
+            writeln!(writer, "</playlist>")?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::from_utf8;
+
+    fn create_episode(guid: &str, title: &str, pub_date: &str, duration: u64) -> Episode {
+        Episode {
+            guid: guid.to_string(),
+            title: title.to_string(),
+            pub_date: pub_date.to_string(),
+            link: "-".to_string(),
+            podcast: "Syntax".to_string(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        }
+    }
+
+    #[test]
+    fn playlist_season_order() {
+        let numbered = |guid: &str, season: u64, episode_number: u64| Episode {
+            season,
+            episode_number,
+            ..create_episode(guid, guid, "Mon, 27 Jul 2020 13:00:00 +0000", 0)
+        };
+        let guids =
+            |episodes: Vec<Episode>| -> Vec<String> { episodes.into_iter().map(|episode| episode.guid).collect() };
+
+        let episodes = vec![numbered("c", 2, 1), numbered("b", 1, 10), numbered("a", 1, 2)];
+        assert_eq!(guids(season_order(episodes)), vec!["a", "b", "c"]);
+
+        // Released the same day, so the feed order is kept from its end
+        let episodes = vec![numbered("c", 1, 3), numbered("b", 1, 0), numbered("a", 1, 1)];
+        assert_eq!(guids(season_order(episodes)), vec!["a", "b", "c"]);
+
+        let episode = numbered("c", 2, 3);
+        assert_eq!(
+            episode.file_name(crate::SEQUENTIAL_FILE_TEMPLATE),
+            "Season 02/03 - c.mp3"
+        );
+    }
+
+    #[test]
+    fn playlist_write() {
+        let episodes = publication_order(vec![
+            create_episode("2", "Tips & Tricks", "Mon, 27 Jul 2020 13:00:00 +0000", 0),
+            create_episode("1", "First", "Wed, 22 Jul 2020 13:00:00 +0000", 90),
+        ]);
+        assert_eq!(episodes[0].guid, "1");
+
+        let directory = Path::new("/music/podcasts");
+        let tracks: Vec<(Episode, PathBuf)> = episodes
+            .into_iter()
+            .map(|episode| {
+                let path = directory.join("1").join(format!("{} one.mp3", episode.guid));
+                (episode, path)
+            })
+            .collect();
+
+        let mut output = Vec::new();
+        write(Format::M3u, "Syntax", &tracks, directory, &mut output).unwrap();
+        let expected_output = r###"#EXTM3U
+#PLAYLIST:Syntax
+#EXTINF:90,Syntax - First
+1/1 one.mp3
+#EXTINF:-1,Syntax - Tips & Tricks
+1/2 one.mp3
+"###;
+        assert_eq!(from_utf8(&output).unwrap(), expected_output);
+
+        let mut output = Vec::new();
+        write(Format::Xspf, "Syntax", &tracks[1..], Path::new("/tmp"), &mut output).unwrap();
+        let expected_output = r###"<?xml version="1.0" encoding="UTF-8"?>
+<playlist version="1" xmlns="http://xspf.org/ns/0/">
+  <title>Syntax</title>
+  <trackList>
+    <track>
+      <location>file:///music/podcasts/1/2%20one.mp3</location>
+      <title>Tips &amp; Tricks</title>
+      <creator>Syntax</creator>
+    </track>
+  </trackList>
+</playlist>
+"###;
+        assert_eq!(from_utf8(&output).unwrap(), expected_output);
+    }
+}
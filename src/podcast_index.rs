@@ -0,0 +1,227 @@
+//! Fills in what the feeds of some podcasts leave out, like the durations or the chapters and
+//! transcripts of the episodes, from the episodes the Podcast Index API knows about. only the
+//! podcasts with enrichment turned on are looked up, since each one costs a request to the API
+use crate::{dates, episodes::Episode};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+const EPISODES_URL: &str = "https://api.podcastindex.org/api/1.0/episodes/byfeedurl";
+
+/// The most episodes the API returns for a feed
+const MAX_EPISODES: &str = "1000";
+
+/// The key and the secret of a Podcast Index account
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    pub key: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct IndexEpisode {
+    #[serde(default)]
+    guid: String,
+    // In seconds. missing or 0 when the index doesn't know either
+    #[serde(default)]
+    duration: Option<u64>,
+    #[serde(default, rename = "chaptersUrl")]
+    chapters_url: Option<String>,
+    #[serde(default, rename = "transcriptUrl")]
+    transcript_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EpisodesResponse {
+    #[serde(default)]
+    items: Vec<IndexEpisode>,
+}
+
+/// The SHA-1 digest of the bytes, which the API wants its requests signed with
+fn sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476, 0xc3d2_e1f0];
+
+    let mut message = bytes.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(bytes.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (word, bytes) in words.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        for index in 16..80 {
+            words[index] = (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => ((b & c) | (!b & d), 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+/// URL of the request for the episodes the index knows of the feed with the passed URL
+pub fn episodes_url(feed_url: &str) -> String {
+    let params = [("url", feed_url), ("max", MAX_EPISODES)];
+
+    match reqwest::Url::parse_with_params(EPISODES_URL, &params) {
+        Ok(url) => url.to_string(),
+        Err(_error) => EPISODES_URL.to_string(),
+    }
+}
+
+/// The headers which authenticate a request made at the passed unix time. the signature is the
+/// SHA-1 of the key, the secret and the time
+pub fn auth_headers(credentials: &Credentials, now: u64) -> Vec<(String, String)> {
+    let signature: String = sha1(format!("{}{}{}", credentials.key, credentials.secret, now).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    vec![
+        ("X-Auth-Key".to_string(), credentials.key.clone()),
+        ("X-Auth-Date".to_string(), now.to_string()),
+        ("Authorization".to_string(), signature),
+    ]
+}
+
+/// The headers of the requests for the episodes of each passed feed
+pub fn headers<'a, I>(credentials: &Credentials, feed_urls: I) -> HashMap<String, Vec<(String, String)>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let headers = auth_headers(credentials, dates::now());
+
+    feed_urls
+        .into_iter()
+        .map(|feed_url| (episodes_url(feed_url), headers.clone()))
+        .collect()
+}
+
+/// Fills in the durations, chapters and transcripts which the episodes lack from the response of
+/// the index. what the feed has is kept. returns how many episodes were changed
+pub fn enrich(episodes: &mut [Episode], response: &[u8]) -> usize {
+    let response: EpisodesResponse = serde_json::from_slice(response).unwrap_or_default();
+    let found: HashMap<&str, &IndexEpisode> = response
+        .items
+        .iter()
+        .map(|episode| (episode.guid.as_str(), episode))
+        .collect();
+
+    let mut enriched = 0;
+    for episode in episodes.iter_mut() {
+        let found = match found.get(episode.guid.as_str()) {
+            Some(found) => found,
+            None => continue,
+        };
+
+        let mut changed = false;
+        if let Some(duration) = found.duration.filter(|duration| episode.duration == 0 && *duration > 0) {
+            episode.duration = duration;
+            changed = true;
+        }
+        if let Some(url) = found
+            .chapters_url
+            .as_ref()
+            .filter(|url| episode.chapters.is_empty() && !url.is_empty())
+        {
+            episode.chapters = url.clone();
+            changed = true;
+        }
+        if let Some(url) = found
+            .transcript_url
+            .as_ref()
+            .filter(|url| episode.transcript.is_empty() && !url.is_empty())
+        {
+            episode.transcript = url.clone();
+            changed = true;
+        }
+
+        if changed {
+            enriched += 1;
+        }
+    }
+
+    enriched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::episodes::read_episodes;
+
+    #[test]
+    fn podcast_index_auth() {
+        let hex = |digest: [u8; 20]| digest.iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+        assert_eq!(hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(hex(sha1(&[b'a'; 1000])), "291e9a6c66994949b57ba5e650361e98fc36b1ba");
+
+        let credentials = Credentials {
+            key: "key".to_string(),
+            secret: "secret".to_string(),
+        };
+        let headers = auth_headers(&credentials, 1_600_000_000);
+        assert_eq!(headers[1], ("X-Auth-Date".to_string(), "1600000000".to_string()));
+        assert_eq!(headers[2].1, hex(sha1(b"keysecret1600000000")));
+        assert_eq!(
+            episodes_url("https://feed.syntax.fm/rss"),
+            "https://api.podcastindex.org/api/1.0/episodes/byfeedurl?url=https%3A%2F%2Ffeed.syntax.fm%2Frss&max=1000"
+        );
+    }
+
+    #[test]
+    fn podcast_index_enrich() {
+        let input = r###"guid,title,pub_date,link,podcast,podcast_id,duration,chapters
+a,Rust,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,0,
+b,Go,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,1800,https://example.com/b.json
+c,C,"Wed, 22 Jul 2020 13:00:00 +0000",-,Podcast,1,0,
+"###;
+        let mut episodes = read_episodes(input.as_bytes(), true).unwrap();
+        let response = r###"{"status": "true", "items": [
+            {"guid": "a", "duration": 3600, "chaptersUrl": "https://example.com/a.json", "transcriptUrl": null},
+            {"guid": "b", "duration": 1700, "chaptersUrl": "https://example.com/other.json",
+             "transcriptUrl": "https://example.com/b.srt"},
+            {"guid": "d", "duration": 60}
+        ]}"###;
+
+        assert_eq!(enrich(&mut episodes, response.as_bytes()), 2);
+        assert_eq!(episodes[0].duration, 3600);
+        assert_eq!(episodes[0].chapters, "https://example.com/a.json");
+        assert!(episodes[0].transcript.is_empty());
+        assert_eq!(episodes[1].duration, 1800);
+        assert_eq!(episodes[1].chapters, "https://example.com/b.json");
+        assert_eq!(episodes[1].transcript, "https://example.com/b.srt");
+        assert_eq!(episodes[2].duration, 0);
+        assert_eq!(enrich(&mut episodes, b"Unauthorized"), 0);
+    }
+}
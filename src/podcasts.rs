@@ -1,38 +1,451 @@
 use crate::{
+    art,
+    cache::ResponseCache,
+    dates,
+    episodes::{feed_move, read_episodes, Episode, Episodes},
+    events::{self, Event, EventKind},
+    feed,
     file_system::{FilePermissions, FileSystem},
+    headers::{self, HeaderList},
+    qr::QrCode,
+    records, resolver,
+    table::{self, Table},
+    thumbnail,
+    timings::Phase,
+    units,
+    variants::{VariantList, MAIN_VARIANT},
     web, Config, Errors,
 };
+use bytes::Bytes;
 use clap::{ArgMatches, Values};
 use colored::*;
-use csv;
-use rss;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{hash_map::DefaultHasher, HashSet},
-    fmt,
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt, fs,
     hash::{Hash, Hasher},
-    io::{Read, Write},
+    io::{BufRead, IsTerminal, Read, Write},
+    sync::Arc,
     time,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Podcast {
     pub id: u64,
     pub url: String,
     pub rss_url: String,
     pub title: String,
+    // Paused podcasts are skipped when updating all the podcasts
+    #[serde(default)]
+    pub paused: bool,
+    // Number of updates in a row which failed to fetch the feed
+    #[serde(default)]
+    pub failures: u64,
+    // Downloaded episodes older than this many days are removed. 0 keeps all of them
+    #[serde(default)]
+    pub window: u64,
+    // Number of the newest episodes `sync` keeps downloaded. 0 uses the --count of sync
+    #[serde(default)]
+    pub sync_count: u64,
+    // Time of the last successful update. the episodes first seen since then are new
+    #[serde(default)]
+    pub last_update: u64,
+    // Number of the newest downloaded episodes kept on disk by `sync`. 0 keeps all of them
+    #[serde(default)]
+    pub keep: u64,
+    // Number of episodes downloaded in each turn of a batch of several podcasts. 0 counts as 1
+    #[serde(default)]
+    pub priority: u64,
+    // Lowercase tags separated by spaces, e.g "tech news"
+    #[serde(default)]
+    pub tags: String,
+    // Shown instead of the title of the feed and used for the names of the downloads. empty if
+    // the podcast wasn't renamed
+    #[serde(default)]
+    pub alias: String,
+    // Updates fill in what the feed leaves out from the Podcast Index
+    #[serde(default)]
+    pub enrich: bool,
+    // Where the episodes are downloaded to, and the template of their names. empty to use the
+    // global ones
+    #[serde(default)]
+    pub download_directory: String,
+    #[serde(default)]
+    pub file_template: String,
+    // Sync updates the podcast without downloading its episodes
+    #[serde(default)]
+    pub manual: bool,
+}
+
+/// The settings which can be changed with `podcasts --set`
+const SETTINGS: &str = "download-dir, file-template, keep, auto-download or tags";
+
+/// Podcasts whose newest episode is older than this are offered for cleanup
+const STALE_AFTER: u64 = 365 * 24 * 60 * 60;
+
+/// Podcasts whose feed failed this many updates in a row are offered for cleanup
+const MAX_FAILURES: u64 = 3;
+
+/// Reads all the saved podcasts. invalid rows are skipped unless strict is set
+pub fn read_podcasts<R>(reader: R, strict: bool) -> Result<Vec<Podcast>, Errors>
+where
+    R: Read,
+{
+    records::read(reader, "podcast_list.csv", strict)
+}
+
+/// Reads the saved podcasts of the app directory. shared by the commands and the TUI
+pub fn load_podcasts(config: &Config) -> Result<Vec<Podcast>, Errors> {
+    let podcasts_list =
+        FileSystem::new(&config.app_directory, "podcast_list.csv", vec![FilePermissions::Read]).open()?;
+
+    read_podcasts(podcasts_list, config.strict)
+}
+
+/// The RSS URL of a podcast which was added, with the podcast or the reason it wasn't added
+pub type Subscription = (String, Result<Podcast, Errors>);
+
+/// Fetches the feeds of the passed URLs and saves their podcasts with the passed tags, along
+/// with the podcasts read from reader. the URLs which are already saved are skipped. returns the
+/// podcast or the error of each new URL
+pub(crate) fn subscribe<R, W>(
+    config: &Config,
+    urls: &[&str],
+    tags: &[&str],
+    reader: R,
+    writer: W,
+) -> Result<Vec<Subscription>, Errors>
+where
+    R: Read,
+    W: Write,
+{
+    let saved = read_podcasts(reader, config.strict)?;
+    let saved_urls: HashSet<&str> = saved.iter().map(|podcast| podcast.rss_url.as_str()).collect();
+
+    // Work only with new URLs
+    let urls: Vec<&str> = urls.iter().copied().filter(|url| !saved_urls.contains(url)).collect();
+
+    let responses = config.timings.measure(Phase::Fetch, || {
+        web::Web::new(time::Duration::from_secs(10), &config.http)
+            .stats(&config.network)
+            .progress(Arc::clone(&config.progress))
+            .jobs(config.jobs)
+            .get(&urls)
+    });
+
+    let mut artwork = Vec::new();
+    let results: Vec<Subscription> = responses
+        .into_iter()
+        .map(|(url, response)| {
+            let podcast = response.and_then(|bytes| {
+                let rss_channel = config.timings.measure(Phase::Parse, || feed::read(&bytes[..]))?;
+
+                let podcast_title = rss_channel.title().to_string();
+                let podcast_url = rss_channel.link().to_string();
+                let mut podcast = Podcast::new(podcast_url, url.to_string(), podcast_title);
+                podcast.add_tags(tags.iter().copied());
+                if let Some(image_url) = art::image_url(&rss_channel) {
+                    artwork.push((podcast.id, image_url));
+                }
+
+                Ok(podcast)
+            });
+            match &podcast {
+                Ok(_podcast) => config.outcome.success(),
+                Err(error) => config.outcome.failure(url, error),
+            }
+
+            (url.to_string(), podcast)
+        })
+        .collect();
+    let podcasts: Vec<&Podcast> = results
+        .iter()
+        .filter_map(|(_url, podcast)| podcast.as_ref().ok())
+        .collect();
+
+    config.timings.measure(Phase::Store, || {
+        write_podcasts(saved.iter().chain(podcasts.iter().copied()), writer)
+    })?;
+
+    let subscribed: Vec<Event> = podcasts
+        .iter()
+        .map(|podcast| Event::new(EventKind::Subscribed, podcast.id, "", &podcast.title))
+        .collect();
+    events::record(&config.app_directory, &subscribed)?;
+    art::download(config, &artwork)?;
+
+    Ok(results)
+}
+
+/// The podcast the passed input names
+fn find_podcast<'a>(podcasts: &'a mut [Podcast], input: &str) -> Result<&'a mut Podcast, Errors> {
+    let id = resolver::podcast(podcasts, input)?.id;
+    // Always found since it was just resolved
+    Ok(podcasts.iter_mut().find(|podcast| podcast.id == id).unwrap())
+}
+
+/// Writes the passed podcasts together with the headers line
+pub fn write_podcasts<'a, I, W>(podcasts: I, writer: W) -> Result<(), Errors>
+where
+    I: IntoIterator<Item = &'a Podcast>,
+    W: Write,
+{
+    let mut writer = csv::Writer::from_writer(writer);
+    for podcast in podcasts {
+        writer.serialize(podcast)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Tags are compared ignoring case and can't contain spaces, which separate them in the column
+pub fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<&str>>().join("-").to_lowercase()
+}
+
+impl Podcast {
+    /// Constructs a new Podcast. the id is derived from the RSS URL
+    pub fn new(url: String, rss_url: String, title: String) -> Self {
+        let mut hasher = DefaultHasher::new();
+        rss_url.hash(&mut hasher);
+
+        Self {
+            id: hasher.finish(),
+            url,
+            rss_url,
+            title,
+            paused: false,
+            failures: 0,
+            window: 0,
+            sync_count: 0,
+            last_update: 0,
+            keep: 0,
+            priority: 0,
+            tags: String::new(),
+            alias: String::new(),
+            enrich: false,
+            download_directory: String::new(),
+            file_template: String::new(),
+            manual: false,
+        }
+    }
+
+    /// The alias of the podcast, or the title of its feed if it has none
+    pub fn name(&self) -> &str {
+        match self.alias.is_empty() {
+            true => &self.title,
+            false => &self.alias,
+        }
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        self.tags.split_whitespace()
+    }
+
+    /// Whether the podcast has the passed tag
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = normalize_tag(tag);
+        self.tags().any(|other| other == tag)
+    }
+
+    /// Changes the setting with the passed key, e.g "keep" and "5". an empty download directory or
+    /// file template goes back to the global one
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Errors> {
+        let invalid = |reason: &str| Errors::InvalidSetting(format!("{}={}. {}", key, value, reason));
+        let value = value.trim();
+
+        match key.trim() {
+            "download-dir" if value.is_empty() => self.download_directory = String::new(),
+            "download-dir" => {
+                // Relative to where the command runs, not to where the downloads run later
+                let directory = std::env::current_dir()?.join(value);
+                self.download_directory = directory.display().to_string();
+            }
+            "file-template" if !value.is_empty() && !value.contains("{title}") && !value.contains("{guid}") => {
+                return Err(invalid(
+                    "The template needs {title} or {guid}, so the episodes get different names",
+                ))
+            }
+            "file-template" => self.file_template = value.to_string(),
+            "keep" => self.keep = value.parse::<u64>().map_err(|_error| invalid("It must be a number"))?,
+            "auto-download" => match value {
+                "true" | "on" | "yes" => self.manual = false,
+                "false" | "off" | "no" => self.manual = true,
+                _ => return Err(invalid("It must be true or false")),
+            },
+            "tags" => {
+                self.tags = String::new();
+                self.add_tags(value.split([',', ' ']));
+            }
+            _ => return Err(invalid(&format!("Use {}", SETTINGS))),
+        }
+
+        Ok(())
+    }
+
+    /// Adds the passed tags to the podcast. tags it already has are skipped
+    pub fn add_tags<'a, I>(&mut self, tags: I)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut all: Vec<String> = self.tags().map(|tag| tag.to_string()).collect();
+        for tag in tags.into_iter().map(normalize_tag) {
+            if !tag.is_empty() && !all.contains(&tag) {
+                all.push(tag);
+            }
+        }
+        self.tags = all.join(" ");
+    }
+
+    /// Removes the passed tags from the podcast
+    pub fn remove_tags<'a, I>(&mut self, tags: I)
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let removed: Vec<String> = tags.into_iter().map(normalize_tag).collect();
+        self.tags = self
+            .tags()
+            .filter(|tag| !removed.iter().any(|removed| removed == tag))
+            .collect::<Vec<&str>>()
+            .join(" ");
+    }
 }
 
 impl fmt::Display for Podcast {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut str = format!("{:12}{}\n", "Title:".green(), self.title);
+        let mut str = format!("{:12}{}\n", "Title:".green(), self.name());
+        if !self.alias.is_empty() {
+            str.push_str(&format!("{:12}{}\n", "Feed title:".green(), self.title));
+        }
         str.push_str(&format!("{:12}{}\n", "Site URL:".green(), self.url));
         str.push_str(&format!("{:12}{}\n", "RSS URL:".green(), self.rss_url));
         str.push_str(&format!("{:12}{}\n", "ID:".green(), self.id));
+        if self.paused {
+            str.push_str(&format!("{:12}{}\n", "Status:".green(), "paused"));
+        }
+        if self.window > 0 {
+            str.push_str(&format!("{:12}last {} days\n", "Window:".green(), self.window));
+        }
+        if self.sync_count > 0 {
+            str.push_str(&format!("{:12}newest {} episodes\n", "Sync:".green(), self.sync_count));
+        }
+        if self.keep > 0 {
+            str.push_str(&format!("{:12}newest {} downloads\n", "Keep:".green(), self.keep));
+        }
+        if self.priority > 1 {
+            str.push_str(&format!(
+                "{:12}{} episodes per turn\n",
+                "Priority:".green(),
+                self.priority
+            ));
+        }
+        if self.enrich {
+            str.push_str(&format!("{:12}{}\n", "Enrich:".green(), "from the Podcast Index"));
+        }
+        if self.manual {
+            str.push_str(&format!("{:12}{}\n", "Downloads:".green(), "not by sync"));
+        }
+        if !self.download_directory.is_empty() {
+            str.push_str(&format!("{:12}{}\n", "Directory:".green(), self.download_directory));
+        }
+        if !self.file_template.is_empty() {
+            str.push_str(&format!("{:12}{}\n", "Template:".green(), self.file_template));
+        }
+        if !self.tags.is_empty() {
+            str.push_str(&format!(
+                "{:12}{}\n",
+                "Tags:".green(),
+                self.tags().collect::<Vec<&str>>().join(", ")
+            ));
+        }
         write!(f, "{}", str)
     }
 }
 
+/// A podcast found with `podcasts --search`. the results of the last search are saved so they can
+/// be added by their number
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub title: String,
+    pub author: String,
+    pub rss_url: String,
+}
+
+/// The relevant part of an iTunes Search API response
+#[derive(Debug, Deserialize)]
+struct ITunesResponse {
+    results: Vec<ITunesResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ITunesResult {
+    collection_name: String,
+    artist_name: String,
+    feed_url: Option<String>,
+}
+
+const SEARCH_RESULTS_FILE: &str = "search_results.csv";
+
+/// How long the responses of the directory search are reused
+const SEARCH_CACHE_TTL: time::Duration = time::Duration::from_secs(24 * 60 * 60);
+
+/// What fetching the feed of a podcast found
+#[derive(Debug, PartialEq)]
+enum FeedHealth {
+    // The number of episodes
+    Ok(usize),
+    // The feed works, but updates will switch to the new URL
+    Moved(String),
+    HttpError(String),
+    ParseError,
+    NoEnclosures,
+}
+
+impl FeedHealth {
+    fn of(rss_url: &str, response: &Result<Bytes, Errors>, redirected: Option<String>) -> Self {
+        let bytes = match response {
+            Ok(bytes) => bytes,
+            Err(error) => return FeedHealth::HttpError(error.to_string()),
+        };
+        let channel = match feed::read(&bytes[..]) {
+            Ok(channel) => channel,
+            Err(error) => {
+                tracing::debug!("Can't parse {}. {}", rss_url, error);
+                return FeedHealth::ParseError;
+            }
+        };
+        let episodes = Episode::from_channel(&channel, &bytes[..], 0);
+        if !episodes.iter().any(|episode| !episode.enclosure_url.is_empty()) {
+            return FeedHealth::NoEnclosures;
+        }
+
+        let new_feed_url = channel.itunes_ext().and_then(|itunes| itunes.new_feed_url());
+        match feed_move(rss_url, new_feed_url, redirected) {
+            Some(url) => FeedHealth::Moved(url),
+            None => FeedHealth::Ok(episodes.len()),
+        }
+    }
+
+    /// Whether updating the podcast gets no episodes out of the feed
+    fn is_broken(&self) -> bool {
+        !matches!(self, FeedHealth::Ok(_) | FeedHealth::Moved(_))
+    }
+}
+
+impl fmt::Display for FeedHealth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeedHealth::Ok(count) => write!(f, "{} ({} episodes)", "OK".green(), count),
+            FeedHealth::Moved(url) => write!(f, "{} to {}", "Moved".yellow(), url),
+            FeedHealth::HttpError(error) => write!(f, "{} {}", "Failed".red(), error),
+            FeedHealth::ParseError => write!(f, "{} it's not an RSS or Atom feed", "Failed".red()),
+            FeedHealth::NoEnclosures => write!(f, "{} none of the episodes has audio", "Empty".red()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Podcasts<'a> {
     matches: &'a ArgMatches,
@@ -55,36 +468,215 @@ impl<'a> Podcasts<'a> {
             )
             .open()?;
 
-            let writer_file = FileSystem::new(
-                &self.config.app_directory,
-                "podcast_list.csv",
-                vec![FilePermissions::Read, FilePermissions::Append],
-            )
-            .open()?;
+            // The whole file is written again, since files saved by older versions have less columns
+            let mut contents = String::new();
+            let mut reader_file = reader_file;
+            reader_file.read_to_string(&mut contents)?;
+
+            let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+            self.add(add_values, contents.as_bytes(), &mut writer_file)?;
 
-            return self.add(&add_values, reader_file, writer_file);
+            return Ok(writer_file.commit()?);
         }
 
-        if let Some(remove_values) = self.matches.values_of("remove") {
+        if self.matches.subcommand_matches("cleanup").is_some() {
             let mut reader_file = FileSystem::new(
                 &self.config.app_directory,
                 "podcast_list.csv",
                 vec![FilePermissions::Read],
             )
             .open()?;
-
-            // WriteTruncate mode erases file content, so we extract it here
             let mut contents = String::new();
             reader_file.read_to_string(&mut contents)?;
 
-            let writer_file = FileSystem::new(
-                &self.config.app_directory,
-                "podcast_list.csv",
-                vec![FilePermissions::WriteTruncate],
-            )
-            .open()?;
+            let saved = read_podcasts(contents.as_bytes(), self.config.strict)?;
+            let candidates = saved
+                .iter()
+                .cloned()
+                .map(|podcast| {
+                    let reasons = self.stale_reasons(&podcast)?;
+                    Ok((podcast, reasons))
+                })
+                .collect::<Result<_, Errors>>()?;
+
+            let stdin = std::io::stdin();
+            let stdout = std::io::stdout();
+            let podcasts = self.cleanup(candidates, stdin.lock(), stdout.lock())?;
+
+            let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+            self.config
+                .timings
+                .measure(Phase::Store, || write_podcasts(&podcasts, &mut writer_file))?;
+            writer_file.commit()?;
+
+            let kept: HashSet<u64> = podcasts.iter().map(|podcast| podcast.id).collect();
+            let removed: Vec<Event> = saved
+                .iter()
+                .filter(|podcast| !kept.contains(&podcast.id))
+                .map(|podcast| Event::new(EventKind::Unsubscribed, podcast.id, "", podcast.name()))
+                .collect();
+            return events::record(&self.config.app_directory, &removed);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("window") {
+            // Always present because they're required arguments
+            let id = matches.value_of("id").unwrap();
+            let days = units::parse_days(matches.value_of("days").unwrap())?;
+
+            return self.rewrite(|podcasts| self.window(podcasts, id, days));
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("sync-count") {
+            // Always present because they're required arguments
+            let id = matches.value_of("id").unwrap();
+            let count = matches.value_of("count").unwrap().parse::<u64>()?;
+
+            return self.rewrite(|podcasts| self.sync_count(podcasts, id, count));
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("keep") {
+            // Always present because they're required arguments
+            let id = matches.value_of("id").unwrap();
+            let count = matches.value_of("count").unwrap().parse::<u64>()?;
+
+            return self.rewrite(|podcasts| self.keep(podcasts, id, count));
+        }
 
-            return self.remove(&remove_values, contents.as_bytes(), writer_file);
+        if let Some(matches) = self.matches.subcommand_matches("tag") {
+            // Always present because they're required arguments
+            let id = matches.value_of("id").unwrap();
+            let tags: Vec<&str> = matches.values_of("tags").unwrap().collect();
+            let remove = matches.is_present("remove");
+
+            return self.rewrite(|podcasts| self.tag(podcasts, id, &tags, remove));
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("enrich") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+            let enrich = !matches.is_present("off");
+
+            return self.rewrite(|podcasts| self.enrich(podcasts, id, enrich));
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("priority") {
+            // Always present because they're required arguments
+            let id = matches.value_of("id").unwrap();
+            let weight = matches.value_of("weight").unwrap().parse::<u64>()?;
+
+            return self.rewrite(|podcasts| self.priority(podcasts, id, weight));
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("variants") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, id)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.variants(matches, podcast, writer);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("headers") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, id)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.headers(matches, podcast, writer);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("qr") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, id)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.qr(podcast, matches.value_of("episode-id"), writer);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("info") {
+            // Always present because it's a required argument
+            let id = matches.value_of("id").unwrap();
+            let podcasts = load_podcasts(self.config)?;
+            let podcast = resolver::podcast(&podcasts, id)?;
+            // Escape sequences would end up in the files stdout is redirected to, so only an
+            // explicit protocol is drawn there
+            let protocol = match matches.value_of("art") {
+                Some("auto") if std::io::stdout().is_terminal() => {
+                    Some(thumbnail::Protocol::detect(|name| std::env::var(name).ok()))
+                }
+                Some(protocol) => thumbnail::Protocol::parse(protocol),
+                None => None,
+            };
+            let preview = protocol.and_then(|protocol| {
+                let artwork = thumbnail::artwork(self.config, podcast)?;
+                thumbnail::render(&artwork, protocol, thumbnail::COLUMNS)
+            });
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.info(podcast, preview.as_deref(), writer);
+        }
+
+        if let Some(values) = self.matches.values_of("set") {
+            // Always at least two values because of min_values
+            let values: Vec<&str> = values.collect();
+
+            return self.rewrite(|podcasts| self.set(podcasts, values[0], &values[1..]));
+        }
+
+        if let Some(values) = self.matches.values_of("rename") {
+            // Always two values because of number_of_values
+            let values: Vec<&str> = values.collect();
+
+            return self.rewrite(|podcasts| self.rename(podcasts, values[0], values[1]));
+        }
+
+        if let Some(remove_values) = self.matches.values_of("remove") {
+            let values: Vec<&str> = remove_values.collect();
+            let stdin = std::io::stdin();
+            let stdout = std::io::stdout();
+
+            let mut removed = Vec::new();
+            self.rewrite(|podcasts| {
+                removed = self.remove(podcasts, &values, stdin.lock(), stdout.lock())?;
+                Ok(())
+            })?;
+
+            let unsubscribed: Vec<Event> = removed
+                .iter()
+                .map(|podcast| Event::new(EventKind::Unsubscribed, podcast.id, "", podcast.name()))
+                .collect();
+            events::record(&self.config.app_directory, &unsubscribed)?;
+
+            if self.matches.is_present("purge") {
+                for podcast in removed.iter() {
+                    self.purge(podcast)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        if self.matches.is_present("check") {
+            let podcasts = load_podcasts(self.config)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.check(&podcasts, writer);
+        }
+
+        if let Some(term) = self.matches.value_of("search") {
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.search(term, writer);
         }
 
         if self.matches.is_present("list") {
@@ -110,95 +702,684 @@ impl<'a> Podcasts<'a> {
         R: Read,
         W: Write,
     {
-        let values = add_values.clone();
-        let mut reader = csv::Reader::from_reader(reader);
-
-        // Load previously saved URLs
-        let saved_urls: HashSet<String> = reader
-            .deserialize()
-            .filter_map(|item: Result<Podcast, csv::Error>| item.map(|podcast| podcast.rss_url).ok())
-            .collect();
-
-        // Work only with new URLs
-        let urls: Vec<&str> = values
+        let search_results = self.search_results();
+        let values: Vec<&str> = add_values
+            .clone()
             .map(|value| value.trim())
-            .filter(|value| {
-                return !saved_urls.contains(*value);
+            // Numbers refer to the results of the last search
+            .map(|value| match value.parse::<usize>() {
+                Ok(number) if number > 0 && number <= search_results.len() => &search_results[number - 1].rss_url,
+                _ => value,
             })
             .collect();
+        let tags: Vec<&str> = self.matches.values_of("tag").into_iter().flatten().collect();
+
+        for (url, podcast) in subscribe(self.config, &values, &tags, reader, writer)? {
+            match podcast {
+                Ok(_podcast) => println!("Added podcast {}", url),
+                Err(Errors::RSS) => tracing::warn!("Can't add podcast {}. It's not an RSS or Atom feed", url),
+                Err(error) => tracing::warn!("Can't add podcast {}. {}", url, error),
+            }
+        }
 
-        let podcasts: Vec<Podcast> = web::Web::new(time::Duration::from_secs(10))
-            .get(&urls)
-            .iter()
-            .filter_map(|(url, response)| match response {
-                Ok(res) => {
-                    println!("Adding podcast {}", url);
-
-                    // Parse RSS feed
-                    let rss_channel = rss::Channel::read_from(&res[..]);
-                    if rss_channel.is_err() {
-                        return None;
+        Ok(())
+    }
+
+    /// Applies the passed change to the saved podcasts and writes them back
+    fn rewrite<F>(&self, change: F) -> Result<(), Errors>
+    where
+        F: FnOnce(&mut Vec<Podcast>) -> Result<(), Errors>,
+    {
+        let mut reader_file = FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+        let mut contents = String::new();
+        reader_file.read_to_string(&mut contents)?;
+
+        let mut podcasts = read_podcasts(contents.as_bytes(), self.config.strict)?;
+        change(&mut podcasts)?;
+
+        let mut writer_file = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+        self.config
+            .timings
+            .measure(Phase::Store, || write_podcasts(&podcasts, &mut writer_file))?;
+
+        Ok(writer_file.commit()?)
+    }
+
+    /// Sets the number of days of downloads to keep for the podcast with the passed id
+    fn window(&self, podcasts: &mut [Podcast], id: &str, days: u64) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        podcast.window = days;
+        match days {
+            0 => println!("Keeping all the downloads of {}", podcast.title),
+            days => println!("Keeping the downloads of the last {} days of {}", days, podcast.title),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the number of the newest episodes which `sync` keeps downloaded for the podcast with
+    /// the passed id
+    fn sync_count(&self, podcasts: &mut [Podcast], id: &str, count: u64) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        podcast.sync_count = count;
+        match count {
+            0 => println!("Syncing the default number of episodes of {}", podcast.title),
+            count => println!("Syncing the newest {} episodes of {}", count, podcast.title),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the number of the newest downloads which `sync` keeps on disk for the podcast with the
+    /// passed id
+    fn keep(&self, podcasts: &mut [Podcast], id: &str, count: u64) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        podcast.keep = count;
+        match count {
+            0 => println!("Keeping all the downloads of {}", podcast.title),
+            count => println!("Keeping the newest {} downloads of {}", count, podcast.title),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the number of episodes the podcast with the passed id downloads in each turn of a
+    /// batch download
+    fn priority(&self, podcasts: &mut [Podcast], id: &str, weight: u64) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        podcast.priority = weight;
+        println!("Downloading {} episodes of {} per turn", weight.max(1), podcast.title);
+
+        Ok(())
+    }
+
+    /// Sets the alias of the podcast with the passed id and renames its saved episodes and
+    /// downloads after it. an empty alias goes back to the title of the feed
+    fn rename(&self, podcasts: &mut [Podcast], id: &str, alias: &str) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+        podcast.alias = alias.trim().to_string();
+
+        let moved = Episodes::new(self.matches, self.config).rename_podcast(podcast)?;
+        match podcast.alias.is_empty() {
+            true => println!("{} is named after its feed again", podcast.title),
+            false => println!("Renamed {} to {}", podcast.title, podcast.alias),
+        }
+        if moved > 0 {
+            println!("Moved {} downloaded episodes", moved);
+        }
+
+        Ok(())
+    }
+
+    /// Changes the passed key=value settings of the podcast with the passed id. nothing is changed
+    /// if one of them is invalid
+    fn set(&self, podcasts: &mut [Podcast], id: &str, settings: &[&str]) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+        let mut changed = podcast.clone();
+        for setting in settings {
+            let (key, value) = setting
+                .split_once('=')
+                .ok_or_else(|| Errors::InvalidSetting(format!("{}. Use key=value with {}", setting, SETTINGS)))?;
+            changed.set(key, value)?;
+        }
+
+        // The downloads which were already saved stay where they are
+        if changed.download_directory != podcast.download_directory {
+            println!(
+                "The episodes of {} which were already downloaded aren't moved",
+                podcast.name()
+            );
+        }
+        *podcast = changed;
+        print!("{}", podcast);
+
+        Ok(())
+    }
+
+    /// Adds the passed tags to the podcast with the passed id, or removes them
+    fn tag(&self, podcasts: &mut [Podcast], id: &str, tags: &[&str], remove: bool) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        match remove {
+            true => podcast.remove_tags(tags.iter().copied()),
+            false => podcast.add_tags(tags.iter().copied()),
+        }
+        match podcast.tags.is_empty() {
+            true => println!("{} has no tags", podcast.title),
+            false => println!(
+                "Tagged {} with {}",
+                podcast.title,
+                podcast.tags().collect::<Vec<&str>>().join(", ")
+            ),
+        }
+
+        Ok(())
+    }
+
+    /// Turns the enrichment of the episodes of the podcast with the passed id from the Podcast
+    /// Index on or off
+    fn enrich(&self, podcasts: &mut [Podcast], id: &str, enrich: bool) -> Result<(), Errors> {
+        let podcast = find_podcast(podcasts, id)?;
+
+        podcast.enrich = enrich;
+        match enrich {
+            true => println!(
+                "Updates fill in the episodes of {} from the Podcast Index",
+                podcast.name()
+            ),
+            false => println!("Updates take the episodes of {} from its feed only", podcast.name()),
+        }
+        if enrich && self.config.podcast_index.is_none() {
+            println!("Set PODCASTS_INDEX_KEY and PODCASTS_INDEX_SECRET to the credentials of a Podcast Index account");
+        }
+
+        Ok(())
+    }
+
+    /// Adds, removes or prefers a variant of the podcast for the current profile, or lists its
+    /// variants
+    fn variants<W>(&self, matches: &ArgMatches, podcast: &Podcast, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let mut list = VariantList::load(&self.config.app_directory, self.config.strict)?;
+        let profile = self.config.profile.as_str();
+
+        if let Some(name) = matches.value_of("add") {
+            // Always present because add requires it
+            let rss_url = matches.value_of("url").unwrap();
+            match list.add(podcast.id, name, rss_url) {
+                true => writeln!(writer, "Added the {} variant of {}", name, podcast.title)?,
+                false => writeln!(writer, "{} already has a {} variant", podcast.title, name)?,
+            }
+
+            return list.save();
+        }
+
+        if let Some(name) = matches.value_of("remove") {
+            if !list.remove(podcast.id, name) {
+                return Err(Errors::UnknownVariant(name.to_string()));
+            }
+            writeln!(writer, "Removed the {} variant of {}", name, podcast.title)?;
+
+            return list.save();
+        }
+
+        if let Some(name) = matches.value_of("prefer") {
+            if !list.prefer(podcast.id, name, profile) {
+                return Err(Errors::UnknownVariant(name.to_string()));
+            }
+            writeln!(
+                writer,
+                "The {} profile downloads the {} variant of {}",
+                profile, name, podcast.title
+            )?;
+
+            return list.save();
+        }
+
+        let preferred = list
+            .preferred(podcast.id, profile)
+            .map(|variant| variant.name.as_str())
+            .unwrap_or(MAIN_VARIANT);
+        let mark = |name: &str| match name == preferred {
+            true => "*".green().to_string(),
+            false => " ".to_string(),
+        };
+        writeln!(writer, "{} {:12} {}", mark(MAIN_VARIANT), MAIN_VARIANT, podcast.rss_url)?;
+        for variant in list.variants(podcast.id) {
+            writeln!(
+                writer,
+                "{} {:12} {}",
+                mark(&variant.name),
+                variant.name,
+                variant.rss_url
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets or unsets extra headers of the requests of the podcast, or lists its headers
+    fn headers<W>(&self, matches: &ArgMatches, podcast: &Podcast, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let mut list = HeaderList::load(&self.config.app_directory, self.config.strict)?;
+        let changes = ["set", "unset", "agent", "referrer"];
+        if !changes.iter().any(|change| matches.is_present(change)) {
+            for header in list.headers(podcast.id) {
+                writeln!(writer, "{}: {}", header.name.green(), header.value)?;
+            }
+
+            return Ok(());
+        }
+
+        for name in matches.values_of("unset").into_iter().flatten() {
+            match list.unset(podcast.id, name) {
+                true => writeln!(writer, "{} no longer sends {}", podcast.title, name)?,
+                false => writeln!(writer, "{} doesn't send {}", podcast.title, name)?,
+            }
+        }
+        let mut changed = Vec::new();
+        for header in matches.values_of("set").into_iter().flatten() {
+            changed.push(web::parse_header(header)?);
+        }
+        if let Some(user_agent) = matches.value_of("agent") {
+            changed.push(web::parse_header(&format!(
+                "User-Agent: {}",
+                headers::user_agent(user_agent)
+            ))?);
+        }
+        if let Some(referrer) = matches.value_of("referrer") {
+            changed.push(web::parse_header(&format!(
+                "Referer: {}",
+                headers::referrer(referrer, &podcast.url)
+            ))?);
+        }
+        for (name, value) in changed {
+            list.set(podcast.id, &name, &value);
+            writeln!(writer, "{} sends {}", podcast.title, name)?;
+        }
+
+        list.save()
+    }
+
+    /// Prints the RSS URL of the podcast as a QR code. with an episode id, the URL of the episode's
+    /// enclosure instead, which a phone can stream
+    fn qr<W>(&self, podcast: &Podcast, episode_id: Option<&str>, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let (title, url) = match episode_id {
+            Some(episode_id) => {
+                let episodes_file = FileSystem::new(
+                    &self.config.app_directory,
+                    &podcast.id.to_string(),
+                    vec![FilePermissions::Read],
+                )
+                .open()
+                .map_err(|_error| Errors::WrongID(podcast.id.to_string()))?;
+                let episodes = read_episodes(episodes_file, self.config.strict)?;
+                let episode = resolver::episode(&episodes, episode_id)?;
+
+                (episode.title.clone(), episode.download_url().to_string())
+            }
+            None => (podcast.title.clone(), podcast.rss_url.clone()),
+        };
+
+        let code = QrCode::encode(url.as_bytes()).ok_or_else(|| Errors::TooLongForQr(url.clone()))?;
+        writeln!(
+            writer,
+            "{}",
+            code.render(colored::control::SHOULD_COLORIZE.should_colorize())
+        )?;
+        writeln!(writer, "{}", title.bold())?;
+        writeln!(writer, "{}", url)?;
+
+        Ok(())
+    }
+
+    /// Prints the details of the podcast under the preview of its artwork
+    fn info<W>(&self, podcast: &Podcast, preview: Option<&str>, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        if let Some(preview) = preview {
+            writeln!(writer, "{}", preview)?;
+        }
+        writeln!(writer, "{:14}{}", "Title:".green(), podcast.title)?;
+        if !podcast.alias.is_empty() {
+            writeln!(writer, "{:14}{}", "Alias:".green(), podcast.alias)?;
+        }
+        writeln!(writer, "{:14}{}", "ID:".green(), podcast.id)?;
+        writeln!(writer, "{:14}{}", "Website:".green(), podcast.url)?;
+        writeln!(writer, "{:14}{}", "RSS URL:".green(), podcast.rss_url)?;
+        if !podcast.tags.is_empty() {
+            writeln!(
+                writer,
+                "{:14}{}",
+                "Tags:".green(),
+                podcast.tags().collect::<Vec<&str>>().join(", ")
+            )?;
+        }
+        // Podcasts which were never updated have no episodes yet
+        let episodes = Episodes::new(self.matches, self.config)
+            .podcast_episodes(podcast.id)
+            .unwrap_or_default();
+        writeln!(writer, "{:14}{}", "Episodes:".green(), episodes.len())?;
+        if podcast.last_update > 0 {
+            writeln!(
+                writer,
+                "{:14}{}",
+                "Updated:".green(),
+                dates::relative_age(podcast.last_update, dates::now())
+            )?;
+        }
+        if podcast.paused {
+            writeln!(writer, "{:14}yes", "Paused:".green())?;
+        }
+
+        Ok(())
+    }
+
+    /// The reasons for offering to remove the passed podcast in `podcasts cleanup`. empty if the
+    /// podcast is fine
+    fn stale_reasons(&self, podcast: &Podcast) -> Result<Vec<String>, Errors> {
+        let mut reasons = Vec::new();
+
+        if podcast.failures >= MAX_FAILURES {
+            reasons.push(format!("the last {} updates failed", podcast.failures));
+        }
+
+        let newest = match fs::File::open(self.config.app_directory.join(podcast.id.to_string())) {
+            Ok(episodes) => read_episodes(episodes, self.config.strict)?
+                .iter()
+                .filter_map(|episode| dates::parse_pub_date(&episode.pub_date))
+                .max(),
+            // The podcast was never updated
+            Err(_error) => None,
+        };
+        if let Some(newest) = newest {
+            if dates::now().saturating_sub(newest) > STALE_AFTER {
+                reasons.push(format!("no new episodes since {}", dates::format_date(newest)));
+            }
+        }
+
+        // Without a player, downloading an episode is the closest thing to listening to it
+        let downloaded = fs::read_dir(self.config.podcast_directory(podcast.id))
+            .map(|mut entries| entries.next().is_some())
+            .unwrap_or(false);
+        if !downloaded {
+            reasons.push("no episodes were ever downloaded".to_string());
+        }
+
+        Ok(reasons)
+    }
+
+    /// Asks what to do with each podcast which has reasons for being removed. returns the podcasts
+    /// to keep, with the paused ones marked
+    fn cleanup<R, W>(
+        &self,
+        candidates: Vec<(Podcast, Vec<String>)>,
+        mut input: R,
+        mut writer: W,
+    ) -> Result<Vec<Podcast>, Errors>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let mut podcasts = Vec::new();
+        let mut offered = 0;
+
+        for (mut podcast, reasons) in candidates {
+            if reasons.is_empty() || podcast.paused {
+                podcasts.push(podcast);
+                continue;
+            }
+
+            offered += 1;
+            writeln!(writer, "{} ({})", podcast.title.bold(), reasons.join(", "))?;
+            write!(writer, "[p]ause, [r]emove or [k]eep? [k] ")?;
+            writer.flush()?;
+
+            let mut answer = String::new();
+            input.read_line(&mut answer)?;
+            match answer.trim().to_lowercase().as_str() {
+                "p" | "pause" => {
+                    podcast.paused = true;
+                    writeln!(writer, "Paused {}", podcast.title)?;
+                    podcasts.push(podcast);
+                }
+                "r" | "remove" => writeln!(writer, "Removed {}", podcast.title)?,
+                _ => podcasts.push(podcast),
+            }
+        }
+
+        if offered == 0 {
+            writeln!(writer, "All the podcasts look active")?;
+        }
+
+        Ok(podcasts)
+    }
+
+    /// Removes the podcasts the passed values refer to and returns them. a value is the ID or the
+    /// RSS URL of a podcast, or else a part of the titles of podcasts, ignoring the case. when a
+    /// part of a title matches several podcasts, asks before removing all of them
+    fn remove<R, W>(
+        &self,
+        podcasts: &mut Vec<Podcast>,
+        values: &[&str],
+        mut input: R,
+        mut writer: W,
+    ) -> Result<Vec<Podcast>, Errors>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let mut ids = HashSet::new();
+
+        for value in values {
+            let exact: Vec<u64> = podcasts
+                .iter()
+                .filter(|podcast| podcast.id.to_string() == *value || podcast.rss_url == *value)
+                .map(|podcast| podcast.id)
+                .collect();
+            if !exact.is_empty() {
+                ids.extend(exact);
+                continue;
+            }
+
+            let title = value.to_lowercase();
+            let matching: Vec<&Podcast> = podcasts
+                .iter()
+                .filter(|podcast| {
+                    !title.is_empty()
+                        && (podcast.title.to_lowercase().contains(&title)
+                            || podcast.alias.to_lowercase().contains(&title))
+                })
+                .collect();
+            match matching[..] {
+                [] => writeln!(writer, "No podcast matches {}", value)?,
+                [podcast] => {
+                    ids.insert(podcast.id);
+                }
+                _ => {
+                    writeln!(writer, "{} podcasts match {}", matching.len(), value.bold())?;
+                    for podcast in matching.iter() {
+                        writeln!(writer, "  {} {}", podcast.id, podcast.name())?;
+                    }
+                    write!(writer, "Remove all of them? [y/N] ")?;
+                    writer.flush()?;
+
+                    let mut answer = String::new();
+                    input.read_line(&mut answer)?;
+                    match answer.trim().to_lowercase().as_str() {
+                        "y" | "yes" => ids.extend(matching.iter().map(|podcast| podcast.id)),
+                        _ => writeln!(writer, "Kept the podcasts matching {}", value)?,
                     }
-                    let rss_channel = rss_channel.unwrap();
-
-                    // Get needed data from RSS feed and return new Podcast struct
-                    let podcast_title = rss_channel.title().to_string();
-                    let podcast_url = rss_channel.link().to_string();
-                    let rss_url = url.to_string();
-                    let mut hasher = DefaultHasher::new();
-                    rss_url.hash(&mut hasher);
-
-                    Some(Podcast {
-                        id: hasher.finish(),
-                        url: podcast_url,
-                        rss_url,
-                        title: podcast_title,
-                    })
                 }
-                Err(_err) => None,
-            })
-            .collect();
+            }
+        }
 
-        // If some podcasts were previously saved, append with no headers
-        let mut writer = if saved_urls.len() > 0 {
-            csv::WriterBuilder::new().has_headers(false).from_writer(writer)
-        } else {
-            csv::WriterBuilder::new().has_headers(true).from_writer(writer)
-        };
+        let (removed, kept): (Vec<Podcast>, Vec<Podcast>) =
+            podcasts.drain(..).partition(|podcast| ids.contains(&podcast.id));
+        *podcasts = kept;
+        for podcast in removed.iter() {
+            writeln!(writer, "Removed {}", podcast.name())?;
+        }
 
-        for podcast in podcasts {
-            writer.serialize(podcast)?;
+        Ok(removed)
+    }
+
+    /// Deletes the saved episodes and the downloads of a removed podcast
+    fn purge(&self, podcast: &Podcast) -> Result<(), Errors> {
+        let episodes = self.config.app_directory.join(podcast.id.to_string());
+        if episodes.exists() {
+            fs::remove_file(episodes)?;
         }
 
-        writer.flush()?;
+        // A directory of its own may hold other files, e.g when it's the music directory
+        let downloads = self.config.podcast_directory(podcast.id);
+        if !podcast.download_directory.is_empty() {
+            println!(
+                "Deleted the episodes of {}. The downloads in {} are kept",
+                podcast.title,
+                downloads.display()
+            );
+            return Ok(());
+        }
+        if downloads.exists() {
+            fs::remove_dir_all(downloads)?;
+        }
+
+        println!("Deleted the episodes and downloads of {}", podcast.title);
         Ok(())
     }
 
-    /// Remove the passed podcasts from the "podcast_list.csv" file which is located in the
-    /// PODCASTS_DIR directory. does nothing if the passed values are not present in the file
-    fn remove<R, W>(&self, remove_values: &Values, reader: R, writer: W) -> Result<(), Errors>
+    /// Fetches the feeds of the podcasts in parallel and writes what was found for each one. fails
+    /// when there are feeds which updates get no episodes out of
+    fn check<W>(&self, podcasts: &[Podcast], mut writer: W) -> Result<(), Errors>
     where
-        R: Read,
         W: Write,
     {
-        let values: Vec<&str> = remove_values.clone().collect();
-        let mut reader = csv::Reader::from_reader(reader);
-
-        // We overwrite the whole file with the remaining podcasts (minus the ones passed as args)
-        let filtered_podcasts: Vec<Podcast> = reader
-            .deserialize()
-            .filter_map(|item: Result<Podcast, csv::Error>| item.ok())
-            .filter(|podcast| values.iter().all(|value| *value != podcast.rss_url))
+        let tags: Vec<&str> = self.matches.values_of("tag").into_iter().flatten().collect();
+        let podcasts: Vec<&Podcast> = podcasts
+            .iter()
+            .filter(|podcast| tags.is_empty() || tags.iter().any(|tag| podcast.has_tag(tag)))
+            .collect();
+        let urls: Vec<&str> = podcasts.iter().map(|podcast| podcast.rss_url.as_str()).collect();
+
+        let headers = HeaderList::load(&self.config.app_directory, self.config.strict)?
+            .by_url(podcasts.iter().map(|podcast| (podcast.rss_url.as_str(), podcast.id)));
+        let web = web::Web::new(time::Duration::from_secs(10), &self.config.http)
+            .stats(&self.config.network)
+            .progress(Arc::clone(&self.config.progress))
+            .jobs(self.config.jobs)
+            .headers(headers);
+        let responses: HashMap<&str, Result<Bytes, Errors>> = self
+            .config
+            .timings
+            .measure(Phase::Fetch, || web.get(&urls))
+            .into_iter()
             .collect();
 
-        let mut writer = csv::Writer::from_writer(writer);
-        for podcast in filtered_podcasts {
-            writer.serialize(podcast)?;
+        let mut broken = 0;
+        for podcast in podcasts {
+            let health = match responses.get(podcast.rss_url.as_str()) {
+                Some(response) => FeedHealth::of(&podcast.rss_url, response, web.moved(&podcast.rss_url)),
+                None => FeedHealth::HttpError("No response".to_string()),
+            };
+            if health.is_broken() {
+                broken += 1;
+            }
+            writeln!(writer, "{} {} {}", podcast.id, podcast.name().bold(), health)?;
         }
 
-        writer.flush()?;
+        match broken {
+            0 => Ok(()),
+            broken => Err(Errors::UnhealthyFeeds(broken)),
+        }
+    }
 
-        Ok(())
+    /// Searches the iTunes directory for podcasts matching the passed term and prints the ones
+    /// which have an RSS feed
+    fn search<W>(&self, term: &str, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let url = web::search_url(term);
+        let cache = ResponseCache::new(&self.config.app_directory, SEARCH_CACHE_TTL);
+        let cached = match self.matches.is_present("no-cache") {
+            true => None,
+            false => cache.get(&url),
+        };
+
+        let response = match &cached {
+            Some(response) => response.clone(),
+            None => {
+                let (_url, response) = self
+                    .config
+                    .timings
+                    .measure(Phase::Fetch, || {
+                        web::Web::new(time::Duration::from_secs(10), &self.config.http)
+                            .stats(&self.config.network)
+                            .progress(Arc::clone(&self.config.progress))
+                            .jobs(self.config.jobs)
+                            .get(&[&url])
+                    })
+                    .pop()
+                    .ok_or_else(|| Errors::NotFound(url.clone()))?;
+                response?
+            }
+        };
+
+        let parsed: ITunesResponse = self
+            .config
+            .timings
+            .measure(Phase::Parse, || serde_json::from_slice(&response[..]))
+            .map_err(|_error| Errors::NotFound(url.clone()))?;
+        // Only valid responses are cached. searching doesn't take the lock, the cache is only an
+        // optimization and a response which can't be saved is fetched once more next time
+        if cached.is_none() {
+            if let Err(error) = cache.put(&url, &response[..]) {
+                tracing::warn!("Can't cache the search results. {}", error);
+            }
+        }
+        let results: Vec<SearchResult> = parsed
+            .results
+            .into_iter()
+            .filter_map(|result| {
+                Some(SearchResult {
+                    rss_url: result.feed_url?,
+                    title: result.collection_name,
+                    author: result.artist_name,
+                })
+            })
+            .collect();
+
+        if results.is_empty() {
+            writeln!(writer, "No podcasts found for \"{}\"", term)?;
+            return Ok(());
+        }
+
+        for (index, result) in results.iter().enumerate() {
+            writeln!(writer, "{}. {}", index + 1, result.title)?;
+            writeln!(writer, "   {:12}{}", "Author:".green(), result.author)?;
+            writeln!(writer, "   {:12}{}", "RSS URL:".green(), result.rss_url)?;
+        }
+        writeln!(writer, "Use podcasts --add <number> to add one of the results")?;
+
+        self.config.timings.measure(Phase::Store, || {
+            let file = FileSystem::new(&self.config.app_directory, SEARCH_RESULTS_FILE, vec![]).atomic()?;
+            let mut writer = csv::Writer::from_writer(file);
+            for result in results {
+                writer.serialize(result)?;
+            }
+
+            let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+            Ok(file.commit()?)
+        })
+    }
+
+    /// The results of the last search. empty if nothing was searched yet
+    fn search_results(&self) -> Vec<SearchResult> {
+        match csv::Reader::from_path(self.config.app_directory.join(SEARCH_RESULTS_FILE)) {
+            Ok(mut reader) => reader
+                .deserialize()
+                .filter_map(|item: Result<SearchResult, csv::Error>| item.ok())
+                .collect(),
+            Err(_error) => Vec::new(),
+        }
     }
 
     /// Lists the saved podcasts
@@ -207,11 +1388,40 @@ impl<'a> Podcasts<'a> {
         R: Read,
         W: Write,
     {
-        let mut reader = csv::Reader::from_reader(reader);
+        let show_art = self.matches.is_present("art");
+        let tags: Vec<&str> = self.matches.values_of("tag").into_iter().flatten().collect();
+
+        // With several tags the podcasts which have any of them are listed
+        let podcasts = read_podcasts(reader, self.config.strict)?
+            .into_iter()
+            .filter(|podcast| tags.is_empty() || tags.iter().any(|tag| podcast.has_tag(tag)));
+        if self.matches.value_of("format") == Some("table") {
+            let mut table = Table::new(vec!["ID", "UPDATED", "TITLE", "TAGS"], 2);
+            for podcast in podcasts {
+                let updated = match podcast.last_update {
+                    0 => "-".to_string(),
+                    last_update => dates::format_date(last_update),
+                };
+                let title = match podcast.paused {
+                    true => format!("{} (paused)", podcast.name()),
+                    false => podcast.name().to_string(),
+                };
+                table.push(vec![podcast.id.to_string(), updated, title, podcast.tags.clone()]);
+            }
+
+            return Ok(table.write(writer, table::terminal_width())?);
+        }
 
-        for value in reader.deserialize() {
-            let podcast: Podcast = value?;
-            writeln!(writer, "{}", podcast)?;
+        for podcast in podcasts {
+            write!(writer, "{}", podcast)?;
+            if show_art {
+                let path = art::path(&self.config.app_directory, podcast.id);
+                match path.exists() {
+                    true => writeln!(writer, "{:12}{}", "Art:".green(), path.display())?,
+                    false => writeln!(writer, "{:12}-", "Art:".green())?,
+                }
+            }
+            writeln!(writer)?;
         }
 
         Ok(())
@@ -221,18 +1431,11 @@ impl<'a> Podcasts<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Config;
+    use crate::{fixtures, Config};
     use clap::{App, Arg};
-    use std::path::PathBuf;
 
     fn create_config() -> Config {
-        let app_directory = "/Users/dmitryshur/.podcasts";
-        let download_directory = "/Users/dmitryshur/.podcasts/downloads";
-
-        Config {
-            app_directory: PathBuf::from(app_directory),
-            download_directory: PathBuf::from(download_directory),
-        }
+        fixtures::config()
     }
 
     fn create_app() -> App<'static> {
@@ -240,14 +1443,14 @@ mod tests {
             App::new("podcasts")
                 .arg(
                     Arg::with_name("list")
-                        .about("Show a list of previously added RSS feeds")
+                        .help("Show a list of previously added RSS feeds")
                         .short('l')
                         .long("--list")
                         .conflicts_with_all(&["add", "remove"]),
                 )
                 .arg(
                     Arg::with_name("add")
-                        .about("Add new RSS feed")
+                        .help("Add new RSS feed")
                         .short('a')
                         .long("--add")
                         .takes_value(true)
@@ -256,12 +1459,93 @@ mod tests {
                 )
                 .arg(
                     Arg::with_name("remove")
-                        .about("Remove an existing RSS feed")
+                        .help("Remove a podcast by ID, RSS URL or title")
                         .short('r')
                         .long("--remove")
                         .takes_value(true)
                         .multiple(true)
                         .conflicts_with_all(&["list", "add"]),
+                )
+                .arg(
+                    Arg::with_name("rename")
+                        .long("--rename")
+                        .takes_value(true)
+                        .number_of_values(2)
+                        .conflicts_with_all(&["list", "add", "remove"]),
+                )
+                .arg(Arg::with_name("purge").long("--purge").requires("remove"))
+                .arg(
+                    Arg::with_name("check")
+                        .long("--check")
+                        .conflicts_with_all(&["list", "add", "remove", "rename"]),
+                )
+                .arg(
+                    Arg::with_name("search")
+                        .help("Search for podcasts by name")
+                        .short('s')
+                        .long("--search")
+                        .takes_value(true)
+                        .conflicts_with_all(&["list", "add", "remove"]),
+                )
+                .arg(
+                    Arg::with_name("no-cache")
+                        .help("Ignore the cached search results")
+                        .long("--no-cache")
+                        .requires("search"),
+                )
+                .subcommand(App::new("cleanup"))
+                .subcommand(
+                    App::new("window")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("days").required(true).index(2)),
+                )
+                .subcommand(
+                    App::new("sync-count")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("count").required(true).index(2)),
+                )
+                .subcommand(
+                    App::new("keep")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("count").required(true).index(2)),
+                )
+                .arg(Arg::with_name("tag").long("--tag").multiple(true).takes_value(true))
+                .arg(Arg::with_name("art").long("--art").requires("list"))
+                .arg(Arg::with_name("format").long("--format").takes_value(true))
+                .subcommand(
+                    App::new("tag")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("tags").required(true).multiple(true).index(2))
+                        .arg(Arg::with_name("remove").long("--remove")),
+                )
+                .subcommand(
+                    App::new("enrich")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("off").long("--off")),
+                )
+                .subcommand(
+                    App::new("priority")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("weight").required(true).index(2)),
+                )
+                .subcommand(
+                    App::new("variants")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("add").long("--add").takes_value(true).requires("url"))
+                        .arg(Arg::with_name("url").long("--url").takes_value(true))
+                        .arg(Arg::with_name("remove").long("--remove").takes_value(true))
+                        .arg(Arg::with_name("prefer").long("--prefer").takes_value(true)),
+                )
+                .subcommand(
+                    App::new("headers")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("set").long("--set").multiple(true).takes_value(true))
+                        .arg(Arg::with_name("unset").long("--unset").multiple(true).takes_value(true)),
+                )
+                .subcommand(
+                    App::new("qr")
+                        .arg(Arg::with_name("id").required(true).index(1))
+                        .arg(Arg::with_name("episode-id").long("--episode-id").takes_value(true)),
                 ),
         )
     }
@@ -276,14 +1560,14 @@ mod tests {
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         // We pass an empty reader, so the headers line should be added
         let input = String::new();
         let input = input.as_bytes();
         let mut output = Vec::new();
-        let expected_output = r###"id,url,rss_url,title
-12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
+        let expected_output = r###"id,url,rss_url,title,paused,failures,window,sync_count,last_update,keep,priority,tags,alias,enrich,download_directory,file_template,manual
+12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203,false,0,0,0,0,0,0,,,false,,,false
 "###;
 
         podcasts
@@ -305,15 +1589,15 @@ mod tests {
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         // We pass an empty reader, so the headers line should be added
         let input = String::new();
         let input = input.as_bytes();
         let mut output = Vec::new();
-        let expected_output = r###"id,url,rss_url,title
-12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
-15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats
+        let expected_output = r###"id,url,rss_url,title,paused,failures,window,sync_count,last_update,keep,priority,tags,alias,enrich,download_directory,file_template,manual
+12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203,false,0,0,0,0,0,0,,,false,,,false
+15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats,false,0,0,0,0,0,0,,,false,,,false
 "###;
 
         podcasts
@@ -333,13 +1617,13 @@ mod tests {
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats"###;
         let input = input.as_bytes();
         let mut output = Vec::new();
-        let expected_output = r###"id,url,rss_url,title
-12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
+        let expected_output = r###"id,url,rss_url,title,paused,failures,window,sync_count,last_update,keep,priority,tags,alias,enrich,download_directory,file_template,manual
+12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203,false,0,0,0,0,0,0,,,false,,,false
 "###;
 
         podcasts
@@ -361,7 +1645,7 @@ mod tests {
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"id,url,rss_url,title
 12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
@@ -369,7 +1653,11 @@ mod tests {
 "###;
         let input = input.as_bytes();
         let mut output = Vec::new();
-        let expected_output = "";
+        // The list is rewritten as is, in the current format
+        let expected_output = r###"id,url,rss_url,title,paused,failures,window,sync_count,last_update,keep,priority,tags,alias,enrich,download_directory,file_template,manual
+12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203,false,0,0,0,0,0,0,,,false,,,false
+15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats,false,0,0,0,0,0,0,,,false,,,false
+"###;
 
         podcasts
             .add(&podcast_matches.values_of("add").unwrap(), input, &mut output)
@@ -383,7 +1671,7 @@ mod tests {
         let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--list"]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"id,url,rss_url,title
 12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
@@ -395,6 +1683,19 @@ mod tests {
             url: "https://developers.google.com/web/shows/http203/podcast/".to_string(),
             rss_url: "http://feeds.feedburner.com/Http203Podcast".to_string(),
             title: "HTTP 203".to_string(),
+            paused: false,
+            failures: 0,
+            window: 0,
+            sync_count: 0,
+            last_update: 0,
+            keep: 0,
+            priority: 0,
+            tags: String::new(),
+            alias: String::new(),
+            enrich: false,
+            download_directory: String::new(),
+            file_template: String::new(),
+            manual: false,
         };
         let expected_output = podcast.to_string();
 
@@ -408,7 +1709,7 @@ mod tests {
         let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--list"]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"id,url,rss_url,title
 12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
@@ -421,6 +1722,19 @@ mod tests {
             url: "https://developers.google.com/web/shows/http203/podcast/".to_string(),
             rss_url: "http://feeds.feedburner.com/Http203Podcast".to_string(),
             title: "HTTP 203".to_string(),
+            paused: false,
+            failures: 0,
+            window: 0,
+            sync_count: 0,
+            last_update: 0,
+            keep: 0,
+            priority: 0,
+            tags: String::new(),
+            alias: String::new(),
+            enrich: false,
+            download_directory: String::new(),
+            file_template: String::new(),
+            manual: false,
         };
 
         let second_podcast = Podcast {
@@ -428,6 +1742,19 @@ mod tests {
             url: "https://syntax.fm".to_string(),
             rss_url: "https://feed.syntax.fm/rss".to_string(),
             title: "Syntax - Tasty Web Development Treats".to_string(),
+            paused: false,
+            failures: 0,
+            window: 0,
+            sync_count: 0,
+            last_update: 0,
+            keep: 0,
+            priority: 0,
+            tags: String::new(),
+            alias: String::new(),
+            enrich: false,
+            download_directory: String::new(),
+            file_template: String::new(),
+            manual: false,
         };
 
         let expected_output = format!("{}\n{}", first_podcast, second_podcast);
@@ -447,22 +1774,25 @@ mod tests {
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"id,url,rss_url,title
 12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
 15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats
 "###;
-        let input = input.as_bytes();
+        let mut list = read_podcasts(input.as_bytes(), true).unwrap();
+        let values: Vec<&str> = podcast_matches.values_of("remove").unwrap().collect();
         let mut output = Vec::new();
-        let expected_output = r###"id,url,rss_url,title
-15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats
+        let expected_output = r###"id,url,rss_url,title,paused,failures,window,sync_count,last_update,keep,priority,tags,alias,enrich,download_directory,file_template,manual
+15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats,false,0,0,0,0,0,0,,,false,,,false
 "###;
 
-        podcasts
-            .remove(&podcast_matches.values_of("remove").unwrap(), input, &mut output)
+        let removed = podcasts
+            .remove(&mut list, &values, "".as_bytes(), Vec::new())
             .expect("Can't remove podcast");
+        write_podcasts(&list, &mut output).unwrap();
 
+        assert_eq!(removed.len(), 1);
         assert_eq!(std::str::from_utf8(&output).unwrap(), expected_output);
     }
 
@@ -472,26 +1802,239 @@ mod tests {
             "pcasts",
             "podcasts",
             "--remove",
-            "http://feeds.feedburner.com/Http203Podcast",
+            "12772734294147401495",
             "--remove",
             "https://feed.syntax.fm/rss",
         ]);
         let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
         let config = create_config();
-        let podcasts = Podcasts::new(&podcast_matches, &config);
+        let podcasts = Podcasts::new(podcast_matches, &config);
 
         let input = r###"id,url,rss_url,title
 12772734294147401495,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
 15913066141282366353,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats
 "###;
-        let input = input.as_bytes();
+        let mut list = read_podcasts(input.as_bytes(), true).unwrap();
+        let values: Vec<&str> = podcast_matches.values_of("remove").unwrap().collect();
         let mut output = Vec::new();
         let expected_output = "";
 
         podcasts
-            .remove(&podcast_matches.values_of("remove").unwrap(), input, &mut output)
+            .remove(&mut list, &values, "".as_bytes(), Vec::new())
             .expect("Can't remove podcast");
+        write_podcasts(&list, &mut output).unwrap();
 
         assert_eq!(std::str::from_utf8(&output).unwrap(), expected_output);
     }
+
+    #[test]
+    fn podcasts_remove_by_title() {
+        let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--remove", "syntax"]);
+        let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
+        let config = create_config();
+        let podcasts = Podcasts::new(podcast_matches, &config);
+
+        let input = r###"id,url,rss_url,title
+1,https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats
+2,https://syntax.fm,https://feed.syntax.fm/supper,Syntax Supper Club
+3,https://developers.google.com/web/shows/http203/podcast/,http://feeds.feedburner.com/Http203Podcast,HTTP 203
+"###;
+
+        // Several podcasts match, so they're only removed after confirming
+        let mut list = read_podcasts(input.as_bytes(), true).unwrap();
+        let removed = podcasts
+            .remove(&mut list, &["SYNTAX"], "n\n".as_bytes(), Vec::new())
+            .expect("Can't remove podcast");
+        assert!(removed.is_empty());
+        assert_eq!(list.len(), 3);
+
+        let removed = podcasts
+            .remove(&mut list, &["SYNTAX"], "y\n".as_bytes(), Vec::new())
+            .expect("Can't remove podcast");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(list.len(), 1);
+
+        let mut output = Vec::new();
+        let removed = podcasts
+            .remove(&mut list, &["http 2", "missing"], "".as_bytes(), &mut output)
+            .expect("Can't remove podcast");
+        assert_eq!(removed.len(), 1);
+        assert!(list.is_empty());
+        assert_eq!(
+            std::str::from_utf8(&output).unwrap(),
+            "No podcast matches missing\nRemoved HTTP 203\n"
+        );
+    }
+
+    #[test]
+    fn podcasts_check() {
+        let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--check"]);
+        let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
+        let config = create_config();
+        let podcasts = Podcasts::new(podcast_matches, &config);
+
+        let input = r###"id,url,rss_url,title
+1,https://syntax.fm,https://feed.syntax.fm/rss,Syntax
+2,https://example.com,https://example.com/gone.xml,Gone
+"###;
+        let list = read_podcasts(input.as_bytes(), true).unwrap();
+
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        let result = podcasts.check(&list, &mut output);
+        assert!(matches!(result, Err(Errors::UnhealthyFeeds(1))));
+        let output = std::str::from_utf8(&output).unwrap();
+        assert!(output.starts_with("1 Syntax OK ("));
+        assert!(output.ends_with("\n2 Gone Failed it's not an RSS or Atom feed\n"));
+
+        let not_found = Err(Errors::NotFound("https://example.com/gone.xml".to_string()));
+        assert_eq!(
+            FeedHealth::of("https://example.com/gone.xml", &not_found, None),
+            FeedHealth::HttpError("Resource not found https://example.com/gone.xml".to_string())
+        );
+
+        let feed = r###"<rss xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd"><channel>
+<title>Moved</title><link>https://example.com</link><description>-</description>
+<itunes:new-feed-url>https://example.com/new.xml</itunes:new-feed-url>
+<item><guid>a</guid><title>A</title><pubDate>Wed, 22 Jul 2020 13:00:00 +0000</pubDate>{}</item>
+</channel></rss>"###;
+        let enclosure = r#"<enclosure url="https://example.com/a.mp3" length="1" type="audio/mpeg"/>"#;
+        let with_audio = Ok(Bytes::from(feed.replace("{}", enclosure)));
+        assert_eq!(
+            FeedHealth::of("https://example.com/old.xml", &with_audio, None),
+            FeedHealth::Moved("https://example.com/new.xml".to_string())
+        );
+        assert_eq!(
+            FeedHealth::of("https://example.com/new.xml", &with_audio, None),
+            FeedHealth::Ok(1)
+        );
+        let without_audio = Ok(Bytes::from(feed.replace("{}", "")));
+        assert_eq!(
+            FeedHealth::of("https://example.com/new.xml", &without_audio, None),
+            FeedHealth::NoEnclosures
+        );
+    }
+
+    #[test]
+    fn podcasts_search() {
+        let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--search", "syntax"]);
+        let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
+        let config = create_config();
+        let podcasts = Podcasts::new(podcast_matches, &config);
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+
+        podcasts.search("syntax", &mut output).expect("Can't search podcasts");
+
+        // The second result has no feed, so it isn't shown
+        let expected_output = r###"1. Syntax - Tasty Web Development Treats
+   Author:     Wes Bos & Scott Tolinski - Full Stack JavaScript Web Developers
+   RSS URL:    https://feed.syntax.fm/rss
+Use podcasts --add <number> to add one of the results
+"###;
+        assert_eq!(std::str::from_utf8(&output).unwrap(), expected_output);
+
+        // The saved result can be added by its number
+        let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "--add", "1"]);
+        let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
+        let mut output = Vec::new();
+        podcasts
+            .add(&podcast_matches.values_of("add").unwrap(), "".as_bytes(), &mut output)
+            .expect("Can't add podcast");
+
+        assert!(std::str::from_utf8(&output).unwrap().ends_with(
+            "https://syntax.fm,https://feed.syntax.fm/rss,Syntax - Tasty Web Development Treats,false,0,0,0,0,0,0,,,false,,,false\n"
+        ));
+    }
+
+    #[test]
+    fn podcasts_tags() {
+        let mut podcast = Podcast::new(
+            "https://syntax.fm".to_string(),
+            "https://feed.syntax.fm/rss".to_string(),
+            "Syntax".to_string(),
+        );
+        podcast.add_tags(vec!["Tech", "web dev", "tech", ""]);
+        assert_eq!(podcast.tags, "tech web-dev");
+        assert!(podcast.has_tag("TECH"));
+        assert!(podcast.has_tag("Web Dev"));
+
+        podcast.remove_tags(vec!["tech", "news"]);
+        assert_eq!(podcast.tags, "web-dev");
+        assert!(!podcast.has_tag("tech"));
+    }
+
+    #[test]
+    fn podcasts_settings() {
+        let mut podcast = Podcast::new(
+            "https://syntax.fm".to_string(),
+            "https://feed.syntax.fm/rss".to_string(),
+            "Syntax".to_string(),
+        );
+        podcast.set("keep", "3").unwrap();
+        podcast.set("auto-download", "off").unwrap();
+        podcast.set("file-template", "{pub_date}-{title}").unwrap();
+        podcast.set("tags", "tech, web").unwrap();
+        assert_eq!(podcast.keep, 3);
+        assert!(podcast.manual);
+        assert_eq!(podcast.file_template, "{pub_date}-{title}");
+        assert_eq!(podcast.tags, "tech web");
+
+        assert!(matches!(podcast.set("keep", "all"), Err(Errors::InvalidSetting(_))));
+        assert!(matches!(
+            podcast.set("file-template", "{pub_date}"),
+            Err(Errors::InvalidSetting(_))
+        ));
+        assert!(matches!(podcast.set("speed", "2"), Err(Errors::InvalidSetting(_))));
+        assert_eq!(podcast.keep, 3);
+
+        // Empty values go back to the global config
+        podcast.set("file-template", "").unwrap();
+        podcast.set("auto-download", "yes").unwrap();
+        assert_eq!(podcast.file_template, "");
+        assert!(!podcast.manual);
+    }
+
+    #[test]
+    fn podcasts_cleanup() {
+        let args = create_app().get_matches_from(vec!["pcasts", "podcasts", "cleanup"]);
+        let podcast_matches = args.subcommand_matches("podcasts").expect("No podcasts matches");
+        let config = create_config();
+        let podcasts = Podcasts::new(podcast_matches, &config);
+
+        let mut broken = Podcast::new(
+            "https://example.com".to_string(),
+            "https://example.com/rss".to_string(),
+            "Broken".to_string(),
+        );
+        broken.failures = 5;
+        assert_eq!(podcasts.stale_reasons(&broken).unwrap()[0], "the last 5 updates failed");
+
+        let candidates = vec![
+            (broken, vec!["the last 5 updates failed".to_string()]),
+            (
+                Podcast::new("a".to_string(), "a/rss".to_string(), "Old".to_string()),
+                vec!["no new episodes since 2018-01-01".to_string()],
+            ),
+            (
+                Podcast::new("b".to_string(), "b/rss".to_string(), "Active".to_string()),
+                vec![],
+            ),
+        ];
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+
+        let remaining = podcasts
+            .cleanup(candidates, "r\np\n".as_bytes(), &mut output)
+            .expect("Can't clean up podcasts");
+
+        let remaining: Vec<(&str, bool)> = remaining
+            .iter()
+            .map(|podcast| (podcast.title.as_str(), podcast.paused))
+            .collect();
+        assert_eq!(remaining, vec![("Old", true), ("Active", false)]);
+        assert!(std::str::from_utf8(&output)
+            .unwrap()
+            .starts_with("Broken (the last 5 updates failed)\n[p]ause, [r]emove or [k]eep? [k] Removed Broken\n"));
+    }
 }
@@ -0,0 +1,93 @@
+//! The power source of the machine, so background syncs on laptops can wait for AC power before
+//! downloading
+use std::{fs, path::Path};
+
+/// The directory where Linux lists the power supplies
+#[cfg(target_os = "linux")]
+const POWER_SUPPLY_DIRECTORY: &str = "/sys/class/power_supply";
+
+/// The charge of the battery in percent when the machine runs on battery. None when it's on AC
+/// power, has no battery or the power source can't be read
+pub fn battery_level() -> Option<u8> {
+    #[cfg(target_os = "linux")]
+    return linux_battery_level(Path::new(POWER_SUPPLY_DIRECTORY));
+
+    #[cfg(target_os = "macos")]
+    return std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .ok()
+        .and_then(|output| pmset_battery_level(&String::from_utf8_lossy(&output.stdout)));
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    None
+}
+
+fn read_value(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok().map(|value| value.trim().to_string())
+}
+
+/// Reads the power supplies of the sysfs directory. any online mains or USB supply means the
+/// machine is charging
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn linux_battery_level(directory: &Path) -> Option<u8> {
+    let mut level = None;
+
+    for entry in fs::read_dir(directory).ok()?.flatten() {
+        let supply = entry.path();
+        match read_value(&supply.join("type")).as_deref() {
+            Some("Mains") | Some("USB") if read_value(&supply.join("online")).as_deref() == Some("1") => return None,
+            Some("Battery") if read_value(&supply.join("status")).as_deref() == Some("Discharging") => {
+                level = read_value(&supply.join("capacity")).and_then(|capacity| capacity.parse::<u8>().ok());
+            }
+            _ => {}
+        }
+    }
+
+    level
+}
+
+/// Parses the output of `pmset -g batt`, e.g "Now drawing from 'Battery Power'" followed by a line
+/// with "85%; discharging"
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn pmset_battery_level(output: &str) -> Option<u8> {
+    if !output.contains("'Battery Power'") {
+        return None;
+    }
+
+    output
+        .split_whitespace()
+        .find_map(|word| word.trim_end_matches(';').strip_suffix('%'))
+        .and_then(|level| level.parse::<u8>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn power_battery_level() {
+        let directory = fixtures::scratch();
+        for (supply, values) in [
+            ("AC", [("type", "Mains"), ("online", "0")]),
+            ("BAT0", [("type", "Battery"), ("status", "Discharging")]),
+        ]
+        .iter()
+        {
+            fs::create_dir_all(directory.join(supply)).unwrap();
+            for (name, value) in values.iter() {
+                fs::write(directory.join(supply).join(name), format!("{}\n", value)).unwrap();
+            }
+        }
+        fs::write(directory.join("BAT0").join("capacity"), "42\n").unwrap();
+        assert_eq!(linux_battery_level(&directory), Some(42));
+
+        fs::write(directory.join("AC").join("online"), "1\n").unwrap();
+        assert_eq!(linux_battery_level(&directory), None);
+
+        let output = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1234)\t85%; discharging; 4:10 remaining present: true";
+        assert_eq!(pmset_battery_level(output), Some(85));
+        assert_eq!(pmset_battery_level("Now drawing from 'AC Power'"), None);
+    }
+}
@@ -0,0 +1,302 @@
+//! Where the progress of the downloads goes. the CLI draws progress bars, while the TUI and
+//! programs which embed the downloader report it their own way or not at all
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Write,
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+};
+
+/// How often `JsonProgress` reports a download, in received bytes
+const JSON_PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Receives the progress of the requests of `Web`. the requests run in parallel, so the methods
+/// are called from several threads at once
+pub trait ProgressSink: fmt::Debug + Send + Sync {
+    /// A batch of requests starts. batches may overlap when commands run several at once
+    fn begin(&self) {}
+
+    /// The requests of a batch are done
+    fn end(&self) {}
+
+    /// The download of the URL started. the length is None when the server doesn't send it
+    fn start(&self, url: &str, length: Option<u64>);
+
+    /// More bytes of the download of the URL arrived. the chunks of a chunked download all
+    /// advance the same download
+    fn advance(&self, url: &str, bytes: u64);
+
+    /// The download of the URL finished or failed
+    fn finish(&self, url: &str);
+}
+
+/// Reports nothing, e.g while the TUI owns the terminal
+#[derive(Debug, Default)]
+pub struct NoProgress;
+
+impl ProgressSink for NoProgress {
+    fn start(&self, _url: &str, _length: Option<u64>) {}
+
+    fn advance(&self, _url: &str, _bytes: u64) {}
+
+    fn finish(&self, _url: &str) {}
+}
+
+#[derive(Default)]
+struct Bars {
+    batches: usize,
+    multi: Option<Arc<MultiProgress>>,
+    // Keeps the bars drawn until the batch ends, otherwise joining returns as soon as the first
+    // download finished
+    placeholder: Option<ProgressBar>,
+    thread: Option<JoinHandle<()>>,
+    bars: HashMap<String, ProgressBar>,
+}
+
+/// Draws a progress bar for each download, or a spinner when its length is unknown
+#[derive(Default)]
+pub struct TerminalProgress {
+    bars: Mutex<Bars>,
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for TerminalProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TerminalProgress")
+    }
+}
+
+impl ProgressSink for TerminalProgress {
+    fn begin(&self) {
+        let mut bars = match self.bars.lock() {
+            Ok(bars) => bars,
+            Err(_error) => return,
+        };
+        bars.batches += 1;
+        if bars.multi.is_some() {
+            return;
+        }
+
+        // The bars of a MultiProgress are only drawn while another thread joins it
+        let multi = Arc::new(MultiProgress::new());
+        bars.placeholder = Some(multi.add(ProgressBar::hidden()));
+        let joined = Arc::clone(&multi);
+        bars.thread = Some(std::thread::spawn(move || {
+            if let Err(_error) = joined.join_and_clear() {
+                println!("Progress bars error");
+            }
+        }));
+        bars.multi = Some(multi);
+    }
+
+    fn end(&self) {
+        let thread = match self.bars.lock() {
+            Ok(mut bars) => {
+                bars.batches = bars.batches.saturating_sub(1);
+                if bars.batches > 0 {
+                    return;
+                }
+                if let Some(placeholder) = bars.placeholder.take() {
+                    placeholder.finish_and_clear();
+                }
+                bars.multi = None;
+                bars.thread.take()
+            }
+            Err(_error) => None,
+        };
+
+        if let Some(thread) = thread {
+            if let Err(_error) = thread.join() {
+                println!("Progress bars error");
+            }
+        }
+    }
+
+    fn start(&self, url: &str, length: Option<u64>) {
+        let file_name = url.rsplit('/').next().unwrap_or(url);
+        let bar = match length {
+            Some(length) => {
+                let bar = ProgressBar::new(length);
+                bar.set_style(
+                    ProgressStyle::default_bar()
+                        .template("{prefix} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                        .progress_chars("#>-"),
+                );
+                bar.set_prefix(file_name);
+                bar
+            }
+            None => {
+                let spinner = ProgressBar::new_spinner();
+                spinner.set_style(
+                    ProgressStyle::default_spinner()
+                        .tick_strings(&["▹▹▹▹▹", "▸▹▹▹▹", "▹▸▹▹▹", "▹▹▸▹▹", "▹▹▹▸▹", "▹▹▹▹▸", "▪▪▪▪▪"])
+                        .template("{spinner:.blue} {msg}"),
+                );
+                spinner.set_message(file_name);
+                spinner
+            }
+        };
+
+        if let Ok(mut bars) = self.bars.lock() {
+            let bar = match &bars.multi {
+                Some(multi) => multi.add(bar),
+                None => bar,
+            };
+            if length.is_none() {
+                bar.enable_steady_tick(120);
+            }
+            bars.bars.insert(url.to_string(), bar);
+        }
+    }
+
+    fn advance(&self, url: &str, bytes: u64) {
+        if let Some(bar) = self.bars.lock().ok().and_then(|bars| bars.bars.get(url).cloned()) {
+            bar.inc(bytes);
+        }
+    }
+
+    fn finish(&self, url: &str) {
+        if let Some(bar) = self.bars.lock().ok().and_then(|mut bars| bars.bars.remove(url)) {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// A download `JsonProgress` reports
+struct Download {
+    length: Option<u64>,
+    received: u64,
+    // The received bytes at the last report
+    reported: u64,
+}
+
+/// Writes the progress as a JSON object per line, for programs which read the output of pcasts
+pub struct JsonProgress {
+    writer: Mutex<Box<dyn Write + Send>>,
+    downloads: Mutex<HashMap<String, Download>>,
+}
+
+impl JsonProgress {
+    pub fn new<W>(writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn write(&self, event: serde_json::Value) {
+        if let Ok(mut writer) = self.writer.lock() {
+            // The progress is only informative, so failing to report it isn't an error
+            let _ = writeln!(writer, "{}", event);
+            let _ = writer.flush();
+        }
+    }
+}
+
+impl fmt::Debug for JsonProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JsonProgress")
+    }
+}
+
+impl ProgressSink for JsonProgress {
+    fn start(&self, url: &str, length: Option<u64>) {
+        if let Ok(mut downloads) = self.downloads.lock() {
+            downloads.insert(
+                url.to_string(),
+                Download {
+                    length,
+                    received: 0,
+                    reported: 0,
+                },
+            );
+        }
+        self.write(json!({"event": "start", "url": url, "length": length}));
+    }
+
+    fn advance(&self, url: &str, bytes: u64) {
+        let report = match self.downloads.lock() {
+            Ok(mut downloads) => match downloads.get_mut(url) {
+                Some(download) => {
+                    download.received += bytes;
+                    if download.received - download.reported >= JSON_PROGRESS_STEP {
+                        download.reported = download.received;
+                        Some((download.length, download.received))
+                    } else {
+                        None
+                    }
+                }
+                None => None,
+            },
+            Err(_error) => None,
+        };
+
+        if let Some((length, received)) = report {
+            self.write(json!({"event": "progress", "url": url, "bytes": received, "length": length}));
+        }
+    }
+
+    fn finish(&self, url: &str) {
+        let received = match self.downloads.lock() {
+            Ok(mut downloads) => downloads.remove(url).map(|download| download.received),
+            Err(_error) => None,
+        };
+        self.write(json!({"event": "finish", "url": url, "bytes": received.unwrap_or(0)}));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn progress_json() {
+        let buffer = SharedBuffer::default();
+        let progress = JsonProgress::new(buffer.clone());
+        let url = "https://example.com/episode.mp3";
+
+        progress.begin();
+        progress.start(url, Some(3 * JSON_PROGRESS_STEP));
+        progress.advance(url, JSON_PROGRESS_STEP / 2);
+        progress.advance(url, JSON_PROGRESS_STEP / 2);
+        progress.advance(url, JSON_PROGRESS_STEP);
+        // Unknown downloads aren't reported
+        progress.advance("https://example.com/other.mp3", JSON_PROGRESS_STEP);
+        progress.finish(url);
+        progress.end();
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0]["event"], "start");
+        assert_eq!(events[0]["length"], 3 * JSON_PROGRESS_STEP);
+        assert_eq!(events[1]["bytes"], JSON_PROGRESS_STEP);
+        assert_eq!(events[2]["bytes"], 2 * JSON_PROGRESS_STEP);
+        assert_eq!(events[3]["event"], "finish");
+        assert_eq!(events[3]["bytes"], 2 * JSON_PROGRESS_STEP);
+    }
+}
@@ -0,0 +1,94 @@
+//! QR codes drawn in the terminal, so a feed or an episode spotted in the CLI can be opened on a
+//! phone. the codes use the medium error correction level, which phone cameras read reliably
+use colored::*;
+use qrcode::EcLevel;
+
+/// Light modules around the code, which scanners need to find it
+const QUIET_ZONE: usize = 2;
+
+#[derive(Debug)]
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encodes the passed bytes in the smallest version which fits them. None if they're longer
+    /// than the largest version holds
+    pub fn encode(data: &[u8]) -> Option<Self> {
+        let code = qrcode::QrCode::with_error_correction_level(data, EcLevel::M).ok()?;
+
+        Some(Self {
+            size: code.width(),
+            modules: code
+                .to_colors()
+                .into_iter()
+                .map(|module| module == qrcode::Color::Dark)
+                .collect(),
+        })
+    }
+
+    /// Whether the module at the passed column and row is dark. the quiet zone is light
+    fn dark(&self, x: usize, y: usize) -> bool {
+        x < self.size && y < self.size && self.modules[y * self.size + x]
+    }
+
+    /// Draws the code with half blocks, two rows of modules in each line of text. the modules are
+    /// black on white when colored, since many scanners can't read inverted codes
+    pub fn render(&self, colorize: bool) -> String {
+        let total = self.size + QUIET_ZONE * 2;
+        let module =
+            |x: usize, y: usize| x >= QUIET_ZONE && y >= QUIET_ZONE && self.dark(x - QUIET_ZONE, y - QUIET_ZONE);
+
+        let mut lines = Vec::new();
+        for y in (0..total).step_by(2) {
+            let mut line = String::new();
+            for x in 0..total {
+                let (top, bottom) = (module(x, y), y + 1 < total && module(x, y + 1));
+                let cell = match colorize {
+                    true => {
+                        let color = |dark: bool| if dark { Color::Black } else { Color::White };
+                        "▀".color(color(top)).on_color(color(bottom)).to_string()
+                    }
+                    false => match (top, bottom) {
+                        (true, true) => "█",
+                        (true, false) => "▀",
+                        (false, true) => "▄",
+                        (false, false) => " ",
+                    }
+                    .to_string(),
+                };
+                line.push_str(&cell);
+            }
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qr_encode() {
+        let code = QrCode::encode(b"https://feed.syntax.fm/rss").expect("Can't encode URL");
+        // 26 bytes need version 2
+        assert_eq!(code.size, 25);
+        // The finder pattern in the top left corner and its separator
+        assert!((0..7).all(|index| code.dark(index, 0) && code.dark(0, index)));
+        assert!((0..8).all(|index| !code.dark(index, 7) && !code.dark(7, index)));
+        assert!(code.dark(8, code.size - 8));
+
+        let rendered = code.render(false);
+        assert_eq!(rendered.lines().count(), (25 + QUIET_ZONE * 2).div_ceil(2));
+        assert!(rendered.lines().all(|line| line.chars().count() == 25 + QUIET_ZONE * 2));
+        // The quiet zone, then the first two rows of the finder pattern in the top left corner
+        let line: Vec<char> = rendered.lines().nth(1).unwrap().chars().collect();
+        assert_eq!(line[..QUIET_ZONE], [' ', ' ']);
+        assert_eq!(line[QUIET_ZONE..QUIET_ZONE + 7], ['█', '▀', '▀', '▀', '▀', '▀', '█']);
+
+        assert!(QrCode::encode(&[b'a'; 3000]).is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! The queue of the downloads, recorded in the app directory as they progress. `episodes download
+//! --resume` downloads what was requested and didn't complete, e.g when the last run died halfway
+use crate::{
+    file_system::{FilePermissions, FileSystem},
+    records, Errors,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+
+/// The file inside the app directory which holds the queue
+pub const QUEUE_FILE: &str = "download_queue.csv";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Requested,
+    Completed,
+    Failed,
+}
+
+/// A change of the state of a download. the last entry of each episode is its state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub podcast_id: u64,
+    // Empty for the completed downloads, which are known by their file only
+    pub guid: String,
+    pub file_name: String,
+    pub status: Status,
+    pub error: String,
+}
+
+impl QueueEntry {
+    pub fn new(podcast_id: u64, guid: &str, file_name: &str, status: Status) -> Self {
+        Self {
+            podcast_id,
+            guid: guid.to_string(),
+            file_name: file_name.to_string(),
+            status,
+            error: String::new(),
+        }
+    }
+}
+
+fn read(app_directory: &Path, strict: bool) -> Result<Vec<QueueEntry>, Errors> {
+    match fs::read(app_directory.join(QUEUE_FILE)) {
+        Ok(contents) => records::read(&contents[..], QUEUE_FILE, strict),
+        // Nothing was downloaded yet
+        Err(_error) => Ok(Vec::new()),
+    }
+}
+
+/// Appends the passed entries to the queue
+pub fn record(app_directory: &Path, entries: &[QueueEntry]) -> Result<(), Errors> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let file = FileSystem::new(app_directory, QUEUE_FILE, vec![FilePermissions::Append]).open()?;
+    let is_empty = file.metadata()?.len() == 0;
+
+    let mut writer = csv::WriterBuilder::new().has_headers(is_empty).from_writer(file);
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Adds the passed downloads to the queue. the queue starts over when everything in it completed
+pub fn request(app_directory: &Path, entries: &[QueueEntry], strict: bool) -> Result<(), Errors> {
+    if pending(app_directory, strict)?.is_empty() {
+        let _ = fs::remove_file(app_directory.join(QUEUE_FILE));
+    }
+
+    record(app_directory, entries)
+}
+
+/// The downloads which were requested and didn't complete, in the order they were requested
+pub fn pending(app_directory: &Path, strict: bool) -> Result<Vec<QueueEntry>, Errors> {
+    let mut downloads: Vec<QueueEntry> = Vec::new();
+    let mut indexes: HashMap<(u64, String), usize> = HashMap::new();
+    for entry in read(app_directory, strict)? {
+        let index = match entry.guid.as_str() {
+            "" => indexes.get(&(entry.podcast_id, entry.file_name.clone())).copied(),
+            guid => downloads
+                .iter()
+                .position(|download| download.podcast_id == entry.podcast_id && download.guid == guid),
+        };
+
+        match index {
+            Some(index) => {
+                // Attachments are saved under another name than the one they were requested with
+                if !entry.guid.is_empty() {
+                    indexes.insert((entry.podcast_id, entry.file_name.clone()), index);
+                    downloads[index].file_name = entry.file_name;
+                }
+                downloads[index].status = entry.status;
+                downloads[index].error = entry.error;
+            }
+            // Files which were downloaded without being requested, e.g by an older version
+            None if entry.guid.is_empty() => {}
+            None => {
+                indexes.insert((entry.podcast_id, entry.file_name.clone()), downloads.len());
+                downloads.push(entry);
+            }
+        }
+    }
+
+    Ok(downloads
+        .into_iter()
+        .filter(|download| download.status != Status::Completed)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn queue_pending() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(&directory).unwrap();
+
+        let requested = vec![
+            QueueEntry::new(1, "a", "a.mp3", Status::Requested),
+            QueueEntry::new(1, "b", "b.mp3", Status::Requested),
+            QueueEntry::new(2, "c", "c.mp3", Status::Requested),
+        ];
+        request(&directory, &requested, true).unwrap();
+        let mut failed = QueueEntry::new(1, "b", "b.mp3", Status::Failed);
+        failed.error = "HTTP 500".to_string();
+        record(
+            &directory,
+            &[QueueEntry::new(1, "", "a.mp3", Status::Completed), failed.clone()],
+        )
+        .unwrap();
+        assert_eq!(pending(&directory, true).unwrap(), vec![failed, requested[2].clone()]);
+
+        // Requesting more keeps what didn't complete
+        request(&directory, &[QueueEntry::new(2, "d", "d.mp3", Status::Requested)], true).unwrap();
+        assert_eq!(pending(&directory, true).unwrap().len(), 3);
+
+        // And starts over once everything completed
+        let completed: Vec<QueueEntry> = ["b.mp3", "c.mp3", "d.mp3"]
+            .iter()
+            .zip([1, 2, 2].iter())
+            .map(|(file_name, podcast_id)| QueueEntry::new(*podcast_id, "", file_name, Status::Completed))
+            .collect();
+        record(&directory, &completed).unwrap();
+        assert!(pending(&directory, true).unwrap().is_empty());
+        request(&directory, &[QueueEntry::new(1, "e", "e.mp3", Status::Requested)], true).unwrap();
+        assert_eq!(read(&directory, true).unwrap().len(), 1);
+    }
+}
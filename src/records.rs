@@ -0,0 +1,90 @@
+//! Reading the rows of the CSV files which hold the podcasts and the episodes
+use crate::Errors;
+use serde::de::DeserializeOwned;
+use std::io::Read;
+
+/// A row which doesn't match the expected columns
+#[derive(Debug, PartialEq)]
+pub struct InvalidRow {
+    pub line: u64,
+    pub error: String,
+}
+
+/// Deserializes all the rows of the passed CSV, separating the valid records from the invalid rows
+pub fn read_all<T, R>(reader: R) -> (Vec<T>, Vec<InvalidRow>)
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    let mut records = Vec::new();
+    let mut invalid = Vec::new();
+
+    for item in csv::Reader::from_reader(reader).deserialize() {
+        match item {
+            Ok(record) => records.push(record),
+            Err(error) => invalid.push(InvalidRow {
+                line: error.position().map(|position| position.line()).unwrap_or(0),
+                error: error.to_string(),
+            }),
+        }
+    }
+
+    (records, invalid)
+}
+
+/// Fails with the first invalid row in strict mode, otherwise warns about the skipped rows. name
+/// describes the file which was read
+pub fn check(invalid: Vec<InvalidRow>, name: &str, strict: bool) -> Result<(), Errors> {
+    if let Some(row) = invalid.first() {
+        if strict {
+            return Err(Errors::InvalidRecord(row.line, row.error.clone()));
+        }
+
+        tracing::warn!(
+            "{} malformed rows skipped in {} — run `pcasts doctor`",
+            invalid.len(),
+            name
+        );
+    }
+
+    Ok(())
+}
+
+/// Deserializes all the rows of the passed CSV. rows which don't match the expected columns are
+/// skipped with a warning, unless strict is set, in which case the first of them is an error
+pub fn read<T, R>(reader: R, name: &str, strict: bool) -> Result<Vec<T>, Errors>
+where
+    T: DeserializeOwned,
+    R: Read,
+{
+    let (records, invalid) = read_all(reader);
+    check(invalid, name, strict)?;
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Podcast;
+
+    #[test]
+    fn records_strict() {
+        let input = "id,url,rss_url,title\n\
+                     1,https://syntax.fm,https://feed.syntax.fm/rss,Syntax\n\
+                     two,https://example.com,https://example.com/rss,Broken\n\
+                     3,https://example.com,https://example.com/other\n";
+
+        let podcasts: Vec<Podcast> = read(input.as_bytes(), "podcast_list.csv", false).expect("Can't read podcasts");
+        assert_eq!(podcasts.len(), 1);
+
+        let (_podcasts, invalid) = read_all::<Podcast, _>(input.as_bytes());
+        let lines: Vec<u64> = invalid.iter().map(|row| row.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+
+        match read::<Podcast, _>(input.as_bytes(), "podcast_list.csv", true) {
+            Err(Errors::InvalidRecord(line, _error)) => assert_eq!(line, 3),
+            result => panic!("Expected an invalid record, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Turns what was typed for a podcast or an episode into the saved one it names, the same way for
+//! every command. the exact forms, e.g the ID or the guid, are tried first and a part of the title
+//! last, so a title which happens to look like an ID never hides the podcast with that ID. input
+//! which names several is asked about on a terminal and fails everywhere else
+use crate::{
+    episodes::Episode,
+    podcasts::{load_podcasts, Podcast},
+    text, Config, Errors,
+};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Names the most recently updated podcast, or the newest episode
+const LATEST: &str = "latest";
+
+/// A prefix of a guid names its episode only when it's at least this long, since the short
+/// numbers would match many guids
+const MIN_GUID_PREFIX: usize = 4;
+
+/// The items which match the input in the first way anything matches it
+fn first_matches<'a, T>(items: &'a [T], matchers: &[&dyn Fn(&T) -> bool]) -> Vec<&'a T> {
+    for matcher in matchers {
+        let matching: Vec<&T> = items.iter().filter(|item| matcher(item)).collect();
+        if !matching.is_empty() {
+            return matching;
+        }
+    }
+
+    Vec::new()
+}
+
+/// The only candidate, or the one picked from the listed candidates on a terminal
+fn choose<'a, T>(input: &str, candidates: Vec<&'a T>, describe: impl Fn(&T) -> String) -> Result<&'a T, Errors> {
+    match candidates[..] {
+        [] => return Err(Errors::WrongID(input.to_string())),
+        [candidate] => return Ok(candidate),
+        _ => {}
+    }
+
+    let descriptions: Vec<String> = candidates.iter().map(|candidate| describe(candidate)).collect();
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err(Errors::AmbiguousID(input.to_string(), descriptions));
+    }
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let index = ask(input, &descriptions, stdin.lock(), stdout.lock())?;
+    Ok(candidates[index])
+}
+
+/// Lists the candidates and reads which one was meant. an answer which isn't one of them fails
+/// like a non interactive run
+fn ask<R, W>(input: &str, descriptions: &[String], mut reader: R, mut writer: W) -> Result<usize, Errors>
+where
+    R: BufRead,
+    W: Write,
+{
+    writeln!(writer, "{} matches {}:", input, descriptions.len())?;
+    for (index, description) in descriptions.iter().enumerate() {
+        writeln!(writer, "{:>3}. {}", index + 1, description)?;
+    }
+    write!(writer, "Which one? [1-{}] ", descriptions.len())?;
+    writer.flush()?;
+
+    let mut answer = String::new();
+    reader.read_line(&mut answer)?;
+    match answer.trim().parse::<usize>() {
+        Ok(number) if number >= 1 && number <= descriptions.len() => Ok(number - 1),
+        _ => Err(Errors::AmbiguousID(input.to_string(), descriptions.to_vec())),
+    }
+}
+
+/// The podcast the input names: its ID, feed URL or alias, "latest", its place in the list of
+/// podcasts, or a part of its title
+pub fn podcast<'a>(podcasts: &'a [Podcast], input: &str) -> Result<&'a Podcast, Errors> {
+    let input = input.trim();
+    // The podcasts are listed in the order they were added
+    let position = input
+        .parse::<usize>()
+        .ok()
+        .and_then(|position| podcasts.get(position.checked_sub(1)?))
+        .map(|podcast| podcast.id);
+    let latest = podcasts.iter().map(|podcast| podcast.last_update).max();
+
+    let matching = first_matches(
+        podcasts,
+        &[
+            &|podcast| podcast.id.to_string() == input || podcast.rss_url == input,
+            &|podcast| !podcast.alias.is_empty() && text::fold(&podcast.alias) == text::fold(input),
+            &|podcast| input == LATEST && podcast.last_update > 0 && Some(podcast.last_update) == latest,
+            &|podcast| position == Some(podcast.id),
+            &|podcast| {
+                !input.is_empty() && (text::matches(&podcast.title, input) || text::matches(&podcast.alias, input))
+            },
+            &|podcast| !input.is_empty() && text::fuzzy_matches(podcast.name(), input),
+        ],
+    );
+
+    choose(input, matching, |podcast| format!("{}  {}", podcast.id, podcast.name()))
+}
+
+/// The ID of the saved podcast the input names, which is also the name of its episodes file
+pub fn podcast_id(config: &Config, input: &str) -> Result<String, Errors> {
+    Ok(podcast(&load_podcasts(config)?, input)?.id.to_string())
+}
+
+/// The podcasts the inputs name, in their order and without the ones named twice
+pub fn podcasts<'a, 'b, I>(podcasts: &'a [Podcast], inputs: I) -> Result<Vec<&'a Podcast>, Errors>
+where
+    I: IntoIterator<Item = &'b str>,
+{
+    let mut resolved: Vec<&Podcast> = Vec::new();
+    for input in inputs {
+        let podcast = podcast(podcasts, input)?;
+        if !resolved.iter().any(|saved| saved.id == podcast.id) {
+            resolved.push(podcast);
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// The episode the input names: its guid, its short number, "latest", the start of its guid, a
+/// part of its title or words of its title in any order
+pub fn episode<'a>(episodes: &'a [Episode], input: &str) -> Result<&'a Episode, Errors> {
+    let input = input.trim();
+    let number = input.parse::<u64>().ok();
+    let words = input.split_whitespace();
+    let latest = episodes.iter().filter_map(Episode::release_time).max();
+
+    let matching = first_matches(
+        episodes,
+        &[
+            &|episode| episode.guid == input,
+            &|episode| number.is_some_and(|number| episode.number > 0 && episode.number == number),
+            &|episode| input == LATEST && episode.release_time().is_some() && episode.release_time() == latest,
+            &|episode| input.len() >= MIN_GUID_PREFIX && episode.guid.starts_with(input),
+            &|episode| !input.is_empty() && text::matches(&episode.title, input),
+            // The letters of a short query are in most long titles, so its words are matched
+            &|episode| words.clone().count() > 1 && words.clone().all(|word| text::matches(&episode.title, word)),
+        ],
+    );
+
+    choose(input, matching, |episode| match episode.number {
+        0 => format!("{}  {}", episode.guid, episode.title),
+        number => format!("{}  {}", number, episode.title),
+    })
+}
+
+/// The episodes the inputs name, in their order and without the ones named twice
+pub fn episodes<'a, 'b, I>(episodes: &'a [Episode], inputs: I) -> Result<Vec<&'a Episode>, Errors>
+where
+    I: IntoIterator<Item = &'b str>,
+{
+    let mut resolved: Vec<&Episode> = Vec::new();
+    for input in inputs {
+        let episode = episode(episodes, input)?;
+        if !resolved.iter().any(|saved| saved.guid == episode.guid) {
+            resolved.push(episode);
+        }
+    }
+
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{episodes::read_episodes, fixtures};
+
+    #[test]
+    fn resolver_inputs() {
+        let mut podcasts = vec![
+            Podcast::new(
+                "https://syntax.fm".to_string(),
+                "https://feed.syntax.fm/rss".to_string(),
+                "Syntax - Tasty Web Development Treats".to_string(),
+            ),
+            Podcast::new(
+                "https://example.com".to_string(),
+                "https://example.com/rss".to_string(),
+                "Web Weekly".to_string(),
+            ),
+        ];
+        podcasts[1].alias = "weekly".to_string();
+        podcasts[1].last_update = 10;
+        let id = podcasts[0].id.to_string();
+
+        assert_eq!(podcast(&podcasts, &id).unwrap().id, podcasts[0].id);
+        assert_eq!(
+            podcast(&podcasts, "https://example.com/rss").unwrap().id,
+            podcasts[1].id
+        );
+        assert_eq!(podcast(&podcasts, "Weekly").unwrap().id, podcasts[1].id);
+        assert_eq!(podcast(&podcasts, "latest").unwrap().id, podcasts[1].id);
+        assert_eq!(podcast(&podcasts, "1").unwrap().id, podcasts[0].id);
+        assert_eq!(podcast(&podcasts, "tasty").unwrap().id, podcasts[0].id);
+        assert_eq!(podcast(&podcasts, "sntx").unwrap().id, podcasts[0].id);
+        assert!(matches!(podcast(&podcasts, "3"), Err(Errors::WrongID(_))));
+        assert!(matches!(
+            podcast(&podcasts, "web"),
+            Err(Errors::AmbiguousID(_, candidates)) if candidates.len() == 2
+        ));
+        assert_eq!(self::podcasts(&podcasts, vec!["1", &id]).unwrap().len(), 1);
+
+        let contents = fixtures::read("syntax.csv").expect("Can't read fixture");
+        let episodes = read_episodes(&contents[..], false).unwrap();
+        let guid = "50f59fbe-93db-4acf-b6db-8f9a078d315a";
+        assert_eq!(episode(&episodes, guid).unwrap().guid, guid);
+        assert_eq!(episode(&episodes, &guid[..8]).unwrap().guid, guid);
+        assert_eq!(episode(&episodes, "turbolinks").unwrap().guid, guid);
+        assert_eq!(episode(&episodes, "sprinkles hasty").unwrap().guid, guid);
+        assert_eq!(episode(&episodes, "latest").unwrap().guid, episodes[0].guid);
+        assert!(matches!(episode(&episodes, "missing episode"), Err(Errors::WrongID(_))));
+    }
+
+    #[test]
+    fn resolver_ask() {
+        let descriptions = vec!["1  First".to_string(), "2  Second".to_string()];
+        let mut output = Vec::new();
+        assert_eq!(ask("web", &descriptions, &b"2\n"[..], &mut output).unwrap(), 1);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("web matches 2:\n  1. 1  First\n"));
+
+        assert!(matches!(
+            ask("web", &descriptions, &b"3\n"[..], Vec::new()),
+            Err(Errors::AmbiguousID(_, _))
+        ));
+    }
+}
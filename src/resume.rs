@@ -0,0 +1,146 @@
+//! `pcasts continue` picks up the episode which was left in the middle most recently, here or on
+//! another device of the gpodder account, so coming back to the podcasts takes one command
+use crate::{
+    dates,
+    episodes::{Episode, Episodes},
+    open,
+    played::{PlayedEntry, PlayedList},
+    podcasts::load_podcasts,
+    Config, Errors,
+};
+use clap::ArgMatches;
+use std::collections::{HashMap, HashSet};
+
+/// The most recently played episode which was left in the middle and is still saved, with the
+/// state of its playback. the ones of removed podcasts and episodes are skipped
+fn next_episode<'a>(
+    played: &'a PlayedList,
+    episodes: &HashMap<u64, Vec<Episode>>,
+) -> Option<(&'a PlayedEntry, Episode)> {
+    played.in_progress().into_iter().find_map(|entry| {
+        episodes
+            .get(&entry.podcast_id)?
+            .iter()
+            .find(|episode| episode.guid == entry.guid)
+            .map(|episode| (entry, episode.clone()))
+    })
+}
+
+pub struct Continuer<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Continuer<'a> {
+    /// Constructs a new Continuer struct which is used to work with the sub command "continue"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Opens the episode with its default application, after downloading it if it wasn't. the
+    /// position is printed, since the default application can't be told where to start
+    pub fn run(&self) -> Result<(), Errors> {
+        let played = PlayedList::load(&self.config.app_directory);
+        let episodes = Episodes::new(self.matches, self.config);
+        let podcast_ids: HashSet<u64> = played.in_progress().iter().map(|entry| entry.podcast_id).collect();
+        let podcast_episodes: HashMap<u64, Vec<Episode>> = load_podcasts(self.config)?
+            .into_iter()
+            .filter(|podcast| podcast_ids.contains(&podcast.id))
+            // Podcasts which were never updated have no episodes file
+            .map(|podcast| (podcast.id, episodes.podcast_episodes(podcast.id).unwrap_or_default()))
+            .collect();
+
+        let (entry, episode) = match next_episode(&played, &podcast_episodes) {
+            Some(next) => next,
+            None => {
+                println!("Nothing to continue, no episode was left in the middle");
+                return Ok(());
+            }
+        };
+
+        let directory = self.config.podcast_directory(episode.podcast_id);
+        let mut path = directory.join(episode.file_name(self.config.podcast_file_template(episode.podcast_id)));
+        if !path.exists() {
+            println!("Downloading {}", episode.title);
+            // The failed downloads are reported with the outcome of the command
+            match episodes.download_batch(vec![episode.clone()])?.into_iter().next() {
+                Some((_episode, Ok(file_name))) => path = directory.join(file_name),
+                _ => return Ok(()),
+            }
+        }
+
+        println!(
+            "Continuing {} of {} at {} of {}",
+            episode.title,
+            episode.podcast,
+            dates::format_duration(entry.position),
+            dates::format_duration(entry.total)
+        );
+        open::launch(&path.display().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::played;
+    use std::fs;
+
+    fn create_entry(guid: &str, position: u64, time: u64) -> PlayedEntry {
+        PlayedEntry {
+            podcast_id: 1,
+            guid: guid.to_string(),
+            position,
+            total: 3_600,
+            time,
+            device: String::new(),
+        }
+    }
+
+    fn create_episodes(guids: &[&str]) -> HashMap<u64, Vec<Episode>> {
+        let contents = crate::fixtures::read("syntax.csv").expect("Can't read fixture");
+        let template = crate::episodes::read_episodes(&contents[..], false).unwrap().remove(0);
+        let episodes = guids
+            .iter()
+            .map(|guid| Episode {
+                guid: guid.to_string(),
+                podcast_id: 1,
+                ..template.clone()
+            })
+            .collect();
+
+        vec![(1, episodes)].into_iter().collect()
+    }
+
+    #[test]
+    fn resume_next_episode() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(&directory).unwrap();
+        played::record(
+            &directory,
+            &[
+                create_entry("older", 600, 100),
+                create_entry("finished", 3_590, 300),
+                create_entry("new", 0, 400),
+                create_entry("removed", 1_200, 500),
+                create_entry("latest", 1_800, 200),
+            ],
+        )
+        .unwrap();
+        let played = PlayedList::load(&directory);
+
+        let episodes = create_episodes(&["older", "finished", "new", "latest"]);
+        let (entry, episode) = next_episode(&played, &episodes).expect("No episode to continue");
+        assert_eq!(episode.guid, "latest");
+        assert_eq!(entry.position, 1_800);
+
+        // An episode which was finished later isn't continued anymore
+        played::record(&directory, &[create_entry("latest", 3_600, 600)]).unwrap();
+        let played = PlayedList::load(&directory);
+        let (_entry, episode) = next_episode(&played, &episodes).expect("No episode to continue");
+        assert_eq!(episode.guid, "older");
+
+        assert!(next_episode(&played, &HashMap::new()).is_none());
+    }
+}
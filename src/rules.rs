@@ -0,0 +1,294 @@
+//! The curated rules and policies of the library, kept apart from the subscriptions so they can
+//! be shared between users and machines. they're written as a small subset of TOML: arrays of
+//! tables whose values are strings or integers
+use crate::{
+    file_system::{FilePermissions, FileSystem},
+    ignore::{IgnoreList, IgnoreRule, RuleKind},
+    podcasts::{read_podcasts, write_podcasts, Podcast},
+    Config, Errors,
+};
+use clap::ArgMatches;
+use std::{collections::HashMap, fs, io::Read};
+
+/// The download policies of a single podcast. podcasts are identified by their RSS URL since
+/// their ids may differ between machines
+#[derive(Debug, Clone, PartialEq)]
+pub struct Policy {
+    pub rss_url: String,
+    pub window: u64,
+    pub sync_count: u64,
+    pub keep: u64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct RuleSet {
+    pub ignore: Vec<IgnoreRule>,
+    pub policies: Vec<Policy>,
+}
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Integer(u64),
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn parse_value(value: &str) -> Option<Value> {
+    if let Some(value) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        let mut string = String::new();
+        let mut chars = value.chars();
+        while let Some(char) = chars.next() {
+            match char {
+                '\\' => string.push(chars.next()?),
+                '"' => return None,
+                char => string.push(char),
+            }
+        }
+
+        return Some(Value::String(string));
+    }
+
+    value.parse::<u64>().ok().map(Value::Integer)
+}
+
+impl RuleSet {
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::new();
+
+        for rule in self.ignore.iter() {
+            let kind = match rule.kind {
+                RuleKind::Id => "id",
+                RuleKind::Pattern => "pattern",
+            };
+            toml.push_str(&format!(
+                "[[ignore]]\nkind = {}\nvalue = {}\n\n",
+                quote(kind),
+                quote(&rule.value)
+            ));
+        }
+
+        for policy in self.policies.iter() {
+            toml.push_str(&format!(
+                "[[podcast]]\nrss_url = {}\nwindow = {}\nsync_count = {}\nkeep = {}\n\n",
+                quote(&policy.rss_url),
+                policy.window,
+                policy.sync_count,
+                policy.keep
+            ));
+        }
+
+        toml
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, Errors> {
+        let mut tables: Vec<(String, HashMap<String, Value>)> = Vec::new();
+
+        for (index, line) in toml.lines().enumerate() {
+            let line = line.trim();
+            let invalid = || Errors::InvalidRules(format!("line {}: {}", index + 1, line));
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("[[").and_then(|line| line.strip_suffix("]]")) {
+                tables.push((name.trim().to_string(), HashMap::new()));
+                continue;
+            }
+
+            let (key, value) = line.split_once('=').ok_or_else(invalid)?;
+            let value = parse_value(value.trim()).ok_or_else(invalid)?;
+            let (_name, table) = tables.last_mut().ok_or_else(invalid)?;
+            table.insert(key.trim().to_string(), value);
+        }
+
+        let mut rules = Self::default();
+        for (name, mut table) in tables {
+            let mut string = |key: &str| match table.remove(key) {
+                Some(Value::String(value)) => Ok(value),
+                _ => Err(Errors::InvalidRules(format!(
+                    "[[{}]] is missing the string {}",
+                    name, key
+                ))),
+            };
+
+            match name.as_str() {
+                "ignore" => {
+                    let kind = match string("kind")?.as_str() {
+                        "id" => RuleKind::Id,
+                        "pattern" => RuleKind::Pattern,
+                        kind => return Err(Errors::InvalidRules(format!("unknown ignore kind {}", kind))),
+                    };
+                    rules.ignore.push(IgnoreRule {
+                        kind,
+                        value: string("value")?,
+                    });
+                }
+                "podcast" => {
+                    let rss_url = string("rss_url")?;
+                    let mut integer = |key: &str| match table.remove(key) {
+                        Some(Value::Integer(value)) => value,
+                        _ => 0,
+                    };
+                    rules.policies.push(Policy {
+                        rss_url,
+                        window: integer("window"),
+                        sync_count: integer("sync_count"),
+                        keep: integer("keep"),
+                    });
+                }
+                name => return Err(Errors::InvalidRules(format!("unknown table [[{}]]", name))),
+            }
+        }
+
+        Ok(rules)
+    }
+}
+
+pub struct Configurator<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Configurator<'a> {
+    /// Constructs a new Configurator struct which is used to work with the sub command "config"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if let Some(matches) = self.matches.subcommand_matches("export-rules") {
+            // Always present because it's a required argument
+            let path = matches.value_of("file").unwrap();
+            let rules = self.export(self.podcasts()?)?;
+            fs::write(path, rules.to_toml())?;
+            println!(
+                "Exported {} ignore rules and the policies of {} podcasts to {}",
+                rules.ignore.len(),
+                rules.policies.len(),
+                path
+            );
+
+            return Ok(());
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("import-rules") {
+            // Always present because it's a required argument
+            let path = matches.value_of("file").unwrap();
+            let rules = RuleSet::from_toml(&fs::read_to_string(path)?)?;
+
+            let mut ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+            let mut podcasts = self.podcasts()?;
+            self.import(rules, &mut ignore_list, &mut podcasts)?;
+            ignore_list.save()?;
+
+            let mut podcasts_list = FileSystem::new(&self.config.app_directory, "podcast_list.csv", vec![]).atomic()?;
+            write_podcasts(&podcasts, &mut podcasts_list)?;
+
+            return Ok(podcasts_list.commit()?);
+        }
+
+        Ok(())
+    }
+
+    fn podcasts(&self) -> Result<Vec<Podcast>, Errors> {
+        let mut podcasts_list = FileSystem::new(
+            &self.config.app_directory,
+            "podcast_list.csv",
+            vec![FilePermissions::Read],
+        )
+        .open()?;
+        let mut contents = String::new();
+        podcasts_list.read_to_string(&mut contents)?;
+
+        read_podcasts(contents.as_bytes(), self.config.strict)
+    }
+
+    /// The ignore rules and the policies of the podcasts which have any
+    fn export(&self, podcasts: Vec<Podcast>) -> Result<RuleSet, Errors> {
+        let ignore_list = IgnoreList::load(&self.config.app_directory, self.config.strict)?;
+
+        Ok(RuleSet {
+            ignore: ignore_list.rules().to_vec(),
+            policies: podcasts
+                .into_iter()
+                .filter(|podcast| podcast.window > 0 || podcast.sync_count > 0 || podcast.keep > 0)
+                .map(|podcast| Policy {
+                    rss_url: podcast.rss_url,
+                    window: podcast.window,
+                    sync_count: podcast.sync_count,
+                    keep: podcast.keep,
+                })
+                .collect(),
+        })
+    }
+
+    /// Adds the ignore rules to the list and applies the policies to the subscribed podcasts.
+    /// policies of podcasts which aren't subscribed to are skipped
+    fn import(&self, rules: RuleSet, ignore_list: &mut IgnoreList, podcasts: &mut [Podcast]) -> Result<(), Errors> {
+        let mut added = 0;
+        for rule in rules.ignore {
+            if ignore_list.add(rule.kind, &rule.value)? {
+                added += 1;
+            }
+        }
+        println!("Added {} ignore rules", added);
+
+        for policy in rules.policies {
+            match podcasts.iter_mut().find(|podcast| podcast.rss_url == policy.rss_url) {
+                Some(podcast) => {
+                    podcast.window = policy.window;
+                    podcast.sync_count = policy.sync_count;
+                    podcast.keep = policy.keep;
+                    println!("Applied the policies of {}", podcast.title);
+                }
+                None => tracing::warn!("Skipping {}, it isn't subscribed to", policy.rss_url),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_toml() {
+        let rules = RuleSet {
+            ignore: vec![
+                IgnoreRule {
+                    kind: RuleKind::Id,
+                    value: "50f59fbe-93db-4acf-b6db-8f9a078d315a".to_string(),
+                },
+                IgnoreRule {
+                    kind: RuleKind::Pattern,
+                    value: "*\"Trailer\"*".to_string(),
+                },
+            ],
+            policies: vec![Policy {
+                rss_url: "https://feed.syntax.fm/rss".to_string(),
+                window: 3,
+                sync_count: 0,
+                keep: 5,
+            }],
+        };
+
+        let toml = rules.to_toml();
+        assert!(toml.contains("[[ignore]]\nkind = \"pattern\"\nvalue = \"*\\\"Trailer\\\"*\"\n"));
+        assert_eq!(RuleSet::from_toml(&toml).expect("Can't parse rules"), rules);
+
+        // Comments and missing integers are allowed
+        let policies = RuleSet::from_toml("# Shared rules\n[[podcast]]\nrss_url = \"https://feed.syntax.fm/rss\"\n")
+            .expect("Can't parse rules")
+            .policies;
+        assert_eq!(policies[0].window, 0);
+
+        assert!(RuleSet::from_toml("kind = \"id\"").is_err());
+        assert!(RuleSet::from_toml("[[ignore]]\nkind = \"other\"\nvalue = \"a\"").is_err());
+        assert!(RuleSet::from_toml("[[podcast]]\nrss_url = unquoted").is_err());
+    }
+}
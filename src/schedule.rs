@@ -0,0 +1,45 @@
+//! The order of the downloads of batches which span several podcasts. the podcasts take turns, so
+//! a large backlog of one podcast doesn't hold back the new episodes of the others
+use std::collections::VecDeque;
+
+/// Merges the queues of the podcasts into one. each turn takes as many items from the front of
+/// every queue as its weight, so a podcast with weight 2 downloads twice as fast as one with
+/// weight 1. a weight of 0 counts as 1
+pub fn interleave<T>(queues: Vec<(u64, Vec<T>)>) -> Vec<T> {
+    let total = queues.iter().map(|(_weight, queue)| queue.len()).sum();
+    let mut queues: Vec<(u64, VecDeque<T>)> = queues
+        .into_iter()
+        .map(|(weight, queue)| (weight.max(1), VecDeque::from(queue)))
+        .collect();
+
+    let mut merged = Vec::with_capacity(total);
+    while merged.len() < total {
+        for (weight, queue) in queues.iter_mut() {
+            for _turn in 0..*weight {
+                match queue.pop_front() {
+                    Some(item) => merged.push(item),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_interleave() {
+        let merged = interleave(vec![
+            (1, vec!["a1", "a2", "a3", "a4"]),
+            (0, vec!["b1"]),
+            (2, vec!["c1", "c2", "c3"]),
+        ]);
+        assert_eq!(merged, ["a1", "b1", "c1", "c2", "a2", "c3", "a3", "a4"]);
+
+        assert!(interleave::<u64>(vec![(1, Vec::new())]).is_empty());
+    }
+}
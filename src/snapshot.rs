@@ -0,0 +1,214 @@
+//! Compressed copies of the saved podcasts and episodes, taken once a day before the first
+//! command which changes them, so a bad bulk operation can be undone with `pcasts restore`
+use crate::{
+    backup, dates, episodes::ARCHIVE_DIRECTORY, file_system::FileSystem, ignore::IGNORE_FILE, verify::MANIFEST_FILE,
+    Config, Errors,
+};
+use clap::ArgMatches;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// The directory inside the app directory which holds the snapshots, one for each day
+pub const SNAPSHOTS_DIRECTORY: &str = "snapshots";
+
+const SNAPSHOT_EXTENSION: &str = ".tar.gz";
+
+/// Number of snapshots which are kept when no other number is configured
+pub const DEFAULT_SNAPSHOTS: usize = 7;
+
+/// Whether the file with the passed name in the app directory is part of the saved state. the
+/// episodes of each podcast are saved in a file named by its id
+fn is_state(name: &str) -> bool {
+    name == "podcast_list.csv" || name == IGNORE_FILE || name == MANIFEST_FILE || name.parse::<u64>().is_ok()
+}
+
+/// Names of the files of the saved state in the passed directory
+fn state_files(directory: &Path) -> Result<Vec<String>, Errors> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && is_state(&name) {
+            names.push(name);
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+/// Dates of the saved snapshots, the oldest first
+pub fn list(app_directory: &Path) -> Vec<String> {
+    let mut dates: Vec<String> = match fs::read_dir(app_directory.join(SNAPSHOTS_DIRECTORY)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_suffix(SNAPSHOT_EXTENSION)
+                    .map(|date| date.to_string())
+            })
+            .collect(),
+        Err(_error) => Vec::new(),
+    };
+    dates.sort();
+
+    dates
+}
+
+fn snapshot_path(app_directory: &Path, date: &str) -> PathBuf {
+    app_directory
+        .join(SNAPSHOTS_DIRECTORY)
+        .join(format!("{}{}", date, SNAPSHOT_EXTENSION))
+}
+
+/// Saves a snapshot of the state if none was taken today and removes the oldest ones, so only the
+/// passed number of snapshots are kept. does nothing if keep is 0 or nothing was saved yet
+pub fn rotate(app_directory: &Path, keep: usize) -> Result<(), Errors> {
+    if keep == 0 || !app_directory.exists() {
+        return Ok(());
+    }
+
+    let today = dates::format_date(dates::now());
+    if !snapshot_path(app_directory, &today).exists() {
+        let files = state_files(app_directory)?;
+        if files.is_empty() {
+            return Ok(());
+        }
+        save(app_directory, &today, &files)?;
+    }
+
+    let dates = list(app_directory);
+    for date in dates.iter().take(dates.len().saturating_sub(keep)) {
+        fs::remove_file(snapshot_path(app_directory, date))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the passed files of the app directory and the archive of removed downloads into a
+/// compressed snapshot named by the date
+fn save(app_directory: &Path, date: &str, files: &[String]) -> Result<(), Errors> {
+    let file = FileSystem::new(
+        &app_directory.join(SNAPSHOTS_DIRECTORY),
+        &format!("{}{}", date, SNAPSHOT_EXTENSION),
+        vec![],
+    )
+    .atomic()?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    for name in files {
+        builder.append_path_with_name(app_directory.join(name), name)?;
+    }
+    let archive = app_directory.join(ARCHIVE_DIRECTORY);
+    if archive.is_dir() {
+        builder.append_dir_all(ARCHIVE_DIRECTORY, archive)?;
+    }
+
+    let mut file = builder.into_inner()?.finish()?;
+    file.flush()?;
+    file.commit()?;
+
+    Ok(())
+}
+
+/// Replaces the state with the one saved in the snapshot of the passed date. files which were
+/// created after the snapshot are removed
+fn restore(app_directory: &Path, date: &str) -> Result<(), Errors> {
+    let file = fs::File::open(snapshot_path(app_directory, date))
+        .map_err(|_error| Errors::MissingSnapshot(format!("the snapshot of {}", date)))?;
+
+    for name in state_files(app_directory)? {
+        fs::remove_file(app_directory.join(name))?;
+    }
+    match fs::remove_dir_all(app_directory.join(ARCHIVE_DIRECTORY)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(Errors::IO(error)),
+        _ => {}
+    }
+
+    tar::Archive::new(GzDecoder::new(file)).unpack(app_directory)?;
+
+    Ok(())
+}
+
+pub struct Restorer<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Restorer<'a> {
+    /// Constructs a new Restorer struct which is used to work with the sub command "restore"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if self.matches.is_present("list") {
+            for date in list(&self.config.app_directory) {
+                println!("{}", date);
+            }
+
+            return Ok(());
+        }
+
+        if let Some(path) = self.matches.value_of("file") {
+            backup::restore(self.config, Path::new(path))?;
+            println!("Restored the library saved in {}", path);
+
+            return Ok(());
+        }
+
+        // Always present because it's required unless --list or a file is passed
+        let date = self.matches.value_of("snapshot").unwrap();
+        restore(&self.config.app_directory, date)?;
+        println!("Restored the podcasts and episodes saved on {}", date);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn snapshot_rotate_restore() {
+        let directory = fixtures::scratch();
+        fs::create_dir_all(directory.join(ARCHIVE_DIRECTORY)).unwrap();
+        fs::write(directory.join("podcast_list.csv"), "podcasts").unwrap();
+        fs::write(directory.join("1"), "episodes").unwrap();
+        fs::write(directory.join(ARCHIVE_DIRECTORY).join("1"), "removed.mp3\n").unwrap();
+        fs::write(directory.join("search_results.csv"), "results").unwrap();
+
+        for date in ["2020-01-01", "2020-01-02"].iter() {
+            save(&directory, date, &["podcast_list.csv".to_string()]).unwrap();
+        }
+        rotate(&directory, 2).unwrap();
+        let today = dates::format_date(dates::now());
+        assert_eq!(list(&directory), vec!["2020-01-02".to_string(), today.clone()]);
+
+        // Changes after the snapshot are undone
+        fs::write(directory.join("podcast_list.csv"), "changed").unwrap();
+        fs::write(directory.join("2"), "added").unwrap();
+        fs::remove_dir_all(directory.join(ARCHIVE_DIRECTORY)).unwrap();
+        restore(&directory, &today).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(directory.join("podcast_list.csv")).unwrap(),
+            "podcasts"
+        );
+        assert_eq!(fs::read_to_string(directory.join("1")).unwrap(), "episodes");
+        assert_eq!(
+            fs::read_to_string(directory.join(ARCHIVE_DIRECTORY).join("1")).unwrap(),
+            "removed.mp3\n"
+        );
+        assert!(!directory.join("2").exists());
+        assert!(directory.join("search_results.csv").exists());
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    events::{self, Event, EventKind},
+    file_system::FileSystem,
+    records, Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+pub const NETWORK_STATS_FILE: &str = "network_stats.csv";
+
+/// Number of parallel requests to a host without any history
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Requests made to a single host, summed across runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HostStats {
+    pub host: String,
+    pub requests: u64,
+    pub failures: u64,
+    pub bytes: u64,
+    pub latency_ms: u64,
+    pub transfer_ms: u64,
+}
+
+impl HostStats {
+    fn new(host: &str) -> Self {
+        Self {
+            host: host.to_string(),
+            ..Self::default()
+        }
+    }
+
+    fn merge(&mut self, other: &HostStats) {
+        self.requests += other.requests;
+        self.failures += other.failures;
+        self.bytes += other.bytes;
+        self.latency_ms += other.latency_ms;
+        self.transfer_ms += other.transfer_ms;
+    }
+
+    /// Average time until the response headers arrived, in milliseconds
+    pub fn average_latency(&self) -> u64 {
+        let successes = self.requests - self.failures;
+        if successes == 0 {
+            return 0;
+        }
+
+        self.latency_ms / successes
+    }
+
+    /// Average download speed in bytes per second
+    pub fn throughput(&self) -> u64 {
+        if self.transfer_ms == 0 {
+            return 0;
+        }
+
+        self.bytes * 1000 / self.transfer_ms
+    }
+
+    /// Number of parallel requests to use for the host. hosts which often fail or are slow get
+    /// fewer connections so they aren't overloaded
+    pub fn concurrency(&self) -> usize {
+        if self.requests < 3 {
+            return DEFAULT_CONCURRENCY;
+        }
+
+        let failure_rate = self.failures as f64 / self.requests as f64;
+        if failure_rate > 0.2 {
+            return 1;
+        }
+
+        // Under 100 KB/s an extra connection mostly competes with the existing ones
+        if self.throughput() < 100_000 || self.average_latency() > 2_000 {
+            return 2;
+        }
+
+        DEFAULT_CONCURRENCY
+    }
+}
+
+/// Collects per host network statistics. cloning it shares the collected statistics
+#[derive(Debug, Clone, Default)]
+pub struct NetworkStats {
+    history: Arc<Mutex<HashMap<String, HostStats>>>,
+    current: Arc<Mutex<HashMap<String, HostStats>>>,
+}
+
+impl NetworkStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The host part of the passed URL
+    pub fn host(url: &str) -> String {
+        match reqwest::Url::parse(url) {
+            Ok(url) => url.host_str().unwrap_or("").to_string(),
+            Err(_error) => String::new(),
+        }
+    }
+
+    /// Records a successful request
+    pub fn record(&self, url: &str, bytes: u64, latency: Duration, transfer: Duration) {
+        if let Ok(mut current) = self.current.lock() {
+            let host = Self::host(url);
+            let stats = current.entry(host.clone()).or_insert_with(|| HostStats::new(&host));
+            stats.requests += 1;
+            stats.bytes += bytes;
+            stats.latency_ms += latency.as_millis() as u64;
+            stats.transfer_ms += transfer.as_millis() as u64;
+        }
+    }
+
+    /// Records a failed request
+    pub fn record_failure(&self, url: &str) {
+        if let Ok(mut current) = self.current.lock() {
+            let host = Self::host(url);
+            let stats = current.entry(host.clone()).or_insert_with(|| HostStats::new(&host));
+            stats.requests += 1;
+            stats.failures += 1;
+        }
+    }
+
+    /// The statistics of the previous runs together with the current one, sorted by host
+    pub fn hosts(&self) -> Vec<HostStats> {
+        let mut hosts: HashMap<String, HostStats> = match self.history.lock() {
+            Ok(history) => history.clone(),
+            Err(_error) => HashMap::new(),
+        };
+
+        if let Ok(current) = self.current.lock() {
+            for (host, stats) in current.iter() {
+                hosts
+                    .entry(host.clone())
+                    .or_insert_with(|| HostStats::new(host))
+                    .merge(stats);
+            }
+        }
+
+        let mut hosts: Vec<HostStats> = hosts.into_values().collect();
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+        hosts
+    }
+
+    /// Number of parallel requests to use for the host of the passed URL, based on the previous runs
+    pub fn concurrency(&self, url: &str) -> usize {
+        let host = Self::host(url);
+
+        match self.history.lock() {
+            Ok(history) => match history.get(&host) {
+                Some(stats) => stats.concurrency(),
+                None => DEFAULT_CONCURRENCY,
+            },
+            Err(_error) => DEFAULT_CONCURRENCY,
+        }
+    }
+
+    /// Loads the statistics saved by the previous runs
+    pub fn load(&self, directory: &Path, strict: bool) -> Result<(), Errors> {
+        let path = directory.join(NETWORK_STATS_FILE);
+        if !path.exists() {
+            return Ok(());
+        }
+
+        let contents = fs::read(path)?;
+        let hosts = records::read::<HostStats, _>(&contents[..], NETWORK_STATS_FILE, strict)?
+            .into_iter()
+            .map(|stats| (stats.host.clone(), stats))
+            .collect();
+        if let Ok(mut history) = self.history.lock() {
+            *history = hosts;
+        }
+
+        Ok(())
+    }
+
+    /// Whether no requests were made by the current run
+    pub fn is_empty(&self) -> bool {
+        match self.current.lock() {
+            Ok(current) => current.is_empty(),
+            Err(_error) => true,
+        }
+    }
+
+    /// Saves the statistics of the previous runs together with the current one. the saved ones are
+    /// read again first, so the runs which saved them since they were loaded aren't lost, which
+    /// holds only while the process lock is held. does nothing if no requests were made
+    pub fn save(&self, directory: &Path) -> Result<(), Errors> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        // The malformed rows were reported when the statistics were loaded
+        let saved = match fs::read(directory.join(NETWORK_STATS_FILE)) {
+            Ok(contents) => records::read_all::<HostStats, _>(&contents[..]).0,
+            Err(_error) => Vec::new(),
+        };
+        if let Ok(mut history) = self.history.lock() {
+            *history = saved.into_iter().map(|stats| (stats.host.clone(), stats)).collect();
+        }
+
+        let file = FileSystem::new(directory, NETWORK_STATS_FILE, vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        for stats in self.hosts() {
+            writer.serialize(stats)?;
+        }
+
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        Ok(file.commit()?)
+    }
+}
+
+pub struct Stats<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Stats<'a> {
+    /// Constructs a new Stats struct which is used to work with the sub command "stats"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        if self.matches.subcommand_matches("network").is_some() {
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return self.network(writer);
+        }
+
+        if let Some(matches) = self.matches.subcommand_matches("library") {
+            let since = events::since(matches.value_of("since"))?;
+            let events = events::read(&self.config.app_directory, since, self.config.strict)?;
+            let writer = std::io::stdout();
+            let writer = writer.lock();
+
+            return library(&events, writer);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the statistics of every host which pcasts downloaded from
+    fn network<W>(&self, mut writer: W) -> Result<(), Errors>
+    where
+        W: Write,
+    {
+        let hosts = self.config.network.hosts();
+        if hosts.is_empty() {
+            writeln!(writer, "No downloads were recorded yet")?;
+            return Ok(());
+        }
+
+        writeln!(
+            writer,
+            "{:32} {:>8} {:>8} {:>10} {:>12} {:>11}",
+            "Host".green(),
+            "Requests".green(),
+            "Failures".green(),
+            "Latency".green(),
+            "Speed".green(),
+            "Connections".green()
+        )?;
+        for stats in hosts {
+            writeln!(
+                writer,
+                "{:32} {:>8} {:>8} {:>8}ms {:>8}KB/s {:>11}",
+                stats.host,
+                stats.requests,
+                stats.failures,
+                stats.average_latency(),
+                stats.throughput() / 1000,
+                stats.concurrency()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes how many events of each kind the log has
+fn library<W>(events: &[Event], mut writer: W) -> Result<(), Errors>
+where
+    W: Write,
+{
+    if events.is_empty() {
+        writeln!(writer, "Nothing happened yet")?;
+        return Ok(());
+    }
+
+    for kind in EventKind::ALL.iter() {
+        let count = events.iter().filter(|event| event.kind == *kind).count();
+        writeln!(writer, "{:12} {}", kind.to_string().green(), count)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn network_stats() {
+        let directory = fixtures::scratch();
+
+        let stats = NetworkStats::new();
+        stats.record(
+            "https://traffic.libsyn.com/secure/syntax/Syntax268.mp3",
+            2_000_000,
+            Duration::from_millis(200),
+            Duration::from_millis(1000),
+        );
+        stats.record_failure("https://feed.syntax.fm/rss");
+        stats.save(&directory).expect("Can't save stats");
+
+        let loaded = NetworkStats::new();
+        loaded.load(&directory, false).expect("Can't load stats");
+        loaded.record_failure("https://feed.syntax.fm/rss");
+        loaded.record_failure("https://feed.syntax.fm/rss");
+
+        let hosts = loaded.hosts();
+        assert_eq!(hosts.len(), 2);
+        assert_eq!(hosts[0].host, "feed.syntax.fm");
+        assert_eq!((hosts[0].requests, hosts[0].failures), (3, 3));
+        assert_eq!(hosts[0].concurrency(), 1);
+        assert_eq!(hosts[1].throughput(), 2_000_000);
+        assert_eq!(hosts[1].average_latency(), 200);
+
+        // The current run doesn't affect the concurrency until it's saved
+        assert_eq!(loaded.concurrency("https://feed.syntax.fm/other"), DEFAULT_CONCURRENCY);
+
+        // Another run saved after this one loaded the statistics, and its requests are kept
+        let other = NetworkStats::new();
+        other.record_failure("https://feed.syntax.fm/rss");
+        other.save(&directory).expect("Can't save stats");
+        loaded.save(&directory).expect("Can't save stats");
+
+        let saved = NetworkStats::new();
+        saved.load(&directory, true).expect("Can't load stats");
+        let hosts = saved.hosts();
+        assert_eq!((hosts[0].requests, hosts[0].failures), (4, 4));
+        assert_eq!(hosts[1].requests, 1);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{
+    episodes::Episodes,
+    file_system::{FilePermissions, FileSystem},
+    podcasts::{load_podcasts, Podcast},
+    power, schedule, Config, Errors,
+};
+use clap::ArgMatches;
+use std::{collections::HashSet, path::Path, time};
+
+pub struct Syncer<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Syncer<'a> {
+    /// Constructs a new Syncer struct which is used to work with the sub command "sync"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Updates all the podcasts which aren't paused, then downloads their newest episodes which
+    /// aren't on disk. episodes which were downloaded and removed before are skipped
+    pub fn run(&self) -> Result<(), Errors> {
+        // Always present because it has a default value
+        let count = self.matches.value_of("count").unwrap().parse::<u64>()?;
+        let min_battery = match self.matches.value_of("min-battery") {
+            Some(min_battery) => min_battery.parse::<u8>()?,
+            None => self.config.min_battery,
+        };
+        let move_to = self.matches.value_of("move-to").map(Path::new);
+        let episodes = Episodes::new(self.matches, self.config);
+
+        // With --tag only the podcasts with the tag are updated and downloaded
+        let tag = self.matches.value_of("tag");
+        let tagged: Option<HashSet<u64>> = match tag {
+            Some(tag) => Some(
+                load_podcasts(self.config)?
+                    .iter()
+                    .filter(|podcast| !podcast.paused && podcast.has_tag(tag))
+                    .map(|podcast| podcast.id)
+                    .collect(),
+            ),
+            None => None,
+        };
+        if tagged.as_ref().is_some_and(|tagged| tagged.is_empty()) {
+            println!("No podcasts are tagged {}", tag.unwrap_or_default());
+            return Ok(());
+        }
+
+        // Updating is cheap, so only the downloads wait for AC power
+        episodes.update_podcasts(tagged.as_ref())?;
+
+        let podcasts: Vec<Podcast> = load_podcasts(self.config)?
+            .into_iter()
+            .filter(|podcast| tag.is_none_or(|tag| podcast.has_tag(tag)))
+            .collect();
+        let mut queues = Vec::new();
+        // The podcasts set to auto-download=false are only updated
+        for podcast in podcasts.iter().filter(|podcast| !podcast.paused && !podcast.manual) {
+            let episodes_file = match FileSystem::new(
+                &self.config.app_directory,
+                &podcast.id.to_string(),
+                vec![FilePermissions::Read],
+            )
+            .open()
+            {
+                Ok(file) => file,
+                Err(error) => {
+                    tracing::warn!("Can't open file for podcast {}. {}", podcast.title, error);
+                    continue;
+                }
+            };
+
+            let count = match podcast.sync_count {
+                0 => count,
+                sync_count => sync_count,
+            };
+            let selection = episodes.sync_selection(
+                episodes_file,
+                count as usize,
+                &episodes.downloaded_files(podcast.id)?,
+                &episodes.archived_files(podcast.id),
+            )?;
+            queues.push((podcast.priority, selection));
+        }
+
+        // The podcasts take turns, so a podcast with many missing episodes doesn't hold back the
+        // others. each round downloads as many episodes as there are jobs
+        let queue = schedule::interleave(queues);
+        for (index, round) in queue.chunks(self.config.jobs).enumerate() {
+            let started = index * self.config.jobs;
+
+            // The episodes which weren't reached are downloaded by the next run
+            if self
+                .config
+                .deadline
+                .is_some_and(|deadline| time::Instant::now() >= deadline)
+            {
+                println!(
+                    "Reached --max-time. Downloaded {} of {} episodes, the rest are left for the next run",
+                    started,
+                    queue.len()
+                );
+                break;
+            }
+
+            // Checked before each round since the battery drains while downloading. the next
+            // sync downloads the rest
+            if let Some(level) = power::battery_level().filter(|level| *level < min_battery) {
+                println!(
+                    "On battery at {}%. Deferring the downloads until AC power or {}%",
+                    level, min_battery
+                );
+                break;
+            }
+
+            // The episodes which failed were reported and recorded in the outcome, they don't stop
+            // the others. an error is one of the library, e.g a file which can't be written
+            for (_episode, file_name) in episodes.download_batch(round.to_vec())? {
+                if let Ok(file_name) = file_name {
+                    self.config.outcome.success();
+                    println!("Downloaded {}", file_name);
+                }
+            }
+        }
+
+        // Pruned after downloading so the newest episodes are the ones kept
+        for podcast in podcasts.iter().filter(|podcast| !podcast.paused && podcast.keep > 0) {
+            episodes.prune(podcast, podcast.keep, move_to, false)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixtures, ApplicationBuilder};
+    use std::fs;
+
+    const SYNTAX_ID: u64 = 15913066141282366353;
+
+    fn sync(config: &Config, args: &[&str]) {
+        let app = ApplicationBuilder::new(fixtures::config()).sync_subcommand().build();
+        let args = app.app.get_matches_from([&["pcasts", "sync"], args].concat());
+        let matches = args.subcommand_matches("sync").expect("No sync matches");
+
+        Syncer::new(matches, config).run().expect("Can't sync");
+    }
+
+    #[test]
+    fn sync_tag() {
+        let mut config = fixtures::config().with_jobs(2);
+        fs::create_dir_all(&config.app_directory).unwrap();
+        fs::write(
+            config.app_directory.join("podcast_list.csv"),
+            format!(
+                "id,url,rss_url,title,sync_count,keep,tags\n\
+                 {},https://syntax.fm,https://feed.syntax.fm/rss,Syntax,3,2,web\n\
+                 2,https://example.com,http://feeds.feedburner.com/Http203Podcast,HTTP 203,0,0,\n",
+                SYNTAX_ID
+            ),
+        )
+        .unwrap();
+        let downloaded = |config: &Config| match fs::read_dir(config.podcast_directory(SYNTAX_ID)) {
+            Ok(entries) => entries.count(),
+            Err(_error) => 0,
+        };
+
+        // No podcast is tagged, so nothing is updated
+        sync(&config, &["--tag", "other"]);
+        assert!(!config.app_directory.join(SYNTAX_ID.to_string()).exists());
+
+        // Past the deadline the tagged podcast is updated, but nothing is downloaded
+        config.deadline = Some(time::Instant::now());
+        sync(&config, &["--tag", "web"]);
+        assert!(config.app_directory.join(SYNTAX_ID.to_string()).exists());
+        assert!(!config.app_directory.join("2").exists());
+        assert_eq!(downloaded(&config), 0);
+
+        // The sync count of the podcast overrides --count, in two rounds of two jobs, and the
+        // downloads are pruned to the ones it keeps afterwards
+        config.deadline = None;
+        sync(&config, &["--tag", "web", "--count", "1"]);
+        assert_eq!(downloaded(&config), 2);
+        assert!(!config.app_directory.join("2").exists());
+    }
+}
@@ -0,0 +1,130 @@
+//! Listings with one line per podcast or episode in aligned columns, for scanning hundreds of
+//! them. `--format table` picks them over the full records
+use colored::Colorize;
+use std::io::{self, IsTerminal, Write};
+
+/// Spaces between the columns
+const GAP: usize = 2;
+
+/// The flexible column is never truncated below this many characters
+const MIN_WIDTH: usize = 10;
+
+/// Rows of cells under a header. the flexible column is truncated so the rows fit the width
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+    flexible: usize,
+}
+
+impl Table {
+    /// A table with the passed column headers. the column at the flexible index is the one which
+    /// is truncated, usually the title
+    pub fn new(headers: Vec<&'static str>, flexible: usize) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+            flexible,
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// Writes the header and the rows. with a width, the flexible column is cut so each line fits
+    pub fn write<W>(&self, mut writer: W, width: Option<usize>) -> Result<(), io::Error>
+    where
+        W: Write,
+    {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| header.chars().count()).collect();
+        for row in self.rows.iter() {
+            for (index, cell) in row.iter().enumerate() {
+                widths[index] = widths[index].max(cell.chars().count());
+            }
+        }
+        if let Some(width) = width {
+            let others: usize = widths
+                .iter()
+                .enumerate()
+                .filter(|(index, _width)| *index != self.flexible)
+                .map(|(_index, width)| width + GAP)
+                .sum();
+            widths[self.flexible] = widths[self.flexible].min(width.saturating_sub(others).max(MIN_WIDTH));
+        }
+
+        let headers: Vec<String> = self.headers.iter().map(|header| header.to_string()).collect();
+        let line = self.line(&headers, &widths);
+        writeln!(writer, "{}", line.bold())?;
+        for row in self.rows.iter() {
+            writeln!(writer, "{}", self.line(row, &widths))?;
+        }
+
+        Ok(())
+    }
+
+    /// The cells padded to the widths of their columns, without spaces after the last one
+    fn line(&self, cells: &[String], widths: &[usize]) -> String {
+        let cells: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", truncate(cell, *width), width = width))
+            .collect();
+
+        cells.join(&" ".repeat(GAP)).trim_end().to_string()
+    }
+}
+
+/// The text cut to the passed number of characters, ending with an ellipsis when it's cut
+fn truncate(text: &str, width: usize) -> String {
+    match text.chars().count() > width {
+        true => format!("{}…", text.chars().take(width.saturating_sub(1)).collect::<String>()),
+        false => text.to_string(),
+    }
+}
+
+/// The width of the terminal stdout is written to. None when it's redirected, so the files and
+/// pipes get whole lines
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+
+    ratatui::crossterm::terminal::size()
+        .ok()
+        .map(|(columns, _rows)| columns as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_columns() {
+        colored::control::set_override(false);
+        let mut table = Table::new(vec!["ID", "TITLE", "DOWNLOADED"], 1);
+        table.push(vec![
+            "1".to_string(),
+            "Hasty Treat - Turbolinks".to_string(),
+            "yes".to_string(),
+        ]);
+        table.push(vec!["12".to_string(), "Potluck".to_string(), String::new()]);
+
+        let mut output = Vec::new();
+        table.write(&mut output, None).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ID  TITLE                     DOWNLOADED\n\
+             1   Hasty Treat - Turbolinks  yes\n\
+             12  Potluck\n"
+        );
+
+        let mut output = Vec::new();
+        table.write(&mut output, Some(30)).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "ID  TITLE           DOWNLOADED\n\
+             1   Hasty Treat -…  yes\n\
+             12  Potluck\n"
+        );
+    }
+}
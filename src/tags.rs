@@ -0,0 +1,130 @@
+//! ID3 tags of the downloaded MP3 files. publishers often leave them empty, which makes the
+//! episodes hard to find in music players
+use crate::{dates, episodes::Episode, Errors};
+use bytes::Bytes;
+use id3::{
+    frame::{Picture, PictureType},
+    Tag, TagLike, Timestamp, Version,
+};
+use std::io::Cursor;
+
+/// Length of the header and of the footer of an ID3v2 tag
+const HEADER_LENGTH: usize = 10;
+
+/// Length in bytes of the ID3v2 tag at the start of the content. 0 if there's no tag
+pub fn tag_length(content: &[u8]) -> usize {
+    if content.len() < HEADER_LENGTH || !content.starts_with(b"ID3") {
+        return 0;
+    }
+
+    // The size is stored as a "synchsafe" integer, 7 bits in each byte
+    let size = content[6..10]
+        .iter()
+        .fold(0, |size, byte| (size << 7) | (*byte as usize & 0x7f));
+    let footer = if content[5] & 0x10 != 0 { HEADER_LENGTH } else { 0 };
+
+    (HEADER_LENGTH + size + footer).min(content.len())
+}
+
+/// Sets the title, album, date and track of the episode in the tag of the passed MP3 content and
+/// embeds the cover art if there's one. other frames set by the publisher are kept
+pub fn write(content: &[u8], episode: &Episode, cover: Option<Vec<u8>>) -> Result<Bytes, Errors> {
+    let length = tag_length(content);
+    let mut tag = match length {
+        0 => Tag::new(),
+        // A broken tag is replaced by a new one
+        length => Tag::read_from2(Cursor::new(&content[..length])).unwrap_or_else(|_error| Tag::new()),
+    };
+
+    tag.set_title(episode.title.as_str());
+    tag.set_album(episode.podcast.as_str());
+    if episode.number > 0 {
+        tag.set_track(episode.number as u32);
+    }
+    if let Some(date) = episode
+        .release_time()
+        .and_then(|pub_date| dates::format_date(pub_date).parse::<Timestamp>().ok())
+    {
+        tag.set_date_recorded(date);
+    }
+    if let Some(cover) = cover {
+        tag.remove_picture_by_type(PictureType::CoverFront);
+        tag.add_frame(Picture {
+            mime_type: "image/jpeg".to_string(),
+            picture_type: PictureType::CoverFront,
+            description: String::new(),
+            data: cover,
+        });
+    }
+
+    let mut tagged = Vec::with_capacity(content.len());
+    tag.write_to(&mut tagged, Version::Id3v24)
+        .map_err(|error| Errors::Tag(error.to_string()))?;
+    tagged.extend_from_slice(&content[length..]);
+
+    Ok(Bytes::from(tagged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_write() {
+        let episode = Episode {
+            guid: "1".to_string(),
+            title: "Episode 12".to_string(),
+            pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+            link: "-".to_string(),
+            podcast: "Podcast".to_string(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 12,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        };
+        let audio = b"\xFF\xFBaudio";
+
+        let mut publisher_tag = Tag::new();
+        publisher_tag.set_title("Untitled");
+        publisher_tag.set_artist("Publisher");
+        let mut content = Vec::new();
+        publisher_tag.write_to(&mut content, Version::Id3v23).unwrap();
+        content.extend_from_slice(audio);
+
+        let tagged = write(&content, &episode, Some(vec![1, 2, 3])).expect("Can't write tags");
+        let length = tag_length(&tagged);
+        assert_eq!(&tagged[length..], &audio[..]);
+
+        let tag = Tag::read_from2(Cursor::new(&tagged[..length])).unwrap();
+        assert_eq!(tag.title(), Some("Episode 12"));
+        assert_eq!(tag.album(), Some("Podcast"));
+        assert_eq!(tag.artist(), Some("Publisher"));
+        assert_eq!(tag.track(), Some(12));
+        assert_eq!(
+            tag.date_recorded().map(|date| date.to_string()),
+            Some("2020-07-22".to_string())
+        );
+        assert_eq!(
+            tag.pictures().next().map(|picture| picture.data.clone()),
+            Some(vec![1, 2, 3])
+        );
+
+        let untagged = write(audio, &episode, None).expect("Can't write tags");
+        assert_eq!(&untagged[tag_length(&untagged)..], &audio[..]);
+    }
+}
@@ -0,0 +1,208 @@
+//! Normalization of the titles of podcasts and episodes, so text which looks the same is treated
+//! the same regardless of how it was encoded, e.g "Café" written with a combining accent. also
+//! renders the HTML of show notes as text
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// The canonical composed form of the passed value. file systems like the one of macOS store
+/// names decomposed, so the names are composed before they're compared or saved
+pub fn compose(value: &str) -> String {
+    value.nfc().collect()
+}
+
+/// Strips the diacritics and folds the case of the passed value, e.g "Café Straße" becomes
+/// "cafe strasse". used for matching and sorting titles
+pub fn fold(value: &str) -> String {
+    value
+        .nfkd()
+        .filter(|char| !is_combining_mark(*char))
+        .flat_map(|char| match char {
+            'ß' | 'ẞ' => vec!['s', 's'],
+            char => char.to_lowercase().collect(),
+        })
+        .collect()
+}
+
+/// Words which say nothing about what an episode is about
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "again", "also", "been", "best", "bonus", "could", "does", "episode", "from", "have", "here",
+    "into", "just", "live", "more", "most", "much", "only", "other", "over", "part", "really", "some", "than", "that",
+    "their", "them", "then", "there", "they", "this", "what", "when", "where", "which", "while", "with", "would",
+    "your",
+];
+
+/// The folded words of the text which can tell what it's about, in their order. short words,
+/// numbers and stop words are left out
+pub fn words(value: &str) -> Vec<String> {
+    fold(value)
+        .split(|char: char| !char.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 4 && !word.chars().all(|char| char.is_ascii_digit()))
+        .filter(|word| !STOP_WORDS.contains(word))
+        .map(|word| word.to_string())
+        .collect()
+}
+
+/// Whether the text contains the passed query, ignoring diacritics and case
+pub fn matches(text: &str, query: &str) -> bool {
+    fold(text).contains(&fold(query))
+}
+
+/// Whether the characters of the query appear in the text in the same order, e.g "sntx" matches
+/// "Syntax". diacritics and case are ignored
+pub fn fuzzy_matches(text: &str, query: &str) -> bool {
+    let text = fold(text);
+    let mut chars = text.chars();
+
+    fold(query)
+        .chars()
+        .filter(|char| !char.is_whitespace())
+        .all(|query_char| chars.any(|char| char == query_char))
+}
+
+/// Decodes the named entities which are common in show notes and all the numeric ones
+fn decode_entity(entity: &str) -> Option<String> {
+    let char = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "rsquo" => '’',
+        "lsquo" => '‘',
+        "rdquo" => '”',
+        "ldquo" => '“',
+        _ => {
+            let number = entity.strip_prefix('#')?;
+            let code = match number.strip_prefix('x').or_else(|| number.strip_prefix('X')) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse::<u32>().ok()?,
+            };
+            std::char::from_u32(code)?
+        }
+    };
+
+    Some(char.to_string())
+}
+
+/// The value of the href attribute of a tag, e.g `a href="https://example.com"`
+fn href(tag: &str) -> Option<String> {
+    let start = tag.to_lowercase().find("href=")? + "href=".len();
+    let value = &tag[start..];
+    let value = match value.chars().next()? {
+        quote @ '"' | quote @ '\'' => value[1..].split(quote).next()?,
+        _ => value.split_whitespace().next()?,
+    };
+
+    Some(value.to_string())
+}
+
+/// Renders the HTML of show notes as plain text. paragraphs and line breaks become new lines, list
+/// items get a bullet and the targets of the links are returned in the order they appear
+pub fn html_to_text(html: &str) -> (String, Vec<String>) {
+    let mut text = String::new();
+    let mut links: Vec<String> = Vec::new();
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if let Some(tag) = rest.strip_prefix('<') {
+            let end = match tag.find('>') {
+                Some(end) => end,
+                None => break,
+            };
+            let (tag, after) = (&tag[..end], &tag[end + 1..]);
+            let name = tag
+                .trim_start_matches('/')
+                .split(|char: char| char.is_whitespace() || char == '/')
+                .next()
+                .unwrap_or("")
+                .to_lowercase();
+
+            match name.as_str() {
+                "br" => text.push('\n'),
+                "p" | "div" | "ul" | "ol" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "blockquote" => {
+                    text.push_str("\n\n")
+                }
+                "li" if !tag.starts_with('/') => text.push_str("\n- "),
+                "a" if !tag.starts_with('/') => {
+                    if let Some(href) = href(tag).filter(|href| !links.contains(href)) {
+                        links.push(href);
+                    }
+                }
+                _ => {}
+            }
+            rest = after;
+        } else if let Some(entity) = rest.strip_prefix('&') {
+            let decoded = entity
+                .find(';')
+                .filter(|end| *end <= 10)
+                .and_then(|end| Some((decode_entity(&entity[..end])?, end)));
+            match decoded {
+                Some((decoded, end)) => {
+                    text.push_str(&decoded);
+                    rest = &entity[end + 1..];
+                }
+                None => {
+                    text.push('&');
+                    rest = entity;
+                }
+            }
+        } else {
+            let end = rest.find(['<', '&']).unwrap_or(rest.len());
+            // The line breaks and indentation of the HTML source aren't part of the text
+            for char in rest[..end].chars() {
+                match char.is_whitespace() {
+                    true if !text.ends_with(char::is_whitespace) => text.push(' '),
+                    true => {}
+                    false => text.push(char),
+                }
+            }
+            rest = &rest[end..];
+        }
+    }
+
+    // At most one empty line between the paragraphs and no spaces around the lines
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines().map(|line| line.trim()) {
+        if line.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+
+    (lines.join("\n"), links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_fold() {
+        assert_eq!(fold("Café Straße"), "cafe strasse");
+        assert_eq!(fold("Cafe\u{301}"), fold("Café"));
+        assert_eq!(compose("Cafe\u{301}"), "Café");
+        assert!(matches("Episode 3: Cafe culture", "CAFÉ"));
+        assert!(!matches("Episode 3: Tea culture", "café"));
+        assert!(fuzzy_matches("Syntax - Tasty Web Development Treats", "sntx web"));
+        assert!(!fuzzy_matches("Syntax", "xs"));
+    }
+
+    #[test]
+    fn text_html_to_text() {
+        let html = r###"<p>In this episode we talk about <a href="https://rollupjs.org">Rollup</a> &amp;
+   Webpack.</p><ul><li>Tips &#8211; tricks</li><li><a href='https://webpack.js.org'>Webpack</a></li></ul>
+<p>Thanks<br/>Wes &amp Scott</p>"###;
+        let (text, links) = html_to_text(html);
+        assert_eq!(
+            text,
+            "In this episode we talk about Rollup & Webpack.\n\n- Tips – tricks\n- Webpack\n\nThanks\nWes &amp Scott"
+        );
+        assert_eq!(links, ["https://rollupjs.org", "https://webpack.js.org"]);
+    }
+}
@@ -0,0 +1,426 @@
+//! Previews of the cover art drawn in the terminal. kitty, iTerm2 and sixel terminals get the
+//! picture, the others shades of ASCII characters. the artwork comes from the feeds, so it's
+//! decoded with limits on its size and on the memory its decoding takes
+use crate::{art, cache::ResponseCache, feed, podcasts::Podcast, web::Web, Config};
+use bytes::Bytes;
+use image::{ImageFormat, ImageReader, Limits};
+use std::{collections::BTreeSet, fs, io, sync::Arc, time};
+
+/// Fetched feeds and artwork are reused for this long, so showing a podcast again doesn't
+/// download them again
+const ARTWORK_CACHE_TTL: time::Duration = time::Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Width of the previews, in columns of the terminal
+pub const COLUMNS: usize = 32;
+
+/// Characters from the darkest to the brightest
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+/// Pixels of a cell of the terminal, which the kitty and sixel images are scaled to
+const CELL_WIDTH: usize = 8;
+const CELL_HEIGHT: usize = 16;
+
+/// Widest and tallest artwork which is decoded, in pixels. the artwork of feeds is at most
+/// 3000x3000
+const MAX_SIDE: u32 = 1 << 13;
+
+/// Most memory the decoding of an artwork may take, in bytes
+const MAX_ALLOCATION: u64 = 1 << 28;
+
+/// The kitty graphics protocol limits each escape sequence to this many bytes of data
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    Sixel,
+    Ascii,
+}
+
+impl Protocol {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "kitty" => Some(Protocol::Kitty),
+            "iterm" => Some(Protocol::Iterm),
+            "sixel" => Some(Protocol::Sixel),
+            "ascii" => Some(Protocol::Ascii),
+            _ => None,
+        }
+    }
+
+    /// The protocol of the terminal, from the variables the terminals set. terminals can't be asked
+    /// about sixel without reading their answer, so only the ones known to have it get sixel
+    pub fn detect<F>(variable: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let term = variable("TERM").unwrap_or_default();
+        let program = variable("TERM_PROGRAM").unwrap_or_default();
+
+        if variable("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term.contains("ghostty") {
+            Protocol::Kitty
+        } else if program == "iTerm.app" || program == "WezTerm" {
+            Protocol::Iterm
+        } else if term.starts_with("foot") || term.starts_with("mlterm") || term.contains("sixel") {
+            Protocol::Sixel
+        } else {
+            Protocol::Ascii
+        }
+    }
+}
+
+/// An image as 8 bit RGB
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pixels {
+    pub width: usize,
+    pub height: usize,
+    rgb: Vec<u8>,
+}
+
+impl Pixels {
+    /// Decodes a PNG or a JPEG. None for the other formats, for broken files and for the ones
+    /// which are larger than the limits
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let format = image::guess_format(bytes).ok()?;
+        if format != ImageFormat::Png && format != ImageFormat::Jpeg {
+            return None;
+        }
+
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_SIDE);
+        limits.max_image_height = Some(MAX_SIDE);
+        limits.max_alloc = Some(MAX_ALLOCATION);
+        let mut reader = ImageReader::with_format(io::Cursor::new(bytes), format);
+        reader.limits(limits);
+        let image = reader.decode().ok()?.into_rgb8();
+
+        Some(Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            rgb: image.into_raw(),
+        })
+    }
+
+    fn at(&self, x: usize, y: usize) -> [u8; 3] {
+        let index = (y * self.width + x) * 3;
+        [self.rgb[index], self.rgb[index + 1], self.rgb[index + 2]]
+    }
+
+    /// Scales the image to the passed size. each new pixel is the average of the pixels it covers
+    pub fn resize(&self, width: usize, height: usize) -> Self {
+        // The pixels in [start, end) of the old size which the new pixel covers
+        let span = |index: usize, new: usize, old: usize| {
+            let start = index * old / new;
+            (start, ((index + 1) * old / new).max(start + 1).min(old))
+        };
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+        for y in 0..height {
+            let (top, bottom) = span(y, height, self.height);
+            for x in 0..width {
+                let (left, right) = span(x, width, self.width);
+                let mut sums = [0usize; 3];
+                for old_y in top..bottom {
+                    for old_x in left..right {
+                        for (sum, value) in sums.iter_mut().zip(self.at(old_x, old_y).iter()) {
+                            *sum += *value as usize;
+                        }
+                    }
+                }
+                let count = (bottom - top) * (right - left);
+                rgb.extend(sums.iter().map(|sum| (sum / count) as u8));
+            }
+        }
+
+        Self { width, height, rgb }
+    }
+
+    /// Rows of the terminal the image takes when it's `columns` wide. cells are about twice as
+    /// tall as they're wide
+    fn rows(&self, columns: usize) -> usize {
+        ((columns * self.height + self.width) / (self.width * 2)).max(1)
+    }
+
+    /// The image as lines of characters whose density follows the brightness of the pixels, each
+    /// with the color of its pixels
+    pub fn cells(&self, columns: usize) -> Vec<Vec<(char, [u8; 3])>> {
+        let thumbnail = self.resize(columns, self.rows(columns));
+
+        (0..thumbnail.height)
+            .map(|y| {
+                (0..thumbnail.width)
+                    .map(|x| {
+                        let [red, green, blue] = thumbnail.at(x, y);
+                        let luma = (red as usize * 299 + green as usize * 587 + blue as usize * 114) / 1000;
+                        (SHADES[luma * (SHADES.len() - 1) / 255] as char, [red, green, blue])
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The cells as lines of text, colored when colors are enabled
+    pub fn ascii(&self, columns: usize) -> Vec<String> {
+        let colorize = colored::control::SHOULD_COLORIZE.should_colorize();
+
+        self.cells(columns)
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|(shade, [red, green, blue])| match colorize {
+                        // colored has no 24 bit colors
+                        true => format!("\x1b[38;2;{};{};{}m{}\x1b[0m", red, green, blue, shade),
+                        false => shade.to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Draws the image with the kitty graphics protocol as raw RGB, scaled by the terminal to the
+    /// passed cells
+    fn kitty(&self, columns: usize) -> String {
+        let rows = self.rows(columns);
+        let thumbnail = self.resize(columns * CELL_WIDTH, rows * CELL_HEIGHT / 2);
+        let payload = base64::encode(&thumbnail.rgb);
+        let chunks: Vec<&[u8]> = payload.as_bytes().chunks(KITTY_CHUNK).collect();
+
+        let mut output = String::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let more = (index + 1 < chunks.len()) as u8;
+            // Base64 is ASCII, so each chunk is valid UTF-8
+            let chunk = String::from_utf8_lossy(chunk);
+            match index {
+                0 => output.push_str(&format!(
+                    "\x1b_Ga=T,f=24,s={},v={},c={},r={},m={};{}\x1b\\",
+                    thumbnail.width, thumbnail.height, columns, rows, more, chunk
+                )),
+                _ => output.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk)),
+            }
+        }
+
+        output
+    }
+
+    /// Draws the image as sixels, with the colors reduced to a cube of 6 levels of each channel
+    fn sixel(&self, columns: usize) -> String {
+        let thumbnail = self.resize(columns * CELL_WIDTH, self.rows(columns) * CELL_HEIGHT);
+        let level = |value: u8| (value as usize * 5 + 127) / 255;
+        let color = |x: usize, y: usize| {
+            let [red, green, blue] = thumbnail.at(x, y);
+            level(red) * 36 + level(green) * 6 + level(blue)
+        };
+
+        let mut output = format!("\x1bPq\"1;1;{};{}", thumbnail.width, thumbnail.height);
+        for index in 0..216 {
+            output.push_str(&format!(
+                "#{};2;{};{};{}",
+                index,
+                index / 36 * 20,
+                index / 6 % 6 * 20,
+                index % 6 * 20
+            ));
+        }
+
+        for band in (0..thumbnail.height).step_by(6) {
+            let rows = band..(band + 6).min(thumbnail.height);
+            let colors: BTreeSet<usize> = rows
+                .clone()
+                .flat_map(|y| (0..thumbnail.width).map(move |x| (x, y)))
+                .map(|(x, y)| color(x, y))
+                .collect();
+
+            for (index, band_color) in colors.iter().enumerate() {
+                if index > 0 {
+                    // Back to the start of the band for the next color
+                    output.push('$');
+                }
+                output.push_str(&format!("#{}", band_color));
+
+                let sixels: Vec<char> = (0..thumbnail.width)
+                    .map(|x| {
+                        let bits = rows
+                            .clone()
+                            .filter(|y| color(x, *y) == *band_color)
+                            .fold(0, |bits, y| bits | 1 << (y - band));
+                        (63 + bits) as u8 as char
+                    })
+                    .collect();
+                let mut start = 0;
+                while start < sixels.len() {
+                    let run = sixels[start..]
+                        .iter()
+                        .take_while(|sixel| **sixel == sixels[start])
+                        .count();
+                    match run {
+                        1..=3 => output.extend(std::iter::repeat_n(sixels[start], run)),
+                        _ => output.push_str(&format!("!{}{}", run, sixels[start])),
+                    }
+                    start += run;
+                }
+            }
+            output.push('-');
+        }
+        output.push_str("\x1b\\");
+
+        output
+    }
+}
+
+/// The preview of the artwork, `columns` wide. None if it can't be decoded, except with iTerm2,
+/// which decodes the file itself
+pub fn render(artwork: &[u8], protocol: Protocol, columns: usize) -> Option<String> {
+    if protocol == Protocol::Iterm {
+        return match artwork.is_empty() {
+            true => None,
+            false => Some(format!(
+                "\x1b]1337;File=inline=1;size={};width={};preserveAspectRatio=1:{}\x07",
+                artwork.len(),
+                columns,
+                base64::encode(artwork)
+            )),
+        };
+    }
+
+    let pixels = Pixels::decode(artwork)?;
+    Some(match protocol {
+        Protocol::Kitty => pixels.kitty(columns),
+        Protocol::Sixel => pixels.sixel(columns),
+        _ => pixels.ascii(columns).join("\n"),
+    })
+}
+
+/// The artwork of the podcast. the one saved by the updates, else the one of its feed, which is
+/// fetched through the response cache one request at a time and within --limit-rate
+pub fn artwork(config: &Config, podcast: &Podcast) -> Option<Bytes> {
+    if let Ok(bytes) = fs::read(art::path(&config.app_directory, podcast.id)) {
+        return Some(Bytes::from(bytes));
+    }
+
+    let cache = ResponseCache::new(&config.app_directory, ARTWORK_CACHE_TTL);
+    let web = Web::new(time::Duration::from_secs(10), &config.http)
+        .stats(&config.network)
+        .progress(Arc::clone(&config.progress))
+        .jobs(1)
+        .limit_rate(config.limit_rate);
+    let fetch = |url: &str| -> Option<Bytes> {
+        if let Some(bytes) = cache.get(url) {
+            return Some(bytes);
+        }
+        match web.get(&[url]).pop()? {
+            (_url, Ok(bytes)) if !bytes.is_empty() => {
+                // Only an optimization, so failing to cache isn't an error
+                let _ = cache.put(url, &bytes);
+                Some(bytes)
+            }
+            (_url, Ok(_bytes)) => None,
+            (_url, Err(error)) => {
+                tracing::debug!(url, %error, "can't fetch the artwork");
+                None
+            }
+        }
+    };
+
+    let feed = fetch(&podcast.rss_url)?;
+    let image_url = feed::read(&feed[..]).ok().as_ref().and_then(art::image_url)?;
+    fetch(&image_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::collections::HashMap;
+
+    /// An image of the passed size whose left half is white and right half black, encoded in the
+    /// passed format
+    fn create_image(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, _y| match x < width / 2 {
+            true => Rgb([255, 255, 255]),
+            false => Rgb([0, 0, 0]),
+        });
+        let mut bytes = io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+
+        bytes.into_inner()
+    }
+
+    /// A PNG header which claims the passed size, followed by a little compressed data
+    fn create_oversized_png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chunk = |kind: &[u8], chunk: &[u8]| {
+            let mut crc = flate2::Crc::new();
+            crc.update(kind);
+            crc.update(chunk);
+            png.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            png.extend_from_slice(kind);
+            png.extend_from_slice(chunk);
+            png.extend_from_slice(&crc.sum().to_be_bytes());
+        };
+        let mut header = Vec::new();
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        chunk(b"IHDR", &header);
+        chunk(b"IDAT", &[0x78, 0x9c, 0x03, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        chunk(b"IEND", &[]);
+
+        png
+    }
+
+    #[test]
+    fn thumbnail_decode() {
+        let jpeg = create_image(16, 8, ImageFormat::Jpeg);
+        let pixels = Pixels::decode(&jpeg).unwrap();
+        assert_eq!((pixels.width, pixels.height), (16, 8));
+        // JPEG is lossy, so the colors are only close
+        assert!(pixels.at(0, 7).iter().all(|value| *value > 200));
+        assert!(pixels.at(15, 0).iter().all(|value| *value < 50));
+
+        // Larger than the limits, the pixels aren't allocated
+        assert!(Pixels::decode(&create_oversized_png(1 << 20, 1 << 20)).is_none());
+        assert!(Pixels::decode(&create_oversized_png(MAX_SIDE, MAX_SIDE)).is_none());
+
+        // Broken files, e.g cut short or with invalid Huffman tables, fail without panicking
+        let mut broken = jpeg.clone();
+        let middle = broken.len() / 2;
+        broken.truncate(middle);
+        broken.extend_from_slice(&[0xff; 64]);
+        let _ = Pixels::decode(&broken);
+        assert!(Pixels::decode(&[0xff, 0xd8, 0xff, 0xc4, 0x00, 0x02]).is_none());
+    }
+
+    #[test]
+    fn thumbnail_render() {
+        let variables: HashMap<&str, &str> = [("TERM", "xterm-kitty")].iter().copied().collect();
+        let detect = |variables: HashMap<&str, &str>| {
+            Protocol::detect(|name| variables.get(name).map(|value| value.to_string()))
+        };
+        assert_eq!(detect(variables), Protocol::Kitty);
+        let variables: HashMap<&str, &str> = [("TERM", "xterm-256color"), ("TERM_PROGRAM", "iTerm.app")]
+            .iter()
+            .copied()
+            .collect();
+        assert_eq!(detect(variables), Protocol::Iterm);
+        assert_eq!(detect(HashMap::new()), Protocol::Ascii);
+        assert_eq!(Protocol::parse("sixel"), Some(Protocol::Sixel));
+
+        let png = create_image(16, 8, ImageFormat::Png);
+        let pixels = Pixels::decode(&png).unwrap();
+        assert_eq!((pixels.width, pixels.height), (16, 8));
+        assert_eq!(pixels.at(0, 7), [255, 255, 255]);
+        assert_eq!(pixels.at(15, 7), [0, 0, 0]);
+        assert_eq!(pixels.resize(2, 1).rgb, vec![255, 255, 255, 0, 0, 0]);
+
+        colored::control::set_override(false);
+        assert_eq!(pixels.ascii(4), vec!["@@  ".to_string()]);
+        let sixel = render(&png, Protocol::Sixel, 1).unwrap();
+        assert!(sixel.starts_with("\x1bPq\"1;1;8;16#0;2;0;0;0"));
+        assert!(sixel.contains("#0!4?!4~$#215!4~!4?-"));
+        assert!(render(&png, Protocol::Kitty, 4)
+            .unwrap()
+            .starts_with("\x1b_Ga=T,f=24,s=32,v=8,c=4,r=1,m=0;"));
+        assert!(render(b"GIF89a", Protocol::Ascii, 4).is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The major phases of a command which are measured by the --timings flag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Fetch,
+    Parse,
+    Store,
+    WriteFiles,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Phase::Fetch => "fetch",
+            Phase::Parse => "parse",
+            Phase::Store => "store",
+            Phase::WriteFiles => "write files",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Collects the time spent in each phase. cloning it shares the collected measurements
+#[derive(Debug, Clone)]
+pub struct Timings {
+    start: Instant,
+    measurements: Arc<Mutex<Vec<(Phase, Duration)>>>,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            measurements: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Runs the passed closure and adds its duration to the passed phase
+    pub fn measure<T, F>(&self, phase: Phase, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let start = Instant::now();
+        let result = f();
+        self.add(phase, start.elapsed());
+
+        result
+    }
+
+    pub fn add(&self, phase: Phase, duration: Duration) {
+        crate::crash::log(format!("{} took {:.3}s", phase, duration.as_secs_f64()));
+        if let Ok(mut measurements) = self.measurements.lock() {
+            measurements.push((phase, duration));
+        }
+    }
+
+    /// Total duration and number of measurements of each phase, in the order they first appeared
+    pub fn summary(&self) -> Vec<(Phase, Duration, usize)> {
+        let mut summary: Vec<(Phase, Duration, usize)> = Vec::new();
+        let measurements = match self.measurements.lock() {
+            Ok(measurements) => measurements.clone(),
+            Err(_error) => return summary,
+        };
+
+        for (phase, duration) in measurements {
+            match summary.iter_mut().find(|(summary_phase, _, _)| *summary_phase == phase) {
+                Some((_, total, count)) => {
+                    *total += duration;
+                    *count += 1;
+                }
+                None => summary.push((phase, duration, 1)),
+            }
+        }
+
+        summary
+    }
+
+    /// Writes the timing breakdown of the command
+    pub fn report<W>(&self, mut writer: W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        writeln!(writer, "Timings:")?;
+        for (phase, duration, count) in self.summary() {
+            writeln!(
+                writer,
+                "  {:12}{:>10.3}s ({} times)",
+                phase.to_string(),
+                duration.as_secs_f64(),
+                count
+            )?;
+        }
+        writeln!(writer, "  {:12}{:>10.3}s", "total", self.start.elapsed().as_secs_f64())?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_summary() {
+        let timings = Timings::new();
+        timings.add(Phase::Fetch, Duration::from_millis(100));
+        timings.add(Phase::Parse, Duration::from_millis(10));
+        timings.clone().add(Phase::Fetch, Duration::from_millis(50));
+
+        let value = timings.measure(Phase::Store, || 42);
+
+        let summary = timings.summary();
+        assert_eq!(value, 42);
+        assert_eq!(summary.len(), 3);
+        assert_eq!(summary[0], (Phase::Fetch, Duration::from_millis(150), 2));
+        assert_eq!(summary[1], (Phase::Parse, Duration::from_millis(10), 1));
+        assert_eq!(summary[2].0, Phase::Store);
+    }
+}
@@ -0,0 +1,298 @@
+//! Keywords of the episodes, so the ones about something can be found in a large library with
+//! `episodes list --topic`. they're derived locally with TF-IDF: a word is a topic of an episode
+//! when it's frequent in its title, description and transcript, and rare in the rest of the library
+use crate::{
+    episodes::{read_episodes, Episode},
+    file_system::FileSystem,
+    podcasts::load_podcasts,
+    records, text,
+    web::Web,
+    Config, Errors,
+};
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Arc,
+    time,
+};
+
+/// The file inside the app directory which holds the topics of the episodes
+pub const TOPICS_FILE: &str = "topics.csv";
+
+/// Number of topics kept for each episode
+const TOPICS_PER_EPISODE: usize = 5;
+
+/// Each word of a title counts as much as this many words of the description, since the titles
+/// usually name what an episode is about
+const TITLE_WEIGHT: f64 = 3.0;
+
+/// A word is only a topic once it's in this many episodes, which leaves out the typos and the
+/// names of the sponsors of a single episode
+const MIN_EPISODES: usize = 2;
+
+/// Number of the most common topics listed after an analysis
+const COMMON_SHOWN: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopicEntry {
+    pub podcast_id: u64,
+    pub guid: String,
+    // Separated by spaces, the most distinctive first
+    pub topics: String,
+}
+
+/// The weighted number of times each word appears in the episode and its transcript
+fn term_counts(episode: &Episode, transcript: Option<&str>) -> HashMap<String, f64> {
+    let mut counts = HashMap::new();
+    for word in text::words(&episode.title) {
+        *counts.entry(word).or_insert(0.0) += TITLE_WEIGHT;
+    }
+
+    let (description, _links) = text::html_to_text(&episode.description);
+    let words = text::words(&description)
+        .into_iter()
+        .chain(transcript.map(text::words).unwrap_or_default());
+    for word in words {
+        *counts.entry(word).or_insert(0.0) += 1.0;
+    }
+
+    counts
+}
+
+/// The topics of each of the passed documents, by the TF-IDF score of their words
+fn analyze(documents: &[HashMap<String, f64>]) -> Vec<Vec<String>> {
+    let mut frequencies: HashMap<&str, usize> = HashMap::new();
+    for document in documents {
+        for word in document.keys() {
+            *frequencies.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    let count = documents.len() as f64;
+    documents
+        .iter()
+        .map(|document| {
+            let total: f64 = document.values().sum();
+            let mut scores: Vec<(&str, f64)> = document
+                .iter()
+                .filter_map(|(word, occurrences)| {
+                    let frequency = frequencies[word.as_str()];
+                    if frequency < MIN_EPISODES {
+                        return None;
+                    }
+                    // Words of every episode score 0 and aren't topics
+                    let score = occurrences / total * (count / frequency as f64).ln();
+                    Some((word.as_str(), score)).filter(|(_word, score)| *score > 0.0)
+                })
+                .collect();
+            scores.sort_by(|first, second| second.1.total_cmp(&first.1).then_with(|| first.0.cmp(second.0)));
+
+            scores
+                .into_iter()
+                .take(TOPICS_PER_EPISODE)
+                .map(|(word, _score)| word.to_string())
+                .collect()
+        })
+        .collect()
+}
+
+/// The saved topics of the episodes
+#[derive(Debug, Default)]
+pub struct TopicList {
+    topics: HashMap<(u64, String), Vec<String>>,
+}
+
+impl TopicList {
+    /// The topics saved by the last analysis. fails if the episodes were never analyzed
+    pub fn load(app_directory: &Path) -> Result<Self, Errors> {
+        let contents = fs::read(app_directory.join(TOPICS_FILE)).map_err(|_error| Errors::MissingTopics)?;
+        let entries: Vec<TopicEntry> = records::read(&contents[..], TOPICS_FILE, false)?;
+
+        Ok(Self {
+            topics: entries
+                .into_iter()
+                .map(|entry| {
+                    let topics = entry.topics.split_whitespace().map(String::from).collect();
+                    ((entry.podcast_id, entry.guid), topics)
+                })
+                .collect(),
+        })
+    }
+
+    /// Whether the passed topic is one of the episode's. diacritics and case are ignored
+    pub fn has(&self, episode: &Episode, topic: &str) -> bool {
+        let topic = text::fold(topic.trim());
+        self.topics
+            .get(&(episode.podcast_id, episode.guid.clone()))
+            .is_some_and(|topics| topics.contains(&topic))
+    }
+}
+
+pub struct Analyzer<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Analyzer<'a> {
+    /// Constructs a new Analyzer struct which is used to work with the sub command "episodes topics"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Derives the topics of all the saved episodes and replaces the saved ones. the whole library
+    /// is analyzed each time, since how rare a word is depends on all the episodes
+    pub fn run(&self) -> Result<(), Errors> {
+        let mut episodes = Vec::new();
+        for podcast in load_podcasts(self.config)? {
+            // Podcasts which were never updated have no episodes file
+            if let Ok(contents) = fs::read(self.config.app_directory.join(podcast.id.to_string())) {
+                episodes.extend(read_episodes(&contents[..], self.config.strict)?);
+            }
+        }
+
+        let transcripts = match self.matches.is_present("transcripts") {
+            true => self.transcripts(&episodes),
+            false => HashMap::new(),
+        };
+        let documents: Vec<HashMap<String, f64>> = episodes
+            .iter()
+            .map(|episode| {
+                term_counts(
+                    episode,
+                    transcripts.get(episode.transcript.as_str()).map(String::as_str),
+                )
+            })
+            .collect();
+        let topics = analyze(&documents);
+
+        let file = FileSystem::new(&self.config.app_directory, TOPICS_FILE, vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        let mut common: HashMap<&str, usize> = HashMap::new();
+        let mut analyzed = 0;
+        for (episode, topics) in episodes.iter().zip(topics.iter()) {
+            if topics.is_empty() {
+                continue;
+            }
+            writer.serialize(TopicEntry {
+                podcast_id: episode.podcast_id,
+                guid: episode.guid.clone(),
+                topics: topics.join(" "),
+            })?;
+            for topic in topics {
+                *common.entry(topic).or_insert(0) += 1;
+            }
+            analyzed += 1;
+        }
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        file.commit()?;
+
+        let mut common: Vec<(&str, usize)> = common.into_iter().collect();
+        common.sort_by(|first, second| second.1.cmp(&first.1).then_with(|| first.0.cmp(second.0)));
+        println!("Found the topics of {} of {} episodes", analyzed, episodes.len());
+        if !common.is_empty() {
+            let shown: Vec<String> = common
+                .iter()
+                .take(COMMON_SHOWN)
+                .map(|(topic, count)| format!("{} ({})", topic, count))
+                .collect();
+            println!("Most common topics: {}", shown.join(", "));
+        }
+
+        Ok(())
+    }
+
+    /// The text of the transcripts of the passed episodes by their URLs. the ones which can't be
+    /// fetched are left out, so their episodes are analyzed without them
+    fn transcripts(&self, episodes: &[Episode]) -> HashMap<String, String> {
+        let urls: HashSet<&str> = episodes
+            .iter()
+            .map(|episode| episode.transcript.as_str())
+            .filter(|url| !url.is_empty())
+            .collect();
+        let urls: Vec<&str> = urls.into_iter().collect();
+
+        Web::new(time::Duration::from_secs(30), &self.config.http)
+            .stats(&self.config.network)
+            .jobs(self.config.jobs)
+            .progress(Arc::clone(&self.config.progress))
+            .get(&urls)
+            .into_iter()
+            .filter_map(|(url, response)| match response {
+                // Subtitles and HTML transcripts both become plain words
+                Ok(bytes) => Some((url.to_string(), text::html_to_text(&String::from_utf8_lossy(&bytes)).0)),
+                Err(error) => {
+                    tracing::debug!("Can't fetch the transcript {}. {}", url, error);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_episode(guid: &str, title: &str, description: &str) -> Episode {
+        Episode {
+            guid: guid.to_string(),
+            title: title.to_string(),
+            pub_date: String::new(),
+            link: "-".to_string(),
+            podcast: String::new(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: description.to_string(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        }
+    }
+
+    #[test]
+    fn topics_tf_idf() {
+        let episodes = [
+            create_episode("1", "Rust async runtimes", "<p>Tokio and async-std, compared</p>"),
+            create_episode(
+                "2",
+                "Rust error handling",
+                "<p>Results, panics and the anyhow crate</p>",
+            ),
+            create_episode("3", "Python packaging", "<p>Poetry and pip compared</p>"),
+            create_episode("4", "Testing Python", "<p>Pytest fixtures</p>"),
+        ];
+        let documents: Vec<HashMap<String, f64>> = episodes.iter().map(|episode| term_counts(episode, None)).collect();
+        let topics = analyze(&documents);
+
+        assert_eq!(topics[0], vec!["rust", "compared"]);
+        assert_eq!(topics[1], vec!["rust"]);
+        assert_eq!(topics[2], vec!["python", "compared"]);
+        assert_eq!(topics[3], vec!["python"]);
+
+        let list = TopicList {
+            topics: episodes
+                .iter()
+                .zip(topics)
+                .map(|(episode, topics)| ((episode.podcast_id, episode.guid.clone()), topics))
+                .collect(),
+        };
+        assert!(list.has(&episodes[1], " RUST"));
+        assert!(!list.has(&episodes[2], "rust"));
+    }
+}
@@ -0,0 +1,573 @@
+//! An interactive browser of the saved podcasts and episodes. episodes are marked for download
+//! and downloaded in the background with the same operations as `episodes download`
+use crate::{
+    episodes::{Episode, Episodes},
+    podcasts::{load_podcasts, Podcast},
+    schedule, text,
+    thumbnail::{self, Pixels},
+    Config, Errors,
+};
+use clap::ArgMatches;
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    sync::mpsc,
+    thread, time,
+};
+
+/// How long to wait for a key before drawing the progress of the downloads again
+const TICK: time::Duration = time::Duration::from_millis(100);
+
+/// Width of the artwork under the podcasts, in columns
+const ARTWORK_COLUMNS: usize = 20;
+
+/// Characters of the artwork and their colors, one line after another
+type Artwork = Vec<Vec<(char, [u8; 3])>>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum DownloadStatus {
+    Queued,
+    Downloading,
+    Done,
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct Download {
+    guid: String,
+    title: String,
+    status: DownloadStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pane {
+    Podcasts,
+    Episodes,
+    Downloads,
+}
+
+/// What the browser has to do after a key was handled
+#[derive(Debug, PartialEq)]
+enum Action {
+    None,
+    LoadEpisodes(u64),
+    StartDownloads,
+}
+
+/// Everything the browser shows. kept apart from the terminal and the files, so the handling of
+/// the keys can be tested
+struct State {
+    podcasts: Vec<Podcast>,
+    episodes: Vec<Episode>,
+    downloaded: HashSet<String>,
+    marked: Vec<Episode>,
+    downloads: Vec<Download>,
+    pane: Pane,
+    podcast_list: ListState,
+    episode_list: ListState,
+    download_list: ListState,
+    query: String,
+    searching: bool,
+    quit: bool,
+    // The podcast whose episodes are shown
+    opened: Option<u64>,
+    // The artwork of the opened podcasts. None while it's fetched and when it can't be
+    artwork: HashMap<u64, Option<Artwork>>,
+}
+
+impl State {
+    fn new(podcasts: Vec<Podcast>) -> Self {
+        Self {
+            podcasts,
+            episodes: Vec::new(),
+            downloaded: HashSet::new(),
+            marked: Vec::new(),
+            downloads: Vec::new(),
+            pane: Pane::Podcasts,
+            podcast_list: ListState::default().with_selected(Some(0)),
+            episode_list: ListState::default().with_selected(Some(0)),
+            download_list: ListState::default(),
+            query: String::new(),
+            searching: false,
+            quit: false,
+            opened: None,
+            artwork: HashMap::new(),
+        }
+    }
+
+    /// The podcasts which match the search, when the podcasts pane is focused
+    fn visible_podcasts(&self) -> Vec<&Podcast> {
+        self.podcasts
+            .iter()
+            .filter(|podcast| self.pane != Pane::Podcasts || text::fuzzy_matches(podcast.name(), &self.query))
+            .collect()
+    }
+
+    /// The episodes which match the search, when the episodes pane is focused
+    fn visible_episodes(&self) -> Vec<&Episode> {
+        self.episodes
+            .iter()
+            .filter(|episode| self.pane != Pane::Episodes || text::fuzzy_matches(&episode.title, &self.query))
+            .collect()
+    }
+
+    fn is_marked(&self, episode: &Episode) -> bool {
+        self.marked
+            .iter()
+            .any(|marked| marked.podcast_id == episode.podcast_id && marked.guid == episode.guid)
+    }
+
+    fn is_downloading(&self) -> bool {
+        self.downloads
+            .iter()
+            .any(|download| download.status == DownloadStatus::Queued || download.status == DownloadStatus::Downloading)
+    }
+
+    /// The list of the focused pane and its length
+    fn focused_list(&mut self) -> (&mut ListState, usize) {
+        match self.pane {
+            Pane::Podcasts => {
+                let length = self.visible_podcasts().len();
+                (&mut self.podcast_list, length)
+            }
+            Pane::Episodes => {
+                let length = self.visible_episodes().len();
+                (&mut self.episode_list, length)
+            }
+            Pane::Downloads => (&mut self.download_list, self.downloads.len()),
+        }
+    }
+
+    fn move_selection(&mut self, forward: bool) {
+        let (list, length) = self.focused_list();
+        if length == 0 {
+            return;
+        }
+
+        let selected = list.selected().unwrap_or(0).min(length - 1);
+        let selected = match forward {
+            true => (selected + 1).min(length - 1),
+            false => selected.saturating_sub(1),
+        };
+        list.select(Some(selected));
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> Action {
+        if self.searching {
+            match code {
+                KeyCode::Char(char) => self.query.push(char),
+                KeyCode::Backspace => {
+                    self.query.pop();
+                }
+                KeyCode::Enter => self.searching = false,
+                KeyCode::Esc => {
+                    self.query.clear();
+                    self.searching = false;
+                }
+                _ => {}
+            }
+            self.focused_list().0.select(Some(0));
+
+            return Action::None;
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('/') => self.searching = self.pane != Pane::Downloads,
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(true),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(false),
+            KeyCode::Tab => {
+                self.query.clear();
+                self.pane = match self.pane {
+                    Pane::Podcasts => Pane::Episodes,
+                    Pane::Episodes => Pane::Downloads,
+                    Pane::Downloads => Pane::Podcasts,
+                };
+            }
+            KeyCode::Enter if self.pane == Pane::Podcasts => {
+                let selected = self.podcast_list.selected().unwrap_or(0);
+                if let Some(podcast) = self.visible_podcasts().get(selected) {
+                    let podcast_id = podcast.id;
+                    self.query.clear();
+                    self.pane = Pane::Episodes;
+                    self.opened = Some(podcast_id);
+
+                    return Action::LoadEpisodes(podcast_id);
+                }
+            }
+            KeyCode::Char(' ') if self.pane == Pane::Episodes => {
+                let selected = self.episode_list.selected().unwrap_or(0);
+                if let Some(episode) = self.visible_episodes().get(selected).map(|episode| (*episode).clone()) {
+                    match self.is_marked(&episode) {
+                        true => self
+                            .marked
+                            .retain(|marked| marked.podcast_id != episode.podcast_id || marked.guid != episode.guid),
+                        false => self.marked.push(episode),
+                    }
+                }
+                self.move_selection(true);
+            }
+            KeyCode::Char('d') if !self.marked.is_empty() => return Action::StartDownloads,
+            _ => {}
+        }
+
+        Action::None
+    }
+
+    fn set_episodes(&mut self, episodes: Vec<Episode>, downloaded: Vec<String>) {
+        self.episodes = episodes;
+        self.downloaded = downloaded.into_iter().collect();
+        self.episode_list.select(Some(0));
+    }
+
+    /// Moves the marked episodes to the downloads and returns them
+    fn queue_marked(&mut self) -> Vec<Episode> {
+        let queued = std::mem::take(&mut self.marked);
+        self.downloads.extend(queued.iter().map(|episode| Download {
+            guid: episode.guid.clone(),
+            title: episode.title.clone(),
+            status: DownloadStatus::Queued,
+        }));
+
+        queued
+    }
+
+    fn update_download(&mut self, guid: &str, status: DownloadStatus, config: &Config) {
+        if status == DownloadStatus::Done {
+            // Shows the episode as downloaded without reading the directory again
+            if let Some(episode) = self.episodes.iter().find(|episode| episode.guid == guid) {
+                self.downloaded
+                    .insert(episode.file_name(config.podcast_file_template(episode.podcast_id)));
+            }
+        }
+
+        for download in self.downloads.iter_mut().filter(|download| download.guid == guid) {
+            download.status = status.clone();
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, config: &Config) {
+        let [panes, status_line] = Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [podcasts_pane, episodes_pane, downloads_pane] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(45),
+            Constraint::Percentage(25),
+        ])
+        .areas(panes);
+        let artwork = self
+            .opened
+            .and_then(|podcast_id| self.artwork.get(&podcast_id))
+            .and_then(|artwork| artwork.as_ref());
+        let artwork_height = artwork.map(|artwork| artwork.len() as u16 + 2).unwrap_or(0);
+        let [podcasts_pane, artwork_pane] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(artwork_height)]).areas(podcasts_pane);
+
+        let block = |title: &'static str, pane: Pane| {
+            let block = Block::bordered().title(title);
+            match self.pane == pane {
+                true => block.border_style(Style::new().fg(Color::Cyan)),
+                false => block,
+            }
+        };
+        let highlight = Style::new().reversed();
+
+        let podcasts: Vec<ListItem> = self
+            .visible_podcasts()
+            .iter()
+            .map(|podcast| match podcast.paused {
+                true => ListItem::new(format!("{} (paused)", podcast.name())).dim(),
+                false => ListItem::new(podcast.name().to_string()),
+            })
+            .collect();
+        let podcasts = List::new(podcasts)
+            .block(block("Podcasts", Pane::Podcasts))
+            .highlight_style(highlight);
+
+        let episodes: Vec<ListItem> = self
+            .visible_episodes()
+            .iter()
+            .map(|episode| {
+                let mark = if self.is_marked(episode) { "[x]" } else { "[ ]" };
+                let item = ListItem::new(format!("{} {}", mark, episode.title));
+                match self
+                    .downloaded
+                    .contains(&episode.file_name(config.podcast_file_template(episode.podcast_id)))
+                {
+                    true => item.green(),
+                    false => item,
+                }
+            })
+            .collect();
+        let episodes = List::new(episodes)
+            .block(block("Episodes", Pane::Episodes))
+            .highlight_style(highlight);
+
+        let downloads: Vec<ListItem> = self
+            .downloads
+            .iter()
+            .map(|download| match &download.status {
+                DownloadStatus::Queued => ListItem::new(format!("queued  {}", download.title)).dim(),
+                DownloadStatus::Downloading => ListItem::new(format!("...     {}", download.title)).yellow(),
+                DownloadStatus::Done => ListItem::new(format!("done    {}", download.title)).green(),
+                DownloadStatus::Failed(error) => ListItem::new(format!("failed  {} ({})", download.title, error)).red(),
+            })
+            .collect();
+        let downloads = List::new(downloads)
+            .block(block("Downloads", Pane::Downloads))
+            .highlight_style(highlight);
+
+        if let Some(artwork) = artwork {
+            let lines: Vec<Line> = artwork
+                .iter()
+                .map(|line| {
+                    line.iter()
+                        .map(|(shade, [red, green, blue])| {
+                            Span::styled(shade.to_string(), Style::new().fg(Color::Rgb(*red, *green, *blue)))
+                        })
+                        .collect()
+                })
+                .collect();
+            frame.render_widget(Paragraph::new(lines).block(Block::bordered()), artwork_pane);
+        }
+        frame.render_stateful_widget(podcasts, podcasts_pane, &mut self.podcast_list);
+        frame.render_stateful_widget(episodes, episodes_pane, &mut self.episode_list);
+        frame.render_stateful_widget(downloads, downloads_pane, &mut self.download_list);
+
+        let status = if self.searching {
+            format!("/{}", self.query)
+        } else if self.quit {
+            "Waiting for the downloads to finish".to_string()
+        } else {
+            format!(
+                "{} marked. Tab: next pane  /: search  Enter: open  Space: mark  d: download  q: quit",
+                self.marked.len()
+            )
+        };
+        frame.render_widget(Paragraph::new(status), status_line);
+    }
+}
+
+/// Downloads the passed episodes in rounds in which the podcasts take turns, and reports the
+/// status of each of them
+fn download(
+    episodes: &Episodes,
+    queued: Vec<Episode>,
+    priorities: &HashMap<u64, u64>,
+    jobs: usize,
+    sender: mpsc::Sender<(String, DownloadStatus)>,
+) {
+    let mut queues: Vec<(u64, Vec<Episode>)> = Vec::new();
+    for episode in queued {
+        match queues
+            .iter_mut()
+            .find(|(podcast_id, _queue)| *podcast_id == episode.podcast_id)
+        {
+            Some((_podcast_id, queue)) => queue.push(episode),
+            None => queues.push((episode.podcast_id, vec![episode])),
+        }
+    }
+    let queues = queues
+        .into_iter()
+        .map(|(podcast_id, queue)| (priorities.get(&podcast_id).copied().unwrap_or(0), queue))
+        .collect();
+
+    for round in schedule::interleave(queues).chunks(jobs) {
+        for episode in round {
+            let _ = sender.send((episode.guid.clone(), DownloadStatus::Downloading));
+        }
+
+        let mut downloaded: HashMap<String, DownloadStatus> = match episodes.download_batch(round.to_vec()) {
+            Ok(downloaded) => downloaded
+                .into_iter()
+                .map(|(episode, file_name)| match file_name {
+                    Ok(_file_name) => (episode.guid, DownloadStatus::Done),
+                    Err(error) => (episode.guid, DownloadStatus::Failed(error.to_string())),
+                })
+                .collect(),
+            Err(error) => {
+                for episode in round {
+                    let _ = sender.send((episode.guid.clone(), DownloadStatus::Failed(error.to_string())));
+                }
+                continue;
+            }
+        };
+        for episode in round {
+            let status = match downloaded.remove(&episode.guid) {
+                Some(status) => status,
+                // Left out by --max-time
+                None => DownloadStatus::Failed("Not started".to_string()),
+            };
+            let _ = sender.send((episode.guid.clone(), status));
+        }
+    }
+}
+
+pub struct Browser<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Browser<'a> {
+    /// Constructs a new Browser struct which is used to work with the sub command "tui"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    pub fn run(&self) -> Result<(), Errors> {
+        let episodes = Episodes::new(self.matches, self.config).hide_progress();
+        let podcasts = load_podcasts(self.config)?;
+        let priorities: HashMap<u64, u64> = podcasts.iter().map(|podcast| (podcast.id, podcast.priority)).collect();
+        let mut state = State::new(podcasts);
+
+        let mut terminal = ratatui::init();
+        let result = thread::scope(|scope| -> Result<(), Errors> {
+            let (sender, receiver) = mpsc::channel::<(String, DownloadStatus)>();
+
+            // The artwork is fetched by a single thread, one podcast after another, so browsing
+            // doesn't start many downloads at once
+            let (artwork_sender, artwork_receiver) = mpsc::channel::<(u64, Option<Artwork>)>();
+            let (request_sender, request_receiver) = mpsc::channel::<Podcast>();
+            scope.spawn(move || {
+                for podcast in request_receiver {
+                    let artwork = thumbnail::artwork(self.config, &podcast)
+                        .and_then(|artwork| Pixels::decode(&artwork))
+                        .map(|pixels| pixels.cells(ARTWORK_COLUMNS));
+                    let _ = artwork_sender.send((podcast.id, artwork));
+                }
+            });
+
+            // Quitting waits for the started downloads, so their files are complete
+            while !state.quit || state.is_downloading() {
+                for (guid, status) in receiver.try_iter() {
+                    state.update_download(&guid, status, self.config);
+                }
+                state.artwork.extend(artwork_receiver.try_iter());
+                terminal.draw(|frame| state.draw(frame, self.config))?;
+
+                if !event::poll(TICK)? {
+                    continue;
+                }
+                let key = match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                    _ => continue,
+                };
+
+                match state.handle_key(key.code) {
+                    Action::LoadEpisodes(podcast_id) => {
+                        // Podcasts which were never updated have no episodes yet
+                        let podcast_episodes = episodes.podcast_episodes(podcast_id).unwrap_or_default();
+                        state.set_episodes(podcast_episodes, episodes.downloaded_files(podcast_id)?);
+                        if let Entry::Vacant(entry) = state.artwork.entry(podcast_id) {
+                            entry.insert(None);
+                            let podcast = state.podcasts.iter().find(|podcast| podcast.id == podcast_id);
+                            if let Some(podcast) = podcast.cloned() {
+                                let _ = request_sender.send(podcast);
+                            }
+                        }
+                    }
+                    Action::StartDownloads => {
+                        let queued = state.queue_marked();
+                        let sender = sender.clone();
+                        let episodes = &episodes;
+                        let priorities = &priorities;
+                        let jobs = self.config.jobs;
+                        scope.spawn(move || download(episodes, queued, priorities, jobs, sender));
+                    }
+                    Action::None => {}
+                }
+            }
+
+            Ok(())
+        });
+        ratatui::restore();
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_episode(guid: &str, title: &str) -> Episode {
+        Episode {
+            guid: guid.to_string(),
+            title: title.to_string(),
+            pub_date: "Wed, 22 Jul 2020 13:00:00 +0000".to_string(),
+            link: "-".to_string(),
+            podcast: "Syntax".to_string(),
+            podcast_id: 1,
+            enclosure_url: String::new(),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number: 0,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        }
+    }
+
+    #[test]
+    fn tui_state() {
+        let mut podcasts = vec![
+            Podcast::new("-".to_string(), "a".to_string(), "Syntax".to_string()),
+            Podcast::new("-".to_string(), "b".to_string(), "HTTP 203".to_string()),
+        ];
+        podcasts[1].id = 2;
+        let mut state = State::new(podcasts);
+
+        for code in [
+            KeyCode::Char('/'),
+            KeyCode::Char('h'),
+            KeyCode::Char('3'),
+            KeyCode::Enter,
+        ]
+        .iter()
+        {
+            assert_eq!(state.handle_key(*code), Action::None);
+        }
+        assert_eq!(state.visible_podcasts().len(), 1);
+        assert_eq!(state.handle_key(KeyCode::Enter), Action::LoadEpisodes(2));
+        assert_eq!(state.pane, Pane::Episodes);
+        assert_eq!(state.opened, Some(2));
+
+        state.set_episodes(
+            vec![create_episode("1", "First"), create_episode("2", "Second")],
+            Vec::new(),
+        );
+        assert_eq!(state.handle_key(KeyCode::Char('d')), Action::None);
+        state.handle_key(KeyCode::Down);
+        state.handle_key(KeyCode::Char(' '));
+        assert_eq!(state.marked.len(), 1);
+        assert_eq!(state.marked[0].guid, "2");
+        assert_eq!(state.handle_key(KeyCode::Char('d')), Action::StartDownloads);
+
+        let queued = state.queue_marked();
+        assert_eq!(queued.len(), 1);
+        assert!(state.marked.is_empty());
+        assert!(state.is_downloading());
+
+        let config = crate::fixtures::config().with_file_template("{title}.{ext}".to_string());
+        state.update_download("2", DownloadStatus::Done, &config);
+        assert!(!state.is_downloading());
+        assert!(state.downloaded.contains("Second.mp3"));
+    }
+}
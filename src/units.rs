@@ -0,0 +1,155 @@
+//! Parses the durations and sizes passed on the command line and in the environment, e.g "1h30m",
+//! "2w" or "1.5M". sizes are in binary multiples, so 1k is 1024 bytes
+use crate::Errors;
+use std::time::Duration;
+
+/// Splits e.g "1h 30m" into its numbers and units. the units are empty when a number has none
+fn components(value: &str) -> Result<Vec<(f64, String)>, String> {
+    let mut components = Vec::new();
+    let mut chars = value.trim().chars().peekable();
+
+    while let Some(&next) = chars.peek() {
+        let mut number = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            number.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut unit = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+            unit.push(c);
+        }
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        match (number.is_empty(), unit.is_empty()) {
+            (true, true) => return Err(format!("Unexpected {}", next)),
+            (true, false) => return Err(format!("Expected a number before {}", unit)),
+            _ => {}
+        }
+        let number = number
+            .parse::<f64>()
+            .map_err(|_error| format!("{} isn't a number", number))?;
+        components.push((number, unit));
+    }
+
+    if components.is_empty() {
+        return Err("It's empty".to_string());
+    }
+
+    Ok(components)
+}
+
+/// Parses a duration like "90s", "10m", "1.5h", "2w" or "1h 30m". a number without a unit is in
+/// seconds
+pub fn parse_duration(value: &str) -> Result<Duration, Errors> {
+    let invalid = |reason: String| Errors::InvalidDuration(value.trim().to_string(), reason);
+    let components = components(value).map_err(invalid)?;
+
+    let mut seconds = 0.0;
+    for (number, unit) in &components {
+        let multiplier = match unit.to_lowercase().as_str() {
+            "" if components.len() == 1 => 1,
+            "" => return Err(invalid(format!("{} has no unit", number))),
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3_600,
+            "d" | "day" | "days" => 86_400,
+            "w" | "week" | "weeks" => 604_800,
+            _ => return Err(invalid(format!("Unknown unit {}. Use s, m, h, d or w", unit))),
+        };
+        seconds += number * multiplier as f64;
+    }
+
+    if seconds < 1.0 {
+        return Err(invalid("It must be at least 1s".to_string()));
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|_error| invalid("It's too long".to_string()))
+}
+
+/// Parses a number of days, e.g "30", or a duration of whole days, e.g "2w"
+pub fn parse_days(value: &str) -> Result<u64, Errors> {
+    if let Ok(days) = value.trim().parse::<u64>() {
+        return Ok(days);
+    }
+
+    let seconds = parse_duration(value)?.as_secs();
+    if seconds % 86_400 != 0 {
+        return Err(Errors::InvalidDuration(
+            value.trim().to_string(),
+            "It must be a whole number of days".to_string(),
+        ));
+    }
+
+    Ok(seconds / 86_400)
+}
+
+/// Parses a size in bytes like "500k", "1.5M" or "2GB". a number without a unit is in bytes
+pub fn parse_size(value: &str) -> Result<u64, Errors> {
+    let invalid = |reason: String| Errors::InvalidSize(value.trim().to_string(), reason);
+    let components = components(value).map_err(invalid)?;
+    let (number, unit) = match &components[..] {
+        [(number, unit)] => (number, unit),
+        _ => return Err(invalid("Expected a single number and unit".to_string())),
+    };
+
+    let multiplier: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(invalid(format!("Unknown unit {}. Use k, M, G or T", unit))),
+    };
+    let bytes = (number * multiplier as f64).round();
+
+    if bytes < 1.0 {
+        return Err(invalid("It must be at least 1 byte".to_string()));
+    }
+    if bytes >= u64::MAX as f64 {
+        return Err(invalid("It's too large".to_string()));
+    }
+
+    Ok(bytes as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_parse() {
+        assert_eq!(parse_duration("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("7d").unwrap(), Duration::from_secs(604_800));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(1_209_600));
+        assert_eq!(parse_duration("1h 30m").unwrap(), Duration::from_secs(5_400));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::from_secs(5_400));
+        assert_eq!(parse_duration("10 minutes").unwrap(), Duration::from_secs(600));
+        assert_eq!(
+            parse_duration("2x").unwrap_err().to_string(),
+            "Invalid duration 2x. Unknown unit x. Use s, m, h, d or w"
+        );
+        assert_eq!(
+            parse_duration("1h 30").unwrap_err().to_string(),
+            "Invalid duration 1h 30. 30 has no unit"
+        );
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("-5m").is_err());
+
+        assert_eq!(parse_days("30").unwrap(), 30);
+        assert_eq!(parse_days("2w").unwrap(), 14);
+        assert!(parse_days("36h").is_err());
+
+        assert_eq!(parse_size("500k").unwrap(), 512_000);
+        assert_eq!(parse_size("2M").unwrap(), 2 * 1024 * 1024);
+        assert_eq!(parse_size("1.5M").unwrap(), 1_572_864);
+        assert_eq!(parse_size("2GB").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_size("1000").unwrap(), 1000);
+        assert_eq!(
+            parse_size("fast").unwrap_err().to_string(),
+            "Invalid size fast. Expected a number before fast"
+        );
+        assert!(parse_size("0k").is_err());
+        assert!(parse_size("1M 2k").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Other feeds of a subscription, e.g the same show in another language or in a lower quality.
+//! each profile, chosen with --profile, may prefer one of them, and the episodes downloaded under
+//! that profile take their enclosures from the preferred feed
+use crate::{episodes::Episode, file_system::FileSystem, records, text, Errors};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const VARIANTS_FILE: &str = "variants.csv";
+
+/// The profile when --profile and PODCASTS_PROFILE aren't set
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The name of the feed the podcast was added with
+pub const MAIN_VARIANT: &str = "main";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variant {
+    pub podcast_id: u64,
+    pub name: String,
+    pub rss_url: String,
+    // The profiles which prefer this variant, separated by spaces
+    pub profiles: String,
+}
+
+impl Variant {
+    pub fn profiles(&self) -> impl Iterator<Item = &str> {
+        self.profiles.split_whitespace()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VariantList {
+    directory: PathBuf,
+    variants: Vec<Variant>,
+}
+
+impl VariantList {
+    /// Loads the variants saved in the app directory. an empty list if none were added yet
+    pub fn load(app_directory: &Path, strict: bool) -> Result<Self, Errors> {
+        let variants = match fs::read(app_directory.join(VARIANTS_FILE)) {
+            Ok(contents) => records::read(&contents[..], VARIANTS_FILE, strict)?,
+            Err(_error) => Vec::new(),
+        };
+
+        Ok(Self {
+            directory: app_directory.to_path_buf(),
+            variants,
+        })
+    }
+
+    /// The variants of the podcast with the passed id
+    pub fn variants(&self, podcast_id: u64) -> impl Iterator<Item = &Variant> {
+        self.variants
+            .iter()
+            .filter(move |variant| variant.podcast_id == podcast_id)
+    }
+
+    /// Adds a variant to the podcast. returns false if the podcast already has a variant with the
+    /// passed name
+    pub fn add(&mut self, podcast_id: u64, name: &str, rss_url: &str) -> bool {
+        if name == MAIN_VARIANT || self.variants(podcast_id).any(|variant| variant.name == name) {
+            return false;
+        }
+
+        self.variants.push(Variant {
+            podcast_id,
+            name: name.to_string(),
+            rss_url: rss_url.to_string(),
+            profiles: String::new(),
+        });
+        true
+    }
+
+    /// Removes the variant of the podcast. returns false if there's no such variant
+    pub fn remove(&mut self, podcast_id: u64, name: &str) -> bool {
+        let count = self.variants.len();
+        self.variants
+            .retain(|variant| variant.podcast_id != podcast_id || variant.name != name);
+
+        self.variants.len() < count
+    }
+
+    /// Makes the profile prefer the variant with the passed name. the main variant is preferred
+    /// by the profiles which don't prefer any other. returns false if there's no such variant
+    pub fn prefer(&mut self, podcast_id: u64, name: &str, profile: &str) -> bool {
+        if name != MAIN_VARIANT && !self.variants(podcast_id).any(|variant| variant.name == name) {
+            return false;
+        }
+
+        for variant in self
+            .variants
+            .iter_mut()
+            .filter(|variant| variant.podcast_id == podcast_id)
+        {
+            let mut profiles: Vec<&str> = variant.profiles().filter(|other| *other != profile).collect();
+            if variant.name == name {
+                profiles.push(profile);
+            }
+            variant.profiles = profiles.join(" ");
+        }
+        true
+    }
+
+    /// The variant of the podcast the profile prefers. None when it prefers the main feed
+    pub fn preferred(&self, podcast_id: u64, profile: &str) -> Option<&Variant> {
+        self.variants(podcast_id)
+            .find(|variant| variant.profiles().any(|other| other == profile))
+    }
+
+    pub fn save(&self) -> Result<(), Errors> {
+        let file = FileSystem::new(&self.directory, VARIANTS_FILE, vec![]).atomic()?;
+        let mut writer = csv::Writer::from_writer(file);
+        for variant in self.variants.iter() {
+            writer.serialize(variant)?;
+        }
+
+        let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+        Ok(file.commit()?)
+    }
+}
+
+/// Finds the episode of a variant feed which is the same as the passed episode of the main feed.
+/// the feeds rarely share guids, so the season and episode numbers, the day of the release and the
+/// title are compared in turn
+pub fn matching<'a>(episode: &Episode, candidates: &'a [Episode]) -> Option<&'a Episode> {
+    if let Some(candidate) = candidates.iter().find(|candidate| candidate.guid == episode.guid) {
+        return Some(candidate);
+    }
+
+    if episode.episode_number > 0 {
+        if let Some(candidate) = candidates
+            .iter()
+            .find(|candidate| candidate.season == episode.season && candidate.episode_number == episode.episode_number)
+        {
+            return Some(candidate);
+        }
+    }
+
+    // Only a single release on that day is a match, a daily show may have several
+    let day = |episode: &Episode| episode.release_time().map(|time| time / (24 * 60 * 60));
+    if let Some(release_day) = day(episode) {
+        let same_day: Vec<&Episode> = candidates
+            .iter()
+            .filter(|candidate| day(candidate) == Some(release_day))
+            .collect();
+        if let [candidate] = same_day[..] {
+            return Some(candidate);
+        }
+    }
+
+    let title = text::fold(&episode.title);
+    candidates
+        .iter()
+        .find(|candidate| text::fold(&candidate.title) == title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn create_episode(guid: &str, title: &str, pub_date: &str, episode_number: u64) -> Episode {
+        Episode {
+            guid: guid.to_string(),
+            title: title.to_string(),
+            pub_date: pub_date.to_string(),
+            link: "-".to_string(),
+            podcast: "Syntax".to_string(),
+            podcast_id: 1,
+            enclosure_url: format!("https://example.com/{}.mp3", guid),
+            enclosure_type: "audio/mpeg".to_string(),
+            enclosure_length: 0,
+            number: 0,
+            first_seen: 0,
+            duration: 0,
+            season: 0,
+            episode_number,
+            episode_type: String::new(),
+            explicit: false,
+            description: String::new(),
+            published_at: 0,
+            alternates: String::new(),
+            people: String::new(),
+            location: String::new(),
+            soundbites: String::new(),
+            chapters: String::new(),
+            transcript: String::new(),
+        }
+    }
+
+    #[test]
+    fn variants_list() {
+        let directory = fixtures::scratch();
+
+        let mut list = VariantList::load(&directory, false).expect("Can't load variants");
+        assert!(list.add(1, "low", "https://example.com/low.rss"));
+        assert!(list.add(1, "de", "https://example.com/de.rss"));
+        assert!(!list.add(1, "low", "https://example.com/other.rss"));
+        assert!(!list.add(1, MAIN_VARIANT, "https://example.com/other.rss"));
+
+        assert!(list.prefer(1, "low", "phone"));
+        assert!(list.prefer(1, "de", "car"));
+        assert!(list.prefer(1, "de", "phone"));
+        assert!(!list.prefer(1, "fr", "phone"));
+        list.save().expect("Can't save variants");
+
+        let mut list = VariantList::load(&directory, false).expect("Can't load variants");
+        assert_eq!(
+            list.preferred(1, "phone").map(|variant| variant.name.as_str()),
+            Some("de")
+        );
+        assert_eq!(
+            list.preferred(1, "car").map(|variant| variant.name.as_str()),
+            Some("de")
+        );
+        assert!(list.preferred(1, DEFAULT_PROFILE).is_none());
+
+        assert!(list.prefer(1, MAIN_VARIANT, "car"));
+        assert!(list.preferred(1, "car").is_none());
+        assert!(list.remove(1, "de"));
+        assert!(list.preferred(1, "phone").is_none());
+        assert_eq!(list.variants(1).count(), 1);
+    }
+
+    #[test]
+    fn variants_matching() {
+        let episode = create_episode("main-12", "Episode 12: Rust", "Wed, 22 Jul 2020 13:00:00 +0000", 12);
+        let candidates = [
+            create_episode("low-11", "Episode 11", "Mon, 20 Jul 2020 13:00:00 +0000", 11),
+            create_episode("low-12", "Folge 12", "Wed, 22 Jul 2020 15:00:00 +0000", 12),
+        ];
+        assert_eq!(
+            matching(&episode, &candidates).map(|episode| episode.guid.as_str()),
+            Some("low-12")
+        );
+
+        // Without numbers the release day is used, then the title
+        let episode = create_episode("main-11", "Episode 11", "Mon, 20 Jul 2020 08:00:00 +0000", 0);
+        assert_eq!(
+            matching(&episode, &candidates).map(|episode| episode.guid.as_str()),
+            Some("low-11")
+        );
+        let episode = create_episode("main-11", "EPISODE 11", "", 0);
+        assert_eq!(
+            matching(&episode, &candidates).map(|episode| episode.guid.as_str()),
+            Some("low-11")
+        );
+
+        let episode = create_episode("main-13", "Episode 13", "Fri, 24 Jul 2020 13:00:00 +0000", 13);
+        assert!(matching(&episode, &candidates).is_none());
+    }
+}
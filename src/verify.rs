@@ -0,0 +1,472 @@
+use crate::{
+    episodes::{Episode, Episodes},
+    file_system::{FilePermissions, FileSystem},
+    journal::Journal,
+    podcasts::load_podcasts,
+    records, resolver, web, Config, Errors,
+};
+use clap::ArgMatches;
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// The file inside the app directory which holds the checksums of the downloaded episodes
+pub const MANIFEST_FILE: &str = "manifest.csv";
+
+/// The checksum of a downloaded episode, recorded when it's saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub podcast_id: u64,
+    pub file_name: String,
+    pub size: u64,
+    pub sha256: String,
+    // The length of the enclosure in the feed. 0 if the feed doesn't tell, and for the files
+    // recorded by older versions
+    #[serde(default)]
+    pub expected_size: u64,
+}
+
+/// The result of checking a downloaded file against its manifest entry
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Valid,
+    Mismatch(String),
+    // The download was shorter than the enclosure
+    Truncated(String),
+    Missing,
+    Unreadable(String),
+}
+
+/// Hex encoded SHA-256 of the passed content
+pub fn checksum<R>(mut reader: R) -> io::Result<String>
+where
+    R: Read,
+{
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Whether a download of the passed size is a part of the enclosure. the lengths in the feeds are
+/// often approximate, e.g because of the ads which are inserted when the file is requested, so a
+/// download is only truncated when it misses more than a tenth of the enclosure
+fn is_truncated(size: u64, expected_size: u64) -> bool {
+    size < expected_size - expected_size / 10
+}
+
+/// Rewrites a manifest of an older version, whose rows lack the later columns, so that the rows
+/// which are appended have as many columns as the others
+fn upgrade(app_directory: &Path) -> Result<(), Errors> {
+    let contents = match fs::read(app_directory.join(MANIFEST_FILE)) {
+        Ok(contents) => contents,
+        Err(_error) => return Ok(()),
+    };
+    let header = contents.split(|byte| *byte == b'\n').next().unwrap_or(&[]);
+    if header.is_empty() || String::from_utf8_lossy(header).contains("expected_size") {
+        return Ok(());
+    }
+
+    let file = FileSystem::new(app_directory, MANIFEST_FILE, vec![]).atomic()?;
+    let mut writer = csv::Writer::from_writer(file);
+    for entry in records::read::<ManifestEntry, _>(&contents[..], MANIFEST_FILE, false)? {
+        writer.serialize(entry)?;
+    }
+    let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+
+    Ok(file.commit()?)
+}
+
+/// Appends the passed entries to the manifest. files which are downloaded again get a new entry,
+/// and the last entry of each file is the one which is used
+pub fn record(app_directory: &Path, entries: &[ManifestEntry]) -> Result<(), Errors> {
+    upgrade(app_directory)?;
+    let file = FileSystem::new(app_directory, MANIFEST_FILE, vec![FilePermissions::Append]).open()?;
+    let is_empty = file.metadata()?.len() == 0;
+
+    let mut writer = csv::WriterBuilder::new().has_headers(is_empty).from_writer(file);
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Stages the manifest with the files of the podcast renamed from the keys to the values of the
+/// passed names, e.g after the podcast itself was renamed
+pub fn stage_renames(
+    app_directory: &Path,
+    podcast_id: u64,
+    names: &HashMap<String, String>,
+    strict: bool,
+    journal: &mut Journal,
+) -> Result<(), Errors> {
+    let contents = match fs::read(app_directory.join(MANIFEST_FILE)) {
+        Ok(contents) => contents,
+        // Nothing was downloaded yet
+        Err(_error) => return Ok(()),
+    };
+
+    let file = FileSystem::new(app_directory, MANIFEST_FILE, vec![]).atomic()?;
+    let mut writer = csv::Writer::from_writer(file);
+    for mut entry in records::read::<ManifestEntry, _>(&contents[..], MANIFEST_FILE, strict)? {
+        if entry.podcast_id == podcast_id {
+            if let Some(new_name) = names.get(&entry.file_name) {
+                entry.file_name = new_name.clone();
+            }
+        }
+        writer.serialize(entry)?;
+    }
+    let file = writer.into_inner().map_err(|error| Errors::IO(error.into_error()))?;
+
+    journal.stage(file)
+}
+
+/// The latest manifest entry of every downloaded file, keyed by the podcast id and file name
+pub fn read_manifest(app_directory: &Path, strict: bool) -> Result<HashMap<(u64, String), ManifestEntry>, Errors> {
+    let contents = match fs::read(app_directory.join(MANIFEST_FILE)) {
+        Ok(contents) => contents,
+        // Nothing was downloaded yet
+        Err(_error) => return Ok(HashMap::new()),
+    };
+
+    Ok(records::read(&contents[..], MANIFEST_FILE, strict)?
+        .into_iter()
+        .map(|entry: ManifestEntry| ((entry.podcast_id, entry.file_name.clone()), entry))
+        .collect())
+}
+
+pub struct Verifier<'a> {
+    matches: &'a ArgMatches,
+    config: &'a Config,
+}
+
+impl<'a> Verifier<'a> {
+    /// Constructs a new Verifier struct which is used to work with the sub command "verify"
+    pub fn new(matches: &'a ArgMatches, config: &'a Config) -> Self {
+        Self { matches, config }
+    }
+
+    /// Hashes the downloaded episodes of the podcast with the passed id, or of all the podcasts, in
+    /// parallel and compares them with the manifest. fails if any of the files doesn't match its
+    /// checksum, unless --repair downloads them again
+    pub fn run(&self) -> Result<(), Errors> {
+        let episodes = Episodes::new(self.matches, self.config);
+        let manifest = read_manifest(&self.config.app_directory, self.config.strict)?;
+        let mut podcasts = load_podcasts(self.config)?;
+        if let Some(id) = self.matches.value_of("id") {
+            let id = resolver::podcast(&podcasts, id)?.id;
+            podcasts.retain(|podcast| podcast.id == id);
+        }
+
+        let mut entries = Vec::new();
+        let mut unrecorded = 0;
+        for podcast in podcasts {
+            let downloaded: HashSet<String> = episodes.downloaded_files(podcast.id)?.into_iter().collect();
+            let archived: HashSet<String> = episodes.archived_files(podcast.id).into_iter().collect();
+
+            // Files which were removed with `episodes remove` or by the window aren't missing
+            entries.extend(
+                manifest
+                    .values()
+                    .filter(|entry| entry.podcast_id == podcast.id)
+                    .filter(|entry| downloaded.contains(&entry.file_name) || !archived.contains(&entry.file_name))
+                    .cloned(),
+            );
+            unrecorded += downloaded
+                .iter()
+                .filter(|file_name| !manifest.contains_key(&(podcast.id, file_name.to_string())))
+                .count();
+        }
+        entries
+            .sort_by(|first, second| (first.podcast_id, &first.file_name).cmp(&(second.podcast_id, &second.file_name)));
+
+        let progress_bar = ProgressBar::new(entries.len() as u64);
+        progress_bar.set_style(
+            ProgressStyle::default_bar()
+                .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} files ({eta})")
+                .progress_chars("#>-"),
+        );
+
+        let outcomes: Vec<Outcome> = web::in_pool(self.config.jobs, || {
+            entries
+                .par_iter()
+                .map(|entry| {
+                    let outcome = self.verify(entry);
+                    progress_bar.inc(1);
+                    outcome
+                })
+                .collect()
+        });
+        progress_bar.finish_and_clear();
+
+        let writer = std::io::stdout();
+        let mut writer = writer.lock();
+        let mut mismatches = self.summary(&entries, &outcomes, unrecorded, &mut writer)?;
+
+        if mismatches > 0 && self.matches.is_present("repair") {
+            let broken: Vec<&ManifestEntry> = entries
+                .iter()
+                .zip(outcomes.iter())
+                .filter(|(_entry, outcome)| !matches!(outcome, Outcome::Valid | Outcome::Missing))
+                .map(|(entry, _outcome)| entry)
+                .collect();
+            mismatches -= self.repair(&episodes, broken, &mut writer)?;
+        }
+        if mismatches > 0 {
+            return Err(Errors::ChecksumMismatch(mismatches));
+        }
+
+        Ok(())
+    }
+
+    /// Downloads the files which failed the verification again. returns the number of files which
+    /// were repaired
+    fn repair<W>(&self, episodes: &Episodes, broken: Vec<&ManifestEntry>, writer: &mut W) -> Result<usize, Errors>
+    where
+        W: Write,
+    {
+        let mut podcasts_episodes: HashMap<u64, Vec<Episode>> = HashMap::new();
+        let mut downloads = Vec::new();
+        for entry in broken {
+            let podcast_episodes = podcasts_episodes
+                .entry(entry.podcast_id)
+                .or_insert_with(|| episodes.podcast_episodes(entry.podcast_id).unwrap_or_default());
+            let episode = podcast_episodes.iter().find(|episode| {
+                episode.file_name(self.config.podcast_file_template(episode.podcast_id)) == entry.file_name
+            });
+            match episode {
+                Some(episode) => {
+                    // The new file is written over the old one, which may be longer
+                    let path = self.config.podcast_directory(entry.podcast_id).join(&entry.file_name);
+                    match fs::remove_file(path) {
+                        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(Errors::IO(error)),
+                        _ => downloads.push(episode.clone()),
+                    }
+                }
+                None => writeln!(
+                    writer,
+                    "Can't repair {}/{}, its episode isn't saved anymore",
+                    entry.podcast_id, entry.file_name
+                )?,
+            }
+        }
+
+        // The ones which fail again are reported by the download
+        let repaired = episodes
+            .download_batch(downloads)?
+            .into_iter()
+            .filter(|(_episode, file_name)| file_name.is_ok())
+            .count();
+        writeln!(writer, "Downloaded {} files again", repaired)?;
+
+        Ok(repaired)
+    }
+
+    /// Compares the downloaded file of the entry with its recorded size and checksum
+    fn verify(&self, entry: &ManifestEntry) -> Outcome {
+        let path = self.config.podcast_directory(entry.podcast_id).join(&entry.file_name);
+
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Outcome::Missing,
+            Err(error) => return Outcome::Unreadable(error.to_string()),
+        };
+
+        // A different size means different content, so there's no need to read the whole file
+        match file.metadata() {
+            Ok(metadata) if metadata.len() < entry.size => {
+                return Outcome::Truncated(format!(
+                    "{} of the {} bytes which were saved",
+                    metadata.len(),
+                    entry.size
+                ))
+            }
+            Ok(metadata) if metadata.len() != entry.size => {
+                return Outcome::Mismatch(format!("{} bytes instead of {}", metadata.len(), entry.size))
+            }
+            Ok(_metadata) => {}
+            Err(error) => return Outcome::Unreadable(error.to_string()),
+        }
+
+        match checksum(io::BufReader::new(file)) {
+            // The file is as it was saved, but the download was cut short
+            Ok(sha256) if sha256 == entry.sha256 && is_truncated(entry.size, entry.expected_size) => {
+                Outcome::Truncated(format!(
+                    "{} of the {} bytes of the enclosure",
+                    entry.size, entry.expected_size
+                ))
+            }
+            Ok(sha256) if sha256 == entry.sha256 => Outcome::Valid,
+            Ok(sha256) => Outcome::Mismatch(format!("checksum {} instead of {}", sha256, entry.sha256)),
+            Err(error) => Outcome::Unreadable(error.to_string()),
+        }
+    }
+
+    /// Writes the files which failed the verification followed by the totals. returns the number
+    /// of files which don't match the manifest
+    fn summary<W>(
+        &self,
+        entries: &[ManifestEntry],
+        outcomes: &[Outcome],
+        unrecorded: usize,
+        writer: &mut W,
+    ) -> Result<usize, Errors>
+    where
+        W: Write,
+    {
+        let (mut mismatches, mut truncated, mut missing, mut unreadable) = (0, 0, 0, 0);
+        for (entry, outcome) in entries.iter().zip(outcomes.iter()) {
+            let path = format!("{}/{}", entry.podcast_id, entry.file_name);
+            match outcome {
+                Outcome::Valid => {}
+                Outcome::Mismatch(reason) => {
+                    mismatches += 1;
+                    writeln!(writer, "{} {} ({})", "mismatch".red(), path, reason)?;
+                }
+                Outcome::Truncated(reason) => {
+                    truncated += 1;
+                    writeln!(writer, "{} {} ({})", "truncated".red(), path, reason)?;
+                }
+                Outcome::Missing => {
+                    missing += 1;
+                    writeln!(writer, "{} {}", "missing".yellow(), path)?;
+                }
+                Outcome::Unreadable(error) => {
+                    unreadable += 1;
+                    writeln!(writer, "{} {} ({})", "unreadable".red(), path, error)?;
+                }
+            }
+        }
+
+        writeln!(
+            writer,
+            "Verified {} files: {} mismatched, {} truncated, {} missing, {} unreadable",
+            entries.len(),
+            mismatches,
+            truncated,
+            missing,
+            unreadable
+        )?;
+        if unrecorded > 0 {
+            writeln!(
+                writer,
+                "{} downloaded files have no checksum. they were downloaded before the manifest was kept",
+                unrecorded
+            )?;
+        }
+
+        Ok(mismatches + truncated + unreadable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{episodes::read_episodes, fixtures, Application, ApplicationBuilder, Library};
+    use std::str::from_utf8;
+
+    fn create_app() -> Application {
+        ApplicationBuilder::new(fixtures::config()).verify_subcommand().build()
+    }
+
+    #[test]
+    fn verify_summary() {
+        let app = create_app();
+        let config = fixtures::config();
+        fs::create_dir_all(config.podcast_directory(1)).unwrap();
+
+        let args = app.app.get_matches_from(vec!["pcasts", "verify"]);
+        let verify_matches = args.subcommand_matches("verify").expect("No verify matches");
+        let verifier = Verifier::new(verify_matches, &config);
+
+        fs::write(config.podcast_directory(1).join("valid.mp3"), "valid").unwrap();
+        fs::write(config.podcast_directory(1).join("changed.mp3"), "chang3").unwrap();
+        fs::write(config.podcast_directory(1).join("short.mp3"), "short").unwrap();
+        fs::write(config.podcast_directory(1).join("cut.mp3"), "cu").unwrap();
+        let entry = |file_name: &str, content: &str, expected_size: u64| ManifestEntry {
+            podcast_id: 1,
+            file_name: file_name.to_string(),
+            size: content.len() as u64,
+            sha256: checksum(content.as_bytes()).unwrap(),
+            expected_size,
+        };
+        let entries = vec![
+            entry("changed.mp3", "change", 0),
+            entry("missing.mp3", "missing", 0),
+            entry("short.mp3", "short", 100),
+            entry("cut.mp3", "cut", 0),
+            entry("valid.mp3", "valid", 5),
+        ];
+        record(&config.app_directory, &entries[..1]).unwrap();
+        record(&config.app_directory, &entries[1..]).unwrap();
+        assert_eq!(read_manifest(&config.app_directory, true).unwrap().len(), 5);
+
+        let outcomes: Vec<Outcome> = entries.iter().map(|entry| verifier.verify(entry)).collect();
+        let mut output = Vec::new();
+        colored::control::set_override(false);
+        let failures = verifier.summary(&entries, &outcomes, 2, &mut output).unwrap();
+
+        let expected_output = r###"mismatch 1/changed.mp3 (checksum 50a4a2b49d54061ddbccc4a297ebd7817301b304fdab5f05e95f6837f0efcf69 instead of 12ea12eace7d655f471ce55e34f89b1b77a3d9d05a445ca82877dd2235beaa51)
+missing 1/missing.mp3
+truncated 1/short.mp3 (5 of the 100 bytes of the enclosure)
+truncated 1/cut.mp3 (2 of the 3 bytes which were saved)
+Verified 5 files: 1 mismatched, 2 truncated, 1 missing, 0 unreadable
+2 downloaded files have no checksum. they were downloaded before the manifest was kept
+"###;
+        assert_eq!(from_utf8(&output).unwrap(), expected_output);
+        assert_eq!(failures, 3);
+    }
+
+    #[test]
+    fn verify_repair() {
+        let config = fixtures::config();
+        let library = Library::new(fixtures::config());
+        let podcast = library.add_podcast("https://feed.syntax.fm/rss").unwrap();
+        // The enclosures of the feed have query strings, so the episode points at the fixture
+        let input = format!(
+            "guid,title,pub_date,link,podcast,podcast_id\n\
+             272eca72-476b-4633-864c-a9fffa3f5976,Potluck,\"Wed, 22 Jul 2020 13:00:00 +0000\",https://traffic.libsyn.com/secure/syntax/Syntax268.mp3,Syntax,{}\n",
+            podcast.id
+        );
+        fs::write(config.app_directory.join(podcast.id.to_string()), &input).unwrap();
+        let episodes = read_episodes(input.as_bytes(), false).unwrap();
+        let (_episode, path) = library.download_episodes(episodes).unwrap().pop().unwrap();
+        let content = fs::read(&path).unwrap();
+        assert_eq!(content, fixtures::read("syntax_268.mp3").unwrap());
+        fs::write(&path, &content[..content.len() / 2]).unwrap();
+
+        // Without --repair the truncated file is only reported
+        let args = create_app().app.get_matches_from(vec!["pcasts", "verify"]);
+        let verify_matches = args.subcommand_matches("verify").expect("No verify matches");
+        assert!(matches!(
+            Verifier::new(verify_matches, &config).run(),
+            Err(Errors::ChecksumMismatch(1))
+        ));
+        assert_eq!(fs::read(&path).unwrap().len(), content.len() / 2);
+
+        let args = create_app().app.get_matches_from(vec!["pcasts", "verify", "--repair"]);
+        let verify_matches = args.subcommand_matches("verify").expect("No verify matches");
+        Verifier::new(verify_matches, &config)
+            .run()
+            .expect("Can't repair the file");
+        assert_eq!(fs::read(&path).unwrap(), content);
+    }
+}